@@ -0,0 +1,521 @@
+//! Contraction hierarchies for fast repeated point-to-point queries.
+//!
+//! Building a [`ContractedGraph`] contracts all nodes of a graph ordered by their
+//! importance and adds shortcut edges preserving the shortest paths between the remaining
+//! nodes. Queries only follow edges leading to more important nodes from both ends, so
+//! only a small fraction of the graph gets visited.
+//!
+//! The preprocessing is expensive, this pays off for static graphs answering a large
+//! number of queries. The fastforwards of the graph are not used.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Add;
+
+use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
+use hashbrown::hash_map::Entry;
+use num_traits::Zero;
+
+use crate::algorithm::graph::path::{DirectedEdgePath, Path};
+use crate::container::{CellMap, HashMap};
+use crate::error::Error;
+use crate::graph::PreparedH3EdgeGraph;
+use crate::HasH3Resolution;
+
+/// limits the number of nodes settled by the witness search during the contraction.
+///
+/// When no witness is found within this limit, a shortcut is added. This may lead to
+/// superfluous shortcuts, but never to wrong results.
+const WITNESS_SEARCH_SETTLE_LIMIT: usize = 500;
+
+#[derive(Clone, Copy)]
+struct ChEdge<W> {
+    weight: W,
+
+    /// the contracted node bypassed by this shortcut. `None` for edges of the original graph.
+    via: Option<usize>,
+}
+
+/// A graph prepared for contraction hierarchy queries.
+pub struct ContractedGraph<W> {
+    h3_resolution: Resolution,
+    cells: Vec<CellIndex>,
+    node_ids: CellMap<usize>,
+
+    /// all edges including the shortcuts, keyed by the ids of their origin and destination nodes.
+    edges: HashMap<(usize, usize), ChEdge<W>>,
+
+    /// edges leading to nodes of a higher rank, indexed by their origin node.
+    upward: Vec<Vec<(usize, W)>>,
+
+    /// edges coming from nodes of a higher rank, indexed by their destination node.
+    downward: Vec<Vec<(usize, W)>>,
+
+    num_shortcuts: usize,
+}
+
+impl<W> ContractedGraph<W>
+where
+    W: Ord + Copy + Add<Output = W> + Zero,
+{
+    pub fn from_prepared_graph(graph: &PreparedH3EdgeGraph<W>) -> Result<Self, Error> {
+        let mut cells = vec![];
+        let mut node_ids = CellMap::default();
+        let mut edges = HashMap::default();
+        for (edge, edge_weight) in graph.iter_edges() {
+            let (origin, destination) = edge.cells();
+            let from = node_id(&mut node_ids, &mut cells, origin);
+            let to = node_id(&mut node_ids, &mut cells, destination);
+            edges.insert(
+                (from, to),
+                ChEdge {
+                    weight: edge_weight.weight,
+                    via: None,
+                },
+            );
+        }
+
+        let mut contraction = Contraction::new(cells.len(), &edges);
+        let (ranks, num_shortcuts) = contraction.contract(&mut edges);
+
+        let mut upward = vec![vec![]; cells.len()];
+        let mut downward = vec![vec![]; cells.len()];
+        for ((from, to), ch_edge) in edges.iter() {
+            if ranks[*to] > ranks[*from] {
+                upward[*from].push((*to, ch_edge.weight));
+            } else {
+                downward[*to].push((*from, ch_edge.weight));
+            }
+        }
+
+        Ok(Self {
+            h3_resolution: graph.h3_resolution(),
+            cells,
+            node_ids,
+            edges,
+            upward,
+            downward,
+            num_shortcuts,
+        })
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// number of shortcut edges added during the contraction.
+    pub fn num_shortcuts(&self) -> usize {
+        self.num_shortcuts
+    }
+
+    /// find the shortest path between `origin_cell` and `destination_cell`.
+    ///
+    /// Returns `None` when one of the cells is not part of the graph or when
+    /// the destination can not be reached.
+    pub fn shortest_path(
+        &self,
+        origin_cell: CellIndex,
+        destination_cell: CellIndex,
+    ) -> Result<Option<Path<W>>, Error> {
+        let (origin, destination) = match (
+            self.node_ids.get(&origin_cell),
+            self.node_ids.get(&destination_cell),
+        ) {
+            (Some(origin), Some(destination)) => (*origin, *destination),
+            _ => return Ok(None),
+        };
+        if origin == destination {
+            return Path::try_from((
+                DirectedEdgePath::OriginIsDestination(origin_cell),
+                W::zero(),
+            ))
+            .map(Some);
+        }
+
+        let forward = upward_search(origin, &self.upward);
+        let backward = upward_search(destination, &self.downward);
+
+        let meeting = forward
+            .iter()
+            .filter_map(|(node, (forward_weight, _))| {
+                backward
+                    .get(node)
+                    .map(|(backward_weight, _)| (*forward_weight + *backward_weight, *node))
+            })
+            .min();
+        let (cost, meeting_node) = match meeting {
+            Some(meeting) => meeting,
+            None => return Ok(None),
+        };
+
+        // edges of the contracted graph from the origin to the meeting node ...
+        let mut ch_edges = vec![];
+        let mut node = meeting_node;
+        while let Some((_, Some(parent))) = forward.get(&node) {
+            ch_edges.push((*parent, node));
+            node = *parent;
+        }
+        ch_edges.reverse();
+
+        // ... and on to the destination
+        let mut node = meeting_node;
+        while let Some((_, Some(parent))) = backward.get(&node) {
+            ch_edges.push((node, *parent));
+            node = *parent;
+        }
+
+        let mut h3edges = vec![];
+        for (from, to) in ch_edges {
+            self.unpack_edge(from, to, &mut h3edges)?;
+        }
+        Path::try_from((DirectedEdgePath::DirectedEdgeSequence(h3edges), cost)).map(Some)
+    }
+
+    /// resolve the shortcuts to the edges of the original graph
+    fn unpack_edge(
+        &self,
+        from: usize,
+        to: usize,
+        h3edges: &mut Vec<DirectedEdgeIndex>,
+    ) -> Result<(), Error> {
+        let mut stack = vec![(from, to)];
+        while let Some((from, to)) = stack.pop() {
+            match self.edges.get(&(from, to)).and_then(|ch_edge| ch_edge.via) {
+                Some(via) => {
+                    stack.push((via, to));
+                    stack.push((from, via));
+                }
+                None => h3edges.push(
+                    self.cells[from]
+                        .edge(self.cells[to])
+                        .ok_or(Error::SegmentedPath)?,
+                ),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W> HasH3Resolution for ContractedGraph<W> {
+    fn h3_resolution(&self) -> Resolution {
+        self.h3_resolution
+    }
+}
+
+fn node_id(node_ids: &mut CellMap<usize>, cells: &mut Vec<CellIndex>, cell: CellIndex) -> usize {
+    *node_ids.entry(cell).or_insert_with(|| {
+        cells.push(cell);
+        cells.len() - 1
+    })
+}
+
+/// dijkstra over the edges of one direction of the contracted graph.
+///
+/// Returns the weight and the parent of all reached nodes.
+fn upward_search<W>(
+    start: usize,
+    adjacency: &[Vec<(usize, W)>],
+) -> HashMap<usize, (W, Option<usize>)>
+where
+    W: Ord + Copy + Add<Output = W> + Zero,
+{
+    let mut reached: HashMap<usize, (W, Option<usize>)> = HashMap::default();
+    let mut to_see = BinaryHeap::new();
+    reached.insert(start, (W::zero(), None));
+    to_see.push(Reverse((W::zero(), start)));
+
+    while let Some(Reverse((weight, node))) = to_see.pop() {
+        if reached.get(&node).is_some_and(|(w, _)| weight > *w) {
+            continue;
+        }
+        for (next, edge_weight) in adjacency[node].iter() {
+            let new_weight = weight + *edge_weight;
+            match reached.entry(*next) {
+                Entry::Occupied(mut occupied) => {
+                    if occupied.get().0 <= new_weight {
+                        continue;
+                    }
+                    occupied.insert((new_weight, Some(node)));
+                }
+                Entry::Vacant(vacant) => {
+                    vacant.insert((new_weight, Some(node)));
+                }
+            }
+            to_see.push(Reverse((new_weight, *next)));
+        }
+    }
+    reached
+}
+
+/// state of the graph during the contraction.
+struct Contraction {
+    out_neighbors: Vec<Vec<usize>>,
+    in_neighbors: Vec<Vec<usize>>,
+    contracted: Vec<bool>,
+    num_contracted_neighbors: Vec<usize>,
+}
+
+impl Contraction {
+    fn new<W>(num_nodes: usize, edges: &HashMap<(usize, usize), ChEdge<W>>) -> Self {
+        let mut out_neighbors = vec![vec![]; num_nodes];
+        let mut in_neighbors = vec![vec![]; num_nodes];
+        for (from, to) in edges.keys() {
+            out_neighbors[*from].push(*to);
+            in_neighbors[*to].push(*from);
+        }
+        Self {
+            out_neighbors,
+            in_neighbors,
+            contracted: vec![false; num_nodes],
+            num_contracted_neighbors: vec![0; num_nodes],
+        }
+    }
+
+    /// contract all nodes, adding the required shortcuts to `edges`.
+    ///
+    /// Nodes are ordered by their edge difference - the number of shortcuts required minus
+    /// the number of removed edges - and the number of already contracted neighbors to
+    /// spread the contraction evenly over the graph.
+    ///
+    /// Returns the rank of each node and the number of added shortcuts.
+    fn contract<W>(&mut self, edges: &mut HashMap<(usize, usize), ChEdge<W>>) -> (Vec<usize>, usize)
+    where
+        W: Ord + Copy + Add<Output = W> + Zero,
+    {
+        let num_nodes = self.contracted.len();
+        let mut queue: BinaryHeap<_> = (0..num_nodes)
+            .map(|node| Reverse((self.priority(node, &self.shortcuts(node, edges)), node)))
+            .collect();
+
+        let mut ranks = vec![0; num_nodes];
+        let mut next_rank = 0;
+        let mut num_shortcuts = 0;
+        while let Some(Reverse((_, node))) = queue.pop() {
+            // lazy update of the priority, the neighborhood may have changed since it was queued.
+            let shortcuts = self.shortcuts(node, edges);
+            let priority = self.priority(node, &shortcuts);
+            if let Some(Reverse((next_priority, _))) = queue.peek() {
+                if priority > *next_priority {
+                    queue.push(Reverse((priority, node)));
+                    continue;
+                }
+            }
+
+            for (from, to, weight) in shortcuts {
+                let shortcut = ChEdge {
+                    weight,
+                    via: Some(node),
+                };
+                match edges.entry((from, to)) {
+                    Entry::Occupied(mut occupied) => {
+                        if occupied.get().weight > weight {
+                            occupied.insert(shortcut);
+                            num_shortcuts += 1;
+                        }
+                    }
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(shortcut);
+                        self.out_neighbors[from].push(to);
+                        self.in_neighbors[to].push(from);
+                        num_shortcuts += 1;
+                    }
+                }
+            }
+
+            self.contracted[node] = true;
+            ranks[node] = next_rank;
+            next_rank += 1;
+            for neighbor in self.out_neighbors[node]
+                .iter()
+                .chain(self.in_neighbors[node].iter())
+            {
+                self.num_contracted_neighbors[*neighbor] += 1;
+            }
+        }
+        (ranks, num_shortcuts)
+    }
+
+    fn priority<W>(&self, node: usize, shortcuts: &[(usize, usize, W)]) -> i64 {
+        let num_removed_edges = self.out_neighbors[node]
+            .iter()
+            .chain(self.in_neighbors[node].iter())
+            .filter(|neighbor| !self.contracted[**neighbor])
+            .count();
+        shortcuts.len() as i64 - num_removed_edges as i64
+            + self.num_contracted_neighbors[node] as i64
+    }
+
+    /// the shortcuts required to keep the shortest paths when `node` gets contracted.
+    fn shortcuts<W>(
+        &self,
+        node: usize,
+        edges: &HashMap<(usize, usize), ChEdge<W>>,
+    ) -> Vec<(usize, usize, W)>
+    where
+        W: Ord + Copy + Add<Output = W> + Zero,
+    {
+        let mut shortcuts = vec![];
+        for from in self.in_neighbors[node].iter() {
+            if self.contracted[*from] {
+                continue;
+            }
+            let in_weight = edges[&(*from, node)].weight;
+            let targets: Vec<_> = self.out_neighbors[node]
+                .iter()
+                .filter(|to| !self.contracted[**to] && *to != from)
+                .map(|to| (*to, in_weight + edges[&(node, *to)].weight))
+                .collect();
+            let max_weight = match targets.iter().map(|(_, weight)| *weight).max() {
+                Some(max_weight) => max_weight,
+                None => continue,
+            };
+
+            let witnesses = self.witness_search(*from, node, max_weight, edges);
+            for (to, weight) in targets {
+                if witnesses
+                    .get(&to)
+                    .is_none_or(|witness_weight| *witness_weight > weight)
+                {
+                    shortcuts.push((*from, to, weight));
+                }
+            }
+        }
+        shortcuts
+    }
+
+    /// weights of the paths from `start` to the surrounding nodes, avoiding `excluded_node`
+    /// and all contracted nodes.
+    fn witness_search<W>(
+        &self,
+        start: usize,
+        excluded_node: usize,
+        max_weight: W,
+        edges: &HashMap<(usize, usize), ChEdge<W>>,
+    ) -> HashMap<usize, W>
+    where
+        W: Ord + Copy + Add<Output = W> + Zero,
+    {
+        let mut weights: HashMap<usize, W> = HashMap::default();
+        let mut to_see = BinaryHeap::new();
+        weights.insert(start, W::zero());
+        to_see.push(Reverse((W::zero(), start)));
+
+        let mut num_settled = 0;
+        while let Some(Reverse((weight, node))) = to_see.pop() {
+            if weights.get(&node).is_some_and(|w| weight > *w) {
+                continue;
+            }
+            num_settled += 1;
+            if num_settled > WITNESS_SEARCH_SETTLE_LIMIT {
+                break;
+            }
+            for next in self.out_neighbors[node].iter() {
+                if *next == excluded_node || self.contracted[*next] {
+                    continue;
+                }
+                let new_weight = weight + edges[&(node, *next)].weight;
+                if new_weight > max_weight {
+                    continue;
+                }
+                match weights.entry(*next) {
+                    Entry::Occupied(mut occupied) => {
+                        if *occupied.get() <= new_weight {
+                            continue;
+                        }
+                        occupied.insert(new_weight);
+                    }
+                    Entry::Vacant(vacant) => {
+                        vacant.insert(new_weight);
+                    }
+                }
+                to_see.push(Reverse((new_weight, *next)));
+            }
+        }
+        weights
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3o::{CellIndex, LatLng, Resolution};
+
+    use crate::algorithm::graph::dijkstra::edge_dijkstra;
+    use crate::container::treemap::H3Treemap;
+    use crate::graph::{GetEdge, H3EdgeGraph, PreparedH3EdgeGraph};
+
+    use super::ContractedGraph;
+
+    /// grid with pseudo-random weights and a few missing edges
+    fn make_graph(center: CellIndex) -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(center.resolution());
+        for cell in center.grid_disk::<Vec<_>>(12) {
+            for edge in cell.edges() {
+                let mut state = u64::from(edge);
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                if state % 11 != 0 {
+                    graph.add_edge(edge, (state % 40) as u32 + 1);
+                }
+            }
+        }
+        graph.try_into().unwrap()
+    }
+
+    #[test]
+    fn ch_matches_dijkstra() {
+        let center = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let graph = make_graph(center);
+        let ch_graph = ContractedGraph::from_prepared_graph(&graph).unwrap();
+        assert!(ch_graph.num_nodes() > 400);
+
+        let origins: Vec<_> = center.grid_ring_fast(5).flatten().step_by(3).collect();
+        let destinations: Vec<_> = center.grid_ring_fast(9).flatten().step_by(4).collect();
+        let mut destinations_treemap: H3Treemap<CellIndex> = Default::default();
+        for destination in destinations.iter() {
+            destinations_treemap.insert(*destination);
+        }
+
+        for origin in origins {
+            let dijkstra_paths =
+                edge_dijkstra(&graph, origin, &destinations_treemap, None).unwrap();
+            for destination in destinations.iter() {
+                let ch_path = ch_graph.shortest_path(origin, *destination).unwrap();
+                let dijkstra_path = dijkstra_paths
+                    .iter()
+                    .find(|path| path.destination_cell == *destination);
+
+                match (ch_path, dijkstra_path) {
+                    (Some(ch_path), Some(dijkstra_path)) => {
+                        assert_eq!(ch_path.cost, dijkstra_path.cost);
+                        assert_eq!(ch_path.origin_cell, origin);
+                        assert_eq!(ch_path.destination_cell, *destination);
+
+                        // the unpacked edges add up to the cost
+                        let edges = ch_path.directed_edge_path.edges();
+                        let edges_cost: u32 = edges
+                            .iter()
+                            .map(|edge| graph.get_edge(*edge).unwrap().weight)
+                            .sum();
+                        assert_eq!(edges_cost, ch_path.cost);
+                    }
+                    (None, None) => (),
+                    _ => panic!("reachability differs between CH and dijkstra"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ch_trivial_queries() {
+        let center = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let graph = make_graph(center);
+        let ch_graph = ContractedGraph::from_prepared_graph(&graph).unwrap();
+
+        let path = ch_graph.shortest_path(center, center).unwrap().unwrap();
+        assert!(path.is_empty());
+        assert_eq!(path.cost, 0);
+
+        let outside = center.grid_ring_fast(20).flatten().next().unwrap();
+        assert!(ch_graph.shortest_path(center, outside).unwrap().is_none());
+    }
+}
@@ -1,7 +1,8 @@
 use std::borrow::Borrow;
 
 use geo::algorithm::simplify::Simplify;
-use geo::{MultiPolygon, Polygon};
+use geo::{CoordsIter, MultiPolygon, Polygon, Rect};
+use once_cell::sync::OnceCell;
 
 use crate::algorithm::resolution::transform_resolution;
 use crate::container::CellSet;
@@ -24,10 +25,65 @@ pub trait CoveredArea {
     /// A slight simplification will be applied to the output geometry and
     /// eventual holes will be removed.
     fn covered_area(&self, reduce_resolution_by: u8) -> Result<MultiPolygon<f64>, Self::Error>;
+
+    /// calculates a [`MultiPolygon`] of the area covered by a graph with at most
+    /// `target_max_vertices` vertices.
+    ///
+    /// Selects the smallest resolution reduction for [`CoveredArea::covered_area`] staying within
+    /// `target_max_vertices`. When even the coarsest resolution exceeds it, the geometry
+    /// of the coarsest resolution is returned.
+    fn covered_area_auto(
+        &self,
+        target_max_vertices: usize,
+    ) -> Result<MultiPolygon<f64>, Self::Error>;
+}
+
+/// Cache of the covered areas of a graph which can not be modified anymore.
+///
+/// The areas are cached per resolution they are calculated at.
+#[derive(Clone, Default)]
+pub(crate) struct CoveredAreaCache {
+    covered_areas: [OnceCell<MultiPolygon<f64>>; 16],
+    bounding_rect: OnceCell<Option<Rect<f64>>>,
+}
+
+impl CoveredAreaCache {
+    /// get the covered area at `resolution`, `calculate` is only called when it is not cached yet.
+    pub(crate) fn covered_area<F>(
+        &self,
+        resolution: Resolution,
+        calculate: F,
+    ) -> Result<MultiPolygon<f64>, Error>
+    where
+        F: FnOnce() -> Result<MultiPolygon<f64>, Error>,
+    {
+        self.covered_areas[u8::from(resolution) as usize]
+            .get_or_try_init(calculate)
+            .cloned()
+    }
+
+    pub(crate) fn bounding_rect<F>(&self, calculate: F) -> Option<Rect<f64>>
+    where
+        F: FnOnce() -> Option<Rect<f64>>,
+    {
+        *self.bounding_rect.get_or_init(calculate)
+    }
+}
+
+/// the resolution the covered area is calculated at.
+pub(crate) fn covered_area_resolution(
+    cell_iter_resolution: Resolution,
+    reduce_resolution_by: u8,
+) -> Resolution {
+    let r: u8 = cell_iter_resolution.into();
+    r.saturating_sub(reduce_resolution_by).try_into().unwrap()
 }
 
 /// calculates a [`MultiPolygon`] of the area covered by a [`CellIndex`] iterator.
-pub(crate) fn cells_covered_area<I>(
+///
+/// The cells are reduced in resolution by `reduce_resolution_by` first. See
+/// [`CoveredArea::covered_area`] for the simplification applied to the output geometry.
+pub fn cells_covered_area<I>(
     cell_iter: I,
     cell_iter_resolution: Resolution,
     reduce_resolution_by: u8,
@@ -36,11 +92,11 @@ where
     I: IntoIterator,
     I::Item: Borrow<CellIndex>,
 {
-    let t_res: Resolution = {
-        let r: u8 = cell_iter_resolution.into();
-        r.saturating_sub(reduce_resolution_by).try_into().unwrap()
-    };
-    let cells: CellSet = transform_resolution(cell_iter, t_res).collect();
+    let t_res = covered_area_resolution(cell_iter_resolution, reduce_resolution_by);
+    cellset_covered_area(transform_resolution(cell_iter, t_res).collect())
+}
+
+fn cellset_covered_area(cells: CellSet) -> Result<MultiPolygon<f64>, Error> {
     Ok(MultiPolygon::new(
         cells
             .into_iter()
@@ -52,3 +108,91 @@ where
             .collect::<Vec<_>>(),
     ))
 }
+
+/// calculates a [`MultiPolygon`] of the area covered by a [`CellIndex`] iterator with at most
+/// `target_max_vertices` vertices.
+pub(crate) fn cells_covered_area_auto<I>(
+    cell_iter: I,
+    cell_iter_resolution: Resolution,
+    target_max_vertices: usize,
+) -> Result<MultiPolygon<f64>, Error>
+where
+    I: IntoIterator,
+    I::Item: Borrow<CellIndex>,
+{
+    cells_covered_area_auto_cached(
+        cell_iter,
+        cell_iter_resolution,
+        target_max_vertices,
+        &CoveredAreaCache::default(),
+    )
+}
+
+/// Variant of [`cells_covered_area_auto`] using and filling the given `cache`.
+pub(crate) fn cells_covered_area_auto_cached<I>(
+    cell_iter: I,
+    cell_iter_resolution: Resolution,
+    target_max_vertices: usize,
+    cache: &CoveredAreaCache,
+) -> Result<MultiPolygon<f64>, Error>
+where
+    I: IntoIterator,
+    I::Item: Borrow<CellIndex>,
+{
+    let mut cells: CellSet = cell_iter.into_iter().map(|c| *c.borrow()).collect();
+    let mut resolution = cell_iter_resolution;
+    loop {
+        // estimate the number of vertices by the number of cells on the boundary of the
+        // area before building the geometry, to skip building overly complex geometries.
+        if estimate_num_vertices(&cells) <= target_max_vertices || resolution == Resolution::Zero {
+            let multi_polygon =
+                cache.covered_area(resolution, || cellset_covered_area(cells.clone()))?;
+            if multi_polygon.coords_count() <= target_max_vertices || resolution == Resolution::Zero
+            {
+                return Ok(multi_polygon);
+            }
+        }
+        resolution = resolution.pred().unwrap_or(Resolution::Zero);
+        cells = transform_resolution(cells.iter(), resolution).collect();
+    }
+}
+
+/// the outline of the area passes through each cell on its boundary with at least two vertices.
+fn estimate_num_vertices(cells: &CellSet) -> usize {
+    cells
+        .iter()
+        .filter(|cell| {
+            cell.grid_disk::<Vec<_>>(1)
+                .iter()
+                .any(|neighbor| !cells.contains(neighbor))
+        })
+        .count()
+        * 2
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::CoordsIter;
+    use h3o::{LatLng, Resolution};
+
+    use crate::algorithm::graph::CoveredArea;
+    use crate::graph::H3EdgeGraph;
+
+    #[test]
+    fn covered_area_auto_stays_within_vertex_budget() {
+        let center = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let mut graph = H3EdgeGraph::new(Resolution::Ten);
+        for cell in center.grid_disk::<Vec<_>>(30) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, 1u32);
+            }
+        }
+
+        let target_max_vertices = 60;
+        assert!(graph.covered_area(0).unwrap().coords_count() > target_max_vertices);
+
+        let covered_area = graph.covered_area_auto(target_max_vertices).unwrap();
+        assert!(!covered_area.0.is_empty());
+        assert!(covered_area.coords_count() <= target_max_vertices);
+    }
+}
@@ -5,6 +5,7 @@ use std::ops::Add;
 
 use num_traits::Zero;
 
+use crate::algorithm::graph::dijkstra::BucketKey;
 use crate::algorithm::graph::path::Path;
 use crate::algorithm::graph::shortest_path::ShortestPathOptions;
 use crate::algorithm::graph::{NearestGraphNodes, ShortestPathManyToMany};
@@ -27,6 +28,16 @@ pub struct ExclusionDiff<T> {
     pub after_cell_exclusion: Vec<T>,
 }
 
+impl<T> ExclusionDiff<T>
+where
+    T: PartialEq,
+{
+    /// the exclusion of the cells changed the results
+    pub fn is_changed(&self) -> bool {
+        self.before_cell_exclusion != self.after_cell_exclusion
+    }
+}
+
 /// "Differential" routing calculates the shortest path from (multiple) origin cells
 /// to the `N` nearest destinations.
 /// This done once to the un-modified graph, and once the the graph with a set of nodes
@@ -74,7 +85,7 @@ where
 
 impl<G, W> DifferentialShortestPath<W> for G
 where
-    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync,
+    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync + BucketKey,
     G: GetCellEdges<EdgeWeightType = W>
         + GetCellNode
         + HasH3Resolution
@@ -110,20 +121,21 @@ where
         let destination_cells =
             check_resolution_and_collect(destination_cells, self.h3_resolution())?;
 
-        let mut paths_before = self.shortest_path_many_to_many_map(
+        let mut paths_before = ignore_empty_origin_set(self.shortest_path_many_to_many_map(
             &origin_cells,
             &destination_cells,
             options,
             &path_transform_fn,
-        )?;
+        ))?;
 
         let exclude_wrapper = ExcludeCells::new(self, exclude_cells);
-        let mut paths_after = exclude_wrapper.shortest_path_many_to_many_map(
-            &origin_cells,
-            &destination_cells,
-            options,
-            path_transform_fn,
-        )?;
+        let mut paths_after =
+            ignore_empty_origin_set(exclude_wrapper.shortest_path_many_to_many_map(
+                &origin_cells,
+                &destination_cells,
+                options,
+                path_transform_fn,
+            ))?;
 
         let mut out_diffs =
             CellMap::with_capacity_and_hasher(paths_before.len(), RandomState::default());
@@ -140,6 +152,17 @@ where
     }
 }
 
+/// origins which are all located within the excluded cells are not an error here, there
+/// just are no paths to compare.
+fn ignore_empty_origin_set<O>(
+    result: Result<CellMap<Vec<O>>, Error>,
+) -> Result<CellMap<Vec<O>>, Error> {
+    match result {
+        Err(Error::EmptyOriginSet) => Ok(Default::default()),
+        result => result,
+    }
+}
+
 fn check_resolution_and_collect<I>(
     in_cells: I,
     h3_resolution: Resolution,
@@ -1,7 +1,7 @@
 use ahash::RandomState;
 use h3o::{CellIndex, DirectedEdgeIndex};
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, VecDeque};
 use std::ops::Add;
 
 use crate::algorithm::graph::path::{DirectedEdgePath, Path};
@@ -143,6 +143,65 @@ pub fn edge_dijkstra<G, W>(
 where
     G: GetCellEdges<EdgeWeightType = W>,
     W: Zero + Ord + Copy + Add,
+{
+    edge_dijkstra_with_queue(
+        graph,
+        origin_cell,
+        destinations,
+        num_destinations_to_reach,
+        BinaryHeapQueue::default(),
+    )
+}
+
+/// Variant of [`edge_dijkstra`] using the given `queue` as priority queue of the cells
+/// still to visit.
+///
+/// All queue implementations find paths of the same costs. When there are multiple paths of the
+/// same cost to a destination, the queues may pick different ones.
+/// See [`BucketQueue`] for when it is preferable over the default [`BinaryHeapQueue`].
+pub fn edge_dijkstra_with_queue<G, W, Q>(
+    graph: &G,
+    origin_cell: CellIndex,
+    destinations: &H3Treemap<CellIndex>,
+    num_destinations_to_reach: Option<usize>,
+    queue: Q,
+) -> Result<Vec<Path<W>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Zero + Ord + Copy + Add,
+    Q: DijkstraQueue<W>,
+{
+    Decompressor::with_thread_local(|decompressor| {
+        edge_dijkstra_with_decompressor(
+            graph,
+            origin_cell,
+            destinations,
+            num_destinations_to_reach,
+            queue,
+            None,
+            decompressor,
+        )
+    })
+}
+
+/// Variant of [`edge_dijkstra_with_queue`] using the given `decompressor` to unpack
+/// fastforwards, so it can be reused for many subsequent routings.
+///
+/// `forbidden_first_edge` is an edge leaving the `origin_cell` which will not be followed
+/// when expanding the origin. It may still be part of paths reaching the origin again later.
+pub(crate) fn edge_dijkstra_with_decompressor<G, W, Q>(
+    graph: &G,
+    origin_cell: CellIndex,
+    destinations: &H3Treemap<CellIndex>,
+    num_destinations_to_reach: Option<usize>,
+    mut to_see: Q,
+    forbidden_first_edge: Option<DirectedEdgeIndex>,
+    decompressor: &mut Decompressor,
+) -> Result<Vec<Path<W>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W>,
+    W: Zero + Ord + Copy + Add,
+    Q: DijkstraQueue<W>,
 {
     // this is the main exit condition. Stop after this many destinations have been reached or
     // the complete graph has been traversed.
@@ -150,14 +209,10 @@ where
         .unwrap_or_else(|| destinations.len())
         .min(destinations.len());
 
-    let mut to_see = BinaryHeap::new();
     let mut parents: IndexMap<CellIndex, DijkstraEntry<W>, RandomState> = IndexMap::default();
     let mut destinations_reached = CellSet::default();
 
-    to_see.push(SmallestHolder {
-        weight: W::zero(),
-        index: 0,
-    });
+    to_see.push(W::zero(), 0);
     parents.insert(
         origin_cell,
         DijkstraEntry {
@@ -166,7 +221,7 @@ where
             edge: None,
         },
     );
-    while let Some(SmallestHolder { weight, index }) = to_see.pop() {
+    while let Some((weight, index)) = to_see.pop() {
         let (cell, dijkstra_entry) = parents.get_index(index).unwrap();
         if destinations.contains(cell)
             && destinations_reached.insert(*cell)
@@ -183,6 +238,11 @@ where
         }
 
         for (succeeding_edge, succeeding_edge_value) in graph.get_edges_originating_from(*cell) {
+            // the origin is always the first entry of the parents.
+            if index == 0 && forbidden_first_edge == Some(succeeding_edge) {
+                continue;
+            }
+
             // use the fastforward if it does not contain any destination. If it would
             // contain a destination we would "jump over" it when we would use the fastforward.
             let (dijkstra_edge, new_weight) = if let Some((fastforward, fastforward_weight)) =
@@ -229,10 +289,7 @@ where
                     }
                 }
             }
-            to_see.push(SmallestHolder {
-                weight: new_weight,
-                index: n,
-            });
+            to_see.push(new_weight, n);
         }
     }
 
@@ -250,19 +307,18 @@ where
         })
         .collect();
 
-    edge_dijkstra_assemble_paths(origin_cell, parents_map, destinations_reached)
+    edge_dijkstra_assemble_paths(origin_cell, parents_map, destinations_reached, decompressor)
 }
 
 fn edge_dijkstra_assemble_paths<'a, W>(
     origin_cell: CellIndex,
     parents_map: CellMap<(&'a CellIndex, &DijkstraEntry<'a, W>)>,
     destinations_reached: CellSet,
+    decompressor: &mut Decompressor,
 ) -> Result<Vec<Path<W>>, Error>
 where
     W: Zero + Ord + Copy,
 {
-    let mut decompressor = Decompressor::default();
-
     // assemble the paths
     let mut paths = Vec::with_capacity(destinations_reached.len());
     for destination_cell in destinations_reached {
@@ -313,6 +369,120 @@ where
     Ok(paths)
 }
 
+/// Priority queue of the cells still to be visited by the dijkstra.
+///
+/// Entries are the aggregated weight to reach a cell and the index of the cell. `pop`
+/// must return the entry with the smallest weight.
+pub trait DijkstraQueue<W> {
+    fn push(&mut self, weight: W, index: usize);
+
+    fn pop(&mut self) -> Option<(W, usize)>;
+}
+
+/// The default queue, a binary heap.
+pub struct BinaryHeapQueue<W>(BinaryHeap<SmallestHolder<W>>);
+
+impl<W: Ord> Default for BinaryHeapQueue<W> {
+    fn default() -> Self {
+        Self(BinaryHeap::new())
+    }
+}
+
+impl<W: Ord> DijkstraQueue<W> for BinaryHeapQueue<W> {
+    fn push(&mut self, weight: W, index: usize) {
+        self.0.push(SmallestHolder { weight, index });
+    }
+
+    fn pop(&mut self) -> Option<(W, usize)> {
+        self.0.pop().map(|sh| (sh.weight, sh.index))
+    }
+}
+
+/// Maps weights to the bucket of a [`BucketQueue`].
+///
+/// The key must not decrease with increasing weights.
+pub trait BucketKey {
+    fn bucket_key(&self) -> u64;
+}
+
+macro_rules! bucket_key_impl {
+    ($($t:ty),*) => {
+        $(
+            impl BucketKey for $t {
+                fn bucket_key(&self) -> u64 {
+                    *self as u64
+                }
+            }
+        )*
+    };
+}
+
+bucket_key_impl!(u8, u16, u32, u64, usize);
+
+/// Bucket queue for weights which are discretized into a limited range of keys.
+///
+/// Pushing and popping is mostly constant time instead of being logarithmic to the
+/// number of queued entries as with the [`BinaryHeapQueue`], so this queue wins on graphs
+/// with huge frontiers - many edges with similar weights. It uses one bucket for every
+/// key between the smallest and the largest queued weight, so it falls behind when
+/// the weights are spread over a wide range of keys. Choose the `bucket_width` so the
+/// number of buckets stays moderate.
+///
+/// Entries within a bucket are still ordered by their weight, so the found paths have the
+/// same costs as the ones of the [`BinaryHeapQueue`]. Ties between entries of the same weight
+/// may be broken differently, so paths of equal cost may consist of different edges.
+pub struct BucketQueue<W> {
+    buckets: VecDeque<BinaryHeap<SmallestHolder<W>>>,
+
+    /// key of the first bucket in `buckets`
+    first_key: u64,
+    bucket_width: u64,
+}
+
+impl<W> BucketQueue<W> {
+    /// `bucket_width` is the number of keys sharing the same bucket.
+    pub fn with_bucket_width(bucket_width: u64) -> Self {
+        Self {
+            buckets: Default::default(),
+            first_key: 0,
+            bucket_width: bucket_width.max(1),
+        }
+    }
+}
+
+impl<W> Default for BucketQueue<W> {
+    fn default() -> Self {
+        Self::with_bucket_width(1)
+    }
+}
+
+impl<W: Ord + BucketKey> DijkstraQueue<W> for BucketQueue<W> {
+    fn push(&mut self, weight: W, index: usize) {
+        let key = weight.bucket_key() / self.bucket_width;
+        if self.buckets.is_empty() {
+            self.first_key = key;
+        }
+        // dijkstra never pushes weights smaller than the last popped one, keys below the first
+        // bucket only happen for entries belonging to the first bucket anyway.
+        let position = key.saturating_sub(self.first_key) as usize;
+        if position >= self.buckets.len() {
+            self.buckets.resize_with(position + 1, BinaryHeap::new);
+        }
+        self.buckets[position].push(SmallestHolder { weight, index });
+    }
+
+    fn pop(&mut self) -> Option<(W, usize)> {
+        loop {
+            let bucket = self.buckets.front_mut()?;
+            if let Some(sh) = bucket.pop() {
+                return Some((sh.weight, sh.index));
+            }
+            self.buckets.pop_front();
+            self.first_key += 1;
+        }
+    }
+}
+
 struct SmallestHolder<W> {
     weight: W,
     index: usize,
@@ -341,7 +511,13 @@ impl<W: Ord> Ord for SmallestHolder<W> {
 
 #[cfg(test)]
 mod tests {
-    use crate::algorithm::graph::dijkstra::SmallestHolder;
+    use h3o::{CellIndex, LatLng, Resolution};
+
+    use crate::algorithm::graph::dijkstra::{
+        edge_dijkstra_with_queue, BinaryHeapQueue, BucketQueue, DijkstraQueue, SmallestHolder,
+    };
+    use crate::container::treemap::H3Treemap;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
 
     #[test]
     fn smallest_holder_partial_eq() {
@@ -368,4 +544,78 @@ mod tests {
         };
         assert!(sh2 > sh1);
     }
+
+    fn check_queue_order<Q: DijkstraQueue<u32>>(mut queue: Q) {
+        for (weight, index) in [(12, 0), (3, 1), (30, 2), (7, 3), (4, 4)] {
+            queue.push(weight, index);
+        }
+        assert_eq!(queue.pop(), Some((3, 1)));
+        queue.push(5, 5);
+        let popped: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(popped, vec![(4, 4), (5, 5), (7, 3), (12, 0), (30, 2)]);
+    }
+
+    #[test]
+    fn queue_order() {
+        check_queue_order(BinaryHeapQueue::default());
+        check_queue_order(BucketQueue::default());
+        check_queue_order(BucketQueue::with_bucket_width(10));
+    }
+
+    #[test]
+    fn queues_produce_equal_costs() {
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+
+        // grid with pseudo-random weights
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for cell in origin.grid_disk::<Vec<_>>(10) {
+            for edge in cell.edges() {
+                let mut state = u64::from(edge);
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                graph.add_edge(edge, (state % 50) as u32 + 1);
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let mut destinations: H3Treemap<CellIndex> = Default::default();
+        for cell in origin.grid_ring_fast(8).flatten() {
+            destinations.insert(cell);
+        }
+
+        let heap_paths = edge_dijkstra_with_queue(
+            &prepared_graph,
+            origin,
+            &destinations,
+            None,
+            BinaryHeapQueue::default(),
+        )
+        .unwrap();
+        assert_eq!(heap_paths.len(), destinations.len());
+
+        for bucket_width in [1, 7] {
+            let bucket_paths = edge_dijkstra_with_queue(
+                &prepared_graph,
+                origin,
+                &destinations,
+                None,
+                BucketQueue::with_bucket_width(bucket_width),
+            )
+            .unwrap();
+
+            // paths of the same cost may differ in the edges in case of ties, so only the
+            // costs are compared.
+            assert_eq!(
+                heap_paths
+                    .iter()
+                    .map(|path| (path.destination_cell, path.cost))
+                    .collect::<Vec<_>>(),
+                bucket_paths
+                    .iter()
+                    .map(|path| (path.destination_cell, path.cost))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
 }
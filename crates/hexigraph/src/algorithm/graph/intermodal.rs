@@ -0,0 +1,364 @@
+//! Routing across graphs of multiple modes of transport.
+//!
+//! An [`IntermodalGraph`] combines graphs of the same h3 resolution - for example a
+//! pedestrian graph and a car graph - and allows to switch between these at transfer
+//! cells. Every switch of the mode adds the transfer penalty to the cost of the route.
+//!
+//! The fastforwards of the graphs are not used.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Add;
+
+use ahash::RandomState;
+use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
+use indexmap::map::Entry::{Occupied, Vacant};
+use indexmap::map::IndexMap;
+use num_traits::Zero;
+
+use crate::algorithm::graph::path::DirectedEdgePath;
+use crate::container::CellSet;
+use crate::error::Error;
+use crate::graph::{GetCellEdges, PreparedH3EdgeGraph};
+use crate::HasH3Resolution;
+
+/// Graphs of multiple modes of transport connected by transfer cells.
+///
+/// The modes are identified by the index of their graph.
+pub struct IntermodalGraph<W> {
+    h3_resolution: Resolution,
+    graphs: Vec<PreparedH3EdgeGraph<W>>,
+    transfer_cells: CellSet,
+    transfer_penalty: W,
+}
+
+/// A part of an [`IntermodalPath`] using a single mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntermodalLeg {
+    /// index of the graph used within the [`IntermodalGraph`].
+    pub mode: usize,
+
+    pub directed_edge_path: DirectedEdgePath,
+}
+
+/// A path through an [`IntermodalGraph`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntermodalPath<W> {
+    pub origin_cell: CellIndex,
+    pub destination_cell: CellIndex,
+
+    /// cost of all legs including the penalties of the transfers.
+    pub cost: W,
+
+    /// the legs of the path in the order they are travelled. A new leg starts at
+    /// each transfer.
+    pub legs: Vec<IntermodalLeg>,
+}
+
+impl<W> IntermodalPath<W> {
+    /// number of mode switches along the path.
+    pub fn num_transfers(&self) -> usize {
+        self.legs.len().saturating_sub(1)
+    }
+}
+
+struct IntermodalEntry<W> {
+    weight: W,
+    parent_index: usize,
+
+    /// the edge which lead to the cell. `None` for the origin and transfers.
+    edge: Option<DirectedEdgeIndex>,
+}
+
+impl<W> IntermodalGraph<W>
+where
+    W: Ord + Copy + Add<Output = W> + Zero,
+{
+    /// Combine the `graphs`, which all need to be of the same h3 resolution.
+    ///
+    /// `transfer_penalty` is added to the cost of a route for every change of the mode.
+    pub fn new(graphs: Vec<PreparedH3EdgeGraph<W>>, transfer_penalty: W) -> Result<Self, Error> {
+        let h3_resolution = graphs
+            .first()
+            .map(|graph| graph.h3_resolution())
+            .ok_or(Error::EmptyGraphSet)?;
+        if let Some(graph) = graphs
+            .iter()
+            .find(|graph| graph.h3_resolution() != h3_resolution)
+        {
+            return Err(Error::MixedH3Resolutions(
+                h3_resolution,
+                graph.h3_resolution(),
+            ));
+        }
+        Ok(Self {
+            h3_resolution,
+            graphs,
+            transfer_cells: Default::default(),
+            transfer_penalty,
+        })
+    }
+
+    /// allow switching between all modes at `cell`.
+    pub fn add_transfer_cell(&mut self, cell: CellIndex) -> Result<(), Error> {
+        if cell.resolution() != self.h3_resolution {
+            return Err(Error::MixedH3Resolutions(
+                self.h3_resolution,
+                cell.resolution(),
+            ));
+        }
+        self.transfer_cells.insert(cell);
+        Ok(())
+    }
+
+    pub fn num_modes(&self) -> usize {
+        self.graphs.len()
+    }
+
+    pub fn graph(&self, mode: usize) -> Option<&PreparedH3EdgeGraph<W>> {
+        self.graphs.get(mode)
+    }
+
+    pub fn transfer_penalty(&self) -> W {
+        self.transfer_penalty
+    }
+
+    /// Find the cheapest path from `origin_cell` - starting with `origin_mode` - to
+    /// `destination_cell`. The destination may be reached using any of the modes.
+    ///
+    /// Returns `None` when the destination is not reachable.
+    pub fn shortest_path(
+        &self,
+        origin_cell: CellIndex,
+        origin_mode: usize,
+        destination_cell: CellIndex,
+    ) -> Result<Option<IntermodalPath<W>>, Error> {
+        if origin_mode >= self.graphs.len() {
+            return Err(Error::IndexOutOfBounds(origin_mode));
+        }
+        for cell in [origin_cell, destination_cell] {
+            if cell.resolution() != self.h3_resolution {
+                return Err(Error::MixedH3Resolutions(
+                    self.h3_resolution,
+                    cell.resolution(),
+                ));
+            }
+        }
+
+        // the state of the dijkstra is the combination of the cell and the mode
+        let mut parents: IndexMap<(CellIndex, usize), IntermodalEntry<W>, RandomState> =
+            IndexMap::default();
+        let mut to_see = BinaryHeap::new();
+
+        parents.insert(
+            (origin_cell, origin_mode),
+            IntermodalEntry {
+                weight: W::zero(),
+                parent_index: usize::MAX,
+                edge: None,
+            },
+        );
+        to_see.push(Reverse((W::zero(), 0)));
+
+        while let Some(Reverse((weight, index))) = to_see.pop() {
+            let (&(cell, mode), entry) = parents.get_index(index).unwrap();
+
+            // We may have inserted a node several time into the binary heap if we found
+            // a better way to access it. Ensure that we are currently dealing with the
+            // best path and discard the others.
+            if weight > entry.weight {
+                continue;
+            }
+            if cell == destination_cell {
+                return Ok(Some(self.assemble_path(
+                    &parents,
+                    index,
+                    origin_cell,
+                    destination_cell,
+                )));
+            }
+
+            let mut successors: Vec<_> = self.graphs[mode]
+                .get_edges_originating_from(cell)
+                .into_iter()
+                .map(|(edge, edge_weight)| {
+                    (
+                        (edge.destination(), mode),
+                        weight + edge_weight.weight,
+                        Some(edge),
+                    )
+                })
+                .collect();
+            if self.transfer_cells.contains(&cell) {
+                successors.extend(
+                    (0..self.graphs.len())
+                        .filter(|other_mode| *other_mode != mode)
+                        .map(|other_mode| {
+                            ((cell, other_mode), weight + self.transfer_penalty, None)
+                        }),
+                );
+            }
+
+            for (state, new_weight, edge) in successors {
+                let n = match parents.entry(state) {
+                    Vacant(e) => {
+                        let n = e.index();
+                        e.insert(IntermodalEntry {
+                            weight: new_weight,
+                            parent_index: index,
+                            edge,
+                        });
+                        n
+                    }
+                    Occupied(mut e) => {
+                        if e.get().weight > new_weight {
+                            e.insert(IntermodalEntry {
+                                weight: new_weight,
+                                parent_index: index,
+                                edge,
+                            });
+                            e.index()
+                        } else {
+                            continue;
+                        }
+                    }
+                };
+                to_see.push(Reverse((new_weight, n)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn assemble_path(
+        &self,
+        parents: &IndexMap<(CellIndex, usize), IntermodalEntry<W>, RandomState>,
+        destination_index: usize,
+        origin_cell: CellIndex,
+        destination_cell: CellIndex,
+    ) -> IntermodalPath<W> {
+        // walk back from the destination to the origin
+        let mut states = vec![];
+        let mut index = destination_index;
+        while index != usize::MAX {
+            let (&(cell, mode), entry) = parents.get_index(index).unwrap();
+            states.push((cell, mode, entry.edge));
+            index = entry.parent_index;
+        }
+        states.reverse();
+
+        let mut legs = vec![];
+        let mut leg_edges = vec![];
+        let (mut leg_cell, mut leg_mode, _) = states[0];
+        for (cell, mode, edge) in states.into_iter().skip(1) {
+            if let Some(edge) = edge {
+                leg_edges.push(edge);
+            } else {
+                // transfer to another mode
+                legs.push(build_leg(
+                    leg_cell,
+                    leg_mode,
+                    std::mem::take(&mut leg_edges),
+                ));
+                leg_mode = mode;
+                leg_cell = cell;
+            }
+        }
+        legs.push(build_leg(leg_cell, leg_mode, leg_edges));
+
+        IntermodalPath {
+            origin_cell,
+            destination_cell,
+            cost: parents[destination_index].weight,
+            legs,
+        }
+    }
+}
+
+fn build_leg(start_cell: CellIndex, mode: usize, edges: Vec<DirectedEdgeIndex>) -> IntermodalLeg {
+    IntermodalLeg {
+        mode,
+        directed_edge_path: if edges.is_empty() {
+            DirectedEdgePath::OriginIsDestination(start_cell)
+        } else {
+            DirectedEdgePath::DirectedEdgeSequence(edges)
+        },
+    }
+}
+
+impl<W> HasH3Resolution for IntermodalGraph<W> {
+    fn h3_resolution(&self) -> Resolution {
+        self.h3_resolution
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3o::{CellIndex, LatLng, Resolution};
+
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    use super::IntermodalGraph;
+
+    const WALK: usize = 0;
+    const DRIVE: usize = 1;
+
+    fn line_graph(cells: &[CellIndex], weight: u32) -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(cells[0].resolution());
+        for w in cells.windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), weight);
+            graph.add_edge(w[1].edge(w[0]).unwrap(), weight);
+        }
+        graph.try_into().unwrap()
+    }
+
+    fn make_graph() -> (Vec<CellIndex>, IntermodalGraph<u32>) {
+        let start = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let end = start.grid_ring_fast(6).flatten().next().unwrap();
+        let cells: Vec<_> = start
+            .grid_path_cells(end)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(cells.len(), 7);
+
+        // walking is possible everywhere, while the road starts at the third cell
+        let graph =
+            IntermodalGraph::new(vec![line_graph(&cells, 10), line_graph(&cells[2..], 1)], 5)
+                .unwrap();
+        (cells, graph)
+    }
+
+    #[test]
+    fn route_switches_mode_at_transfer_cell() {
+        let (cells, mut graph) = make_graph();
+        graph.add_transfer_cell(cells[2]).unwrap();
+
+        let path = graph
+            .shortest_path(cells[0], WALK, cells[6])
+            .unwrap()
+            .unwrap();
+        assert_eq!(path.cost, 2 * 10 + 5 + 4);
+        assert_eq!(path.num_transfers(), 1);
+        assert_eq!(path.legs[0].mode, WALK);
+        assert_eq!(path.legs[0].directed_edge_path.cells(), cells[..3]);
+        assert_eq!(path.legs[1].mode, DRIVE);
+        assert_eq!(path.legs[1].directed_edge_path.cells(), cells[2..]);
+    }
+
+    #[test]
+    fn route_without_transfer_cells_stays_in_mode() {
+        let (cells, graph) = make_graph();
+        let path = graph
+            .shortest_path(cells[0], WALK, cells[6])
+            .unwrap()
+            .unwrap();
+        assert_eq!(path.cost, 6 * 10);
+        assert_eq!(path.num_transfers(), 0);
+        assert_eq!(path.legs[0].mode, WALK);
+
+        // the road is not reachable when starting with driving outside of it
+        assert!(graph
+            .shortest_path(cells[0], DRIVE, cells[6])
+            .unwrap()
+            .is_none());
+    }
+}
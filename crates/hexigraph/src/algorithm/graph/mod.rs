@@ -1,12 +1,21 @@
+pub use ch::ContractedGraph;
 pub use covered_area::CoveredArea;
 pub use differential_shortest_path::DifferentialShortestPath;
+pub use dijkstra::{
+    edge_dijkstra, edge_dijkstra_with_queue, BinaryHeapQueue, BucketKey, BucketQueue, DijkstraQueue,
+};
+pub use intermodal::IntermodalGraph;
 pub use nearest_graph_nodes::NearestGraphNodes;
 pub use shortest_path::{ShortestPath, ShortestPathManyToMany};
-pub use within_weight_threshold::{WithinWeightThreshold, WithinWeightThresholdMany};
+pub use within_weight_threshold::{
+    WithinWeightThreshold, WithinWeightThresholdMany, WithinWeightThresholdOptions,
+};
 
+pub mod ch;
 pub mod covered_area;
 pub mod differential_shortest_path;
 mod dijkstra;
+pub mod intermodal;
 pub mod nearest_graph_nodes;
 pub mod path;
 pub mod shortest_path;
@@ -3,15 +3,17 @@ use std::cmp::Ordering;
 use crate::algorithm::edge::cell_centroid_distance_m;
 use geo::LineString;
 use h3o::geom::ToGeo;
-use h3o::{CellIndex, DirectedEdgeIndex};
+use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
 
 use crate::algorithm::geom::edges_to_multilinestring;
+use crate::algorithm::resolution::transform_resolution;
+use crate::container::HashSet;
 
 use crate::error::Error;
 
 /// [DirectedEdgePath] describes a path between a cell and another.
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum DirectedEdgePath {
     /// path is empty as origin and destination are the same.
     OriginIsDestination(CellIndex),
@@ -108,8 +110,32 @@ impl DirectedEdgePath {
         }
     }
 
+    /// return the cells the path passes through at the coarser `h3_resolution`.
+    ///
+    /// Consecutive repeats of the same cell are removed, so the returned cells
+    /// still describe the path from its origin to its destination, just with
+    /// less detail.
+    pub fn simplify_to_cells_at_resolution(
+        &self,
+        h3_resolution: Resolution,
+    ) -> Result<Vec<CellIndex>, Error> {
+        let cells = self.cells();
+        if let Some(cell) = cells.first() {
+            if cell.resolution() < h3_resolution {
+                return Err(Error::TooHighH3Resolution(h3_resolution));
+            }
+        }
+        let mut coarse_cells: Vec<_> = transform_resolution(cells, h3_resolution).collect();
+        coarse_cells.dedup();
+        coarse_cells.shrink_to_fit();
+        Ok(coarse_cells)
+    }
+
     /// calculate the length of the path in meters using the exact length of the
-    /// contained edges
+    /// contained edges.
+    ///
+    /// The length is calculated on every call. [`Path::length_m`] provides the length
+    /// calculated once when the [`Path`] was created.
     pub fn length_m(&self) -> f64 {
         match self {
             Self::OriginIsDestination(_) => 0.0,
@@ -120,9 +146,12 @@ impl DirectedEdgePath {
     }
 }
 
+/// relative tolerance when comparing the cached path length to a recalculated one
+const LENGTH_M_RELATIVE_TOLERANCE: f64 = 1e-9;
+
 /// [Path] describes a path between a cell and another with an associated cost
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Path<W> {
     /// The cell the path starts at.
     ///
@@ -140,9 +169,45 @@ pub struct Path<W> {
 
     /// describes the path
     pub directed_edge_path: DirectedEdgePath,
+
+    /// length of the `directed_edge_path` in meters, calculated once during construction.
+    length_m: f64,
 }
 
 impl<W> Path<W> {
+    pub fn new(
+        origin_cell: CellIndex,
+        destination_cell: CellIndex,
+        cost: W,
+        directed_edge_path: DirectedEdgePath,
+    ) -> Self {
+        let length_m = directed_edge_path.length_m();
+        Self {
+            origin_cell,
+            destination_cell,
+            cost,
+            directed_edge_path,
+            length_m,
+        }
+    }
+
+    /// the length of the path in meters using the exact length of the
+    /// contained edges.
+    ///
+    /// The length is calculated when the path is created, so calling this is cheap.
+    #[inline]
+    pub fn length_m(&self) -> f64 {
+        debug_assert!(self.length_m_is_consistent());
+        self.length_m
+    }
+
+    /// check the cached length against the summed lengths of the edges
+    fn length_m_is_consistent(&self) -> bool {
+        let recalculated_length_m = self.directed_edge_path.length_m();
+        (self.length_m - recalculated_length_m).abs()
+            <= recalculated_length_m * LENGTH_M_RELATIVE_TOLERANCE
+    }
+
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.directed_edge_path.is_empty()
@@ -152,6 +217,43 @@ impl<W> Path<W> {
     pub fn len(&self) -> usize {
         self.directed_edge_path.len()
     }
+
+    /// hash of the route the path takes.
+    ///
+    /// Paths following the same edges share the same signature, regardless of their
+    /// `origin_cell`, `destination_cell` and `cost`. The signature is the 64bit FNV-1a hash
+    /// of the little-endian bytes of the edge indexes - or of the cell for paths where the
+    /// origin is the destination - so it is stable across processes and platforms.
+    pub fn route_signature(&self) -> u64 {
+        let indexes: Vec<u64> = match &self.directed_edge_path {
+            DirectedEdgePath::OriginIsDestination(cell) => vec![u64::from(*cell)],
+            DirectedEdgePath::DirectedEdgeSequence(edges) => {
+                edges.iter().map(|edge| u64::from(*edge)).collect()
+            }
+        };
+        fnv1a_64(indexes.iter().flat_map(|index| index.to_le_bytes()))
+    }
+}
+
+/// Remove paths following the same route.
+pub trait DedupByRoute {
+    /// remove all paths following the same route as a path earlier in the collection.
+    ///
+    /// Keeps the order of the remaining paths.
+    fn dedup_by_route(&mut self);
+}
+
+impl<W> DedupByRoute for Vec<Path<W>> {
+    fn dedup_by_route(&mut self) {
+        let keep: Vec<_> = {
+            let mut seen_routes = HashSet::default();
+            self.iter()
+                .map(|path| seen_routes.insert(&path.directed_edge_path))
+                .collect()
+        };
+        let mut keep_iter = keep.into_iter();
+        self.retain(|_| keep_iter.next().unwrap_or(true));
+    }
 }
 
 impl<W> TryFrom<(DirectedEdgePath, W)> for Path<W> {
@@ -160,15 +262,30 @@ impl<W> TryFrom<(DirectedEdgePath, W)> for Path<W> {
     fn try_from((path_directed_edges, cost): (DirectedEdgePath, W)) -> Result<Self, Self::Error> {
         let origin_cell = path_directed_edges.origin_cell()?;
         let destination_cell = path_directed_edges.destination_cell()?;
-        Ok(Self {
+        Ok(Self::new(
             origin_cell,
             destination_cell,
             cost,
-            directed_edge_path: path_directed_edges,
-        })
+            path_directed_edges,
+        ))
+    }
+}
+
+/// the cached length is derived from the `directed_edge_path` and is not considered
+impl<W> PartialEq for Path<W>
+where
+    W: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.origin_cell == other.origin_cell
+            && self.destination_cell == other.destination_cell
+            && self.cost == other.cost
+            && self.directed_edge_path == other.directed_edge_path
     }
 }
 
+impl<W> Eq for Path<W> where W: Eq {}
+
 impl PartialOrd<Self> for DirectedEdgePath {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -213,6 +330,15 @@ where
     }
 }
 
+/// 64bit FNV-1a hash of `bytes`
+fn fnv1a_64(bytes: impl IntoIterator<Item = u8>) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
 #[inline]
 fn index_or_zero(cell: Result<CellIndex, Error>) -> u64 {
     cell.map(|c| c.into()).unwrap_or(0)
@@ -220,9 +346,13 @@ fn index_or_zero(cell: Result<CellIndex, Error>) -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use h3o::DirectedEdgeIndex;
+    use geo::{Coord, LineString};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::{DirectedEdgeIndex, Resolution};
+
+    use crate::algorithm::edge::cell_centroid_distance_m;
 
-    use super::{DirectedEdgePath, Path};
+    use super::{fnv1a_64, DedupByRoute, DirectedEdgePath, Path};
 
     #[test]
     fn pathdirectededges_deterministic_ordering() {
@@ -275,4 +405,120 @@ mod tests {
         assert_eq!(paths[1], r2);
         assert_eq!(paths[2], r3);
     }
+
+    #[test]
+    fn length_m() {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.5, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Nine))
+        .collect();
+        let edges: Vec<_> = cells.windows(2).map(|w| w[0].edge(w[1]).unwrap()).collect();
+        let path: Path<_> = (DirectedEdgePath::DirectedEdgeSequence(edges.clone()), 1)
+            .try_into()
+            .unwrap();
+
+        // the cached length matches a fresh sum over the edges
+        let summed_length_m: f64 = edges.iter().copied().map(cell_centroid_distance_m).sum();
+        assert!(summed_length_m > 0.0);
+        assert!((path.length_m() - summed_length_m).abs() <= summed_length_m * 1e-9);
+        assert!(path.length_m_is_consistent());
+        assert_eq!(path.length_m(), path.length_m());
+
+        // a stale cache gets detected
+        let stale = Path {
+            directed_edge_path: DirectedEdgePath::DirectedEdgeSequence(edges[1..].to_vec()),
+            ..path.clone()
+        };
+        assert!(!stale.length_m_is_consistent());
+
+        let empty_path: Path<_> = (DirectedEdgePath::OriginIsDestination(cells[0]), 1)
+            .try_into()
+            .unwrap();
+        assert_eq!(empty_path.length_m(), 0.0);
+    }
+
+    #[test]
+    fn fnv1a_64_test_vectors() {
+        assert_eq!(fnv1a_64(*b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a_64(*b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a_64(*b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn route_signature_and_dedup() {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.4, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Nine))
+        .collect();
+        let edges: Vec<_> = cells.windows(2).map(|w| w[0].edge(w[1]).unwrap()).collect();
+
+        let route: Path<_> = (DirectedEdgePath::DirectedEdgeSequence(edges.clone()), 5)
+            .try_into()
+            .unwrap();
+        // requested from neighbouring cells, but connected to the graph at the same nodes
+        let substituted_route = Path {
+            origin_cell: cells[0].grid_disk::<Vec<_>>(1)[1],
+            destination_cell: cells[cells.len() - 1].grid_disk::<Vec<_>>(1)[1],
+            ..route.clone()
+        };
+        let other_route: Path<_> = (
+            DirectedEdgePath::DirectedEdgeSequence(edges[1..].to_vec()),
+            4,
+        )
+            .try_into()
+            .unwrap();
+
+        assert_ne!(route.origin_cell, substituted_route.origin_cell);
+        assert_eq!(route.route_signature(), substituted_route.route_signature());
+        assert_ne!(route.route_signature(), other_route.route_signature());
+
+        let mut paths = vec![
+            route.clone(),
+            other_route.clone(),
+            substituted_route,
+            other_route.clone(),
+        ];
+        paths.dedup_by_route();
+        assert_eq!(paths, vec![route, other_route]);
+    }
+
+    #[test]
+    fn simplify_to_cells_at_resolution() {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((24.2, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Nine))
+        .collect();
+        let path = DirectedEdgePath::DirectedEdgeSequence(
+            cells.windows(2).map(|w| w[0].edge(w[1]).unwrap()).collect(),
+        );
+        let full_cells = path.cells();
+        assert!(full_cells.len() > 100);
+
+        let coarse_cells = path
+            .simplify_to_cells_at_resolution(Resolution::Six)
+            .unwrap();
+        assert!(coarse_cells.len() * 10 < full_cells.len());
+        assert!(coarse_cells.windows(2).all(|w| w[0] != w[1]));
+        assert_eq!(
+            coarse_cells.first().copied(),
+            path.origin_cell().unwrap().parent(Resolution::Six)
+        );
+        assert_eq!(
+            coarse_cells.last().copied(),
+            path.destination_cell().unwrap().parent(Resolution::Six)
+        );
+
+        assert!(path
+            .simplify_to_cells_at_resolution(Resolution::Ten)
+            .is_err());
+    }
 }
@@ -1,14 +1,17 @@
 //! Dijkstra shortest-path routing.
 //!
-use h3o::CellIndex;
+use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
 use hashbrown::hash_map::Entry;
 use std::borrow::Borrow;
 use std::ops::Add;
 
-use crate::algorithm::graph::dijkstra::edge_dijkstra;
-use crate::algorithm::graph::path::Path;
+use crate::algorithm::graph::dijkstra::{
+    edge_dijkstra_with_decompressor, BinaryHeapQueue, BucketKey, BucketQueue,
+};
+use crate::algorithm::graph::path::{DirectedEdgePath, Path};
 use crate::algorithm::graph::NearestGraphNodes;
 use crate::algorithm::resolution::transform_resolution;
+use crate::container::block::Decompressor;
 use crate::container::treemap::H3Treemap;
 use crate::container::CellMap;
 use num_traits::Zero;
@@ -38,6 +41,41 @@ pub trait ShortestPathOptions {
     fn num_destinations_to_reach(&self) -> Option<usize> {
         None
     }
+
+    /// number of origin cells routed within a single task when routing from many origins
+    /// in parallel.
+    ///
+    /// Larger chunks reduce the scheduling overhead for very many origins. When not set,
+    /// each origin is an own task.
+    fn origin_chunk_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// the edge on which the origin cell was reached, for example derived from the heading
+    /// of a GPS position snapped to the graph.
+    ///
+    /// When set, routing will not start with a U-turn back along the reverse of this edge. This
+    /// only applies to the origin cell the edge leads to, other origins are not affected.
+    fn forbid_uturn_at_origin(&self) -> Option<DirectedEdgeIndex> {
+        None
+    }
+
+    /// include origin cells which are connected to the graph, but from which no destination
+    /// could be reached, in the results of the many-to-many routing with an empty vec of paths.
+    ///
+    /// By default these origins are omitted, which makes them indistinguishable from
+    /// origins which are not connected to the graph at all.
+    fn include_unreachable_origins(&self) -> bool {
+        false
+    }
+
+    /// use a [`BucketQueue`] with the given bucket width instead of the default
+    /// [`BinaryHeapQueue`] as the priority queue of the dijkstra.
+    ///
+    /// See [`BucketQueue`] for the graphs this is beneficial for.
+    fn bucket_queue_width(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// Default implementation of a type implementing the `ShortestPathOptions`
@@ -101,7 +139,9 @@ where
     /// `path_transform_fn` can be used to directly convert the paths to a less memory intensive
     /// type.
     ///
-    /// All cells must be in the h3 resolution of the graph.
+    /// All cells must be in the h3 resolution of the graph. Fails with [`Error::EmptyOriginSet`]
+    /// when none of the origins is part of the graph, so this is distinguishable from origins
+    /// without any reachable destinations.
     fn shortest_path_many_to_many_map<I, OPT, PM, O>(
         &self,
         origin_cells: I,
@@ -115,12 +155,153 @@ where
         OPT: ShortestPathOptions + Send + Sync,
         PM: Fn(Path<W>) -> Result<O, Error> + Send + Sync,
         O: Send + Ord + Clone;
+
+    /// Variant of [`ShortestPathManyToMany::shortest_path_many_to_many`] routing to
+    /// already [`PreparedDestinations`].
+    #[inline]
+    fn shortest_path_many_to_many_prepared<I, OPT>(
+        &self,
+        origin_cells: I,
+        destinations: &PreparedDestinations,
+        options: &OPT,
+    ) -> Result<CellMap<Vec<Path<W>>>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+        OPT: ShortestPathOptions + Send + Sync,
+    {
+        self.shortest_path_many_to_many_prepared_map(origin_cells, destinations, options, Ok)
+    }
+
+    /// Variant of [`ShortestPathManyToMany::shortest_path_many_to_many_map`] routing to
+    /// already [`PreparedDestinations`].
+    ///
+    /// This allows to reuse the destinations for many subsequent calls with different origins.
+    fn shortest_path_many_to_many_prepared_map<I, OPT, PM, O>(
+        &self,
+        origin_cells: I,
+        destinations: &PreparedDestinations,
+        options: &OPT,
+        path_transform_fn: PM,
+    ) -> Result<CellMap<Vec<O>>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+        OPT: ShortestPathOptions + Send + Sync,
+        PM: Fn(Path<W>) -> Result<O, Error> + Send + Sync,
+        O: Send + Ord + Clone;
+}
+
+/// Destination cells mapped to the cells of the graph they are reachable by.
+///
+/// Locating the destinations within the graph has to be done before routing. When many
+/// routing requests are performed using the same destinations, building this once and reusing
+/// it with [`ShortestPathManyToMany::shortest_path_many_to_many_prepared_map`] avoids doing the
+/// same work over and over again.
+pub struct PreparedDestinations {
+    h3_resolution: Resolution,
+    substmap: SubstituteMap,
+    treemap: H3Treemap<CellIndex>,
+}
+
+impl PreparedDestinations {
+    /// Locate the `destination_cells` within the `graph`.
+    ///
+    /// In contrast to routing with [`ShortestPathManyToMany::shortest_path_many_to_many_map`],
+    /// the origins are not known here. So destinations are only substituted by graph cells
+    /// which are destinations within the graph.
+    pub fn new<G, I, OPT>(graph: &G, destination_cells: I, options: &OPT) -> Result<Self, Error>
+    where
+        G: GetCellNode + NearestGraphNodes + HasH3Resolution,
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+        OPT: ShortestPathOptions,
+    {
+        Self::with_origins(
+            graph,
+            destination_cells,
+            options.max_distance_to_graph(),
+            &Default::default(),
+        )
+    }
+
+    /// Locate the `destination_cells` within the `graph` for routing from the given `origin_cells`.
+    ///
+    /// The destinations are located the same way [`ShortestPathManyToMany::shortest_path_many_to_many_map`]
+    /// does for these origins. So routing from any subset of the origins to the result
+    /// yields the same paths as routing from all origins at once.
+    pub fn for_origins<G, I, OPT>(
+        graph: &G,
+        origin_cells: I,
+        destination_cells: I,
+        options: &OPT,
+    ) -> Result<Self, Error>
+    where
+        G: GetCellNode + NearestGraphNodes + HasH3Resolution,
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+        OPT: ShortestPathOptions,
+    {
+        let filtered_origin_cells =
+            substitute_origin_cells(graph, options.max_distance_to_graph(), origin_cells, true)?;
+        if filtered_origin_cells.is_empty() {
+            return Err(Error::EmptyOriginSet);
+        }
+        let origins_treemap: H3Treemap<CellIndex> =
+            filtered_origin_cells.iter().map(|(k, _)| *k).collect();
+        Self::with_origins(
+            graph,
+            destination_cells,
+            options.max_distance_to_graph(),
+            &origins_treemap,
+        )
+    }
+
+    fn with_origins<G, I>(
+        graph: &G,
+        destination_cells: I,
+        max_distance_to_graph: u32,
+        origins_treemap: &H3Treemap<CellIndex>,
+    ) -> Result<Self, Error>
+    where
+        G: GetCellNode + NearestGraphNodes + HasH3Resolution,
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+    {
+        let substmap = substitute_destination_cells(
+            graph,
+            max_distance_to_graph,
+            destination_cells,
+            origins_treemap,
+        )?;
+        let treemap = H3Treemap::from_iter(substmap.0.keys().copied());
+        Ok(Self {
+            h3_resolution: graph.h3_resolution(),
+            substmap,
+            treemap,
+        })
+    }
+
+    /// number of graph cells the destinations are reachable by
+    pub fn len(&self) -> usize {
+        self.substmap.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.substmap.is_empty()
+    }
+}
+
+impl HasH3Resolution for PreparedDestinations {
+    fn h3_resolution(&self) -> Resolution {
+        self.h3_resolution
+    }
 }
 
 impl<W, G> ShortestPathManyToMany<W> for G
 where
     G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes + Sync,
-    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync,
+    W: PartialOrd + PartialEq + Add + Copy + Send + Ord + Zero + Sync + BucketKey,
 {
     fn shortest_path_many_to_many_map<I, OPT, PM, O>(
         &self,
@@ -136,6 +317,9 @@ where
         PM: Fn(Path<W>) -> Result<O, Error> + Send + Sync,
         O: Send + Ord + Clone,
     {
+        if self.is_edgeless() {
+            return Err(Error::EdgelessGraph);
+        }
         let filtered_origin_cells = substitute_origin_cells(
             self,
             options.max_distance_to_graph(),
@@ -143,71 +327,158 @@ where
             true, // speeds up the creation of the treemap from the origins further below
         )?;
         if filtered_origin_cells.is_empty() {
-            return Ok(Default::default());
+            return Err(Error::EmptyOriginSet);
         }
 
-        let destination_substmap = {
+        let destinations = {
             let origins_treemap: H3Treemap<CellIndex> =
                 filtered_origin_cells.iter().map(|(k, _)| *k).collect();
 
-            substitute_destination_cells(
+            PreparedDestinations::with_origins(
                 self,
-                options.max_distance_to_graph(),
                 destination_cells,
+                options.max_distance_to_graph(),
                 &origins_treemap,
             )?
         };
 
-        if destination_substmap.0.is_empty() {
-            return Ok(Default::default());
+        shortest_path_many_to_many_prepared_internal(
+            self,
+            filtered_origin_cells,
+            &destinations,
+            options,
+            path_transform_fn,
+        )
+    }
+
+    fn shortest_path_many_to_many_prepared_map<I, OPT, PM, O>(
+        &self,
+        origin_cells: I,
+        destinations: &PreparedDestinations,
+        options: &OPT,
+        path_transform_fn: PM,
+    ) -> Result<CellMap<Vec<O>>, Error>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<CellIndex>,
+        OPT: ShortestPathOptions + Send + Sync,
+        PM: Fn(Path<W>) -> Result<O, Error> + Send + Sync,
+        O: Send + Ord + Clone,
+    {
+        if self.is_edgeless() {
+            return Err(Error::EdgelessGraph);
+        }
+        if destinations.h3_resolution() != self.h3_resolution() {
+            return Err(Error::MixedH3Resolutions(
+                self.h3_resolution(),
+                destinations.h3_resolution(),
+            ));
         }
 
-        let destination_treemap = H3Treemap::from_iter(destination_substmap.0.keys().copied());
+        let filtered_origin_cells =
+            substitute_origin_cells(self, options.max_distance_to_graph(), origin_cells, false)?;
+        if filtered_origin_cells.is_empty() {
+            return Err(Error::EmptyOriginSet);
+        }
 
-        debug!(
-            "shortest_path many-to-many: from {} cells to {} cells at resolution {} with max_distance_to_graph = {}",
-            filtered_origin_cells.len(),
-            destination_substmap.0.len(),
-            self.h3_resolution(),
-            options.max_distance_to_graph()
-        );
+        shortest_path_many_to_many_prepared_internal(
+            self,
+            filtered_origin_cells,
+            destinations,
+            options,
+            path_transform_fn,
+        )
+    }
+}
 
-        let mut cellmap: CellMap<Vec<O>> = Default::default();
-        for par_result in filtered_origin_cells
-            .par_iter()
-            .map(|(graph_connected_origin_cell, output_origin_cells)| {
-                shortest_path_many_worker(
-                    self,
-                    *graph_connected_origin_cell,
-                    output_origin_cells.as_slice(),
-                    &destination_treemap,
-                    &destination_substmap,
-                    options,
-                    |path| {
-                        let origin_cell = path.origin_cell;
-                        path_transform_fn(path).map(|transformed| (origin_cell, transformed))
-                    },
-                )
-            })
-            .collect::<Result<Vec<_>, _>>()?
-        {
-            for (origin_cell, transformed) in par_result {
-                match cellmap.entry(origin_cell) {
-                    Entry::Occupied(mut entry) => entry.get_mut().push(transformed),
-                    Entry::Vacant(entry) => {
-                        entry.insert(vec![transformed]);
-                    }
+fn shortest_path_many_to_many_prepared_internal<G, W, OPT, PM, O>(
+    graph: &G,
+    filtered_origin_cells: Vec<(CellIndex, Vec<CellIndex>)>,
+    destinations: &PreparedDestinations,
+    options: &OPT,
+    path_transform_fn: PM,
+) -> Result<CellMap<Vec<O>>, Error>
+where
+    G: GetCellEdges<EdgeWeightType = W> + HasH3Resolution + Sync,
+    W: Add + Copy + Ord + Zero + Send + Sync + BucketKey,
+    OPT: ShortestPathOptions + Send + Sync,
+    PM: Fn(Path<W>) -> Result<O, Error> + Send + Sync,
+    O: Send + Ord + Clone,
+{
+    if destinations.is_empty() {
+        return Err(Error::DestinationsNotInGraph);
+    }
+
+    debug!(
+        "shortest_path many-to-many: from {} cells to {} cells at resolution {} with max_distance_to_graph = {}",
+        filtered_origin_cells.len(),
+        destinations.len(),
+        graph.h3_resolution(),
+        options.max_distance_to_graph()
+    );
+
+    let mut cellmap: CellMap<Vec<O>> = Default::default();
+    for (origin_cell, transformed) in par_map_chunks(
+        &filtered_origin_cells,
+        options.origin_chunk_size().unwrap_or(1),
+        |chunk| {
+            Decompressor::with_thread_local(|decompressor| {
+                let mut transformed_paths = vec![];
+                for (graph_connected_origin_cell, output_origin_cells) in chunk {
+                    transformed_paths.extend(shortest_path_many_worker(
+                        graph,
+                        *graph_connected_origin_cell,
+                        output_origin_cells.as_slice(),
+                        destinations,
+                        options,
+                        decompressor,
+                        |path| {
+                            let origin_cell = path.origin_cell;
+                            path_transform_fn(path).map(|transformed| (origin_cell, transformed))
+                        },
+                    )?);
                 }
+                Ok(transformed_paths)
+            })
+        },
+    )? {
+        match cellmap.entry(origin_cell) {
+            Entry::Occupied(mut entry) => entry.get_mut().push(transformed),
+            Entry::Vacant(entry) => {
+                entry.insert(vec![transformed]);
+            }
+        }
+    }
+    if options.include_unreachable_origins() {
+        for (_, output_origin_cells) in filtered_origin_cells.iter() {
+            for output_origin_cell in output_origin_cells {
+                cellmap.entry(*output_origin_cell).or_default();
             }
         }
-        Ok(cellmap)
     }
+    Ok(cellmap)
+}
+
+/// apply `map_fn` in parallel to chunks of `chunk_size` items and collect the results.
+fn par_map_chunks<T, O, F>(items: &[T], chunk_size: usize, map_fn: F) -> Result<Vec<O>, Error>
+where
+    T: Sync,
+    O: Send,
+    F: Fn(&[T]) -> Result<Vec<O>, Error> + Send + Sync,
+{
+    Ok(items
+        .par_chunks(chunk_size.max(1))
+        .map(map_fn)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect())
 }
 
 impl<W, G> ShortestPath<W> for G
 where
     G: GetCellEdges<EdgeWeightType = W> + GetCellNode + HasH3Resolution + NearestGraphNodes,
-    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
+    W: PartialOrd + PartialEq + Add + Copy + Ord + Zero + BucketKey,
 {
     fn shortest_path<I, OPT>(
         &self,
@@ -220,6 +491,9 @@ where
         I::Item: Borrow<CellIndex>,
         OPT: ShortestPathOptions,
     {
+        if self.is_edgeless() {
+            return Err(Error::EdgelessGraph);
+        }
         let (graph_connected_origin_cell, requested_origin_cells) = {
             let mut filtered_origin_cells = substitute_origin_cells(
                 self,
@@ -228,38 +502,38 @@ where
                 false, // not necessary
             )?;
             if filtered_origin_cells.is_empty() {
-                return Ok(Default::default());
+                return Err(Error::EmptyOriginSet);
             } else {
                 filtered_origin_cells.remove(0)
             }
         };
 
-        let destination_substmap = {
+        let destinations = {
             let mut origins_treemap: H3Treemap<CellIndex> = Default::default();
             origins_treemap.insert(graph_connected_origin_cell);
-            substitute_destination_cells(
+            PreparedDestinations::with_origins(
                 self,
-                options.max_distance_to_graph(),
                 destination_cells,
+                options.max_distance_to_graph(),
                 &origins_treemap,
             )?
         };
 
-        if destination_substmap.0.is_empty() {
-            return Ok(Default::default());
+        if destinations.is_empty() {
+            return Err(Error::DestinationsNotInGraph);
         }
 
-        let destination_treemap = H3Treemap::from_iter(destination_substmap.0.keys().copied());
-
-        shortest_path_many_worker(
-            self,
-            graph_connected_origin_cell,
-            requested_origin_cells.as_slice(),
-            &destination_treemap,
-            &destination_substmap,
-            options,
-            Ok,
-        )
+        Decompressor::with_thread_local(|decompressor| {
+            shortest_path_many_worker(
+                self,
+                graph_connected_origin_cell,
+                requested_origin_cells.as_slice(),
+                &destinations,
+                options,
+                decompressor,
+                Ok,
+            )
+        })
     }
 }
 
@@ -267,29 +541,59 @@ fn shortest_path_many_worker<G, W, OPT, PM, O>(
     graph: &G,
     origin_cell: CellIndex,
     requested_origin_cells: &[CellIndex],
-    destination_cells: &H3Treemap<CellIndex>,
-    destination_substmap: &SubstituteMap,
+    destinations: &PreparedDestinations,
     options: &OPT,
+    decompressor: &mut Decompressor,
     path_transform_fn: PM,
 ) -> Result<Vec<O>, Error>
 where
     G: GetCellEdges<EdgeWeightType = W>,
-    W: Add + Copy + Ord + Zero,
+    W: Add + Copy + Ord + Zero + BucketKey,
     PM: Fn(Path<W>) -> Result<O, Error>,
     O: Clone,
     OPT: ShortestPathOptions,
 {
-    let found_paths = edge_dijkstra(
-        graph,
+    let found_paths = match origin_only_paths(
         origin_cell,
-        destination_cells,
+        destinations,
         options.num_destinations_to_reach(),
-    )?;
+    )? {
+        Some(paths) => paths,
+        None => {
+            let forbidden_first_edge = options
+                .forbid_uturn_at_origin()
+                .filter(|arrival_edge| arrival_edge.destination() == origin_cell)
+                .and_then(|arrival_edge| origin_cell.edge(arrival_edge.origin()));
+            match options.bucket_queue_width() {
+                Some(bucket_width) => edge_dijkstra_with_decompressor(
+                    graph,
+                    origin_cell,
+                    &destinations.treemap,
+                    options.num_destinations_to_reach(),
+                    BucketQueue::with_bucket_width(bucket_width),
+                    forbidden_first_edge,
+                    decompressor,
+                )?,
+                None => edge_dijkstra_with_decompressor(
+                    graph,
+                    origin_cell,
+                    &destinations.treemap,
+                    options.num_destinations_to_reach(),
+                    BinaryHeapQueue::default(),
+                    forbidden_first_edge,
+                    decompressor,
+                )?,
+            }
+        }
+    };
 
     let mut transformed_paths = Vec::with_capacity(found_paths.len());
 
     for path in found_paths.into_iter() {
-        for destination_cell in destination_substmap.cells_substituted_by(path.destination_cell) {
+        for destination_cell in destinations
+            .substmap
+            .cells_substituted_by(path.destination_cell)
+        {
             for origin_cell in requested_origin_cells {
                 let mut this_path = path.clone();
                 this_path.origin_cell = *origin_cell;
@@ -302,6 +606,33 @@ where
     Ok(transformed_paths)
 }
 
+/// shortcut for the case the origin itself is the only destination to reach.
+///
+/// The origin is always the first cell visited by the dijkstra, so the result is the
+/// zero-cost path from the origin to itself. Returns `None` when the graph
+/// needs to be traversed.
+fn origin_only_paths<W>(
+    origin_cell: CellIndex,
+    destinations: &PreparedDestinations,
+    num_destinations_to_reach: Option<usize>,
+) -> Result<Option<Vec<Path<W>>>, Error>
+where
+    W: Zero,
+{
+    let num_destinations_to_reach = num_destinations_to_reach
+        .unwrap_or(usize::MAX)
+        .min(destinations.treemap.len());
+    if num_destinations_to_reach > 1 || !destinations.treemap.contains(&origin_cell) {
+        return Ok(None);
+    }
+    let path = (
+        DirectedEdgePath::OriginIsDestination(origin_cell),
+        W::zero(),
+    )
+        .try_into()?;
+    Ok(Some(vec![path]))
+}
+
 /// Maps Cells which are part of the graph - the keys - to requested
 /// cells values.
 #[derive(Default)]
@@ -337,7 +668,8 @@ impl SubstituteMap {
 /// The cell resolution is changed to the resolution of the graph.
 ///
 /// There must be at least one destination to get Result::Ok, otherwise
-/// the complete graph would be traversed.
+/// the complete graph would be traversed. Fails with [`Error::EmptyDestinationSet`] when
+/// no destinations are given at all.
 fn substitute_destination_cells<G, I>(
     graph: &G,
     max_distance_to_graph: u32,
@@ -353,7 +685,9 @@ where
     // destinations_cells
     let mut destination_substmap = SubstituteMap::default();
 
+    let mut num_destinations = 0_usize;
     for destination in transform_resolution(destination_cells, graph.h3_resolution()) {
+        num_destinations += 1;
         for (graph_cell, node_type, _) in
             graph.nearest_graph_nodes(destination, max_distance_to_graph)?
         {
@@ -366,6 +700,9 @@ where
         }
     }
 
+    if num_destinations == 0 {
+        return Err(Error::EmptyDestinationSet);
+    }
     if destination_substmap.is_empty() {
         return Err(Error::DestinationsNotInGraph);
     }
@@ -413,13 +750,21 @@ where
 
 #[cfg(test)]
 mod tests {
-    use h3o::{LatLng, Resolution};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::{CellIndex, DirectedEdgeIndex, LatLng, Resolution};
     use std::convert::TryInto;
 
-    use crate::algorithm::graph::shortest_path::DefaultShortestPathOptions;
-    use crate::algorithm::graph::ShortestPathManyToMany;
+    use std::sync::Mutex;
+
+    use crate::algorithm::graph::dijkstra::edge_dijkstra;
+    use crate::algorithm::graph::shortest_path::{
+        par_map_chunks, DefaultShortestPathOptions, PreparedDestinations, ShortestPathOptions,
+    };
+    use crate::algorithm::graph::{ShortestPath, ShortestPathManyToMany};
+    use crate::container::treemap::H3Treemap;
+    use crate::error::Error;
     use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
-    use geo::Coord;
+    use geo::{Coord, LineString};
 
     #[test]
     fn test_shortest_path_same_origin_and_destination() {
@@ -462,4 +807,401 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_shortest_path_origin_only_fast_path() {
+        let res = Resolution::Eight;
+        let origin = {
+            let ll: LatLng = Coord::from((23.3, 12.3)).try_into().unwrap();
+            ll.to_cell(res)
+        };
+        let prepared_graph: PreparedH3EdgeGraph<_> = {
+            let mut graph = H3EdgeGraph::new(res);
+            for edge in origin.edges() {
+                graph.add_edge(edge, 5_u32);
+            }
+            graph.try_into().unwrap()
+        };
+
+        let paths = prepared_graph
+            .shortest_path(origin, [origin], &DefaultShortestPathOptions::default())
+            .unwrap();
+
+        // the same routing using the full dijkstra
+        let mut treemap: H3Treemap<CellIndex> = Default::default();
+        treemap.insert(origin);
+        let expected = edge_dijkstra(&prepared_graph, origin, &treemap, None).unwrap();
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths, expected);
+        assert!(paths[0].is_empty());
+        assert_eq!(paths[0].cost, 0);
+
+        // the neighbors still require traversing the graph
+        let neighbor = origin.edges().next().unwrap().destination();
+        let paths = prepared_graph
+            .shortest_path(
+                origin,
+                [origin, neighbor],
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 2);
+    }
+
+    #[test]
+    fn test_shortest_path_prepared_destinations() {
+        let res = Resolution::Eight;
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.6, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(res))
+        .collect();
+        assert!(cells.len() > 20);
+
+        // bidirectional line graph, so every cell is an origin and a destination
+        let prepared_graph: PreparedH3EdgeGraph<_> = {
+            let mut graph = H3EdgeGraph::new(res);
+            for w in cells.windows(2) {
+                graph.add_edge(w[0].edge(w[1]).unwrap(), 3_u32);
+                graph.add_edge(w[1].edge(w[0]).unwrap(), 3_u32);
+            }
+            graph.try_into().unwrap()
+        };
+
+        let options = DefaultShortestPathOptions::default();
+        let destinations = vec![cells[0], cells[cells.len() / 2], cells[cells.len() - 1]];
+        let origins = vec![cells[2], cells[5], cells[10], cells[15]];
+
+        let expected = prepared_graph
+            .shortest_path_many_to_many(&origins, &destinations, &options)
+            .unwrap();
+
+        let prepared_destinations =
+            PreparedDestinations::new(&prepared_graph, &destinations, &options).unwrap();
+        assert_eq!(prepared_destinations.len(), destinations.len());
+
+        let mut found = prepared_graph
+            .shortest_path_many_to_many_prepared(&origins[..2], &prepared_destinations, &options)
+            .unwrap();
+        found.extend(
+            prepared_graph
+                .shortest_path_many_to_many_prepared(
+                    &origins[2..],
+                    &prepared_destinations,
+                    &options,
+                )
+                .unwrap(),
+        );
+
+        assert_eq!(found.len(), origins.len());
+        for origin in origins.iter() {
+            let mut expected_paths = expected.get(origin).unwrap().clone();
+            expected_paths.sort_unstable();
+            let mut found_paths = found.get(origin).unwrap().clone();
+            found_paths.sort_unstable();
+            assert_eq!(expected_paths, found_paths);
+        }
+    }
+
+    struct ChunkedOptions(Option<usize>);
+
+    impl ShortestPathOptions for ChunkedOptions {
+        fn origin_chunk_size(&self) -> Option<usize> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_origin_chunk_size() {
+        let res = Resolution::Eight;
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.6, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(res))
+        .collect();
+
+        let prepared_graph: PreparedH3EdgeGraph<_> = {
+            let mut graph = H3EdgeGraph::new(res);
+            for w in cells.windows(2) {
+                graph.add_edge(w[0].edge(w[1]).unwrap(), 3_u32);
+                graph.add_edge(w[1].edge(w[0]).unwrap(), 3_u32);
+            }
+            graph.try_into().unwrap()
+        };
+        let destinations = vec![cells[0], cells[cells.len() - 1]];
+
+        let route = |chunk_size| {
+            let mut found: Vec<_> = prepared_graph
+                .shortest_path_many_to_many(&cells, &destinations, &ChunkedOptions(chunk_size))
+                .unwrap()
+                .into_iter()
+                .collect();
+            found.sort_unstable_by_key(|(origin, _)| *origin);
+            for (_, paths) in found.iter_mut() {
+                paths.sort_unstable();
+            }
+            found
+        };
+
+        let expected = route(None);
+        assert_eq!(expected.len(), cells.len());
+        for chunk_size in [0, 1, 3, cells.len() * 2] {
+            assert_eq!(route(Some(chunk_size)), expected);
+        }
+    }
+
+    #[test]
+    fn test_par_map_chunks_honors_chunk_size() {
+        let items: Vec<_> = (0..10).collect();
+        let chunk_lengths = Mutex::new(vec![]);
+        let mut mapped = par_map_chunks(&items, 3, |chunk| {
+            chunk_lengths.lock().unwrap().push(chunk.len());
+            Ok(chunk.iter().map(|i| i * 2).collect())
+        })
+        .unwrap();
+        mapped.sort_unstable();
+        assert_eq!(mapped, (0..20).step_by(2).collect::<Vec<_>>());
+
+        let mut chunk_lengths = chunk_lengths.into_inner().unwrap();
+        chunk_lengths.sort_unstable();
+        assert_eq!(chunk_lengths, vec![1, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_shortest_path_empty_inputs() {
+        let res = Resolution::Eight;
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(res);
+        let edge = origin.edges().next().unwrap();
+        let destination = edge.destination();
+        let off_graph = LatLng::new(40.0, 23.3).unwrap().to_cell(res);
+
+        let prepared_graph: PreparedH3EdgeGraph<_> = {
+            let mut graph = H3EdgeGraph::new(res);
+            graph.add_edge(edge, 5_u32);
+            graph.try_into().unwrap()
+        };
+        let options = DefaultShortestPathOptions::default();
+        let route = |origins: Vec<_>, destinations: Vec<_>| {
+            prepared_graph.shortest_path_many_to_many(&origins, &destinations, &options)
+        };
+
+        assert!(route(vec![origin], vec![destination]).is_ok());
+        assert!(matches!(
+            route(vec![], vec![destination]),
+            Err(Error::EmptyOriginSet)
+        ));
+        assert!(matches!(
+            route(vec![off_graph], vec![destination]),
+            Err(Error::EmptyOriginSet)
+        ));
+        assert!(matches!(
+            route(vec![origin], vec![]),
+            Err(Error::EmptyDestinationSet)
+        ));
+        assert!(matches!(
+            route(vec![origin], vec![off_graph]),
+            Err(Error::DestinationsNotInGraph)
+        ));
+
+        assert!(matches!(
+            prepared_graph.shortest_path(off_graph, [destination], &options),
+            Err(Error::EmptyOriginSet)
+        ));
+        assert!(matches!(
+            prepared_graph.shortest_path(origin, Vec::<h3o::CellIndex>::new(), &options),
+            Err(Error::EmptyDestinationSet)
+        ));
+
+        assert!(matches!(
+            PreparedDestinations::new(&prepared_graph, Vec::<h3o::CellIndex>::new(), &options),
+            Err(Error::EmptyDestinationSet)
+        ));
+        let prepared_destinations =
+            PreparedDestinations::new(&prepared_graph, [destination], &options).unwrap();
+        assert!(matches!(
+            prepared_graph.shortest_path_many_to_many_prepared(
+                vec![off_graph],
+                &prepared_destinations,
+                &options
+            ),
+            Err(Error::EmptyOriginSet)
+        ));
+    }
+
+    #[test]
+    fn test_shortest_path_edgeless_graph() {
+        let res = Resolution::Eight;
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(res);
+        let destination = origin.edges().next().unwrap().destination();
+
+        let prepared_graph: PreparedH3EdgeGraph<u32> = H3EdgeGraph::new(res).try_into().unwrap();
+        let options = DefaultShortestPathOptions::default();
+
+        assert!(matches!(
+            prepared_graph.shortest_path(origin, [destination], &options),
+            Err(Error::EdgelessGraph)
+        ));
+        assert!(matches!(
+            prepared_graph.shortest_path_many_to_many(&vec![origin], &vec![destination], &options),
+            Err(Error::EdgelessGraph)
+        ));
+    }
+
+    struct ArrivalEdgeOptions(Option<DirectedEdgeIndex>);
+
+    impl ShortestPathOptions for ArrivalEdgeOptions {
+        fn forbid_uturn_at_origin(&self) -> Option<DirectedEdgeIndex> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_forbid_uturn_at_origin() {
+        let res = Resolution::Eight;
+        let center = LatLng::new(12.3, 23.3).unwrap().to_cell(res);
+        let ring: Vec<_> = center.grid_ring_fast(1).flatten().collect();
+        assert_eq!(ring.len(), 6);
+
+        // a circular road around the center cell usable in both directions
+        let prepared_graph: PreparedH3EdgeGraph<u32> = {
+            let mut graph = H3EdgeGraph::new(res);
+            for i in 0..ring.len() {
+                let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+                graph.add_edge(a.edge(b).unwrap(), 10);
+                graph.add_edge(b.edge(a).unwrap(), 10);
+            }
+            graph.try_into().unwrap()
+        };
+
+        let origin = ring[0];
+        let destination = ring[5];
+        let arrival_edge = destination.edge(origin).unwrap();
+
+        let paths = prepared_graph
+            .shortest_path(origin, [destination], &ArrivalEdgeOptions(None))
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].cost, 10);
+
+        // going back to the destination is not allowed, so the route has to follow the
+        // ring in forward direction.
+        let paths = prepared_graph
+            .shortest_path(
+                origin,
+                [destination],
+                &ArrivalEdgeOptions(Some(arrival_edge)),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].cost, 50);
+        assert_eq!(paths[0].directed_edge_path.cells(), ring);
+
+        // the arrival edge does not lead to other origins, so these are not affected.
+        let paths = prepared_graph
+            .shortest_path(ring[1], [ring[0]], &ArrivalEdgeOptions(Some(arrival_edge)))
+            .unwrap();
+        assert_eq!(paths[0].cost, 10);
+    }
+
+    struct UnreachableOriginsOptions(bool);
+
+    impl ShortestPathOptions for UnreachableOriginsOptions {
+        fn include_unreachable_origins(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_include_unreachable_origins() {
+        let res = Resolution::Eight;
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(res);
+        let destination = origin.edges().next().unwrap().destination();
+
+        // a second component of the graph without a connection to the destination
+        let isolated_origin = LatLng::new(14.3, 23.3).unwrap().to_cell(res);
+        let isolated_edge = isolated_origin.edges().next().unwrap();
+
+        // not part of the graph at all
+        let offgraph_origin = LatLng::new(16.3, 23.3).unwrap().to_cell(res);
+
+        let prepared_graph: PreparedH3EdgeGraph<u32> = {
+            let mut graph = H3EdgeGraph::new(res);
+            graph.add_edge(origin.edge(destination).unwrap(), 5);
+            graph.add_edge(isolated_edge, 5);
+            graph.try_into().unwrap()
+        };
+        let origins = vec![origin, isolated_origin, offgraph_origin];
+
+        let paths = prepared_graph
+            .shortest_path_many_to_many(
+                &origins,
+                &vec![destination],
+                &UnreachableOriginsOptions(false),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths.get(&origin).unwrap().len(), 1);
+
+        let paths = prepared_graph
+            .shortest_path_many_to_many(
+                &origins,
+                &vec![destination],
+                &UnreachableOriginsOptions(true),
+            )
+            .unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths.get(&origin).unwrap().len(), 1);
+        assert!(paths.get(&isolated_origin).unwrap().is_empty());
+        assert!(!paths.contains_key(&offgraph_origin));
+    }
+
+    struct BucketQueueOptions(Option<u64>);
+
+    impl ShortestPathOptions for BucketQueueOptions {
+        fn bucket_queue_width(&self) -> Option<u64> {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_shortest_path_bucket_queue() {
+        let res = Resolution::Eight;
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(res);
+        let prepared_graph: PreparedH3EdgeGraph<u32> = {
+            let mut graph = H3EdgeGraph::new(res);
+            for cell in origin.grid_disk::<Vec<_>>(5) {
+                for edge in cell.edges() {
+                    graph.add_edge(edge, (u64::from(edge) % 13) as u32 + 1);
+                }
+            }
+            graph.try_into().unwrap()
+        };
+        let destinations: Vec<_> = origin.grid_ring_fast(4).flatten().collect();
+
+        let costs = |bucket_queue_width| {
+            let mut costs: Vec<_> = prepared_graph
+                .shortest_path(
+                    origin,
+                    &destinations,
+                    &BucketQueueOptions(bucket_queue_width),
+                )
+                .unwrap()
+                .into_iter()
+                .map(|path| (path.destination_cell, path.cost))
+                .collect();
+            costs.sort_unstable();
+            costs
+        };
+
+        let expected = costs(None);
+        assert_eq!(expected.len(), destinations.len());
+        for bucket_queue_width in [1, 5] {
+            assert_eq!(costs(Some(bucket_queue_width)), expected);
+        }
+    }
 }
@@ -11,6 +11,18 @@ use rayon::prelude::*;
 use crate::error::Error;
 use crate::graph::GetCellEdges;
 
+/// Options for [`WithinWeightThreshold`] and [`WithinWeightThresholdMany`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WithinWeightThresholdOptions {
+    /// Controls how the origin cells are contained in the result.
+    ///
+    /// * `None`: each origin is part of the result of its own traversal. With multiple origins
+    ///   its weight gets aggregated like the weight of any other cell.
+    /// * `Some(true)`: all origins are contained with a zero weight.
+    /// * `Some(false)`: origins are only contained when they are reached from another origin.
+    pub include_origin: Option<bool>,
+}
+
 /// Find all cells connected to the graph around a origin cell within a given threshold
 pub trait WithinWeightThreshold<W> {
     /// Find all cells connected to the graph within a given `weight_threshold` around the
@@ -19,6 +31,21 @@ pub trait WithinWeightThreshold<W> {
         &self,
         origin_cell: CellIndex,
         weight_threshold: W,
+    ) -> Result<CellMap<W>, Error> {
+        self.cells_within_weight_threshold_with_options(
+            origin_cell,
+            weight_threshold,
+            &WithinWeightThresholdOptions::default(),
+        )
+    }
+
+    /// Same as [`WithinWeightThreshold::cells_within_weight_threshold`], but allows
+    /// passing [`WithinWeightThresholdOptions`].
+    fn cells_within_weight_threshold_with_options(
+        &self,
+        origin_cell: CellIndex,
+        weight_threshold: W,
+        options: &WithinWeightThresholdOptions,
     ) -> Result<CellMap<W>, Error>;
 }
 
@@ -27,12 +54,18 @@ where
     G: GetCellEdges<EdgeWeightType = W>,
     W: Zero + Ord + Copy + Add,
 {
-    fn cells_within_weight_threshold(
+    fn cells_within_weight_threshold_with_options(
         &self,
         origin_cell: CellIndex,
         weight_threshold: W,
+        options: &WithinWeightThresholdOptions,
     ) -> Result<CellMap<W>, Error> {
-        edge_dijkstra_weight_threshold(self, origin_cell, weight_threshold)
+        let mut cellmap = edge_dijkstra_weight_threshold(self, origin_cell, weight_threshold)?;
+        if options.include_origin == Some(false) {
+            // the origin is visited first, so it can not be reached by another path.
+            cellmap.remove(&origin_cell);
+        }
+        Ok(cellmap)
     }
 }
 
@@ -49,6 +82,28 @@ pub trait WithinWeightThresholdMany<W> {
         weight_threshold: W,
         agg_fn: AGG,
     ) -> Result<CellMap<W>, Error>
+    where
+        I: IntoParallelIterator,
+        I::Item: Borrow<CellIndex>,
+        AGG: Fn(&mut W, W) + Sync,
+    {
+        self.cells_within_weight_threshold_many_with_options(
+            origin_cells,
+            weight_threshold,
+            &WithinWeightThresholdOptions::default(),
+            agg_fn,
+        )
+    }
+
+    /// Same as [`WithinWeightThresholdMany::cells_within_weight_threshold_many`], but allows
+    /// passing [`WithinWeightThresholdOptions`].
+    fn cells_within_weight_threshold_many_with_options<I, AGG>(
+        &self,
+        origin_cells: I,
+        weight_threshold: W,
+        options: &WithinWeightThresholdOptions,
+        agg_fn: AGG,
+    ) -> Result<CellMap<W>, Error>
     where
         I: IntoParallelIterator,
         I::Item: Borrow<CellIndex>,
@@ -60,10 +115,11 @@ where
     G: GetCellEdges<EdgeWeightType = W> + WithinWeightThreshold<W> + Sync,
     W: Zero + Ord + Copy + Add + Send + Sync,
 {
-    fn cells_within_weight_threshold_many<I, AGG>(
+    fn cells_within_weight_threshold_many_with_options<I, AGG>(
         &self,
         origin_cells: I,
         weight_threshold: W,
+        options: &WithinWeightThresholdOptions,
         agg_fn: AGG,
     ) -> Result<CellMap<W>, Error>
     where
@@ -71,9 +127,27 @@ where
         I::Item: Borrow<CellIndex>,
         AGG: Fn(&mut W, W) + Sync,
     {
-        origin_cells
+        let origin_cells: Vec<CellIndex> = origin_cells
             .into_par_iter()
-            .map(|item| self.cells_within_weight_threshold(*item.borrow(), weight_threshold))
+            .map(|item| *item.borrow())
+            .collect();
+
+        // when the origins are included or excluded explicitly, they are left out of the
+        // traversals and only added after the aggregation, so their zero weight can not
+        // be changed by `agg_fn`.
+        let single_options = WithinWeightThresholdOptions {
+            include_origin: options.include_origin.map(|_| false),
+        };
+
+        let mut cellmap = origin_cells
+            .par_iter()
+            .map(|origin_cell| {
+                self.cells_within_weight_threshold_with_options(
+                    *origin_cell,
+                    weight_threshold,
+                    &single_options,
+                )
+            })
             .try_reduce_with(|cellmap1, cellmap2| {
                 // select the source and target maps, to move the contents of the map with fewer elements, to the map
                 // with more elements. This should save quite a few hashing operations.
@@ -95,10 +169,27 @@ where
                 }
                 Ok(target_cellmap)
             })
-            .unwrap_or_else(|| Ok(Default::default()))
+            .unwrap_or_else(|| Ok(Default::default()))?;
+
+        if options.include_origin == Some(true) {
+            for origin_cell in origin_cells {
+                cellmap.insert(origin_cell, W::zero());
+            }
+        }
+        Ok(cellmap)
     }
 }
 
+/// Index of the tightest of the ascending sorted `thresholds` containing `weight`.
+///
+/// Allows assigning the results of a single [`WithinWeightThresholdMany::cells_within_weight_threshold_many`]
+/// call using the largest threshold to multiple bands. Returns `None` when `weight` exceeds
+/// all thresholds.
+pub fn threshold_band<W: Ord>(thresholds: &[W], weight: &W) -> Option<usize> {
+    let band = thresholds.partition_point(|threshold| threshold < weight);
+    (band < thresholds.len()).then_some(band)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,4 +269,93 @@ mod tests {
         assert_eq!(weights_freq[&20], 2);
         assert_eq!(weights_freq[&30], 2);
     }
+
+    #[test]
+    fn test_cells_within_weight_threshold_include_origin() {
+        let (cell_sequence, prepared_graph) = line_graph(10);
+
+        let exclude_origin = WithinWeightThresholdOptions {
+            include_origin: Some(false),
+        };
+        let include_origin = WithinWeightThresholdOptions {
+            include_origin: Some(true),
+        };
+
+        let within_threshold = prepared_graph
+            .cells_within_weight_threshold_with_options(cell_sequence[0], 30, &exclude_origin)
+            .unwrap();
+        assert_eq!(within_threshold.len(), 3);
+        assert!(!within_threshold.contains_key(&cell_sequence[0]));
+
+        // an origin which is not part of the graph
+        let outside = cell_sequence[0]
+            .grid_ring_fast(5)
+            .flatten()
+            .find(|cell| !cell_sequence.contains(cell))
+            .unwrap();
+        let origin_cells = vec![cell_sequence[0], cell_sequence[1], outside];
+
+        // use the maximum weight encountered, which would replace the zero weight of
+        // the second origin when the origins were aggregated
+        let max_agg = |existing: &mut u32, new: u32| {
+            if new > *existing {
+                *existing = new
+            }
+        };
+
+        let within_threshold = prepared_graph
+            .cells_within_weight_threshold_many(origin_cells.clone(), 30, max_agg)
+            .unwrap();
+        assert_eq!(within_threshold.get(&cell_sequence[1]), Some(&10));
+        assert_eq!(within_threshold.get(&outside), Some(&0));
+
+        let within_threshold = prepared_graph
+            .cells_within_weight_threshold_many_with_options(
+                origin_cells.clone(),
+                30,
+                &include_origin,
+                max_agg,
+            )
+            .unwrap();
+        for origin_cell in origin_cells.iter() {
+            assert_eq!(within_threshold.get(origin_cell), Some(&0));
+        }
+
+        // the second origin is still reached from the first one
+        let within_threshold = prepared_graph
+            .cells_within_weight_threshold_many_with_options(
+                origin_cells,
+                30,
+                &exclude_origin,
+                max_agg,
+            )
+            .unwrap();
+        assert!(!within_threshold.contains_key(&cell_sequence[0]));
+        assert!(!within_threshold.contains_key(&outside));
+        assert_eq!(within_threshold.get(&cell_sequence[1]), Some(&10));
+    }
+
+    #[test]
+    fn test_threshold_band() {
+        let (cell_sequence, prepared_graph) = line_graph(10);
+        let thresholds = [10, 20, 40];
+
+        let within_threshold = prepared_graph
+            .cells_within_weight_threshold(cell_sequence[0], *thresholds.last().unwrap())
+            .unwrap();
+        assert_eq!(within_threshold.len(), 5);
+
+        for (cell, weight) in within_threshold.iter() {
+            let band = threshold_band(&thresholds, weight).unwrap();
+            assert!(*weight <= thresholds[band]);
+            if band > 0 {
+                assert!(*weight > thresholds[band - 1]);
+            }
+            if *cell == cell_sequence[3] {
+                assert_eq!(band, 2);
+            }
+        }
+        assert_eq!(threshold_band(&thresholds, &0), Some(0));
+        assert_eq!(threshold_band(&thresholds, &41), None);
+    }
 }
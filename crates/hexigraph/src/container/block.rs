@@ -1,4 +1,5 @@
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::marker::PhantomData;
 use std::mem::size_of;
 
@@ -176,11 +177,30 @@ pub struct Decompressor {
     buf: Vec<u8>,
 }
 
+thread_local! {
+    static THREAD_DECOMPRESSOR: RefCell<Decompressor> = const { RefCell::new(Decompressor::new()) };
+}
+
 impl Decompressor {
     pub const fn new() -> Self {
         Self { buf: vec![] }
     }
 
+    /// run `f` with the decompressor of the current thread.
+    ///
+    /// The buffer of the decompressor is kept between the calls, which avoids reallocating
+    /// it for every decompressed block on threads decompressing many blocks, like the
+    /// workers of the parallel routing. Nested calls get a new decompressor.
+    pub fn with_thread_local<F, R>(f: F) -> R
+    where
+        F: FnOnce(&mut Decompressor) -> R,
+    {
+        THREAD_DECOMPRESSOR.with(|decompressor| match decompressor.try_borrow_mut() {
+            Ok(mut decompressor) => f(&mut decompressor),
+            Err(_) => f(&mut Decompressor::new()),
+        })
+    }
+
     fn decompress_block_into_inner_buf<T>(&mut self, block: &IndexBlock<T>) -> Result<(), Error>
     where
         T: TryFrom<u64>,
@@ -220,6 +240,47 @@ impl Decompressor {
     }
 }
 
+impl Decompressor {
+    /// decompress only the index at position `pos` within the `block`.
+    ///
+    /// The bytes of the indexes are stored grouped by their significance, so the last
+    /// byte of the index is located in the last group. Decoding stops once the bytes up to
+    /// this one are decoded, which skips only the remainder of the last group.
+    /// Returns `None` when `pos` is out of bounds.
+    pub fn decompress_index_at<T>(
+        &mut self,
+        block: &IndexBlock<T>,
+        pos: usize,
+    ) -> Result<Option<T>, Error>
+    where
+        T: TryFrom<u64>,
+        Error: From<T::Error>,
+    {
+        if pos >= block.num_indexes {
+            return Ok(None);
+        }
+
+        // the last byte of the index is located in the last group of bytes
+        let required_size = pos + (size_of::<u64>() - 1) * block.num_indexes + 1;
+        let buf = &mut self.buf;
+        buf.clear();
+        rle_decode_step_bytes(&block.block_data, |byte, repetitions| {
+            buf.extend(std::iter::repeat_n(byte, repetitions as usize));
+            buf.len() < required_size
+        })?;
+
+        if self.buf.len() < required_size {
+            return Err(Error::DecompressionError(format!(
+                "Expected to decompress at least {} bytes, but got {} bytes",
+                required_size,
+                self.buf.len()
+            )));
+        }
+        let h3index = h3index_from_block_buf(&self.buf, pos, block.num_indexes);
+        Ok(Some(T::try_from(h3index)?))
+    }
+}
+
 impl Default for Decompressor {
     fn default() -> Self {
         Self::new()
@@ -229,7 +290,7 @@ impl Default for Decompressor {
 #[inline]
 fn h3index_from_block_buf(buf: &[u8], pos: usize, num_indexes: usize) -> u64 {
     assert!(pos < num_indexes);
-    assert!(buf.len() >= (num_indexes * size_of::<u64>() / size_of::<u8>()));
+    assert!(buf.len() > pos + ((size_of::<u64>() / size_of::<u8>()) - 1) * num_indexes);
     u64::from_le_bytes([
         buf[pos],
         buf[pos + num_indexes],
@@ -355,6 +416,30 @@ mod tests {
         compressed_cells
     }
 
+    #[test]
+    fn test_thread_local_decompressor() {
+        let cells = make_grid_disk(8);
+        let compressed_cells = IndexBlock::from(cells.as_slice());
+        let decompress = |decompressor: &mut Decompressor| {
+            decompressor
+                .decompress_block(&compressed_cells)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        };
+
+        assert_eq!(Decompressor::with_thread_local(decompress), cells);
+
+        // the buffer is kept for subsequent calls
+        let capacity = Decompressor::with_thread_local(|decompressor| decompressor.buf.capacity());
+        assert!(capacity >= cells.len() * std::mem::size_of::<u64>());
+
+        // nested calls work as well
+        let nested =
+            Decompressor::with_thread_local(|_| Decompressor::with_thread_local(decompress));
+        assert_eq!(nested, cells);
+    }
+
     #[test]
     fn test_indexblock_roundtrip_grid_disk1() {
         let civ = grid_disk_indexblock_roundtrip(make_grid_disk(1));
@@ -2,6 +2,8 @@ use std::marker::PhantomData;
 
 use roaring::RoaringTreemap;
 
+use crate::error::Error;
+
 #[cfg(feature = "serde")]
 pub mod serde;
 
@@ -60,6 +62,19 @@ impl<T> H3Treemap<T>
 where
     T: Copy + Into<u64>,
 {
+    /// Create from an iterator of values which are already sorted in ascending order
+    /// and free of duplicates.
+    ///
+    /// Skips the sorting done by the [`FromIterator`] implementation. Fails when the
+    /// values are not strictly ascending.
+    pub fn from_sorted_iter<I: IntoIterator<Item = T>>(iter: I) -> Result<Self, Error> {
+        Ok(Self {
+            treemap: RoaringTreemap::from_sorted_iter(iter.into_iter().map(|c| c.into()))
+                .map_err(|e| Error::UnsortedValues(e.valid_until()))?,
+            phantom_data: Default::default(),
+        })
+    }
+
     /// Pushes value in the treemap only if it is greater than the current maximum value.
     /// Returns whether the value was inserted.
     #[inline]
@@ -119,7 +134,8 @@ where
 #[cfg(test)]
 mod tests {
     use super::H3Treemap;
-    use h3o::CellIndex;
+    use crate::error::Error;
+    use h3o::{CellIndex, LatLng, Resolution};
 
     #[test]
     fn iter() {
@@ -127,4 +143,25 @@ mod tests {
         let treemap: H3Treemap<_> = idx.grid_disk(1);
         assert_eq!(treemap.len(), 7);
     }
+
+    #[test]
+    fn from_sorted_iter() {
+        let center = LatLng::new(48.5, 9.5).unwrap().to_cell(Resolution::Ten);
+        let mut cells = center.grid_disk::<Vec<_>>(20);
+        cells.sort_unstable();
+
+        let from_sorted = H3Treemap::from_sorted_iter(cells.iter().copied()).unwrap();
+        let collected: H3Treemap<_> = cells.iter().copied().collect();
+        assert_eq!(from_sorted.len(), collected.len());
+        assert!(from_sorted
+            .iter()
+            .zip(collected.iter())
+            .all(|(a, b)| a.unwrap() == b.unwrap()));
+
+        cells.swap(3, 4);
+        assert!(matches!(
+            H3Treemap::from_sorted_iter(cells.iter().copied()),
+            Err(Error::UnsortedValues(4))
+        ));
+    }
 }
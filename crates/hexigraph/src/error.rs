@@ -32,12 +32,44 @@ pub enum Error {
     #[error("none of the routing destinations is part of the routing graph")]
     DestinationsNotInGraph,
 
+    #[error("no routing destinations given")]
+    EmptyDestinationSet,
+
+    #[error("none of the routing origins is part of the routing graph")]
+    EmptyOriginSet,
+
+    #[error("the routing graph contains no edges")]
+    EdgelessGraph,
+
+    #[error("no graphs given")]
+    EmptyGraphSet,
+
     #[error("empty exclude cells")]
     EmptyExcludeCells,
 
     #[error("minimum fastforward length must be >= {0}")]
     TooShortLongEdge(usize),
 
+    #[error("maximum fastforward length must be >= the minimum length of {0}")]
+    TooShortMaxLongEdge(usize),
+
+    #[error("values are not sorted, only the first {0} values are in order")]
+    UnsortedValues(u64),
+
+    #[error("index {0} is out of bounds")]
+    IndexOutOfBounds(usize),
+
+    #[error("the graph builder keeps no index of the ways")]
+    MissingWayIndex,
+
     #[error(transparent)]
     IOError(#[from] std::io::Error),
+
+    #[cfg(feature = "json")]
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+
+    #[cfg(feature = "json")]
+    #[error("invalid json graph: {0}")]
+    JsonFormat(String),
 }
@@ -0,0 +1,146 @@
+//! Comparison of the edges of two graphs
+use h3o::DirectedEdgeIndex;
+
+use crate::container::DirectedEdgeMap;
+use crate::error::Error;
+use crate::graph::PreparedH3EdgeGraph;
+use crate::HasH3Resolution;
+
+/// The differences between the edges of two graphs.
+///
+/// All edges are ordered by their index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphDiff<W> {
+    /// edges only contained in the new graph, with their weight.
+    pub added_edges: Vec<(DirectedEdgeIndex, W)>,
+
+    /// edges only contained in the old graph, with their weight.
+    pub removed_edges: Vec<(DirectedEdgeIndex, W)>,
+
+    /// edges contained in both graphs with a changed weight.
+    ///
+    /// tuple: (edge, old weight, new weight)
+    pub changed_edges: Vec<(DirectedEdgeIndex, W, W)>,
+}
+
+impl<W> GraphDiff<W> {
+    /// both graphs contain the same edges with the same weights
+    pub fn is_empty(&self) -> bool {
+        self.added_edges.is_empty()
+            && self.removed_edges.is_empty()
+            && self.changed_edges.is_empty()
+    }
+}
+
+/// compare the edges of the `old_graph` with the edges of the `new_graph`.
+///
+/// Only the weights of the individual edges are compared, [`FastForward`](crate::graph::fastforward::FastForward)s
+/// are ignored. Both graphs must have the same h3 resolution.
+pub fn diff_graphs<W>(
+    old_graph: &PreparedH3EdgeGraph<W>,
+    new_graph: &PreparedH3EdgeGraph<W>,
+) -> Result<GraphDiff<W>, Error>
+where
+    W: PartialEq + Copy,
+{
+    if old_graph.h3_resolution() != new_graph.h3_resolution() {
+        return Err(Error::MixedH3Resolutions(
+            old_graph.h3_resolution(),
+            new_graph.h3_resolution(),
+        ));
+    }
+
+    let mut old_edges: DirectedEdgeMap<W> = old_graph
+        .iter_edges()
+        .map(|(edge, edge_weight)| (edge, edge_weight.weight))
+        .collect();
+
+    let mut added_edges = Vec::new();
+    let mut changed_edges = Vec::new();
+    for (edge, edge_weight) in new_graph.iter_edges() {
+        match old_edges.remove(&edge) {
+            Some(old_weight) => {
+                if old_weight != edge_weight.weight {
+                    changed_edges.push((edge, old_weight, edge_weight.weight));
+                }
+            }
+            None => added_edges.push((edge, edge_weight.weight)),
+        }
+    }
+    // all edges remaining are not contained in the new graph
+    let mut removed_edges: Vec<_> = old_edges.into_iter().collect();
+
+    added_edges.sort_unstable_by_key(|(edge, _)| *edge);
+    removed_edges.sort_unstable_by_key(|(edge, _)| *edge);
+    changed_edges.sort_unstable_by_key(|(edge, _, _)| *edge);
+
+    Ok(GraphDiff {
+        added_edges,
+        removed_edges,
+        changed_edges,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Coord, LineString};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::{DirectedEdgeIndex, Resolution};
+
+    use crate::algorithm::edge::continuous_cells_to_edges;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    use super::diff_graphs;
+
+    fn build_graph(edges: &[(DirectedEdgeIndex, u32)]) -> PreparedH3EdgeGraph<u32> {
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for (edge, weight) in edges {
+            graph.add_edge(*edge, *weight);
+        }
+        PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap()
+    }
+
+    #[test]
+    fn test_diff_graphs() {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.4, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Nine))
+        .collect();
+        let edges: Vec<_> = continuous_cells_to_edges(cells)
+            .map(|edge| (edge, 10))
+            .collect();
+        assert!(edges.len() > 10);
+        let old_graph = build_graph(&edges);
+
+        let mut modified_edges = edges.clone();
+        let (removed_edge, removed_weight) = modified_edges.remove(3);
+        modified_edges[5].1 = 20;
+        let changed_edge = modified_edges[5].0;
+        let new_graph = build_graph(&modified_edges);
+
+        assert!(diff_graphs(&old_graph, &old_graph).unwrap().is_empty());
+
+        let diff = diff_graphs(&old_graph, &new_graph).unwrap();
+        assert!(diff.added_edges.is_empty());
+        assert_eq!(diff.removed_edges, vec![(removed_edge, removed_weight)]);
+        assert_eq!(diff.changed_edges, vec![(changed_edge, 10, 20)]);
+
+        // the reverse comparison
+        let diff = diff_graphs(&new_graph, &old_graph).unwrap();
+        assert_eq!(diff.added_edges, vec![(removed_edge, removed_weight)]);
+        assert!(diff.removed_edges.is_empty());
+        assert_eq!(diff.changed_edges, vec![(changed_edge, 20, 10)]);
+    }
+
+    #[test]
+    fn test_diff_graphs_mixed_resolutions() {
+        let old_graph = build_graph(&[]);
+        let new_graph =
+            PreparedH3EdgeGraph::from_h3edge_graph(H3EdgeGraph::<u32>::new(Resolution::Ten), 3)
+                .unwrap();
+        assert!(diff_graphs(&old_graph, &new_graph).is_err());
+    }
+}
@@ -43,15 +43,35 @@ impl FastForward {
     }
 
     /// length of `self` as the number of contained h3edges
+    ///
+    /// This is read from the `edge_path` without decompressing it.
     pub const fn h3edges_len(&self) -> usize {
         self.edge_path.len().saturating_sub(1)
     }
 
+    /// access the [`DirectedEdgeIndex`] at position `idx` of the `edge_path` without
+    /// collecting the complete path.
+    ///
+    /// The decompression stops once the bytes required for the edge are decoded. As the
+    /// bytes of all edges are stored grouped by their significance, this still decodes
+    /// most of the `edge_path`, see [`Decompressor::decompress_index_at`].
+    pub fn edge_at(
+        &self,
+        idx: usize,
+        decompressor: &mut Decompressor,
+    ) -> Result<DirectedEdgeIndex, Error> {
+        decompressor
+            .decompress_index_at(&self.edge_path, idx)?
+            .ok_or(Error::IndexOutOfBounds(idx))
+    }
+
     pub fn to_linestring(&self) -> Result<LineString<f64>, Error> {
-        let mut decomp = Decompressor::new();
-        let edges = decomp
-            .decompress_block(&self.edge_path)?
-            .collect::<Result<Vec<_>, _>>()?;
+        let edges = Decompressor::with_thread_local(|decompressor| -> Result<Vec<_>, Error> {
+            decompressor
+                .decompress_block(&self.edge_path)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(Error::from)
+        })?;
         let mut mls = edges_to_multilinestring(edges.into_iter());
 
         if mls.0.len() != 1 {
@@ -101,3 +121,46 @@ where
     out_vec.extend(iter.map(|h3edge| h3edge.borrow().destination()));
     out_vec
 }
+
+#[cfg(test)]
+mod tests {
+    use geo::{Coord, LineString};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::Resolution;
+
+    use crate::container::block::Decompressor;
+
+    use super::FastForward;
+
+    #[test]
+    fn edge_at_matches_sequential_decompression() {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((23.8, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Eight))
+        .collect();
+        let fastforward = FastForward::try_from(
+            cells
+                .windows(2)
+                .map(|w| w[0].edge(w[1]).unwrap())
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+
+        let mut decompressor = Decompressor::default();
+        let edges = decompressor
+            .decompress_block(&fastforward.edge_path)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert!(edges.len() > 10);
+        assert_eq!(fastforward.h3edges_len(), edges.len() - 1);
+
+        for (idx, edge) in edges.iter().enumerate() {
+            assert_eq!(&fastforward.edge_at(idx, &mut decompressor).unwrap(), edge);
+        }
+        assert!(fastforward.edge_at(edges.len(), &mut decompressor).is_err());
+    }
+}
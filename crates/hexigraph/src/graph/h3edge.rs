@@ -2,16 +2,18 @@ use std::ops::Add;
 
 use ahash::RandomState;
 use geo::MultiPolygon;
-use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
+use h3o::{CellIndex, DirectedEdgeIndex, LatLng, Resolution};
 use hashbrown::hash_map::Entry;
+use rayon::prelude::*;
 use tracing::debug;
 
-use crate::algorithm::graph::covered_area::cells_covered_area;
+use crate::algorithm::edge::reverse_directed_edge;
+use crate::algorithm::graph::covered_area::{cells_covered_area, cells_covered_area_auto};
 use crate::algorithm::graph::CoveredArea;
 use crate::container::{CellMap, DirectedEdgeMap};
 use crate::error::Error;
 use crate::graph::node::NodeType;
-use crate::graph::{EdgeWeight, GetEdge, GetStats};
+use crate::graph::{EdgeAttributes, EdgeWeight, GetEdge, GetEdgeAttributes, GetStats};
 use crate::HasH3Resolution;
 
 use super::GraphStats;
@@ -21,6 +23,10 @@ use super::GraphStats;
 pub struct H3EdgeGraph<W> {
     pub edges: DirectedEdgeMap<W>,
     pub h3_resolution: Resolution,
+
+    /// optional side-table of attributes of the edges. `None` unless attributes were set,
+    /// so graphs without attributes do not pay for them.
+    pub edge_attributes: Option<DirectedEdgeMap<EdgeAttributes>>,
 }
 
 impl<W> H3EdgeGraph<W>
@@ -31,6 +37,7 @@ where
         Self {
             h3_resolution,
             edges: Default::default(),
+            edge_attributes: None,
         }
     }
 
@@ -82,9 +89,21 @@ where
         for (edge, weight) in other.edges.into_iter() {
             self.add_edge(edge, weight);
         }
+        if let Some(edge_attributes) = other.edge_attributes {
+            self.edge_attributes
+                .get_or_insert_with(Default::default)
+                .extend(edge_attributes);
+        }
         Ok(())
     }
 
+    /// attach `attributes` to the `edge`.
+    pub fn set_edge_attributes(&mut self, edge: DirectedEdgeIndex, attributes: EdgeAttributes) {
+        self.edge_attributes
+            .get_or_insert_with(Default::default)
+            .insert(edge, attributes);
+    }
+
     /// cells which are valid targets to route to
     ///
     /// This is a rather expensive operation as nodes are not stored anywhere
@@ -97,9 +116,100 @@ where
         extract_nodes(&self.edges)
     }
 
+    /// iterate over the nodes of the graph together with the coordinates of their centroids
+    ///
+    /// As [`H3EdgeGraph::nodes`], this requires extracting the nodes from the edges first.
+    pub fn iter_nodes_with_coords(&self) -> impl Iterator<Item = (CellIndex, NodeType, LatLng)> {
+        self.nodes()
+            .into_iter()
+            .map(|(cell, node_type)| (cell, node_type, LatLng::from(cell)))
+    }
+
     pub fn iter_edges(&self) -> impl Iterator<Item = (DirectedEdgeIndex, &W)> {
         self.edges.iter().map(|(edge, weight)| (*edge, weight))
     }
+
+    /// the transposed graph with all edges reversed while keeping their weights.
+    ///
+    /// Routing from a cell in the reversed graph finds the paths leading to that cell in
+    /// this graph. As the nodes are derived from the edges, origins become destinations and
+    /// vice versa. Edge attributes move to the reversed edges.
+    pub fn reverse(&self) -> Self {
+        Self {
+            edges: self
+                .edges
+                .iter()
+                .map(|(edge, weight)| (reverse_directed_edge(*edge), *weight))
+                .collect(),
+            h3_resolution: self.h3_resolution,
+            edge_attributes: self.edge_attributes.as_ref().map(|edge_attributes| {
+                edge_attributes
+                    .iter()
+                    .map(|(edge, attributes)| (reverse_directed_edge(*edge), attributes.clone()))
+                    .collect()
+            }),
+        }
+    }
+}
+
+impl<W> H3EdgeGraph<W>
+where
+    W: PartialOrd + PartialEq + Add + Copy + Send + Sync,
+{
+    /// Build a graph from a large number of `edges` using multiple threads.
+    ///
+    /// The edges are partitioned by the hash of their origin cell and the partitions are built
+    /// concurrently. As all edges of an origin cell end up in the same partition, the partitions
+    /// do not share any edges and are merged without further comparisons.
+    ///
+    /// The result equals adding all edges using [`H3EdgeGraph::add_edge`], so for
+    /// duplicated edges the lower weight takes precedence.
+    ///
+    /// All edges are required to be of the given `h3_resolution`.
+    pub fn try_from_edges_parallel(
+        h3_resolution: Resolution,
+        edges: Vec<(DirectedEdgeIndex, W)>,
+    ) -> Result<Self, Error> {
+        if let Some(edge_h3_resolution) = edges
+            .par_iter()
+            .map(|(edge, _)| edge.origin().resolution())
+            .find_any(|edge_h3_resolution| *edge_h3_resolution != h3_resolution)
+        {
+            return Err(Error::MixedH3Resolutions(h3_resolution, edge_h3_resolution));
+        }
+
+        let num_partitions = rayon::current_num_threads().max(1);
+        let hasher = RandomState::default();
+        let mut partitions: Vec<Vec<(DirectedEdgeIndex, W)>> = (0..num_partitions)
+            .map(|_| Vec::with_capacity(edges.len() / num_partitions))
+            .collect();
+        for (edge, weight) in edges {
+            let partition = (hasher.hash_one(edge.origin()) % num_partitions as u64) as usize;
+            partitions[partition].push((edge, weight));
+        }
+
+        let partition_graphs: Vec<_> = partitions
+            .into_par_iter()
+            .map(|partition| {
+                let mut graph = Self::new(h3_resolution);
+                graph.edges.reserve(partition.len());
+                for (edge, weight) in partition {
+                    graph.add_edge(edge, weight);
+                }
+                graph.edges
+            })
+            .collect();
+
+        let mut graph = Self::new(h3_resolution);
+        graph
+            .edges
+            .reserve(partition_graphs.iter().map(|edges| edges.len()).sum());
+        // the partitions are disjoint, so there are no duplicated edges to resolve
+        graph
+            .edges
+            .par_extend(partition_graphs.into_par_iter().flatten());
+        Ok(graph)
+    }
 }
 
 fn extract_nodes<W>(edge_map: &DirectedEdgeMap<W>) -> CellMap<NodeType> {
@@ -150,6 +260,14 @@ where
     }
 }
 
+impl<W> GetEdgeAttributes for H3EdgeGraph<W> {
+    fn get_edge_attributes(&self, edge: DirectedEdgeIndex) -> Option<&EdgeAttributes> {
+        self.edge_attributes
+            .as_ref()
+            .and_then(|edge_attributes| edge_attributes.get(&edge))
+    }
+}
+
 impl<W> CoveredArea for H3EdgeGraph<W>
 where
     W: PartialOrd + PartialEq + Add + Copy,
@@ -163,6 +281,17 @@ where
             reduce_resolution_by,
         )
     }
+
+    fn covered_area_auto(
+        &self,
+        target_max_vertices: usize,
+    ) -> Result<MultiPolygon<f64>, Self::Error> {
+        cells_covered_area_auto(
+            self.nodes().iter().map(|(cell, _)| *cell),
+            self.h3_resolution(),
+            target_max_vertices,
+        )
+    }
 }
 
 /// change the resolution of a graph to a lower resolution
@@ -218,6 +347,8 @@ where
     Ok(H3EdgeGraph {
         edges: downsampled_edges,
         h3_resolution: target_h3_resolution,
+        // attributes of edges do not apply to the downsampled edges
+        edge_attributes: None,
     })
 }
 
@@ -236,18 +367,26 @@ mod tests {
     use h3o::geom::{PolyfillConfig, ToCells};
     use h3o::{LatLng, Resolution};
 
+    use crate::algorithm::graph::shortest_path::DefaultShortestPathOptions;
+    use crate::algorithm::graph::ShortestPath;
+    use crate::graph::PreparedH3EdgeGraph;
+
     use super::{downsample_graph, H3EdgeGraph, NodeType};
 
-    #[test]
-    fn test_downsample() {
-        let full_h3_res = Resolution::Eight;
-        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+    fn line_cells(h3_resolution: Resolution) -> Vec<h3o::CellIndex> {
+        h3o::geom::LineString::from_degrees(LineString::from(vec![
             Coord::from((23.3, 12.3)),
             Coord::from((24.2, 12.2)),
         ]))
         .unwrap()
-        .to_cells(PolyfillConfig::new(full_h3_res))
-        .collect();
+        .to_cells(PolyfillConfig::new(h3_resolution))
+        .collect()
+    }
+
+    #[test]
+    fn test_downsample() {
+        let full_h3_res = Resolution::Eight;
+        let cells = line_cells(full_h3_res);
         assert!(cells.len() > 100);
 
         let mut graph = H3EdgeGraph::new(full_h3_res);
@@ -293,4 +432,91 @@ mod tests {
         );
         assert_eq!(nodes.get(&edges2[0].1), Some(&NodeType::Destination));
     }
+
+    #[test]
+    fn test_try_from_edges_parallel() {
+        let res = Resolution::Eight;
+        let cells = line_cells(res);
+
+        let mut edges = Vec::with_capacity(cells.len() * 4);
+        for (i, w) in cells.windows(2).enumerate() {
+            let weight = (i % 7) as u32;
+            edges.push((w[0].edge(w[1]).unwrap(), weight + 2));
+            edges.push((w[1].edge(w[0]).unwrap(), weight + 1));
+            // duplicate with a higher weight, which must not take precedence
+            edges.push((w[0].edge(w[1]).unwrap(), weight + 10));
+        }
+
+        let mut serial_graph = H3EdgeGraph::new(res);
+        for (edge, weight) in edges.iter() {
+            serial_graph.add_edge(*edge, *weight);
+        }
+
+        // deterministic shuffle
+        let mut shuffled = Vec::with_capacity(edges.len());
+        let (evens, odds): (Vec<_>, Vec<_>) =
+            edges.iter().enumerate().partition(|(i, _)| i % 2 == 0);
+        shuffled.extend(odds.into_iter().rev().map(|(_, e)| *e));
+        shuffled.extend(evens.into_iter().map(|(_, e)| *e));
+
+        let parallel_graph = H3EdgeGraph::try_from_edges_parallel(res, shuffled).unwrap();
+        assert_eq!(parallel_graph.num_edges(), serial_graph.num_edges());
+        for (edge, weight) in serial_graph.iter_edges() {
+            assert_eq!(parallel_graph.edge_weight(edge), Some(weight));
+        }
+
+        // mixed resolutions
+        let other_res_edge = line_cells(Resolution::Seven)
+            .windows(2)
+            .map(|w| w[0].edge(w[1]).unwrap())
+            .next()
+            .unwrap();
+        assert!(H3EdgeGraph::try_from_edges_parallel(res, vec![(other_res_edge, 1u32)]).is_err());
+    }
+
+    #[test]
+    fn test_reverse() {
+        let res = Resolution::Eight;
+        let cells = line_cells(res);
+        let (a, b) = (cells[0], cells[cells.len() - 1]);
+
+        // `None` when there is no route. Cells which are not origins or destinations of
+        // the graph lead to errors, which are treated the same.
+        let route_cost = |graph: &H3EdgeGraph<u32>, origin, destination| {
+            let prepared_graph = PreparedH3EdgeGraph::try_from(graph.clone()).unwrap();
+            prepared_graph
+                .shortest_path(
+                    origin,
+                    [destination],
+                    &DefaultShortestPathOptions::default(),
+                )
+                .ok()
+                .and_then(|paths| paths.first().map(|path| path.cost))
+        };
+
+        // symmetric graph with weights differing by direction
+        let mut graph = H3EdgeGraph::new(res);
+        for w in cells.windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), 2);
+            graph.add_edge(w[1].edge(w[0]).unwrap(), 3);
+        }
+        let reversed = graph.reverse();
+        assert_eq!(reversed.num_edges(), graph.num_edges());
+        assert_eq!(route_cost(&graph, a, b), route_cost(&reversed, b, a));
+        assert_ne!(route_cost(&graph, a, b), route_cost(&graph, b, a));
+
+        // one-way graph
+        let mut graph = H3EdgeGraph::new(res);
+        for w in cells.windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), 2);
+        }
+        let reversed = graph.reverse();
+        assert!(route_cost(&graph, a, b).is_some());
+        assert!(route_cost(&graph, b, a).is_none());
+        assert!(route_cost(&reversed, a, b).is_none());
+        assert_eq!(route_cost(&reversed, b, a), route_cost(&graph, a, b));
+
+        assert_eq!(graph.nodes().get(&a), Some(&NodeType::Origin));
+        assert_eq!(reversed.nodes().get(&a), Some(&NodeType::Destination));
+    }
 }
@@ -1,16 +1,20 @@
 use crate::error::Error;
+pub use attributes::EdgeAttributes;
 pub use h3edge::{H3EdgeGraph, H3EdgeGraphBuilder};
 use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
 use node::NodeType;
-pub use prepared::PreparedH3EdgeGraph;
+pub use prepared::{FastForwardOptions, PreparedH3EdgeGraph};
 
 use crate::graph::fastforward::FastForward;
 
+pub mod attributes;
+pub mod diff;
 pub mod fastforward;
 pub mod h3edge;
 pub mod modifiers;
 pub mod node;
 pub mod prepared;
+pub mod validate;
 
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GraphStats {
@@ -41,6 +45,25 @@ pub trait GetCellEdges {
         &self,
         cell: CellIndex,
     ) -> Vec<(DirectedEdgeIndex, EdgeWeight<Self::EdgeWeightType>)>;
+
+    /// `true` when the graph contains no edges at all, so no routing can succeed.
+    ///
+    /// Defaults to `false` for graphs which can not tell cheaply.
+    fn is_edgeless(&self) -> bool {
+        false
+    }
+}
+
+/// access to the [`EdgeAttributes`] side-table of a graph.
+pub trait GetEdgeAttributes {
+    /// the attributes of `edge`. Always `None` when the graph carries no edge attributes.
+    fn get_edge_attributes(&self, edge: DirectedEdgeIndex) -> Option<&EdgeAttributes>;
+}
+
+/// weights which carry the duration it takes to traverse an edge.
+pub trait TravelDuration {
+    /// the travel duration in seconds
+    fn travel_duration_secs(&self) -> f64;
 }
 
 pub trait GetEdge {
@@ -82,6 +82,10 @@ where
             not_excluded
         }
     }
+
+    fn is_edgeless(&self) -> bool {
+        self.inner_graph.is_edgeless()
+    }
 }
 
 impl<'a, G, W> HasH3Resolution for ExcludeCells<'a, G, W>
@@ -6,19 +6,23 @@ use geo::{Coord, MultiPoint, MultiPolygon, Point, Polygon, Rect};
 use h3o::{CellIndex, DirectedEdgeIndex, LatLng, Resolution};
 use hashbrown::hash_map::Entry;
 use num_traits::Zero;
+use once_cell::sync::OnceCell;
 use rayon::prelude::*;
 
 use crate::algorithm::edge::reverse_directed_edge;
-use crate::algorithm::graph::covered_area::cells_covered_area;
+use crate::algorithm::graph::covered_area::{
+    cells_covered_area, cells_covered_area_auto_cached, covered_area_resolution, CoveredAreaCache,
+};
 use crate::algorithm::graph::CoveredArea;
 use crate::container::block::Decompressor;
 use crate::container::treemap::H3Treemap;
-use crate::container::{CellMap, DirectedEdgeMap};
+use crate::container::{CellMap, CellSet, DirectedEdgeMap};
 use crate::error::Error;
 use crate::graph::fastforward::FastForward;
 use crate::graph::node::NodeType;
 use crate::graph::{
-    EdgeWeight, GetCellEdges, GetCellNode, GetStats, GraphStats, H3EdgeGraph, IterateCellNodes,
+    EdgeAttributes, EdgeWeight, GetCellEdges, GetCellNode, GetEdgeAttributes, GetStats, GraphStats,
+    H3EdgeGraph, IterateCellNodes, TravelDuration,
 };
 use crate::HasH3Resolution;
 
@@ -74,6 +78,18 @@ pub struct PreparedH3EdgeGraph<W> {
     outgoing_edges: CellMap<OwnedEdgeTupleList<W>>,
     h3_resolution: Resolution,
     graph_nodes: CellMap<NodeType>,
+
+    /// optional side-table of attributes of the edges, taken over from the [`H3EdgeGraph`].
+    edge_attributes: Option<DirectedEdgeMap<EdgeAttributes>>,
+
+    /// the covered areas only need to be calculated once, as long as the graph
+    /// does not get prepared again.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    covered_area_cache: CoveredAreaCache,
+
+    /// the highest speed any edge of the graph is traversed with, in meters per second.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_edge_speed_cache: OnceCell<f64>,
 }
 
 unsafe impl<W> Sync for PreparedH3EdgeGraph<W> where W: Sync {}
@@ -95,6 +111,23 @@ impl<W> PreparedH3EdgeGraph<W> {
         }
         (num_edges, num_fast_forwards)
     }
+
+    /// iterate over the nodes of the graph together with the coordinates of their centroids
+    pub fn iter_nodes_with_coords(
+        &self,
+    ) -> impl Iterator<Item = (CellIndex, NodeType, LatLng)> + '_ {
+        self.graph_nodes
+            .iter()
+            .map(|(cell, node_type)| (*cell, *node_type, LatLng::from(*cell)))
+    }
+
+    /// replace the side-table of edge attributes.
+    pub fn set_edge_attributes(
+        &mut self,
+        edge_attributes: Option<DirectedEdgeMap<EdgeAttributes>>,
+    ) {
+        self.edge_attributes = edge_attributes;
+    }
 }
 
 impl<W> PreparedH3EdgeGraph<W>
@@ -117,19 +150,21 @@ where
         &self,
     ) -> Result<impl Iterator<Item = (DirectedEdgeIndex, EdgeWeight<W>)>, Error> {
         let mut covered_edges = H3Treemap::<DirectedEdgeIndex>::default();
-        let mut decompressor = Decompressor::default();
-        for (_, owned_edge_values) in self.outgoing_edges.iter() {
-            for (_, owned_edge_value) in owned_edge_values.iter() {
-                if let Some(boxed_fastforward) = owned_edge_value.fastforward.as_ref() {
-                    for edge in decompressor
-                        .decompress_block::<DirectedEdgeIndex>(&boxed_fastforward.0.edge_path)?
-                        .skip(1)
-                    {
-                        covered_edges.insert(edge?);
+        Decompressor::with_thread_local(|decompressor| -> Result<(), Error> {
+            for (_, owned_edge_values) in self.outgoing_edges.iter() {
+                for (_, owned_edge_value) in owned_edge_values.iter() {
+                    if let Some(boxed_fastforward) = owned_edge_value.fastforward.as_ref() {
+                        for edge in decompressor
+                            .decompress_block::<DirectedEdgeIndex>(&boxed_fastforward.0.edge_path)?
+                            .skip(1)
+                        {
+                            covered_edges.insert(edge?);
+                        }
                     }
                 }
             }
-        }
+            Ok(())
+        })?;
         Ok(self.iter_edges().filter_map(move |(edge, weight)| {
             if covered_edges.contains(&edge) {
                 None
@@ -143,6 +178,54 @@ where
 /// Iterator item type to build [`PreparedH3EdgeGraph`] from
 pub type FromIterItem<W> = (DirectedEdgeIndex, W, Option<(Vec<DirectedEdgeIndex>, W)>);
 
+impl<W> PreparedH3EdgeGraph<W>
+where
+    W: Copy + TravelDuration,
+{
+    /// the highest speed any edge of the graph is traversed with, in meters per second.
+    ///
+    /// The speed of an edge is the distance between the centroids of its cells divided
+    /// by its travel duration. Edges with a travel duration of zero result in an infinite
+    /// speed. The value is computed on the first call and cached until cells of the
+    /// graph get prepared again.
+    pub fn max_edge_speed_m_per_s(&self) -> f64 {
+        *self.max_edge_speed_cache.get_or_init(|| {
+            self.iter_edges()
+                .map(|(edge, edge_weight)| {
+                    let (cell_from, cell_to) = edge.cells();
+                    let distance_m = LatLng::from(cell_from).distance_m(LatLng::from(cell_to));
+                    let travel_duration_secs = edge_weight.weight.travel_duration_secs();
+                    if travel_duration_secs > 0.0 {
+                        distance_m / travel_duration_secs
+                    } else {
+                        f64::INFINITY
+                    }
+                })
+                .fold(0.0, f64::max)
+        })
+    }
+
+    /// a lower bound of the travel duration in seconds between `origin_cell`
+    /// and `destination_cell`.
+    ///
+    /// The great-circle distance between the centroids of the cells traversed with
+    /// the [`max_edge_speed_m_per_s`](Self::max_edge_speed_m_per_s) of the graph. As no path can
+    /// be shorter than the great-circle distance or faster than the fastest edge, this
+    /// never overestimates the actual travel duration and is usable as an admissible
+    /// heuristic for A*.
+    pub fn min_travel_duration_secs(
+        &self,
+        origin_cell: CellIndex,
+        destination_cell: CellIndex,
+    ) -> f64 {
+        let max_speed = self.max_edge_speed_m_per_s();
+        if max_speed <= 0.0 || max_speed.is_infinite() {
+            return 0.0;
+        }
+        LatLng::from(origin_cell).distance_m(LatLng::from(destination_cell)) / max_speed
+    }
+}
+
 impl<W> PreparedH3EdgeGraph<W>
 where
     W: Copy + Send + Sync,
@@ -207,6 +290,9 @@ where
                 outgoing_edges,
                 h3_resolution,
                 graph_nodes,
+                edge_attributes: None,
+                covered_area_cache: Default::default(),
+                max_edge_speed_cache: Default::default(),
             })
         } else {
             Err(Error::InsufficientNumberOfEdges)
@@ -236,6 +322,14 @@ impl<W> GetCellNode for PreparedH3EdgeGraph<W> {
     }
 }
 
+impl<W> GetEdgeAttributes for PreparedH3EdgeGraph<W> {
+    fn get_edge_attributes(&self, edge: DirectedEdgeIndex) -> Option<&EdgeAttributes> {
+        self.edge_attributes
+            .as_ref()
+            .and_then(|edge_attributes| edge_attributes.get(&edge))
+    }
+}
+
 impl<W: Copy> GetCellEdges for PreparedH3EdgeGraph<W> {
     type EdgeWeightType = W;
 
@@ -253,35 +347,70 @@ impl<W: Copy> GetCellEdges for PreparedH3EdgeGraph<W> {
         }
         out_vec
     }
+
+    fn is_edgeless(&self) -> bool {
+        self.outgoing_edges.is_empty()
+    }
 }
 
 const MIN_LONGEDGE_LENGTH: usize = 3;
 
+/// Options controlling the creation of [`FastForward`]s when preparing a graph.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FastForwardOptions {
+    /// minimum number of edges a [`FastForward`] must consist of.
+    pub min_length: usize,
+
+    /// maximum number of edges a [`FastForward`] may consist of.
+    ///
+    /// Very long fastforwards are expensive to decompress. When a sequence of
+    /// edges exceeds this length, the fastforward ends there and the remaining edges
+    /// get traversed one by one.
+    pub max_length: Option<usize>,
+}
+
+impl Default for FastForwardOptions {
+    fn default() -> Self {
+        Self {
+            min_length: 4,
+            max_length: None,
+        }
+    }
+}
+
+impl FastForwardOptions {
+    fn validate(&self) -> Result<(), Error> {
+        if self.min_length < MIN_LONGEDGE_LENGTH {
+            return Err(Error::TooShortLongEdge(MIN_LONGEDGE_LENGTH));
+        }
+        if let Some(max_length) = self.max_length {
+            if max_length < self.min_length {
+                return Err(Error::TooShortMaxLongEdge(self.min_length));
+            }
+        }
+        Ok(())
+    }
+}
+
 fn to_fastforward_edges<W>(
     input_graph: H3EdgeGraph<W>,
-    min_fastforward_length: usize,
+    options: &FastForwardOptions,
 ) -> Result<CellMap<OwnedEdgeTupleList<W>>, Error>
 where
     W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync,
 {
-    if min_fastforward_length < MIN_LONGEDGE_LENGTH {
-        return Err(Error::TooShortLongEdge(min_fastforward_length));
-    }
+    options.validate()?;
 
     let outgoing_edge_vecs = input_graph
         .edges
         .par_iter()
         .try_fold(Vec::new, |mut output_vec, (edge, weight)| {
-            assemble_edge_with_fastforward(
-                &input_graph.edges,
-                min_fastforward_length,
-                *edge,
-                weight,
+            assemble_edge_with_fastforward(&input_graph.edges, options, *edge, weight).map(
+                |cell_edge| {
+                    output_vec.push(cell_edge);
+                    output_vec
+                },
             )
-            .map(|cell_edge| {
-                output_vec.push(cell_edge);
-                output_vec
-            })
         })
         .collect::<Result<Vec<_>, _>>()?;
 
@@ -322,7 +451,7 @@ where
 
 fn assemble_edge_with_fastforward<W>(
     input_edges: &DirectedEdgeMap<W>,
-    min_fastforward_length: usize,
+    options: &FastForwardOptions,
     edge: DirectedEdgeIndex,
     weight: &W,
 ) -> Result<(CellIndex, OwnedEdgeTuple<W>), Error>
@@ -351,6 +480,11 @@ where
 
         let mut last_edge = edge;
         loop {
+            if let Some(max_length) = options.max_length {
+                if edge_path.len() >= max_length {
+                    break;
+                }
+            }
             let last_edge_reverse = reverse_directed_edge(last_edge);
             // follow the edges until the end or a conjunction is reached
             let following_edges: Vec<_> = last_edge
@@ -382,7 +516,7 @@ where
             last_edge = following_edge;
         }
 
-        if edge_path.len() >= min_fastforward_length {
+        if edge_path.len() >= options.min_length {
             graph_entry.fastforward = Some(Box::new((
                 FastForward::try_from(edge_path)?,
                 fastforward_weight,
@@ -399,18 +533,140 @@ where
     pub fn from_h3edge_graph(
         graph: H3EdgeGraph<W>,
         min_fastforward_length: usize,
+    ) -> Result<Self, Error> {
+        Self::from_h3edge_graph_with_options(
+            graph,
+            &FastForwardOptions {
+                min_length: min_fastforward_length,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn from_h3edge_graph_with_options(
+        mut graph: H3EdgeGraph<W>,
+        fastforward_options: &FastForwardOptions,
     ) -> Result<Self, Error> {
         let h3_resolution = graph.h3_resolution();
         let graph_nodes = graph.nodes();
-        let outgoing_edges = to_fastforward_edges(graph, min_fastforward_length)?;
+        let edge_attributes = graph.edge_attributes.take();
+        let outgoing_edges = to_fastforward_edges(graph, fastforward_options)?;
         Ok(Self {
             graph_nodes,
             h3_resolution,
             outgoing_edges,
+            edge_attributes,
+            covered_area_cache: Default::default(),
+            max_edge_speed_cache: Default::default(),
         })
     }
 }
 
+impl<W> PreparedH3EdgeGraph<W>
+where
+    W: PartialOrd + PartialEq + Add<Output = W> + Copy + Send + Sync,
+{
+    /// prepare the graph again after the edges of the `changed_cells` have been modified.
+    ///
+    /// `graph` is the graph this one was prepared from with the modifications applied, the
+    /// `fastforward_options` must be the ones the graph was prepared with. Only the edges
+    /// whose fastforwards may be affected by the modifications get prepared again. These are
+    /// the edges originating within the minimum fastforward length of a changed cell and the
+    /// edges whose fastforwards pass a changed cell.
+    pub fn reprepare_cells(
+        &mut self,
+        graph: &H3EdgeGraph<W>,
+        changed_cells: &CellSet,
+        fastforward_options: &FastForwardOptions,
+    ) -> Result<(), Error> {
+        fastforward_options.validate()?;
+        if graph.h3_resolution != self.h3_resolution {
+            return Err(Error::MixedH3Resolutions(
+                self.h3_resolution,
+                graph.h3_resolution,
+            ));
+        }
+
+        // paths too short for a fastforward are not stored, so all edges which may
+        // reach a changed cell within the minimum fastforward length are included.
+        let mut cells_to_prepare: CellSet = changed_cells
+            .iter()
+            .flat_map(|cell| cell.grid_disk::<Vec<_>>(fastforward_options.min_length as u32))
+            .collect();
+        Decompressor::with_thread_local(|decompressor| -> Result<(), Error> {
+            for (cell, owned_edge_values) in self.outgoing_edges.iter() {
+                if cells_to_prepare.contains(cell) {
+                    continue;
+                }
+                for (_, owned_edge_value) in owned_edge_values.iter() {
+                    if let Some(boxed_fastforward) = owned_edge_value.fastforward.as_ref() {
+                        for edge in decompressor
+                            .decompress_block::<DirectedEdgeIndex>(&boxed_fastforward.0.edge_path)?
+                        {
+                            if changed_cells.contains(&edge?.destination()) {
+                                cells_to_prepare.insert(*cell);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })?;
+
+        for cell in cells_to_prepare.iter() {
+            let mut edges_with_weights = graph
+                .edges_from_cell(*cell)
+                .map(|(edge, weight)| {
+                    assemble_edge_with_fastforward(&graph.edges, fastforward_options, *edge, weight)
+                        .map(|(_, edge_with_weight)| edge_with_weight)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if edges_with_weights.is_empty() {
+                self.outgoing_edges.remove(cell);
+            } else {
+                edges_with_weights.sort_unstable_by_key(|eww| eww.0);
+                self.outgoing_edges
+                    .insert(*cell, edges_with_weights.into_boxed_slice());
+            }
+        }
+
+        for cell in changed_cells.iter() {
+            let is_origin = graph.edges_from_cell(*cell).next().is_some();
+            let is_destination = cell
+                .edges()
+                .any(|edge| graph.edges.contains_key(&reverse_directed_edge(edge)));
+            match (is_origin, is_destination) {
+                (true, true) => self
+                    .graph_nodes
+                    .insert(*cell, NodeType::OriginAndDestination),
+                (true, false) => self.graph_nodes.insert(*cell, NodeType::Origin),
+                (false, true) => self.graph_nodes.insert(*cell, NodeType::Destination),
+                (false, false) => self.graph_nodes.remove(cell),
+            };
+
+            for edge in cell.edges() {
+                match graph.get_edge_attributes(edge) {
+                    Some(attributes) => {
+                        self.edge_attributes
+                            .get_or_insert_with(Default::default)
+                            .insert(edge, attributes.clone());
+                    }
+                    None => {
+                        if let Some(edge_attributes) = self.edge_attributes.as_mut() {
+                            edge_attributes.remove(&edge);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.covered_area_cache = Default::default();
+        self.max_edge_speed_cache = Default::default();
+        Ok(())
+    }
+}
+
 impl<W> TryFrom<H3EdgeGraph<W>> for PreparedH3EdgeGraph<W>
 where
     W: PartialOrd + PartialEq + Add + Copy + Ord + Zero + Send + Sync,
@@ -418,7 +674,7 @@ where
     type Error = Error;
 
     fn try_from(graph: H3EdgeGraph<W>) -> Result<Self, Self::Error> {
-        Self::from_h3edge_graph(graph, 4)
+        Self::from_h3edge_graph_with_options(graph, &FastForwardOptions::default())
     }
 }
 
@@ -427,12 +683,14 @@ where
     W: PartialOrd + PartialEq + Add + Copy + Ord + Zero,
 {
     fn from(prepared_graph: PreparedH3EdgeGraph<W>) -> Self {
+        let edges = prepared_graph
+            .iter_edges()
+            .map(|(edge, edge_value)| (edge, edge_value.weight))
+            .collect();
         Self {
-            edges: prepared_graph
-                .iter_edges()
-                .map(|(edge, edge_value)| (edge, edge_value.weight))
-                .collect(),
+            edges,
             h3_resolution: prepared_graph.h3_resolution,
+            edge_attributes: prepared_graph.edge_attributes,
         }
     }
 }
@@ -441,10 +699,27 @@ impl<W> CoveredArea for PreparedH3EdgeGraph<W> {
     type Error = Error;
 
     fn covered_area(&self, reduce_resolution_by: u8) -> Result<MultiPolygon<f64>, Self::Error> {
-        cells_covered_area(
+        self.covered_area_cache.covered_area(
+            covered_area_resolution(self.h3_resolution(), reduce_resolution_by),
+            || {
+                cells_covered_area(
+                    self.graph_nodes.iter().map(|(cell, _)| cell),
+                    self.h3_resolution(),
+                    reduce_resolution_by,
+                )
+            },
+        )
+    }
+
+    fn covered_area_auto(
+        &self,
+        target_max_vertices: usize,
+    ) -> Result<MultiPolygon<f64>, Self::Error> {
+        cells_covered_area_auto_cached(
             self.graph_nodes.iter().map(|(cell, _)| cell),
             self.h3_resolution(),
-            reduce_resolution_by,
+            target_max_vertices,
+            &self.covered_area_cache,
         )
     }
 }
@@ -478,50 +753,55 @@ impl<W> BoundingRect<f64> for PreparedH3EdgeGraph<W> {
     type Output = Option<Rect<f64>>;
 
     fn bounding_rect(&self) -> Self::Output {
-        let mut iter = self.iter_cell_nodes();
-        let mut rect = {
-            // consume until encountering the first valid cell
-            if let Some(coord) = iter
-                .next()
-                .map(|(cell, _)| -> Coord { LatLng::from(*cell).into() })
-            {
-                Point::from(coord).bounding_rect()
-            } else {
-                return None;
-            }
-        };
+        self.covered_area_cache
+            .bounding_rect(|| nodes_bounding_rect(self))
+    }
+}
 
-        for (cell, _) in iter {
-            let coord: Coord = LatLng::from(*cell).into();
-            rect = Rect::new(
-                Coord {
-                    x: if coord.x < rect.min().x {
-                        coord.x
-                    } else {
-                        rect.min().x
-                    },
-                    y: if coord.y < rect.min().y {
-                        coord.y
-                    } else {
-                        rect.min().y
-                    },
+fn nodes_bounding_rect<W>(graph: &PreparedH3EdgeGraph<W>) -> Option<Rect<f64>> {
+    let mut iter = graph.iter_cell_nodes();
+    let mut rect = {
+        // consume until encountering the first valid cell
+        if let Some(coord) = iter
+            .next()
+            .map(|(cell, _)| -> Coord { LatLng::from(*cell).into() })
+        {
+            Point::from(coord).bounding_rect()
+        } else {
+            return None;
+        }
+    };
+
+    for (cell, _) in iter {
+        let coord: Coord = LatLng::from(*cell).into();
+        rect = Rect::new(
+            Coord {
+                x: if coord.x < rect.min().x {
+                    coord.x
+                } else {
+                    rect.min().x
                 },
-                Coord {
-                    x: if coord.x > rect.max().x {
-                        coord.x
-                    } else {
-                        rect.max().x
-                    },
-                    y: if coord.y > rect.max().y {
-                        coord.y
-                    } else {
-                        rect.max().y
-                    },
+                y: if coord.y < rect.min().y {
+                    coord.y
+                } else {
+                    rect.min().y
                 },
-            );
-        }
-        Some(rect)
+            },
+            Coord {
+                x: if coord.x > rect.max().x {
+                    coord.x
+                } else {
+                    rect.max().x
+                },
+                y: if coord.y > rect.max().y {
+                    coord.y
+                } else {
+                    rect.max().y
+                },
+            },
+        );
     }
+    Some(rect)
 }
 
 #[cfg(test)]
@@ -530,18 +810,21 @@ mod tests {
     use geo::LineString;
     use h3o::geom::{PolyfillConfig, ToCells};
 
-    fn build_line_prepared_graph() -> PreparedH3EdgeGraph<u32> {
-        let full_h3_res = Resolution::Eight;
+    fn line_cells() -> Vec<CellIndex> {
         let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
             Coord::from((23.3, 12.3)),
             Coord::from((24.2, 12.2)),
         ]))
         .unwrap()
-        .to_cells(PolyfillConfig::new(full_h3_res))
+        .to_cells(PolyfillConfig::new(Resolution::Eight))
         .collect();
         assert!(cells.len() > 100);
+        cells
+    }
 
-        let mut graph = H3EdgeGraph::new(full_h3_res);
+    fn build_line_prepared_graph() -> PreparedH3EdgeGraph<u32> {
+        let cells = line_cells();
+        let mut graph = H3EdgeGraph::new(Resolution::Eight);
         for w in cells.windows(2) {
             graph.add_edge(w[0].edge(w[1]).unwrap(), 20u32);
         }
@@ -557,9 +840,141 @@ mod tests {
         assert!(graph.iter_edges().count() > 50);
     }
 
+    #[test]
+    fn test_iter_nodes_with_coords() {
+        let graph = build_line_prepared_graph();
+        let mut num_nodes = 0;
+        for (cell, node_type, coord) in graph.iter_nodes_with_coords() {
+            assert_eq!(coord, LatLng::from(cell));
+            assert_eq!(graph.get_cell_node(cell), Some(node_type));
+            num_nodes += 1;
+        }
+        assert_eq!(num_nodes, line_cells().len());
+
+        let h3edge_graph: H3EdgeGraph<_> = graph.into();
+        assert_eq!(h3edge_graph.iter_nodes_with_coords().count(), num_nodes);
+        for (cell, _, coord) in h3edge_graph.iter_nodes_with_coords() {
+            assert_eq!(coord, LatLng::from(cell));
+        }
+    }
+
     #[test]
     fn test_iter_non_overlapping_edges() {
         let graph = build_line_prepared_graph();
         assert_eq!(graph.iter_edges_non_overlapping().unwrap().count(), 1);
     }
+
+    #[test]
+    fn test_fastforward_options() {
+        let cells = line_cells();
+
+        // two disconnected line segments of different lengths
+        let mut graph = H3EdgeGraph::new(Resolution::Eight);
+        for w in cells[..10].windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), 20u32);
+        }
+        for w in cells[20..].windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), 20u32);
+        }
+
+        let prepare = |options: FastForwardOptions| {
+            PreparedH3EdgeGraph::from_h3edge_graph_with_options(graph.clone(), &options).unwrap()
+        };
+
+        let default_graph = prepare(FastForwardOptions::default());
+        assert_eq!(default_graph.count_edges().1, 2);
+
+        let higher_min_graph = prepare(FastForwardOptions {
+            min_length: 15,
+            ..Default::default()
+        });
+        assert_eq!(higher_min_graph.count_edges().1, 1);
+
+        let capped_graph = prepare(FastForwardOptions {
+            min_length: 4,
+            max_length: Some(6),
+        });
+        assert_eq!(capped_graph.count_edges().1, 2);
+        for (_, edge_weight) in capped_graph.iter_edges() {
+            if let Some((fastforward, _)) = edge_weight.fastforward {
+                assert_eq!(fastforward.edge_path.len(), 6);
+            }
+        }
+
+        assert!(PreparedH3EdgeGraph::from_h3edge_graph_with_options(
+            graph,
+            &FastForwardOptions {
+                min_length: 6,
+                max_length: Some(5),
+            },
+        )
+        .is_err());
+    }
+
+    /// the exterior rings of `area` starting at their smallest coordinate, sorted, as the
+    /// vertex the rings start at depends on the iteration order of the cells.
+    fn normalized_exteriors(area: &MultiPolygon<f64>) -> Vec<Vec<(u64, u64)>> {
+        let mut exteriors: Vec<_> = area
+            .0
+            .iter()
+            .map(|polygon| {
+                let mut coords: Vec<_> = polygon
+                    .exterior()
+                    .coords()
+                    .map(|c| (c.x.to_bits(), c.y.to_bits()))
+                    .collect();
+                // drop the closing coordinate
+                coords.pop();
+                let min_pos = coords
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, coord)| **coord)
+                    .map(|(pos, _)| pos)
+                    .unwrap_or(0);
+                coords.rotate_left(min_pos);
+                coords
+            })
+            .collect();
+        exteriors.sort();
+        exteriors
+    }
+
+    #[test]
+    fn covered_area_is_cached() {
+        let center = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let mut graph = H3EdgeGraph::new(Resolution::Ten);
+        for cell in center.grid_disk::<Vec<_>>(10) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, 1u32);
+            }
+        }
+        let prepared_graph = PreparedH3EdgeGraph::try_from(graph.clone()).unwrap();
+
+        let first = prepared_graph.covered_area(2).unwrap();
+        assert_eq!(prepared_graph.covered_area(2).unwrap(), first);
+
+        let uncached = cells_covered_area(
+            prepared_graph.graph_nodes.iter().map(|(cell, _)| cell),
+            prepared_graph.h3_resolution(),
+            2,
+        )
+        .unwrap();
+        assert_eq!(
+            normalized_exteriors(&first),
+            normalized_exteriors(&uncached)
+        );
+        assert_eq!(
+            normalized_exteriors(&first),
+            normalized_exteriors(&graph.covered_area(2).unwrap())
+        );
+
+        // the area is taken from the cache of the graph without calculating it again
+        let cached = prepared_graph
+            .covered_area_cache
+            .covered_area(covered_area_resolution(Resolution::Ten, 2), || {
+                unreachable!("the covered area is already cached")
+            })
+            .unwrap();
+        assert_eq!(cached, first);
+    }
 }
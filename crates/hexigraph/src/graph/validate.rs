@@ -0,0 +1,172 @@
+//! Sanity checks for graphs
+use h3o::Resolution;
+
+use crate::container::{CellMap, CellSet};
+use crate::graph::{IterateCellNodes, PreparedH3EdgeGraph};
+use crate::HasH3Resolution;
+
+/// Results of [`validate_graph`].
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GraphValidation {
+    pub h3_resolution: Resolution,
+    pub num_nodes: usize,
+    pub num_edges: usize,
+
+    /// number of edges not matching the h3 resolution of the graph
+    pub num_edges_with_wrong_resolution: usize,
+
+    /// number of edges connecting cells which are not nodes of the graph
+    pub num_dangling_edges: usize,
+
+    /// number of weakly connected components of the graph
+    pub num_connected_components: usize,
+
+    /// number of nodes without any edges
+    pub num_isolated_nodes: usize,
+}
+
+impl GraphValidation {
+    /// the graph violates invariants the routing relies upon.
+    ///
+    /// Multiple connected components and isolated nodes are reported, but are
+    /// not considered to be violations.
+    pub fn has_violations(&self) -> bool {
+        self.num_edges_with_wrong_resolution > 0 || self.num_dangling_edges > 0
+    }
+}
+
+/// check the structure of the graph
+pub fn validate_graph<W>(graph: &PreparedH3EdgeGraph<W>) -> GraphValidation
+where
+    W: Copy,
+{
+    let h3_resolution = graph.h3_resolution();
+
+    // union-find over the nodes of the graph to find the connected components
+    let node_ids: CellMap<usize> = graph
+        .iter_cell_nodes()
+        .enumerate()
+        .map(|(node_id, (cell, _))| (*cell, node_id))
+        .collect();
+    let mut parents: Vec<usize> = (0..node_ids.len()).collect();
+    let mut connected_cells = CellSet::default();
+
+    let mut num_edges = 0;
+    let mut num_edges_with_wrong_resolution = 0;
+    let mut num_dangling_edges = 0;
+    for (edge, _) in graph.iter_edges() {
+        num_edges += 1;
+        if edge.origin().resolution() != h3_resolution {
+            num_edges_with_wrong_resolution += 1;
+        }
+        match (
+            node_ids.get(&edge.origin()),
+            node_ids.get(&edge.destination()),
+        ) {
+            (Some(origin_id), Some(destination_id)) => {
+                union(&mut parents, *origin_id, *destination_id);
+                connected_cells.insert(edge.origin());
+                connected_cells.insert(edge.destination());
+            }
+            _ => num_dangling_edges += 1,
+        }
+    }
+
+    let num_connected_components = (0..parents.len())
+        .filter(|node_id| find(&mut parents, *node_id) == *node_id)
+        .count();
+    let num_isolated_nodes = node_ids
+        .keys()
+        .filter(|cell| !connected_cells.contains(*cell))
+        .count();
+
+    GraphValidation {
+        h3_resolution,
+        num_nodes: node_ids.len(),
+        num_edges,
+        num_edges_with_wrong_resolution,
+        num_dangling_edges,
+        num_connected_components,
+        num_isolated_nodes,
+    }
+}
+
+fn find(parents: &mut [usize], node_id: usize) -> usize {
+    let mut root = node_id;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    // path compression
+    let mut current = node_id;
+    while parents[current] != root {
+        let next = parents[current];
+        parents[current] = root;
+        current = next;
+    }
+    root
+}
+
+fn union(parents: &mut [usize], node_id_a: usize, node_id_b: usize) {
+    let root_a = find(parents, node_id_a);
+    let root_b = find(parents, node_id_b);
+    if root_a != root_b {
+        parents[root_b] = root_a;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use h3o::{LatLng, Resolution};
+
+    use crate::algorithm::edge::continuous_cells_to_edges;
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+
+    use super::validate_graph;
+
+    fn line_edges(lat: f64, h3_resolution: Resolution) -> Vec<h3o::DirectedEdgeIndex> {
+        let origin = LatLng::new(lat, 20.0).unwrap().to_cell(h3_resolution);
+        let destination = LatLng::new(lat, 20.05).unwrap().to_cell(h3_resolution);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        continuous_cells_to_edges(cells).collect()
+    }
+
+    #[test]
+    fn test_validate_graph() {
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        // two separate lines
+        for edge in line_edges(10.0, Resolution::Nine)
+            .into_iter()
+            .chain(line_edges(10.5, Resolution::Nine))
+        {
+            graph.add_edge(edge, 1u32);
+        }
+        let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+
+        let validation = validate_graph(&prepared_graph);
+        assert!(!validation.has_violations());
+        assert_eq!(validation.num_connected_components, 2);
+        assert_eq!(validation.num_isolated_nodes, 0);
+        assert_eq!(validation.num_dangling_edges, 0);
+    }
+
+    #[test]
+    fn test_validate_corrupted_graph() {
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for edge in line_edges(10.0, Resolution::Nine) {
+            graph.add_edge(edge, 1u32);
+        }
+        // an edge not matching the resolution of the graph
+        let wrong_resolution_edge = line_edges(10.5, Resolution::Ten)[0];
+        graph.add_edge(wrong_resolution_edge, 1u32);
+        let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+
+        let validation = validate_graph(&prepared_graph);
+        assert!(validation.has_violations());
+        assert_eq!(validation.num_edges_with_wrong_resolution, 1);
+    }
+}
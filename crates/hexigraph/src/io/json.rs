@@ -0,0 +1,179 @@
+//! Human-readable JSON serialization of graphs.
+//!
+//! Intended for inspecting small graphs and for test fixtures, not for production storage - the
+//! binary formats are far more compact. Edges are written as hexadecimal h3 index strings and
+//! ordered by their index, so the output of the same graph is always identical.
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use h3o::DirectedEdgeIndex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::container::block::Decompressor;
+use crate::error::Error;
+use crate::graph::prepared::FromIterItem;
+use crate::graph::PreparedH3EdgeGraph;
+use crate::HasH3Resolution;
+
+#[derive(Serialize, Deserialize)]
+struct JsonGraph<W> {
+    h3_resolution: u8,
+    edges: Vec<JsonEdge<W>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonEdge<W> {
+    edge: String,
+    weight: W,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fastforward: Option<JsonFastForward<W>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonFastForward<W> {
+    edges: Vec<String>,
+    weight: W,
+}
+
+fn parse_edge(edge: &str) -> Result<DirectedEdgeIndex, Error> {
+    DirectedEdgeIndex::from_str(edge).map_err(|e| Error::JsonFormat(e.to_string()))
+}
+
+/// write the `graph` as JSON, indented when `pretty` is set.
+pub fn write_json<W, Wr>(
+    graph: &PreparedH3EdgeGraph<W>,
+    writer: Wr,
+    pretty: bool,
+) -> Result<(), Error>
+where
+    W: Serialize + Copy,
+    Wr: Write,
+{
+    let mut edges = Decompressor::with_thread_local(|decompressor| {
+        graph
+            .iter_edges()
+            .map(|(edge, edge_weight)| {
+                let fastforward = match edge_weight.fastforward {
+                    Some((fastforward, fastforward_weight)) => Some(JsonFastForward {
+                        edges: decompressor
+                            .decompress_block(&fastforward.edge_path)?
+                            .map(|edge| edge.map(|edge| edge.to_string()))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        weight: fastforward_weight,
+                    }),
+                    None => None,
+                };
+                Ok((
+                    edge,
+                    JsonEdge {
+                        edge: edge.to_string(),
+                        weight: edge_weight.weight,
+                        fastforward,
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+    edges.sort_unstable_by_key(|(edge, _)| *edge);
+
+    let json_graph = JsonGraph {
+        h3_resolution: graph.h3_resolution().into(),
+        edges: edges.into_iter().map(|(_, json_edge)| json_edge).collect(),
+    };
+    if pretty {
+        serde_json::to_writer_pretty(writer, &json_graph)?;
+    } else {
+        serde_json::to_writer(writer, &json_graph)?;
+    }
+    Ok(())
+}
+
+/// read a graph written by [`write_json`].
+pub fn read_json<W, R>(reader: R) -> Result<PreparedH3EdgeGraph<W>, Error>
+where
+    W: DeserializeOwned + Copy + Send + Sync,
+    R: Read,
+{
+    let json_graph: JsonGraph<W> = serde_json::from_reader(reader)?;
+    let items = json_graph
+        .edges
+        .into_iter()
+        .map(|json_edge| -> Result<FromIterItem<W>, Error> {
+            let fastforward = match json_edge.fastforward {
+                Some(json_fastforward) => Some((
+                    json_fastforward
+                        .edges
+                        .iter()
+                        .map(|edge| parse_edge(edge))
+                        .collect::<Result<Vec<_>, _>>()?,
+                    json_fastforward.weight,
+                )),
+                None => None,
+            };
+            Ok((parse_edge(&json_edge.edge)?, json_edge.weight, fastforward))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let graph = PreparedH3EdgeGraph::try_from_iter(items.into_iter())?;
+    if u8::from(graph.h3_resolution()) != json_graph.h3_resolution {
+        return Err(Error::JsonFormat(format!(
+            "edges do not match the h3 resolution {}",
+            json_graph.h3_resolution
+        )));
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use geo::{Coord, LineString};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::Resolution;
+
+    use crate::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use crate::HasH3Resolution;
+
+    use super::{read_json, write_json};
+
+    fn line_graph() -> PreparedH3EdgeGraph<u32> {
+        let cells: Vec<_> = h3o::geom::LineString::from_degrees(LineString::from(vec![
+            Coord::from((23.3, 12.3)),
+            Coord::from((24.2, 12.2)),
+        ]))
+        .unwrap()
+        .to_cells(PolyfillConfig::new(Resolution::Eight))
+        .collect();
+
+        let mut graph = H3EdgeGraph::new(Resolution::Eight);
+        for w in cells.windows(2) {
+            graph.add_edge(w[0].edge(w[1]).unwrap(), 20u32);
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+        assert_eq!(prepared_graph.count_edges().1, 1);
+        prepared_graph
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let graph = line_graph();
+
+        let mut json = vec![];
+        write_json(&graph, &mut json, true).unwrap();
+
+        // valid json using hex h3 indexes
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["h3_resolution"], 8);
+        let first_edge = value["edges"][0]["edge"].as_str().unwrap();
+        assert!(u64::from_str_radix(first_edge, 16).is_ok());
+
+        let graph2: PreparedH3EdgeGraph<u32> = read_json(json.as_slice()).unwrap();
+        assert_eq!(graph2.h3_resolution(), graph.h3_resolution());
+        assert_eq!(graph2.count_edges(), graph.count_edges());
+
+        let mut json2 = vec![];
+        write_json(&graph2, &mut json2, true).unwrap();
+        assert_eq!(json, json2);
+    }
+}
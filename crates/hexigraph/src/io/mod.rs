@@ -1,2 +1,6 @@
+#[cfg(feature = "json")]
+pub mod json;
 #[cfg(feature = "osm")]
 pub mod osm;
+#[cfg(feature = "osm")]
+pub mod osmchange;
@@ -1,19 +1,23 @@
 //! Support for OpenStreetMap data formats
 
 use std::io::BufReader;
-use std::ops::Add;
+use std::ops::{Add, Range};
 use std::path::Path;
 
 use crate::algorithm::edge::{continuous_cells_to_edges, reverse_directed_edge};
-use crate::container::HashMap;
+use crate::container::{DirectedEdgeMap, HashMap, HashSet};
 use geo::{Coord, LineString};
 use h3o::geom::{PolyfillConfig, ToCells};
 use h3o::{DirectedEdgeIndex, Resolution};
+use hashbrown::hash_map::Entry;
+use num_traits::Zero;
 pub use osmpbfreader;
 use osmpbfreader::{OsmPbfReader, Tags};
 
 use crate::error::Error;
-use crate::graph::{H3EdgeGraph, H3EdgeGraphBuilder};
+use crate::graph::{
+    EdgeAttributes, FastForwardOptions, H3EdgeGraph, H3EdgeGraphBuilder, PreparedH3EdgeGraph,
+};
 
 /// hide errors in the io error to avoid having osmpbfreader in the public api.
 impl From<osmpbfreader::Error> for Error {
@@ -23,8 +27,12 @@ impl From<osmpbfreader::Error> for Error {
 }
 
 pub struct EdgeProperties<T> {
-    pub is_bidirectional: bool,
+    /// weight of the edge in the direction of the way
     pub weight: T,
+
+    /// weight of the reversed edge. `None` when the way can only be traversed in
+    /// the direction of the way.
+    pub reverse_weight: Option<T>,
 }
 
 pub trait WayAnalyzer<T> {
@@ -39,6 +47,59 @@ pub trait WayAnalyzer<T> {
         edge: DirectedEdgeIndex,
         way_properties: &Self::WayProperties,
     ) -> Result<EdgeProperties<T>, Error>;
+
+    /// attributes to attach to all edges of a way, see [`tag_attributes`].
+    ///
+    /// Returns `None` by default, so the graph does not carry any attributes.
+    fn way_edge_attributes(&self, _tags: &Tags) -> Option<EdgeAttributes> {
+        None
+    }
+}
+
+/// collect the values of the given tag `keys` as [`EdgeAttributes`].
+///
+/// Returns `None` when none of the keys is present.
+pub fn tag_attributes(tags: &Tags, keys: &[&str]) -> Option<EdgeAttributes> {
+    let attributes: EdgeAttributes = keys
+        .iter()
+        .filter_map(|key| tags.get(*key).map(|value| (*key, value.as_str())))
+        .collect();
+    (!attributes.is_empty()).then_some(attributes)
+}
+
+/// The edges each way contributed to a graph.
+///
+/// Graphs do not record which ways their edges originate from, so ways can only be
+/// removed from a graph or be replaced in it when this index was kept while building it.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WayIndex<T> {
+    ways: HashMap<i64, IndexedWay<T>>,
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct IndexedWay<T> {
+    edges: Vec<(DirectedEdgeIndex, T)>,
+    attributes: Option<EdgeAttributes>,
+}
+
+impl<T> Default for WayIndex<T> {
+    fn default() -> Self {
+        Self {
+            ways: Default::default(),
+        }
+    }
+}
+
+impl<T> WayIndex<T> {
+    pub fn contains_way(&self, way_id: i64) -> bool {
+        self.ways.contains_key(&way_id)
+    }
+
+    pub fn num_ways(&self) -> usize {
+        self.ways.len()
+    }
 }
 
 /// Builds [`H3EdgeGraph`] instances from .osm.pbf files.
@@ -49,6 +110,15 @@ pub struct OsmPbfH3EdgeGraphBuilder<
     h3_resolution: Resolution,
     way_analyzer: WA,
     graph: H3EdgeGraph<T>,
+
+    /// edges of the added ways, these are only inserted into the graph when it gets built.
+    edges: Vec<(DirectedEdgeIndex, T)>,
+
+    /// attributes of the added ways together with the range of their edges in `edges`.
+    edge_attributes: Vec<(Range<usize>, EdgeAttributes)>,
+
+    /// `None` unless enabled using [`OsmPbfH3EdgeGraphBuilder::with_way_index`].
+    way_index: Option<WayIndex<T>>,
 }
 
 impl<T, WA> OsmPbfH3EdgeGraphBuilder<T, WA>
@@ -61,9 +131,33 @@ where
             h3_resolution,
             way_analyzer,
             graph: H3EdgeGraph::new(h3_resolution),
+            edges: Vec::new(),
+            edge_attributes: Vec::new(),
+            way_index: None,
         }
     }
 
+    /// continue building on an existing `graph`, for example to apply changes to it.
+    pub fn from_graph(graph: H3EdgeGraph<T>, way_analyzer: WA) -> Self {
+        Self {
+            h3_resolution: graph.h3_resolution,
+            way_analyzer,
+            graph,
+            edges: Vec::new(),
+            edge_attributes: Vec::new(),
+            way_index: None,
+        }
+    }
+
+    /// keep track of the edges of the added ways in `way_index`.
+    ///
+    /// Required to remove ways using [`OsmPbfH3EdgeGraphBuilder::remove_ways`]. When continuing
+    /// to build on an existing graph, this must be the index kept while building that graph.
+    pub fn with_way_index(mut self, way_index: WayIndex<T>) -> Self {
+        self.way_index = Some(way_index);
+        self
+    }
+
     pub fn read_pbf(&mut self, pbf_path: &Path) -> Result<(), Error> {
         let pbf_file = BufReader::new(std::fs::File::open(pbf_path)?);
         let mut pbf = OsmPbfReader::new(pbf_file);
@@ -79,34 +173,205 @@ where
                     nodeid_coordinates.insert(node.id, coordinate);
                 }
                 osmpbfreader::OsmObj::Way(way) => {
-                    if let Some(way_props) = self.way_analyzer.analyze_way_tags(&way.tags)? {
-                        let coordinates: Vec<_> = way
-                            .nodes
+                    self.add_way(
+                        way.id.0,
+                        &way.tags,
+                        way.nodes
                             .iter()
-                            .filter_map(|node_id| nodeid_coordinates.get(node_id).copied())
-                            .collect();
-                        if coordinates.len() >= 2 {
-                            for edge in continuous_cells_to_edges(
-                                h3o::geom::LineString::from_degrees(LineString::from(coordinates))?
-                                    .to_cells(PolyfillConfig::new(self.h3_resolution)),
-                            ) {
-                                let edge_props =
-                                    self.way_analyzer.way_edge_properties(edge, &way_props)?;
-
-                                self.graph.add_edge(edge, edge_props.weight);
-                                if edge_props.is_bidirectional {
-                                    self.graph
-                                        .add_edge(reverse_directed_edge(edge), edge_props.weight);
-                                }
-                            }
-                        }
-                    }
+                            .filter_map(|node_id| nodeid_coordinates.get(node_id).copied()),
+                    )?;
                 }
                 osmpbfreader::OsmObj::Relation(_) => {}
             }
         }
         Ok(())
     }
+
+    /// add the way with the id `way_id`, the given `tags` and node `coordinates` to the graph.
+    ///
+    /// The coordinates are only consumed when the way analyzer accepts the way. Returns
+    /// the edges added for the way.
+    pub fn add_way<I>(
+        &mut self,
+        way_id: i64,
+        tags: &Tags,
+        coordinates: I,
+    ) -> Result<Vec<DirectedEdgeIndex>, Error>
+    where
+        I: IntoIterator<Item = Coord>,
+    {
+        let way_props = match self.way_analyzer.analyze_way_tags(tags)? {
+            Some(way_props) => way_props,
+            None => return Ok(vec![]),
+        };
+        let coordinates: Vec<_> = coordinates.into_iter().collect();
+        if coordinates.len() < 2 {
+            return Ok(vec![]);
+        }
+        let attributes = self.way_analyzer.way_edge_attributes(tags);
+        let mut way_edges = vec![];
+        for edge in continuous_cells_to_edges(
+            h3o::geom::LineString::from_degrees(LineString::from(coordinates))?
+                .to_cells(PolyfillConfig::new(self.h3_resolution)),
+        ) {
+            let edge_props = self.way_analyzer.way_edge_properties(edge, &way_props)?;
+
+            way_edges.push((edge, edge_props.weight));
+            if let Some(reverse_weight) = edge_props.reverse_weight {
+                way_edges.push((reverse_directed_edge(edge), reverse_weight));
+            }
+        }
+
+        self.add_pending_edges(way_edges.iter().copied(), attributes.as_ref());
+        let added_edges = way_edges.iter().map(|(edge, _)| *edge).collect();
+        if let Some(way_index) = self.way_index.as_mut() {
+            way_index.ways.insert(
+                way_id,
+                IndexedWay {
+                    edges: way_edges,
+                    attributes,
+                },
+            );
+        }
+        Ok(added_edges)
+    }
+
+    /// remove the ways with the given ids from the graph. Returns the removed edges.
+    ///
+    /// Edges which are also part of other ways stay in the graph with the weights and
+    /// attributes of these ways. Requires the builder to keep a [`WayIndex`].
+    pub fn remove_ways<I>(&mut self, way_ids: I) -> Result<Vec<DirectedEdgeIndex>, Error>
+    where
+        I: IntoIterator<Item = i64>,
+    {
+        self.insert_pending_edges()?;
+        let mut way_index = self.way_index.take().ok_or(Error::MissingWayIndex)?;
+
+        let mut removed_edges: HashSet<DirectedEdgeIndex> = Default::default();
+        for way_id in way_ids {
+            if let Some(way) = way_index.ways.remove(&way_id) {
+                removed_edges.extend(way.edges.into_iter().map(|(edge, _)| edge));
+            }
+        }
+        for edge in removed_edges.iter() {
+            self.graph.edges.remove(edge);
+            if let Some(edge_attributes) = self.graph.edge_attributes.as_mut() {
+                edge_attributes.remove(edge);
+            }
+        }
+
+        // restore the edges shared with the remaining ways
+        for way in way_index.ways.values() {
+            self.add_pending_edges(
+                way.edges
+                    .iter()
+                    .filter(|(edge, _)| removed_edges.contains(edge))
+                    .copied(),
+                way.attributes.as_ref(),
+            );
+        }
+        self.way_index = Some(way_index);
+        self.insert_pending_edges()?;
+        Ok(removed_edges.into_iter().collect())
+    }
+
+    /// queue `edges` sharing the same `attributes` for insertion into the graph.
+    fn add_pending_edges<I>(&mut self, edges: I, attributes: Option<&EdgeAttributes>)
+    where
+        I: IntoIterator<Item = (DirectedEdgeIndex, T)>,
+    {
+        let start = self.edges.len();
+        self.edges.extend(edges);
+        if let Some(attributes) = attributes {
+            if self.edges.len() > start {
+                self.edge_attributes
+                    .push((start..self.edges.len(), attributes.clone()));
+            }
+        }
+    }
+
+    /// insert the edges of the ways added so far into the graph.
+    fn insert_pending_edges(&mut self) -> Result<(), Error> {
+        if self.edges.is_empty() {
+            return Ok(());
+        }
+        let edges = std::mem::take(&mut self.edges);
+        let edge_attributes = std::mem::take(&mut self.edge_attributes);
+        if !edge_attributes.is_empty() || self.graph.edge_attributes.is_some() {
+            self.insert_edge_attributes(&edges, &edge_attributes);
+        }
+        let added = H3EdgeGraph::try_from_edges_parallel(self.h3_resolution, edges)?;
+        if self.graph.edges.is_empty() {
+            self.graph.edges = added.edges;
+        } else {
+            self.graph.try_add(added)?;
+        }
+        Ok(())
+    }
+
+    /// set the attributes of the pending `edges` in the graph.
+    ///
+    /// The attributes follow the weights: each edge gets the attributes of the way its
+    /// weight is taken from. As in [`H3EdgeGraph::add_edge`] this is the way with the lowest
+    /// weight, or the first of the ways with equal weights. Edges already contained in the
+    /// graph keep their attributes unless their weight gets replaced.
+    fn insert_edge_attributes(
+        &mut self,
+        edges: &[(DirectedEdgeIndex, T)],
+        edge_attributes: &[(Range<usize>, EdgeAttributes)],
+    ) {
+        let mut winners: DirectedEdgeMap<(T, Option<&EdgeAttributes>)> = Default::default();
+        let mut attributes_iter = edge_attributes.iter().peekable();
+        for (i, (edge, weight)) in edges.iter().enumerate() {
+            while attributes_iter
+                .peek()
+                .is_some_and(|(range, _)| range.end <= i)
+            {
+                attributes_iter.next();
+            }
+            let attributes = attributes_iter
+                .peek()
+                .filter(|(range, _)| range.contains(&i))
+                .map(|(_, attributes)| attributes);
+
+            match winners.entry(*edge) {
+                Entry::Occupied(mut occ) => {
+                    if weight < &occ.get().0 {
+                        occ.insert((*weight, attributes));
+                    }
+                }
+                Entry::Vacant(vac) => {
+                    vac.insert((*weight, attributes));
+                }
+            }
+        }
+
+        for (edge, (weight, attributes)) in winners {
+            let replaces_weight = self
+                .graph
+                .edge_weight(edge)
+                .is_none_or(|existing_weight| &weight < existing_weight);
+            if !replaces_weight {
+                continue;
+            }
+            match attributes {
+                Some(attributes) => self.graph.set_edge_attributes(edge, attributes.clone()),
+                None => {
+                    if let Some(graph_edge_attributes) = self.graph.edge_attributes.as_mut() {
+                        graph_edge_attributes.remove(&edge);
+                    }
+                }
+            }
+        }
+    }
+
+    /// build the graph and return it together with the [`WayIndex`], when one was kept.
+    pub fn build_graph_with_way_index(
+        mut self,
+    ) -> Result<(H3EdgeGraph<T>, Option<WayIndex<T>>), Error> {
+        self.insert_pending_edges()?;
+        Ok((self.graph, self.way_index))
+    }
 }
 
 impl<T, WA> H3EdgeGraphBuilder<T> for OsmPbfH3EdgeGraphBuilder<T, WA>
@@ -115,6 +380,266 @@ where
     WA: WayAnalyzer<T>,
 {
     fn build_graph(self) -> Result<H3EdgeGraph<T>, Error> {
-        Ok(self.graph)
+        self.build_graph_with_way_index().map(|(graph, _)| graph)
+    }
+}
+
+/// Build a [`PreparedH3EdgeGraph`] from the ways of the given .osm.pbf files.
+///
+/// The ways to include and the weights of their edges are determined by `way_analyzer`.
+pub fn build_graph_from_pbf<T, WA, I, P>(
+    pbf_paths: I,
+    h3_resolution: Resolution,
+    way_analyzer: WA,
+    fastforward_options: &FastForwardOptions,
+) -> Result<PreparedH3EdgeGraph<T>, Error>
+where
+    T: PartialOrd + PartialEq + Add + Copy + Ord + Zero + Send + Sync,
+    WA: WayAnalyzer<T>,
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    PreparedH3EdgeGraph::from_h3edge_graph_with_options(
+        build_h3edge_graph_from_pbf(pbf_paths, h3_resolution, way_analyzer)?,
+        fastforward_options,
+    )
+}
+
+/// Variant of [`build_graph_from_pbf`] returning the graph before it gets prepared.
+pub fn build_h3edge_graph_from_pbf<T, WA, I, P>(
+    pbf_paths: I,
+    h3_resolution: Resolution,
+    way_analyzer: WA,
+) -> Result<H3EdgeGraph<T>, Error>
+where
+    T: PartialOrd + PartialEq + Add + Copy + Send + Sync,
+    WA: WayAnalyzer<T>,
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    let mut builder = OsmPbfH3EdgeGraphBuilder::new(h3_resolution, way_analyzer);
+    for pbf_path in pbf_paths {
+        builder.read_pbf(pbf_path.as_ref())?;
+    }
+    builder.build_graph()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use geo::Coord;
+    use h3o::{DirectedEdgeIndex, LatLng, Resolution};
+    use osmpbfreader::Tags;
+
+    use crate::error::Error;
+    use crate::graph::{
+        EdgeAttributes, FastForwardOptions, GetEdgeAttributes, GetStats, H3EdgeGraphBuilder,
+        PreparedH3EdgeGraph,
+    };
+
+    use super::{
+        build_graph_from_pbf, tag_attributes, EdgeProperties, OsmPbfH3EdgeGraphBuilder, WayAnalyzer,
+    };
+
+    struct HighwayAnalyzer;
+
+    impl WayAnalyzer<u32> for HighwayAnalyzer {
+        type WayProperties = bool;
+
+        fn analyze_way_tags(&self, tags: &Tags) -> Result<Option<Self::WayProperties>, Error> {
+            Ok(tags.get("highway").map(|_| {
+                tags.get("oneway")
+                    .map(|oneway| oneway.as_str() == "yes")
+                    .unwrap_or(false)
+            }))
+        }
+
+        fn way_edge_properties(
+            &self,
+            _edge: DirectedEdgeIndex,
+            is_oneway: &Self::WayProperties,
+        ) -> Result<EdgeProperties<u32>, Error> {
+            Ok(EdgeProperties {
+                weight: 1,
+                reverse_weight: (!is_oneway).then_some(1),
+            })
+        }
+    }
+
+    fn fixture_path() -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/crossing-ways.osm.pbf")
+    }
+
+    #[test]
+    fn test_build_graph_from_pbf() {
+        let graph = build_graph_from_pbf(
+            [fixture_path()],
+            Resolution::Ten,
+            HighwayAnalyzer,
+            &FastForwardOptions::default(),
+        )
+        .unwrap();
+
+        let stats = graph.get_stats().unwrap();
+        assert_eq!(stats.h3_resolution, Resolution::Ten);
+        assert!(stats.num_nodes > 20);
+        // the oneway way only contributes edges in a single direction
+        assert!(stats.num_edges > stats.num_nodes);
+        assert!(stats.num_edges < 2 * stats.num_nodes);
+    }
+
+    /// [`HighwayAnalyzer`] keeping the names of the ways
+    struct NamedHighwayAnalyzer;
+
+    impl WayAnalyzer<u32> for NamedHighwayAnalyzer {
+        type WayProperties = bool;
+
+        fn analyze_way_tags(&self, tags: &Tags) -> Result<Option<Self::WayProperties>, Error> {
+            HighwayAnalyzer.analyze_way_tags(tags)
+        }
+
+        fn way_edge_properties(
+            &self,
+            edge: DirectedEdgeIndex,
+            is_oneway: &Self::WayProperties,
+        ) -> Result<EdgeProperties<u32>, Error> {
+            HighwayAnalyzer.way_edge_properties(edge, is_oneway)
+        }
+
+        fn way_edge_attributes(&self, tags: &Tags) -> Option<EdgeAttributes> {
+            tag_attributes(tags, &["name", "ref", "surface"])
+        }
+    }
+
+    #[test]
+    fn test_edge_attributes() {
+        let mut tags = Tags::new();
+        tags.insert("highway".into(), "residential".into());
+        tags.insert("name".into(), "Main Street".into());
+        let coordinates = vec![Coord::from((23.3, 12.3)), Coord::from((23.32, 12.31))];
+
+        let mut builder = OsmPbfH3EdgeGraphBuilder::new(Resolution::Ten, NamedHighwayAnalyzer);
+        builder.add_way(1, &tags, coordinates).unwrap();
+        let graph: PreparedH3EdgeGraph<u32> = builder.build_graph().unwrap().try_into().unwrap();
+
+        let check_graph = |graph: &PreparedH3EdgeGraph<u32>| {
+            let mut num_edges = 0;
+            for (edge, _) in graph.iter_edges() {
+                let attributes = graph.get_edge_attributes(edge).unwrap();
+                assert_eq!(attributes.get("name"), Some("Main Street"));
+                assert_eq!(attributes.len(), 1);
+                num_edges += 1;
+            }
+            assert!(num_edges > 10);
+        };
+        check_graph(&graph);
+
+        #[cfg(feature = "serde")]
+        {
+            let serialized = bincode::serialize(&graph).unwrap();
+            let deserialized: PreparedH3EdgeGraph<u32> = bincode::deserialize(&serialized).unwrap();
+            check_graph(&deserialized);
+        }
+
+        // edges outside of the way carry no attributes
+        let other_cell = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let other_edge = other_cell.edges().next().unwrap();
+        assert!(graph.get_edge_attributes(other_edge).is_none());
+    }
+
+    /// [`NamedHighwayAnalyzer`] taking the weights from the `cost` tag
+    struct CostAnalyzer;
+
+    impl WayAnalyzer<u32> for CostAnalyzer {
+        type WayProperties = u32;
+
+        fn analyze_way_tags(&self, tags: &Tags) -> Result<Option<Self::WayProperties>, Error> {
+            Ok(tags.get("cost").map(|cost| cost.parse().unwrap()))
+        }
+
+        fn way_edge_properties(
+            &self,
+            _edge: DirectedEdgeIndex,
+            cost: &Self::WayProperties,
+        ) -> Result<EdgeProperties<u32>, Error> {
+            Ok(EdgeProperties {
+                weight: *cost,
+                reverse_weight: Some(*cost),
+            })
+        }
+
+        fn way_edge_attributes(&self, tags: &Tags) -> Option<EdgeAttributes> {
+            NamedHighwayAnalyzer.way_edge_attributes(tags)
+        }
+    }
+
+    #[test]
+    fn test_overlapping_ways_attributes_follow_weight() {
+        let way_tags = |cost: &str, name: Option<&str>| {
+            let mut tags = Tags::new();
+            tags.insert("cost".into(), cost.into());
+            if let Some(name) = name {
+                tags.insert("name".into(), name.into());
+            }
+            tags
+        };
+        let coordinates = vec![Coord::from((23.3, 12.3)), Coord::from((23.32, 12.31))];
+
+        let build = |ways: &[Tags]| {
+            let mut builder = OsmPbfH3EdgeGraphBuilder::new(Resolution::Ten, CostAnalyzer);
+            for (way_id, tags) in ways.iter().enumerate() {
+                builder
+                    .add_way(way_id as i64, tags, coordinates.clone())
+                    .unwrap();
+            }
+            let graph: PreparedH3EdgeGraph<u32> =
+                builder.build_graph().unwrap().try_into().unwrap();
+            let mut num_edges = 0;
+            let mut names = vec![];
+            for (edge, edge_weight) in graph.iter_edges() {
+                names.push(
+                    graph
+                        .get_edge_attributes(edge)
+                        .and_then(|attributes| attributes.get("name").map(String::from)),
+                );
+                assert_eq!(edge_weight.weight, 1);
+                num_edges += 1;
+            }
+            assert!(num_edges > 10);
+            names.dedup();
+            names
+        };
+
+        let slow = way_tags("5", Some("Slow Road"));
+        let fast = way_tags("1", Some("Fast Road"));
+        let fast_unnamed = way_tags("1", None);
+        let expected_fast = vec![Some("Fast Road".to_string())];
+
+        // the attributes of the way with the lower weight win, regardless of the order
+        assert_eq!(build(&[slow.clone(), fast.clone()]), expected_fast);
+        assert_eq!(build(&[fast.clone(), slow.clone()]), expected_fast);
+
+        // of equal weights the first way wins
+        assert_eq!(
+            build(&[fast.clone(), way_tags("1", Some("Other Road"))]),
+            expected_fast
+        );
+
+        // a winning way without attributes leaves the edges without attributes
+        assert_eq!(build(&[slow, fast_unnamed]), vec![None]);
+    }
+
+    #[test]
+    fn test_no_edge_attributes_by_default() {
+        let graph = build_graph_from_pbf(
+            [fixture_path()],
+            Resolution::Ten,
+            HighwayAnalyzer,
+            &FastForwardOptions::default(),
+        )
+        .unwrap();
+        let (edge, _) = graph.iter_edges().next().unwrap();
+        assert!(graph.get_edge_attributes(edge).is_none());
     }
 }
@@ -0,0 +1,499 @@
+//! Incremental updates of graphs using OpenStreetMap change files (`.osc`).
+//!
+//! Ways can only be removed from a graph or be replaced in it when the [`WayIndex`] kept
+//! while building the graph is available. Applying a change file to the builder of a
+//! graph reports the cells whose edges changed, so only these need to be prepared
+//! again using [`PreparedH3EdgeGraph::reprepare_cells`].
+//!
+//! The change files of the OSM replication only contain the objects which changed, so
+//! applying them to a graph comes with some limitations:
+//!
+//! * Ways get added using the nodes contained in the change file. Created ways referencing
+//!   nodes which are not part of the change file are skipped, modified ways keep their
+//!   previous edges.
+//! * Ways are not updated when only their nodes got moved, as these ways are not part
+//!   of the change file.
+//!
+//! For complete consistency the graph still needs to be rebuilt from time to time.
+//!
+//! [`WayIndex`]: crate::io::osm::WayIndex
+//! [`PreparedH3EdgeGraph::reprepare_cells`]: crate::graph::PreparedH3EdgeGraph::reprepare_cells
+use std::io::BufRead;
+use std::path::Path;
+
+use geo::Coord;
+use osmpbfreader::Tags;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use tracing::debug;
+
+use crate::container::{CellSet, HashMap};
+use crate::error::Error;
+use crate::io::osm::{OsmPbfH3EdgeGraphBuilder, WayAnalyzer};
+
+/// Counts of the ways found in an OsmChange file.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OsmChangeSummary {
+    /// created or modified ways which were passed to the way analyzer.
+    pub ways_applied: usize,
+
+    /// created or modified ways skipped as not all of their nodes are part of the change.
+    pub ways_missing_nodes: usize,
+
+    /// deleted ways.
+    pub ways_deleted: usize,
+
+    /// origin and destination cells of the removed and added edges.
+    pub changed_cells: CellSet,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Create,
+    Modify,
+    Delete,
+}
+
+struct ChangedWay {
+    id: i64,
+    tags: Tags,
+    node_ids: Vec<i64>,
+}
+
+/// apply the OsmChange file at `osc_path` to the graph of the `builder`.
+///
+/// The `builder` must keep a [`WayIndex`](crate::io::osm::WayIndex). See the
+/// [module documentation](self) for the limitations.
+pub fn apply_osm_change<T, WA>(
+    builder: &mut OsmPbfH3EdgeGraphBuilder<T, WA>,
+    osc_path: &Path,
+) -> Result<OsmChangeSummary, Error>
+where
+    T: PartialOrd + PartialEq + std::ops::Add + Copy + Send + Sync,
+    WA: WayAnalyzer<T>,
+{
+    let reader = std::io::BufReader::new(std::fs::File::open(osc_path)?);
+    apply_osm_change_from_reader(builder, reader)
+}
+
+/// Variant of [`apply_osm_change`] reading the OsmChange XML from `reader`.
+pub fn apply_osm_change_from_reader<T, WA, R>(
+    builder: &mut OsmPbfH3EdgeGraphBuilder<T, WA>,
+    reader: R,
+) -> Result<OsmChangeSummary, Error>
+where
+    T: PartialOrd + PartialEq + std::ops::Add + Copy + Send + Sync,
+    WA: WayAnalyzer<T>,
+    R: BufRead,
+{
+    let mut summary = OsmChangeSummary::default();
+    let mut nodeid_coordinates: HashMap<i64, Coord> = Default::default();
+    // only the last change of a way is relevant
+    let mut ways: HashMap<i64, (Action, ChangedWay)> = Default::default();
+
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = vec![];
+    let mut action = None;
+    let mut current_way: Option<ChangedWay> = None;
+    loop {
+        match reader.read_event_into(&mut buf).map_err(xml_error)? {
+            Event::Start(element) => {
+                handle_element(
+                    &element,
+                    &mut action,
+                    &mut current_way,
+                    &mut nodeid_coordinates,
+                )?;
+            }
+            Event::Empty(element) => {
+                handle_element(
+                    &element,
+                    &mut action,
+                    &mut current_way,
+                    &mut nodeid_coordinates,
+                )?;
+                if element.name().as_ref() == b"way" {
+                    finish_way(&mut current_way, action, &mut ways);
+                }
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"way" => finish_way(&mut current_way, action, &mut ways),
+                b"create" | b"modify" | b"delete" => action = None,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    // ways are only added after reading the complete file, as nodes
+    // may be contained in later blocks of the change.
+    let mut removed_way_ids = vec![];
+    let mut added_ways = vec![];
+    for (action, way) in ways.into_values() {
+        if action == Action::Delete {
+            removed_way_ids.push(way.id);
+            summary.ways_deleted += 1;
+            continue;
+        }
+        let coordinates: Option<Vec<_>> = way
+            .node_ids
+            .iter()
+            .map(|node_id| nodeid_coordinates.get(node_id).copied())
+            .collect();
+        if let Some(coordinates) = coordinates {
+            if action == Action::Modify {
+                removed_way_ids.push(way.id);
+            }
+            added_ways.push((way, coordinates));
+        } else {
+            summary.ways_missing_nodes += 1;
+        }
+    }
+
+    let mut changed_edges = builder.remove_ways(removed_way_ids)?;
+    for (way, coordinates) in added_ways {
+        changed_edges.extend(builder.add_way(way.id, &way.tags, coordinates)?);
+        summary.ways_applied += 1;
+    }
+    summary.changed_cells = changed_edges
+        .iter()
+        .flat_map(|edge| [edge.origin(), edge.destination()])
+        .collect();
+
+    debug!(
+        "applied osm change: {} ways applied, {} ways missing nodes, {} ways deleted, {} cells changed",
+        summary.ways_applied,
+        summary.ways_missing_nodes,
+        summary.ways_deleted,
+        summary.changed_cells.len()
+    );
+    Ok(summary)
+}
+
+fn handle_element(
+    element: &BytesStart,
+    action: &mut Option<Action>,
+    current_way: &mut Option<ChangedWay>,
+    nodeid_coordinates: &mut HashMap<i64, Coord>,
+) -> Result<(), Error> {
+    match element.name().as_ref() {
+        b"create" => *action = Some(Action::Create),
+        b"modify" => *action = Some(Action::Modify),
+        b"delete" => *action = Some(Action::Delete),
+        b"node" => {
+            if matches!(action, Some(Action::Create | Action::Modify)) {
+                if let (Some(id), Some(lat), Some(lon)) = (
+                    attribute(element, b"id")?,
+                    attribute(element, b"lat")?,
+                    attribute(element, b"lon")?,
+                ) {
+                    nodeid_coordinates.insert(
+                        parse(&id)?,
+                        Coord {
+                            x: parse(&lon)?,
+                            y: parse(&lat)?,
+                        },
+                    );
+                }
+            }
+        }
+        b"way" => {
+            if let Some(id) = attribute(element, b"id")? {
+                *current_way = Some(ChangedWay {
+                    id: parse(&id)?,
+                    tags: Tags::new(),
+                    node_ids: vec![],
+                });
+            }
+        }
+        b"nd" => {
+            if let (Some(way), Some(node_ref)) = (current_way.as_mut(), attribute(element, b"ref")?)
+            {
+                way.node_ids.push(parse(&node_ref)?);
+            }
+        }
+        b"tag" => {
+            if let (Some(way), Some(key), Some(value)) = (
+                current_way.as_mut(),
+                attribute(element, b"k")?,
+                attribute(element, b"v")?,
+            ) {
+                way.tags.insert(key.into(), value.into());
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn finish_way(
+    current_way: &mut Option<ChangedWay>,
+    action: Option<Action>,
+    ways: &mut HashMap<i64, (Action, ChangedWay)>,
+) {
+    if let (Some(way), Some(action)) = (current_way.take(), action) {
+        ways.insert(way.id, (action, way));
+    }
+}
+
+fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>, Error> {
+    for attr in element.attributes() {
+        let attr = attr.map_err(xml_error)?;
+        if attr.key.as_ref() == name {
+            return Ok(Some(attr.unescape_value().map_err(xml_error)?.into_owned()));
+        }
+    }
+    Ok(None)
+}
+
+fn parse<V: std::str::FromStr>(value: &str) -> Result<V, Error> {
+    value.parse().map_err(|_| {
+        Error::IOError(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid value in osm change: {value}"),
+        ))
+    })
+}
+
+/// hide errors in the io error to avoid having quick-xml in the public api.
+fn xml_error<E>(err: E) -> Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    Error::IOError(std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use geo::{Coord, LineString};
+    use h3o::geom::{PolyfillConfig, ToCells};
+    use h3o::{DirectedEdgeIndex, Resolution};
+    use osmpbfreader::Tags;
+
+    use crate::algorithm::edge::continuous_cells_to_edges;
+    use crate::container::block::Decompressor;
+    use crate::error::Error;
+    use crate::graph::prepared::FromIterItem;
+    use crate::graph::{
+        FastForwardOptions, GetCellNode, GetStats, H3EdgeGraphBuilder, PreparedH3EdgeGraph,
+    };
+    use crate::io::osm::{EdgeProperties, OsmPbfH3EdgeGraphBuilder, WayAnalyzer, WayIndex};
+
+    use super::apply_osm_change_from_reader;
+
+    struct HighwayAnalyzer;
+
+    impl WayAnalyzer<u32> for HighwayAnalyzer {
+        type WayProperties = u32;
+
+        fn analyze_way_tags(&self, tags: &Tags) -> Result<Option<Self::WayProperties>, Error> {
+            Ok(tags
+                .get("highway")
+                .map(|highway| if highway.as_str() == "primary" { 1 } else { 3 }))
+        }
+
+        fn way_edge_properties(
+            &self,
+            _edge: DirectedEdgeIndex,
+            weight: &Self::WayProperties,
+        ) -> Result<EdgeProperties<u32>, Error> {
+            Ok(EdgeProperties {
+                weight: *weight,
+                reverse_weight: Some(*weight),
+            })
+        }
+    }
+
+    fn highway_tags(highway: &str) -> Tags {
+        let mut tags = Tags::new();
+        tags.insert("highway".into(), highway.into());
+        tags
+    }
+
+    fn way_edges(coordinates: &[(f64, f64)]) -> Vec<DirectedEdgeIndex> {
+        continuous_cells_to_edges(
+            h3o::geom::LineString::from_degrees(LineString::from(
+                coordinates
+                    .iter()
+                    .map(|c| Coord::from(*c))
+                    .collect::<Vec<_>>(),
+            ))
+            .unwrap()
+            .to_cells(PolyfillConfig::new(Resolution::Ten)),
+        )
+        .collect()
+    }
+
+    const OSC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6" generator="test">
+  <create>
+    <node id="-1" version="1" lat="12.3" lon="23.3"/>
+    <node id="-2" version="1" lat="12.31" lon="23.32"/>
+    <way id="-10" version="1">
+      <nd ref="-1"/>
+      <nd ref="-2"/>
+      <tag k="highway" v="residential"/>
+    </way>
+    <way id="-11" version="1">
+      <nd ref="-1"/>
+      <nd ref="-99"/>
+      <tag k="highway" v="residential"/>
+    </way>
+  </create>
+  <delete>
+    <way id="42" version="3"/>
+  </delete>
+</osmChange>
+"#;
+
+    #[test]
+    fn apply_change_adding_a_way() {
+        let fixture_path =
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata/crossing-ways.osm.pbf");
+        let mut builder = OsmPbfH3EdgeGraphBuilder::new(Resolution::Ten, HighwayAnalyzer)
+            .with_way_index(WayIndex::default());
+        builder.read_pbf(&fixture_path).unwrap();
+        let (graph, way_index) = builder.build_graph_with_way_index().unwrap();
+        let num_edges_before = graph.num_edges();
+
+        let mut builder = OsmPbfH3EdgeGraphBuilder::from_graph(graph, HighwayAnalyzer)
+            .with_way_index(way_index.unwrap());
+        let summary = apply_osm_change_from_reader(&mut builder, OSC.as_bytes()).unwrap();
+        assert_eq!(summary.ways_applied, 1);
+        assert_eq!(summary.ways_missing_nodes, 1);
+        assert_eq!(summary.ways_deleted, 1);
+
+        let graph = builder.build_graph().unwrap();
+        let new_edges = way_edges(&[(23.3, 12.3), (23.32, 12.31)]);
+        assert!(new_edges.len() > 5);
+        for edge in new_edges.iter() {
+            assert!(graph.edges.contains_key(edge));
+            assert!(summary.changed_cells.contains(&edge.origin()));
+        }
+        assert!(graph.num_edges() > num_edges_before);
+    }
+
+    #[test]
+    fn apply_change_requires_way_index() {
+        let mut builder = OsmPbfH3EdgeGraphBuilder::new(Resolution::Ten, HighwayAnalyzer);
+        assert!(matches!(
+            apply_osm_change_from_reader(&mut builder, OSC.as_bytes()),
+            Err(Error::MissingWayIndex)
+        ));
+    }
+
+    /// edges of the graph with their weights and the edges of their fastforwards.
+    fn prepared_edges(graph: &PreparedH3EdgeGraph<u32>) -> Vec<FromIterItem<u32>> {
+        let mut decompressor = Decompressor::default();
+        let mut edges: Vec<_> = graph
+            .iter_edges()
+            .map(|(edge, edge_weight)| {
+                let fastforward = edge_weight.fastforward.map(|(fastforward, weight)| {
+                    let ff_edges = decompressor
+                        .decompress_block(&fastforward.edge_path)
+                        .unwrap()
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap();
+                    (ff_edges, weight)
+                });
+                (edge, edge_weight.weight, fastforward)
+            })
+            .collect();
+        edges.sort_unstable_by_key(|(edge, _, _)| *edge);
+        edges
+    }
+
+    #[test]
+    fn apply_change_removing_and_replacing_ways() {
+        let primary = [(23.3, 12.3), (23.34, 12.3)];
+        let residential = [(23.31, 12.28), (23.31, 12.33)];
+        let track = [(23.32, 12.28), (23.33, 12.29)];
+        let mut builder = OsmPbfH3EdgeGraphBuilder::new(Resolution::Ten, HighwayAnalyzer)
+            .with_way_index(WayIndex::default());
+        for (way_id, highway, coordinates) in [
+            (1, "primary", primary),
+            (2, "residential", residential),
+            (3, "residential", track),
+        ] {
+            builder
+                .add_way(
+                    way_id,
+                    &highway_tags(highway),
+                    coordinates.iter().map(|c| Coord::from(*c)),
+                )
+                .unwrap();
+        }
+        let (graph, way_index) = builder.build_graph_with_way_index().unwrap();
+        let options = FastForwardOptions::default();
+        let mut prepared_graph =
+            PreparedH3EdgeGraph::from_h3edge_graph_with_options(graph.clone(), &options).unwrap();
+
+        // way 1 gets a different geometry, way 3 gets deleted
+        let osc = r#"<?xml version="1.0" encoding="UTF-8"?>
+<osmChange version="0.6" generator="test">
+  <modify>
+    <node id="10" version="2" lat="12.31" lon="23.3"/>
+    <node id="11" version="2" lat="12.31" lon="23.34"/>
+    <way id="1" version="2">
+      <nd ref="10"/>
+      <nd ref="11"/>
+      <tag k="highway" v="primary"/>
+    </way>
+  </modify>
+  <delete>
+    <way id="3" version="2"/>
+  </delete>
+</osmChange>
+"#;
+        let mut builder = OsmPbfH3EdgeGraphBuilder::from_graph(graph, HighwayAnalyzer)
+            .with_way_index(way_index.unwrap());
+        let summary = apply_osm_change_from_reader(&mut builder, osc.as_bytes()).unwrap();
+        assert_eq!(summary.ways_applied, 1);
+        assert_eq!(summary.ways_deleted, 1);
+        let (graph, way_index) = builder.build_graph_with_way_index().unwrap();
+        let way_index = way_index.unwrap();
+        assert!(!way_index.contains_way(3));
+        assert_eq!(way_index.num_ways(), 2);
+
+        let shared_edges: Vec<_> = way_edges(&residential)
+            .into_iter()
+            .filter(|edge| graph.edges.contains_key(edge))
+            .collect();
+        for edge in way_edges(&primary) {
+            // edges shared with way 2 stay with the weight of way 2
+            if shared_edges.contains(&edge) {
+                assert_eq!(graph.edge_weight(edge), Some(&3));
+            } else {
+                assert!(graph.edge_weight(edge).is_none());
+            }
+        }
+        for edge in way_edges(&track) {
+            assert!(graph.edge_weight(edge).is_none());
+        }
+        for edge in way_edges(&[(23.3, 12.31), (23.34, 12.31)]) {
+            assert_eq!(graph.edge_weight(edge), Some(&1));
+        }
+
+        // preparing only the changed cells results in the same graph as preparing all cells
+        prepared_graph
+            .reprepare_cells(&graph, &summary.changed_cells, &options)
+            .unwrap();
+        let expected_graph: PreparedH3EdgeGraph<u32> =
+            PreparedH3EdgeGraph::from_h3edge_graph_with_options(graph, &options).unwrap();
+        assert_eq!(
+            prepared_edges(&prepared_graph),
+            prepared_edges(&expected_graph)
+        );
+        assert_eq!(
+            prepared_graph.get_stats().unwrap().num_nodes,
+            expected_graph.get_stats().unwrap().num_nodes
+        );
+        for (cell, node_type, _) in expected_graph.iter_nodes_with_coords() {
+            assert_eq!(prepared_graph.get_cell_node(cell), Some(node_type));
+        }
+    }
+}
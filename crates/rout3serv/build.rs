@@ -13,6 +13,11 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_client(false)
         .build_server(true)
+        // allows storing the simplification of differential shortest path routes
+        .type_attribute(
+            "rout3serv.SimplificationAlgorithm",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
         .out_dir("src/grpc/api/")
         .compile(&["proto/rout3serv.proto"], &["proto"])?;
 
@@ -20,5 +25,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     if tonic_output_path.exists() {
         rename(tonic_output_path, Path::new("src/grpc/api/generated.rs"))?;
     }
+
+    // the vendored Arrow Flight protocol is only needed with the `arrow-flight` feature.
+    // The client gets generated as well as it is used by the tests.
+    if std::env::var_os("CARGO_FEATURE_ARROW_FLIGHT").is_some() {
+        println!("cargo:rerun-if-changed=proto/Flight.proto");
+        tonic_build::configure()
+            .build_client(true)
+            .build_server(true)
+            .compile(&["proto/Flight.proto"], &["proto"])?;
+    }
     Ok(())
 }
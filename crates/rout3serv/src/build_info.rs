@@ -10,6 +10,11 @@ pub fn build_timestamp() -> &'static str {
     env!("VERGEN_BUILD_TIMESTAMP")
 }
 
+/// version of the serialization format of graphs, see [`crate::weight::GRAPH_FORMAT_VERSION`].
+pub fn graph_format_version() -> u32 {
+    crate::weight::GRAPH_FORMAT_VERSION
+}
+
 pub fn long_version() -> &'static str {
     concat!(
         env!("CARGO_PKG_VERSION"),
@@ -1,16 +1,29 @@
 use std::collections::HashMap;
 use std::ops::Deref;
+use std::str::FromStr;
+use std::time::Duration;
 
 use serde::Deserialize;
 use tonic::Status;
 
+use crate::cors::CorsConfig;
 use crate::io::dataframe::DataframeDataset;
 use crate::io::objectstore::ObjectStoreConfig;
+use crate::io::GraphKey;
+use crate::metrics::MetricsConfig;
 
 fn default_graphs_prefix() -> String {
     "graphs/".to_string()
 }
 
+fn default_fetch_timeout_secs() -> u64 {
+    300
+}
+
+fn default_stream_channel_capacity() -> usize {
+    5
+}
+
 #[derive(Deserialize, Clone)]
 pub struct GraphsConfig {
     #[serde(default = "default_graphs_prefix")]
@@ -18,6 +31,32 @@ pub struct GraphsConfig {
 
     /// capacity for the internal LRU cache
     pub cache_size: Option<usize>,
+
+    /// graphs to load into the cache when the server starts.
+    ///
+    /// Given as file names, for example `mygraph_10.ipc`
+    #[serde(default)]
+    pub preload: Vec<String>,
+
+    /// refuse to load graphs with a serialized size larger than this number of bytes
+    pub max_graph_bytes: Option<u64>,
+
+    /// refuse to load graphs with more nodes than this. Checked before downloading the graph,
+    /// except for graphs written without the number of nodes in their metadata.
+    pub max_graph_nodes: Option<usize>,
+}
+
+impl GraphsConfig {
+    pub fn preload_graph_keys(&self) -> anyhow::Result<Vec<GraphKey>> {
+        self.preload
+            .iter()
+            .map(|name| {
+                GraphKey::from_str(name).map_err(|e| {
+                    anyhow::Error::msg(format!("invalid graph to preload {name}: {e}"))
+                })
+            })
+            .collect()
+    }
 }
 
 fn default_outputs_prefix() -> String {
@@ -30,6 +69,44 @@ pub struct OutputsConfig {
     pub prefix: String,
 }
 
+/// retry behaviour of requests to the objectstore failing with transient errors.
+///
+/// Only applies to objectstores accessed over the network.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// maximum number of times a request is retried
+    pub max_retries: usize,
+
+    /// backoff before the first retry. Grows exponentially with each further retry.
+    pub initial_backoff_ms: u64,
+
+    /// maximum time from the initial request after which no further retries are attempted
+    pub max_elapsed_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 10,
+            initial_backoff_ms: 100,
+            max_elapsed_ms: 180_000,
+        }
+    }
+}
+
+impl From<&RetryConfig> for object_store::RetryConfig {
+    fn from(retry_config: &RetryConfig) -> Self {
+        let mut object_store_retry_config = object_store::RetryConfig::default();
+        object_store_retry_config.max_retries = retry_config.max_retries;
+        object_store_retry_config.retry_timeout =
+            Duration::from_millis(retry_config.max_elapsed_ms);
+        object_store_retry_config.backoff.init_backoff =
+            Duration::from_millis(retry_config.initial_backoff_ms);
+        object_store_retry_config
+    }
+}
+
 #[derive(Deserialize, Clone, Default, Copy)]
 #[serde(try_from = "f32")]
 pub struct NonZeroPositiveFactor(f32);
@@ -66,12 +143,56 @@ pub struct RoutingMode {
 pub struct ServerConfig {
     pub bind_to: String,
     pub objectstore: ObjectStoreConfig,
+
+    /// retries of failed objectstore requests
+    #[serde(default)]
+    pub objectstore_retry: RetryConfig,
+
+    /// upper limit for the duration of fetching a single graph or dataset file from the
+    /// objectstore in seconds. Stalled fetches fail after this duration. `0` disables the
+    /// timeout.
+    #[serde(default = "default_fetch_timeout_secs")]
+    pub fetch_timeout_secs: u64,
+
     pub graphs: GraphsConfig,
     pub outputs: OutputsConfig,
     pub datasets: HashMap<String, DataframeDataset>,
 
     #[serde(default)]
     pub routing_modes: HashMap<String, RoutingMode>,
+
+    /// upper limit for the `num_destinations_to_reach` of shortest path requests.
+    ///
+    /// Requests asking for more destinations, or not limiting the number of destinations
+    /// at all, get clamped to this value.
+    #[serde(default)]
+    pub max_destinations_to_reach: Option<u32>,
+
+    /// upper limit for the number of cells a geometry sent by a client may be converted to.
+    ///
+    /// Protects the server from running out of memory when very large geometries are
+    /// submitted.
+    #[serde(default)]
+    pub max_polyfill_cells: Option<usize>,
+
+    /// serve prometheus metrics. Requires the `metrics` feature.
+    #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+
+    /// accept gRPC-web requests from browsers in addition to native gRPC.
+    #[serde(default)]
+    pub grpc_web: bool,
+
+    /// CORS settings for browser clients using gRPC-web. Requires `grpc_web`.
+    #[serde(default)]
+    pub cors: Option<CorsConfig>,
+
+    /// number of messages of a streamed response buffered ahead of the client.
+    ///
+    /// Larger values improve the throughput for fast clients, while for slow clients more
+    /// memory is spent on messages waiting to be sent.
+    #[serde(default = "default_stream_channel_capacity")]
+    pub stream_channel_capacity: usize,
 }
 
 impl ServerConfig {
@@ -79,9 +200,28 @@ impl ServerConfig {
         for dataset in self.datasets.values() {
             dataset.validate()?;
         }
+        self.graphs.preload_graph_keys()?;
+        if self.max_destinations_to_reach == Some(0) {
+            return Err(anyhow::Error::msg("max_destinations_to_reach must be > 0"));
+        }
+        if self.stream_channel_capacity == 0 {
+            return Err(anyhow::Error::msg("stream_channel_capacity must be > 0"));
+        }
+        if let Some(cors_config) = self.cors.as_ref() {
+            if !self.grpc_web {
+                return Err(anyhow::Error::msg("cors requires grpc_web to be enabled"));
+            }
+            // only building the layer validates the origins, methods and headers
+            let _cors_layer = cors_config.cors_layer()?;
+        }
         Ok(())
     }
 
+    /// the timeout for fetching single objects from the objectstore, if any.
+    pub fn fetch_timeout(&self) -> Option<Duration> {
+        (self.fetch_timeout_secs > 0).then(|| Duration::from_secs(self.fetch_timeout_secs))
+    }
+
     pub fn get_routing_mode(&self, routing_mode_name: &str) -> Result<RoutingMode, Status> {
         if routing_mode_name.is_empty() {
             return Ok(RoutingMode::default());
@@ -0,0 +1,113 @@
+//! CORS for browser clients using gRPC-web
+//!
+//! Only applied when `grpc_web` is enabled, as native gRPC clients are not
+//! subject to CORS.
+use http::{HeaderName, HeaderValue, Method};
+use serde::Deserialize;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+fn default_allowed_methods() -> Vec<String> {
+    vec!["POST".to_string()]
+}
+
+fn default_allowed_headers() -> Vec<String> {
+    ["content-type", "x-grpc-web", "x-user-agent", "grpc-timeout"]
+        .map(String::from)
+        .to_vec()
+}
+
+/// response headers browser clients need to read to evaluate the result of a gRPC-web call
+const EXPOSED_HEADERS: [&str; 3] = ["grpc-status", "grpc-message", "grpc-status-details-bin"];
+
+#[derive(Deserialize, Clone)]
+pub struct CorsConfig {
+    /// origins allowed to make requests. `*` allows any origin.
+    pub allowed_origins: Vec<String>,
+
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+}
+
+impl CorsConfig {
+    pub fn cors_layer(&self) -> anyhow::Result<CorsLayer> {
+        let allow_origin = if self.allowed_origins.iter().any(|origin| origin == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                self.allowed_origins
+                    .iter()
+                    .map(|origin| HeaderValue::from_str(origin))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )
+        };
+        let allow_methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_uppercase().as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let allow_headers = self
+            .allowed_headers
+            .iter()
+            .map(|header| HeaderName::from_bytes(header.as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(allow_methods)
+            .allow_headers(allow_headers)
+            .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use http::{Method, Request, Response};
+    use tower::{service_fn, Layer, ServiceExt};
+
+    use super::CorsConfig;
+
+    async fn preflight(config: &CorsConfig, origin: &str) -> Response<String> {
+        let service =
+            config
+                .cors_layer()
+                .unwrap()
+                .layer(service_fn(|_request: Request<String>| async {
+                    Ok::<_, Infallible>(Response::new(String::new()))
+                }));
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/rout3serv.Rout3Serv/Version")
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .header("access-control-request-headers", "content-type,x-grpc-web")
+            .body(String::new())
+            .unwrap();
+        service.oneshot(request).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn preflight_allowed_origin() {
+        let config: CorsConfig =
+            serde_yaml::from_str("allowed_origins: [\"https://example.com\"]").unwrap();
+
+        let response = preflight(&config, "https://example.com").await;
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "https://example.com"
+        );
+        assert!(response.headers()["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+
+        let response = preflight(&config, "https://other.example.com").await;
+        assert!(!response
+            .headers()
+            .contains_key("access-control-allow-origin"));
+    }
+}
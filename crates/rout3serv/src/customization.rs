@@ -3,8 +3,11 @@ use std::cmp::Ordering;
 use std::ops::{Add, Deref};
 use std::sync::Arc;
 
+use hexigraph::algorithm::graph::BucketKey;
 use hexigraph::graph::node::NodeType;
-use hexigraph::graph::{EdgeWeight, GetCellEdges, GetCellNode, PreparedH3EdgeGraph};
+use hexigraph::graph::{
+    EdgeAttributes, EdgeWeight, GetCellEdges, GetCellNode, GetEdgeAttributes, PreparedH3EdgeGraph,
+};
 use hexigraph::HasH3Resolution;
 use num_traits::Zero;
 use uom::si::f32::Time;
@@ -99,6 +102,10 @@ impl Weight for CustomizedWeight {
         self.weight.edge_preference()
     }
 
+    fn report_fields(&self) -> Vec<(String, f64)> {
+        self.weight.report_fields()
+    }
+
     fn from_travel_duration(travel_duration: Time) -> Self {
         Self {
             weight: StandardWeight::from_travel_duration(travel_duration),
@@ -117,16 +124,41 @@ impl Ord for CustomizedWeight {
     }
 }
 
+impl BucketKey for CustomizedWeight {
+    fn bucket_key(&self) -> u64 {
+        self.overall_weight() as u64
+    }
+}
+
 /// A prepared graph with customized weight comparisons
+#[derive(Clone)]
 pub struct CustomizedGraph {
     inner_graph: Arc<PreparedH3EdgeGraph<StandardWeight>>,
     routing_mode: RoutingMode,
+
+    /// factor applied to the travel durations of all edges
+    weight_scale: f32,
 }
 
 impl CustomizedGraph {
     pub fn set_routing_mode(&mut self, routing_mode: RoutingMode) {
         self.routing_mode = routing_mode;
     }
+
+    /// scale the travel durations of all edges by `weight_scale`.
+    ///
+    /// As all edges get scaled uniformly, this does not change which routes are found, only
+    /// their travel durations. Must be positive.
+    pub fn set_weight_scale(&mut self, weight_scale: f32) {
+        self.weight_scale = weight_scale;
+    }
+
+    fn customize_weight(&self, weight: StandardWeight) -> CustomizedWeight {
+        CustomizedWeight {
+            weight: weight.scale_travel_duration(self.weight_scale),
+            edge_preference_factor: self.routing_mode.edge_preference_factor,
+        }
+    }
 }
 
 impl From<Arc<PreparedH3EdgeGraph<StandardWeight>>> for CustomizedGraph {
@@ -134,6 +166,7 @@ impl From<Arc<PreparedH3EdgeGraph<StandardWeight>>> for CustomizedGraph {
         CustomizedGraph {
             inner_graph,
             routing_mode: RoutingMode::default(),
+            weight_scale: 1.0,
         }
     }
 }
@@ -144,6 +177,12 @@ impl GetCellNode for CustomizedGraph {
     }
 }
 
+impl GetEdgeAttributes for CustomizedGraph {
+    fn get_edge_attributes(&self, edge: DirectedEdgeIndex) -> Option<&EdgeAttributes> {
+        self.inner_graph.get_edge_attributes(edge)
+    }
+}
+
 impl GetCellEdges for CustomizedGraph {
     type EdgeWeightType = CustomizedWeight;
 
@@ -158,26 +197,19 @@ impl GetCellEdges for CustomizedGraph {
                 (
                     edge,
                     EdgeWeight {
-                        weight: CustomizedWeight {
-                            weight: edge_weight.weight,
-                            edge_preference_factor: self.routing_mode.edge_preference_factor,
-                        },
+                        weight: self.customize_weight(edge_weight.weight),
                         fastforward: edge_weight.fastforward.map(|(fastforward, road_weight)| {
-                            (
-                                fastforward,
-                                CustomizedWeight {
-                                    weight: road_weight,
-                                    edge_preference_factor: self
-                                        .routing_mode
-                                        .edge_preference_factor,
-                                },
-                            )
+                            (fastforward, self.customize_weight(road_weight))
                         }),
                     },
                 )
             })
             .collect()
     }
+
+    fn is_edgeless(&self) -> bool {
+        self.inner_graph.is_edgeless()
+    }
 }
 
 impl HasH3Resolution for CustomizedGraph {
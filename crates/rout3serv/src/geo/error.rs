@@ -5,4 +5,7 @@ pub enum Error {
 
     #[error(transparent)]
     Geozero(#[from] geozero::error::GeozeroError),
+
+    #[error(transparent)]
+    Hexigraph(#[from] hexigraph::error::Error),
 }
@@ -0,0 +1,113 @@
+use geo_types::{Coord, Point};
+use geojson::{Feature, Geometry};
+use h3o::LatLng;
+use hexigraph::algorithm::graph::path::{DirectedEdgePath, Path};
+use uom::si::time::second;
+
+use crate::geo::Error;
+use crate::weight::Weight;
+
+/// build a GeoJSON [`Feature`] of a routed path.
+pub trait ToGeoJsonFeature {
+    fn to_geojson_feature(&self) -> Result<Feature, Error>;
+}
+
+impl<W> ToGeoJsonFeature for Path<W>
+where
+    W: Weight,
+{
+    /// The geometry is the linestring of the path, or the centroid of the cell when
+    /// the origin is the destination. The cost, the length and the endpoints are added
+    /// as properties. The h3 cells are written as hex strings.
+    fn to_geojson_feature(&self) -> Result<Feature, Error> {
+        let geometry = match &self.directed_edge_path {
+            DirectedEdgePath::OriginIsDestination(cell) => {
+                Geometry::from(&Point::from(Coord::from(LatLng::from(*cell))))
+            }
+            DirectedEdgePath::DirectedEdgeSequence(_) => {
+                Geometry::from(&self.directed_edge_path.to_linestring()?)
+            }
+        };
+        let mut feature = Feature::from(geometry);
+        feature.set_property(
+            "travel_duration_secs",
+            self.cost.travel_duration().get::<second>() as f64,
+        );
+        feature.set_property("edge_preference", self.cost.edge_preference() as f64);
+        feature.set_property("path_length_m", self.length_m());
+        feature.set_property("origin_cell", self.origin_cell.to_string());
+        feature.set_property("destination_cell", self.destination_cell.to_string());
+        Ok(feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use geo_types::{Coord, LineString, Point};
+    use h3o::{LatLng, Resolution};
+    use hexigraph::algorithm::graph::path::{DirectedEdgePath, Path};
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::weight::StandardWeight;
+
+    use super::ToGeoJsonFeature;
+
+    #[test]
+    fn path_to_geojson_feature() {
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let edge = origin.edges().next().unwrap();
+        let path: Path<_> = (
+            DirectedEdgePath::DirectedEdgeSequence(vec![edge]),
+            StandardWeight::new(2.0, Time::new::<second>(30.0)),
+        )
+            .try_into()
+            .unwrap();
+
+        let feature = path.to_geojson_feature().unwrap();
+        let linestring =
+            LineString::<f64>::try_from(feature.geometry.clone().unwrap().value).unwrap();
+        assert_eq!(linestring, path.directed_edge_path.to_linestring().unwrap());
+
+        let property_f64 = |name: &str| feature.property(name).unwrap().as_f64().unwrap();
+        assert!(approx_eq!(f64, property_f64("travel_duration_secs"), 30.0));
+        assert!(approx_eq!(f64, property_f64("edge_preference"), 2.0));
+        assert!(approx_eq!(
+            f64,
+            property_f64("path_length_m"),
+            path.directed_edge_path.length_m()
+        ));
+        assert_eq!(
+            feature.property("origin_cell").unwrap().as_str(),
+            Some(origin.to_string().as_str())
+        );
+        assert_eq!(
+            feature.property("destination_cell").unwrap().as_str(),
+            Some(edge.destination().to_string().as_str())
+        );
+    }
+
+    #[test]
+    fn origin_is_destination_to_geojson_feature() {
+        let cell = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let path: Path<_> = (
+            DirectedEdgePath::OriginIsDestination(cell),
+            StandardWeight::new(1.0, Time::new::<second>(0.0)),
+        )
+            .try_into()
+            .unwrap();
+
+        let feature = path.to_geojson_feature().unwrap();
+        let point = Point::<f64>::try_from(feature.geometry.clone().unwrap().value).unwrap();
+        assert_eq!(point, Point::from(Coord::from(LatLng::from(cell))));
+        assert_eq!(
+            feature.property("path_length_m").unwrap().as_f64(),
+            Some(0.0)
+        );
+        assert_eq!(
+            feature.property("origin_cell").unwrap().as_str(),
+            feature.property("destination_cell").unwrap().as_str()
+        );
+    }
+}
@@ -1,7 +1,9 @@
 mod buffer;
 pub mod error;
-mod webmercator;
+mod feature;
+pub mod webmercator;
 pub mod wkb;
 
 pub use buffer::*;
 pub use error::*;
+pub use feature::*;
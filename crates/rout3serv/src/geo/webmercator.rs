@@ -57,3 +57,75 @@ pub fn webmercator_to_wgs84(c: Coord) -> Coord {
     ));
     EXTEND_EPSG_4326.clamp_coordinate(ll_c)
 }
+
+/// transform all coordinates of the slice in place from WGS84 to spherical mercator.
+#[allow(dead_code)]
+pub fn wgs84_to_webmercator_slice(coords: &mut [Coord]) {
+    for c in coords.iter_mut() {
+        *c = wgs84_to_webmercator(*c);
+    }
+}
+
+/// transform all coordinates of the slice in place from spherical mercator to WGS84.
+#[allow(dead_code)]
+pub fn webmercator_to_wgs84_slice(coords: &mut [Coord]) {
+    for c in coords.iter_mut() {
+        *c = webmercator_to_wgs84(*c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use geo::MapCoords;
+    use geo_types::{Coord, LineString};
+
+    use super::{
+        webmercator_to_wgs84, webmercator_to_wgs84_slice, wgs84_to_webmercator,
+        wgs84_to_webmercator_slice,
+    };
+
+    fn grid() -> Vec<Coord> {
+        let mut coords = vec![];
+        for x in -18..=18 {
+            for y in -8..=8 {
+                coords.push(Coord::from((x as f64 * 10.0, y as f64 * 10.0)));
+            }
+        }
+        coords
+    }
+
+    #[test]
+    fn slice_matches_geometry_transform() {
+        let coords = grid();
+        let linestring = LineString::from(coords.clone());
+
+        let mut transformed = coords.clone();
+        wgs84_to_webmercator_slice(&mut transformed);
+        let transformed_ls = linestring.map_coords(wgs84_to_webmercator);
+        for (a, b) in transformed.iter().zip(transformed_ls.0.iter()) {
+            assert!(approx_eq!(f64, a.x, b.x, ulps = 2));
+            assert!(approx_eq!(f64, a.y, b.y, ulps = 2));
+        }
+
+        let mut back = transformed.clone();
+        webmercator_to_wgs84_slice(&mut back);
+        let back_ls = transformed_ls.map_coords(webmercator_to_wgs84);
+        for (a, b) in back.iter().zip(back_ls.0.iter()) {
+            assert!(approx_eq!(f64, a.x, b.x, ulps = 2));
+            assert!(approx_eq!(f64, a.y, b.y, ulps = 2));
+        }
+    }
+
+    #[test]
+    fn slice_roundtrip() {
+        let coords = grid();
+        let mut transformed = coords.clone();
+        wgs84_to_webmercator_slice(&mut transformed);
+        webmercator_to_wgs84_slice(&mut transformed);
+        for (a, b) in coords.iter().zip(transformed.iter()) {
+            assert!(approx_eq!(f64, a.x, b.x, epsilon = 1e-9));
+            assert!(approx_eq!(f64, a.y, b.y, epsilon = 1e-9));
+        }
+    }
+}
@@ -1,18 +1,29 @@
 #![allow(clippy::derive_partial_eq_without_eq)] // for the generated code. https://github.com/tokio-rs/prost/issues/661
 
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
 use geo::chaikin_smoothing::ChaikinSmoothing;
 use geo::simplify::Simplify;
-use geo_types::Geometry;
-use h3o::Resolution;
+use geo::simplify_vw::SimplifyVw;
+use geo_types::{Geometry, LineString};
+use h3o::{CellIndex, DirectedEdgeIndex, Resolution};
+use hexigraph::algorithm::edge::cell_centroid_distance_m;
 use hexigraph::algorithm::graph::path::Path;
 use hexigraph::algorithm::graph::shortest_path;
+use hexigraph::algorithm::resolution::transform_resolution;
+use hexigraph::graph::node::NodeType;
+use hexigraph::graph::{EdgeAttributes, GetCellNode, GetEdge, GetEdgeAttributes};
+use hexigraph::HasH3Resolution;
+use serde::{Deserialize, Serialize};
 use tonic::{Code, Status};
-use tracing::Level;
+use tracing::{info, Level};
 use uom::si::time::second;
 
-use crate::grpc::api::generated::{GraphHandle, RouteH3Indexes, RouteWkb, ShortestPathOptions};
+use crate::grpc::api::generated::{
+    CellNodeType, ClassifiedCell, ClassifyCellsResponse, GraphHandle, RouteClassCost,
+    RouteH3EdgeWeights, RouteH3Indexes, RouteWkb, ShortestPathOptions, SimplificationAlgorithm,
+};
 use crate::grpc::error::{logged_status, ToStatusResult};
 use crate::grpc::geometry::to_wkb;
 use crate::io::GraphKey;
@@ -26,29 +37,93 @@ impl Route for RouteWkb {}
 
 impl Route for RouteH3Indexes {}
 
-const SIMPLIFICATION_EPSILON: f64 = 0.00001;
+impl Route for RouteH3EdgeWeights {}
 
-impl RouteWkb {
-    pub fn from_path<T>(path: &Path<T>, smoothen: bool) -> Result<Self, Status>
-    where
-        T: Weight,
-    {
-        let mut linestring = path
-            .directed_edge_path
-            .to_linestring()
-            .to_status_result_with_message(Code::Internal, || {
-                "can not build linestring from path".to_string()
-            })?;
+/// maximum distance (in degrees) used by Douglas-Peucker when the client did not request a
+/// specific epsilon
+pub const DEFAULT_DOUGLAS_PEUCKER_EPSILON: f64 = 0.00001;
+
+/// minimum triangle area (in square degrees) used by Visvalingam-Whyatt when the client did not
+/// request a specific epsilon
+pub const DEFAULT_VISVALINGAM_WHYATT_EPSILON: f64 = 0.000000001;
+
+impl SimplificationAlgorithm {
+    /// the epsilon to use when the client did not request a specific one. The
+    /// epsilons of the algorithms have different units, so each has its own default.
+    pub fn default_epsilon(&self) -> f64 {
+        match self {
+            Self::DouglasPeucker => DEFAULT_DOUGLAS_PEUCKER_EPSILON,
+            Self::VisvalingamWhyatt => DEFAULT_VISVALINGAM_WHYATT_EPSILON,
+        }
+    }
+}
+
+/// simplification to apply to route geometries
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RouteSimplification {
+    pub algorithm: SimplificationAlgorithm,
+
+    /// the maximum distance for Douglas-Peucker, the minimum triangle area for
+    /// Visvalingam-Whyatt. 0 disables the simplification.
+    pub epsilon: f64,
+}
+
+impl Default for RouteSimplification {
+    fn default() -> Self {
+        let algorithm = SimplificationAlgorithm::DouglasPeucker;
+        Self {
+            algorithm,
+            epsilon: algorithm.default_epsilon(),
+        }
+    }
+}
 
-        if smoothen {
-            // apply only one iteration to break edges
-            linestring = linestring.chaikin_smoothing(1);
+impl RouteSimplification {
+    fn apply(&self, linestring: LineString) -> LineString {
+        if self.epsilon > 0.0 {
+            match self.algorithm {
+                SimplificationAlgorithm::DouglasPeucker => linestring.simplify(&self.epsilon),
+                SimplificationAlgorithm::VisvalingamWhyatt => linestring.simplify_vw(&self.epsilon),
+            }
+        } else {
+            linestring
         }
+    }
+}
 
-        // remove redundant vertices. This reduces the amount of data to transfer
-        // without losing any significant information
-        linestring = linestring.simplify(&SIMPLIFICATION_EPSILON);
+/// build the linestring of a path.
+fn route_linestring<T>(
+    path: &Path<T>,
+    smoothen: bool,
+    simplification: RouteSimplification,
+) -> Result<LineString, Status> {
+    let mut linestring = path
+        .directed_edge_path
+        .to_linestring()
+        .to_status_result_with_message(Code::Internal, || {
+            "can not build linestring from path".to_string()
+        })?;
 
+    if smoothen {
+        // apply only one iteration to break edges
+        linestring = linestring.chaikin_smoothing(1);
+    }
+
+    // remove redundant vertices. This reduces the amount of data to transfer
+    // without losing any significant information
+    Ok(simplification.apply(linestring))
+}
+
+impl RouteWkb {
+    pub fn from_path<T>(
+        path: &Path<T>,
+        smoothen: bool,
+        simplification: RouteSimplification,
+    ) -> Result<Self, Status>
+    where
+        T: Weight,
+    {
+        let linestring = route_linestring(path, smoothen, simplification)?;
         let wkb_bytes = to_wkb(&Geometry::LineString(linestring))?;
         Ok(Self {
             origin_cell: u64::from(path.origin_cell),
@@ -56,7 +131,8 @@ impl RouteWkb {
             travel_duration_secs: path.cost.travel_duration().get::<second>() as f64,
             edge_preference: path.cost.edge_preference() as f64,
             wkb: wkb_bytes,
-            path_length_m: path.directed_edge_path.length_m(),
+            path_length_m: path.length_m(),
+            cost_fields: path.cost.report_fields().into_iter().collect(),
         })
     }
 }
@@ -68,17 +144,31 @@ pub enum RouteH3IndexesKind {
 }
 
 impl RouteH3Indexes {
-    pub fn from_path<T>(path: &Path<T>, kind: RouteH3IndexesKind) -> Result<Self, Status>
+    /// build from a [`Path`].
+    ///
+    /// Setting a `target_resolution` decimates the cells of the path to that coarser
+    /// resolution. It is only applied for [`RouteH3IndexesKind::Cells`].
+    pub fn from_path<T>(
+        path: &Path<T>,
+        kind: RouteH3IndexesKind,
+        target_resolution: Option<Resolution>,
+    ) -> Result<Self, Status>
     where
         T: Weight,
     {
         let h3indexes = match kind {
-            RouteH3IndexesKind::Cells => path
-                .directed_edge_path
-                .cells()
-                .into_iter()
-                .map(u64::from)
-                .collect(),
+            RouteH3IndexesKind::Cells => match target_resolution {
+                Some(h3_resolution) => path
+                    .directed_edge_path
+                    .simplify_to_cells_at_resolution(h3_resolution)
+                    .to_status_result_with_message(Code::InvalidArgument, || {
+                        format!("can not reduce path cells to resolution {h3_resolution}")
+                    })?,
+                None => path.directed_edge_path.cells(),
+            }
+            .into_iter()
+            .map(u64::from)
+            .collect(),
             RouteH3IndexesKind::Edges => path
                 .directed_edge_path
                 .edges()
@@ -93,7 +183,97 @@ impl RouteH3Indexes {
             travel_duration_secs: path.cost.travel_duration().get::<second>() as f64,
             edge_preference: path.cost.edge_preference() as f64,
             h3indexes,
-            path_length_m: path.directed_edge_path.length_m(),
+            path_length_m: path.length_m(),
+        })
+    }
+}
+
+/// the edge attribute holding the highway class of an edge
+pub const HIGHWAY_CLASS_ATTRIBUTE: &str = "highway";
+
+/// the highway class of edges without a [`HIGHWAY_CLASS_ATTRIBUTE`]
+pub const UNKNOWN_HIGHWAY_CLASS: &str = "unknown";
+
+/// travel duration and length of a route accumulated per highway class of its edges.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouteClassBreakdown {
+    /// (travel duration in seconds, length in meters) by highway class
+    classes: BTreeMap<String, (f64, f64)>,
+}
+
+impl RouteClassBreakdown {
+    /// account the travel duration and the length of `edge` to its highway class.
+    pub fn add_edge(
+        &mut self,
+        edge: DirectedEdgeIndex,
+        edge_attributes: Option<&EdgeAttributes>,
+        travel_duration_secs: f64,
+    ) {
+        let highway_class = edge_attributes
+            .and_then(|attributes| attributes.get(HIGHWAY_CLASS_ATTRIBUTE))
+            .unwrap_or(UNKNOWN_HIGHWAY_CLASS);
+        let (class_duration_secs, class_length_m) =
+            self.classes.entry(highway_class.to_string()).or_default();
+        *class_duration_secs += travel_duration_secs;
+        *class_length_m += cell_centroid_distance_m(edge);
+    }
+
+    pub fn into_class_costs(self) -> Vec<RouteClassCost> {
+        self.classes
+            .into_iter()
+            .map(
+                |(highway_class, (travel_duration_secs, path_length_m))| RouteClassCost {
+                    highway_class,
+                    travel_duration_secs,
+                    path_length_m,
+                },
+            )
+            .collect()
+    }
+}
+
+impl RouteH3EdgeWeights {
+    /// build from a [`Path`] with the weights of the edges taken from `graph`.
+    ///
+    /// The edges are additionally accumulated in a [`RouteClassBreakdown`] using the
+    /// highway classes stored in the edge attributes of the `graph`.
+    pub fn from_path<T, G>(path: &Path<T>, graph: &G) -> Result<Self, Status>
+    where
+        T: Weight,
+        G: GetEdge + GetEdgeAttributes,
+        G::EdgeWeightType: Weight,
+    {
+        let edges = path.directed_edge_path.edges();
+        let mut h3edges = Vec::with_capacity(edges.len());
+        let mut edge_travel_duration_secs = Vec::with_capacity(edges.len());
+        let mut class_breakdown = RouteClassBreakdown::default();
+        for edge in edges {
+            let edge_weight = graph.get_edge(*edge).ok_or_else(|| {
+                logged_status!(
+                    format!("edge {edge} of the path is not part of the graph"),
+                    Code::Internal,
+                    Level::ERROR
+                )
+            })?;
+            let travel_duration_secs = edge_weight.weight.travel_duration().get::<second>() as f64;
+            class_breakdown.add_edge(
+                *edge,
+                graph.get_edge_attributes(*edge),
+                travel_duration_secs,
+            );
+            h3edges.push(u64::from(*edge));
+            edge_travel_duration_secs.push(travel_duration_secs);
+        }
+
+        Ok(Self {
+            origin_cell: u64::from(path.origin_cell),
+            destination_cell: u64::from(path.destination_cell),
+            travel_duration_secs: path.cost.travel_duration().get::<second>() as f64,
+            edge_preference: path.cost.edge_preference() as f64,
+            h3edges,
+            edge_travel_duration_secs,
+            path_length_m: path.length_m(),
+            class_breakdown: class_breakdown.into_class_costs(),
         })
     }
 }
@@ -148,6 +328,48 @@ impl TryFrom<&Option<GraphHandle>> for GraphKey {
     }
 }
 
+impl From<Option<NodeType>> for CellNodeType {
+    fn from(node_type: Option<NodeType>) -> Self {
+        match node_type {
+            None => Self::NotInGraph,
+            Some(NodeType::Origin) => Self::Origin,
+            Some(NodeType::Destination) => Self::Destination,
+            Some(NodeType::OriginAndDestination) => Self::OriginAndDestination,
+        }
+    }
+}
+
+impl ClassifyCellsResponse {
+    /// classify the `cells` by their node type in the `graph`.
+    ///
+    /// The cells get transformed to the resolution of the graph first.
+    pub fn classify<G>(graph: &G, cells: &[u64]) -> Result<Self, Status>
+    where
+        G: GetCellNode + HasH3Resolution,
+    {
+        let cells = cells
+            .iter()
+            .map(|h3index| CellIndex::try_from(*h3index))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                logged_status!("invalid h3 index", Code::InvalidArgument, Level::DEBUG, e)
+            })?;
+        let mut cells: Vec<_> = transform_resolution(cells, graph.h3_resolution()).collect();
+        cells.sort_unstable();
+        cells.dedup();
+
+        Ok(Self {
+            cells: cells
+                .into_iter()
+                .map(|cell| ClassifiedCell {
+                    cell: cell.into(),
+                    node_type: CellNodeType::from(graph.get_cell_node(cell)).into(),
+                })
+                .collect(),
+        })
+    }
+}
+
 impl shortest_path::ShortestPathOptions for ShortestPathOptions {
     fn max_distance_to_graph(&self) -> u32 {
         self.num_gap_cells_to_graph
@@ -162,3 +384,389 @@ impl shortest_path::ShortestPathOptions for ShortestPathOptions {
         }
     }
 }
+
+impl ShortestPathOptions {
+    /// the simplification to apply to route geometries. The epsilon defaults to
+    /// [`SimplificationAlgorithm::default_epsilon`].
+    pub fn route_simplification(&self) -> Result<RouteSimplification, Status> {
+        let algorithm = self.simplification_algorithm();
+        let epsilon = match self.simplify_epsilon {
+            None => algorithm.default_epsilon(),
+            Some(epsilon) if epsilon.is_finite() && epsilon >= 0.0 => epsilon,
+            Some(_) => {
+                return Err(logged_status!(
+                    "invalid simplify_epsilon",
+                    Code::InvalidArgument,
+                    Level::DEBUG
+                ))
+            }
+        };
+        Ok(RouteSimplification { algorithm, epsilon })
+    }
+
+    /// the validated factor to scale the travel durations of all edges with
+    pub fn weight_scaling(&self) -> Result<f32, Status> {
+        match self.weight_scale {
+            None => Ok(1.0),
+            Some(weight_scale) if weight_scale.is_normal() && weight_scale > 0.0 => {
+                Ok(weight_scale)
+            }
+            Some(_) => Err(logged_status!(
+                "invalid weight_scale",
+                Code::InvalidArgument,
+                Level::DEBUG
+            )),
+        }
+    }
+
+    /// limit `num_destinations_to_reach` to `max_destinations_to_reach`.
+    ///
+    /// Requests not setting `num_destinations_to_reach` get limited as well.
+    pub fn clamp_num_destinations_to_reach(&mut self, max_destinations_to_reach: Option<u32>) {
+        if let Some(max_destinations_to_reach) = max_destinations_to_reach {
+            if self.num_destinations_to_reach == 0
+                || self.num_destinations_to_reach > max_destinations_to_reach
+            {
+                info!(
+                    "clamping num_destinations_to_reach from {} to {}",
+                    self.num_destinations_to_reach, max_destinations_to_reach
+                );
+                self.num_destinations_to_reach = max_destinations_to_reach;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{coord, LineString};
+    use h3o::{LatLng, Resolution};
+    use hexigraph::algorithm::edge::continuous_cells_to_edges;
+    use hexigraph::algorithm::graph::path::{DirectedEdgePath, Path};
+    use hexigraph::algorithm::graph::shortest_path::DefaultShortestPathOptions;
+    use hexigraph::algorithm::graph::ShortestPath;
+    use hexigraph::graph::{EdgeAttributes, H3EdgeGraph, PreparedH3EdgeGraph};
+    use num_traits::Zero;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::weight::{StandardWeight, Weight};
+
+    use super::{
+        route_linestring, CellNodeType, ClassifyCellsResponse, RouteH3EdgeWeights,
+        RouteSimplification, RouteWkb, ShortestPathOptions, SimplificationAlgorithm,
+        DEFAULT_DOUGLAS_PEUCKER_EPSILON, DEFAULT_VISVALINGAM_WHYATT_EPSILON, UNKNOWN_HIGHWAY_CLASS,
+    };
+
+    #[test]
+    fn route_simplification_epsilon() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let destination = LatLng::new(10.01, 20.02).unwrap().to_cell(Resolution::Ten);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let path = Path::new(
+            origin,
+            destination,
+            1,
+            DirectedEdgePath::DirectedEdgeSequence(continuous_cells_to_edges(cells).collect()),
+        );
+
+        let num_vertices = |epsilon| {
+            route_linestring(
+                &path,
+                false,
+                RouteSimplification {
+                    algorithm: SimplificationAlgorithm::DouglasPeucker,
+                    epsilon,
+                },
+            )
+            .unwrap()
+            .0
+            .len()
+        };
+        let unsimplified = path.directed_edge_path.to_linestring().unwrap().0.len();
+
+        assert_eq!(num_vertices(0.0), unsimplified);
+        assert!(num_vertices(0.00001) <= unsimplified);
+        assert!(num_vertices(0.001) < num_vertices(0.00001));
+        assert_eq!(num_vertices(1.0), 2);
+    }
+
+    #[test]
+    fn route_edge_weights_sum_up_to_cost() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let destination = LatLng::new(10.01, 20.02).unwrap().to_cell(Resolution::Ten);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let mut graph = H3EdgeGraph::new(Resolution::Ten);
+        for (i, edge) in continuous_cells_to_edges(cells).enumerate() {
+            graph.add_edge(
+                edge,
+                StandardWeight::new(1.0, Time::new::<second>(2.0 + (i % 3) as f32)),
+            );
+        }
+        // short fastforwards to also cover edges decompressed from these
+        let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+
+        let path = prepared_graph
+            .shortest_path(
+                origin,
+                [destination],
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap()
+            .remove(0);
+        let route = RouteH3EdgeWeights::from_path(&path, &prepared_graph).unwrap();
+
+        assert_eq!(route.h3edges.len(), path.len());
+        assert_eq!(route.h3edges.len(), route.edge_travel_duration_secs.len());
+        assert!(route
+            .edge_travel_duration_secs
+            .iter()
+            .all(|secs| (2.0..=4.0).contains(secs)));
+        let summed_secs: f64 = route.edge_travel_duration_secs.iter().sum();
+        assert!(
+            (summed_secs + start_travel_duration_secs() - route.travel_duration_secs).abs() < 1e-3
+        );
+    }
+
+    /// the travel duration of the zero weight every path cost starts from
+    fn start_travel_duration_secs() -> f64 {
+        StandardWeight::zero().travel_duration().get::<second>() as f64
+    }
+
+    #[test]
+    fn route_class_breakdown_sums_up_to_totals() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let destination = LatLng::new(10.01, 20.02).unwrap().to_cell(Resolution::Ten);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let edges: Vec<_> = continuous_cells_to_edges(cells).collect();
+
+        // the first half is a primary road, the second half residential with the
+        // last edge lacking any attributes.
+        let mut graph = H3EdgeGraph::new(Resolution::Ten);
+        for (i, edge) in edges.iter().enumerate() {
+            let (highway_class, secs) = if i < edges.len() / 2 {
+                ("primary", 2.0)
+            } else {
+                ("residential", 5.0)
+            };
+            graph.add_edge(*edge, StandardWeight::new(1.0, Time::new::<second>(secs)));
+            if i + 1 < edges.len() {
+                graph.set_edge_attributes(
+                    *edge,
+                    EdgeAttributes::from_iter([("highway", highway_class)]),
+                );
+            }
+        }
+        let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+
+        let path = prepared_graph
+            .shortest_path(
+                origin,
+                [destination],
+                &DefaultShortestPathOptions::default(),
+            )
+            .unwrap()
+            .remove(0);
+        let route = RouteH3EdgeWeights::from_path(&path, &prepared_graph).unwrap();
+
+        let class_names: Vec<_> = route
+            .class_breakdown
+            .iter()
+            .map(|class_cost| class_cost.highway_class.as_str())
+            .collect();
+        assert_eq!(
+            class_names,
+            vec!["primary", "residential", UNKNOWN_HIGHWAY_CLASS]
+        );
+        assert!(
+            (route.class_breakdown[0].travel_duration_secs - 2.0 * (edges.len() / 2) as f64).abs()
+                < 1e-3
+        );
+        assert!((route.class_breakdown[2].travel_duration_secs - 5.0).abs() < 1e-3);
+
+        let summed_secs: f64 = route
+            .class_breakdown
+            .iter()
+            .map(|class_cost| class_cost.travel_duration_secs)
+            .sum();
+        assert!(
+            (summed_secs + start_travel_duration_secs() - route.travel_duration_secs).abs() < 1e-3
+        );
+        let summed_length_m: f64 = route
+            .class_breakdown
+            .iter()
+            .map(|class_cost| class_cost.path_length_m)
+            .sum();
+        assert!((summed_length_m - route.path_length_m).abs() < 1e-3);
+    }
+
+    /// a weight carrying a monetary cost in addition to the travel duration
+    struct TollWeight {
+        travel_duration: Time,
+        toll_eur: f64,
+    }
+
+    impl Weight for TollWeight {
+        fn travel_duration(&self) -> Time {
+            self.travel_duration
+        }
+
+        fn report_fields(&self) -> Vec<(String, f64)> {
+            vec![("toll_eur".to_string(), self.toll_eur)]
+        }
+
+        fn from_travel_duration(travel_duration: Time) -> Self {
+            Self {
+                travel_duration,
+                toll_eur: 0.0,
+            }
+        }
+    }
+
+    #[test]
+    fn route_wkb_reports_weight_fields() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Ten);
+        let edge = origin.edges().next().unwrap();
+        let path = Path::new(
+            origin,
+            edge.destination(),
+            TollWeight {
+                travel_duration: Time::new::<second>(12.0),
+                toll_eur: 3.5,
+            },
+            DirectedEdgePath::DirectedEdgeSequence(vec![edge]),
+        );
+
+        let route = RouteWkb::from_path(&path, false, RouteSimplification::default()).unwrap();
+        assert!((route.travel_duration_secs - 12.0).abs() < 1e-6);
+        assert_eq!(route.cost_fields.len(), 1);
+        assert_eq!(route.cost_fields.get("toll_eur"), Some(&3.5));
+
+        // the standard weight has no additional fields
+        let path = Path::new(
+            path.origin_cell,
+            path.destination_cell,
+            StandardWeight::new(1.0, Time::new::<second>(12.0)),
+            path.directed_edge_path,
+        );
+        let route = RouteWkb::from_path(&path, false, RouteSimplification::default()).unwrap();
+        assert!(route.cost_fields.is_empty());
+    }
+
+    #[test]
+    fn route_simplification_default_epsilon() {
+        let simplification = ShortestPathOptions::default()
+            .route_simplification()
+            .unwrap();
+        assert_eq!(
+            simplification.algorithm,
+            SimplificationAlgorithm::DouglasPeucker
+        );
+        assert_eq!(simplification.epsilon, DEFAULT_DOUGLAS_PEUCKER_EPSILON);
+
+        let options = ShortestPathOptions {
+            simplification_algorithm: SimplificationAlgorithm::VisvalingamWhyatt.into(),
+            ..Default::default()
+        };
+        let simplification = options.route_simplification().unwrap();
+        assert_eq!(simplification.epsilon, DEFAULT_VISVALINGAM_WHYATT_EPSILON);
+
+        let options = ShortestPathOptions {
+            simplification_algorithm: SimplificationAlgorithm::VisvalingamWhyatt.into(),
+            simplify_epsilon: Some(0.5),
+            ..Default::default()
+        };
+        assert_eq!(options.route_simplification().unwrap().epsilon, 0.5);
+    }
+
+    #[test]
+    fn simplification_algorithms() {
+        let simplify = |linestring: &LineString, algorithm| {
+            RouteSimplification {
+                algorithm,
+                epsilon: 0.5,
+            }
+            .apply(linestring.clone())
+        };
+
+        // a zig-zag with a small amplitude gets removed by both algorithms
+        let zigzag: LineString = (0..=10)
+            .map(|i| coord! { x: i as f64, y: (i % 2) as f64 * 0.05 })
+            .collect();
+        for algorithm in [
+            SimplificationAlgorithm::DouglasPeucker,
+            SimplificationAlgorithm::VisvalingamWhyatt,
+        ] {
+            let simplified = simplify(&zigzag, algorithm);
+            assert_eq!(simplified.0.len(), 2);
+            assert_eq!(simplified.0.first(), zigzag.0.first());
+            assert_eq!(simplified.0.last(), zigzag.0.last());
+        }
+
+        // a wide, but shallow bump is within the distance tolerance of Douglas-Peucker,
+        // but spans a large area, so Visvalingam-Whyatt retains it.
+        let bump: LineString = vec![(0.0, 0.0), (5.0, 0.3), (10.0, 0.0)].into();
+        assert_eq!(
+            simplify(&bump, SimplificationAlgorithm::DouglasPeucker)
+                .0
+                .len(),
+            2
+        );
+        assert_eq!(
+            simplify(&bump, SimplificationAlgorithm::VisvalingamWhyatt),
+            bump
+        );
+    }
+
+    #[test]
+    fn classify_cells() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let edge = origin.edges().next().unwrap();
+        let destination = edge.destination();
+        let off_graph = LatLng::new(40.0, 20.0).unwrap().to_cell(Resolution::Nine);
+
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+        let graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let response = ClassifyCellsResponse::classify(
+            &graph,
+            &[
+                u64::from(off_graph),
+                u64::from(destination),
+                // a child gets transformed to the resolution of the graph
+                u64::from(origin.center_child(Resolution::Eleven).unwrap()),
+            ],
+        )
+        .unwrap();
+        let classified: Vec<_> = response
+            .cells
+            .iter()
+            .map(|classified_cell| (classified_cell.cell, classified_cell.node_type()))
+            .collect();
+
+        let mut expected = vec![
+            (u64::from(origin), CellNodeType::Origin),
+            (u64::from(destination), CellNodeType::Destination),
+            (u64::from(off_graph), CellNodeType::NotInGraph),
+        ];
+        // ordered by cell
+        expected.sort_unstable_by_key(|(cell, _)| *cell);
+        assert_eq!(classified, expected);
+
+        assert!(ClassifyCellsResponse::classify(&graph, &[0]).is_err());
+    }
+}
@@ -1,19 +1,22 @@
 use ahash::RandomState;
 use std::cmp::max;
 use std::sync::Arc;
+use std::time::Instant;
 
 use geo_types::Coord;
 use h3o::{CellIndex, LatLng, Resolution};
 use hexigraph::algorithm::graph::differential_shortest_path::ExclusionDiff;
 use hexigraph::algorithm::graph::path::Path;
+use hexigraph::algorithm::graph::shortest_path::ShortestPathOptions as _;
 use hexigraph::algorithm::graph::DifferentialShortestPath;
 use hexigraph::algorithm::resolution::transform_resolution;
 use hexigraph::container::treemap::H3Treemap;
 use hexigraph::container::CellSet;
 use hexigraph::graph::PreparedH3EdgeGraph;
 use hexigraph::HasH3Resolution;
-use polars::prelude::{DataFrame, DataFrameJoinOps, JoinType, NamedFrom, Series};
+use polars::prelude::{DataFrame, DataFrameJoinOps, FillNullStrategy, JoinType, NamedFrom, Series};
 use polars_core::prelude::JoinArgs;
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use tonic::{Code, Status};
 use tracing::Level;
@@ -22,15 +25,23 @@ use uom::si::time::second;
 use crate::grpc::api::generated::{
     DifferentialShortestPathRequest, DifferentialShortestPathRoutes, RouteWkb, ShortestPathOptions,
 };
+use crate::grpc::api::RouteSimplification;
 use crate::grpc::error::{logged_status, StatusCodeAndMessage, ToStatusResult};
-use crate::grpc::geometry::{buffer_meters, from_wkb, geom_to_h3};
-use crate::grpc::util::{change_cell_resolution_dedup, StrId};
+use crate::grpc::geometry::{buffer_meters, from_wkb, geom_to_h3, Containment};
+use crate::grpc::util::{change_cell_resolution_dedup, routing_finished, routing_span, StrId};
 use crate::grpc::ServerImpl;
 use crate::io::dataframe::CellDataFrame;
 use crate::io::memory_cache::FetchError;
+use crate::io::GraphKey;
 use crate::weight::{StandardWeight, Weight};
 
 pub struct DspInput {
+    /// id of the output. See [`deterministic_object_id`].
+    pub object_id: String,
+
+    /// the `object_id` has been derived from the request
+    pub deterministic_object_id: bool,
+
     /// the cells within the disturbance
     pub disturbance: H3Treemap<CellIndex>,
 
@@ -41,8 +52,12 @@ pub struct DspInput {
     pub destinations: Vec<CellIndex>,
 
     pub store_output: bool,
+
+    /// only keep the origins where the disturbance changed the routing results
+    pub only_changed: bool,
     pub options: ShortestPathOptions,
     pub graph: Arc<PreparedH3EdgeGraph<StandardWeight>>,
+    pub graph_key: GraphKey,
 
     /// Setting a `downsampled_graph` will allow performing an initial routing at a lower resolution
     /// to reduce the number of routings to perform on the full-resolution graph. This has the potential
@@ -52,6 +67,26 @@ pub struct DspInput {
     pub downsampled_graph: Option<Arc<PreparedH3EdgeGraph<StandardWeight>>>,
     pub ref_dataframe: CellDataFrame,
     pub ref_dataframe_cells: CellSet,
+
+    /// column of the `ref_dataframe` to aggregate over the reached destinations
+    pub value_column_name: Option<String>,
+
+    pub ref_dataset_join: RefDatasetJoin,
+}
+
+/// how the `ref_dataframe` gets joined to the statistics of the origins
+#[derive(Serialize, Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum RefDatasetJoin {
+    /// only keep origins contained in the `ref_dataframe`
+    #[default]
+    Inner,
+
+    /// route and keep all origins of the buffer, also the ones missing in the `ref_dataframe`.
+    /// The `ref_dataframe` columns are null for these.
+    Left,
+
+    /// same as `Left`, but nulls in the numeric `ref_dataframe` columns are replaced by zero.
+    LeftFillZero,
 }
 
 /// collect/prepare/download all input data needed for the differential shortest path
@@ -63,6 +98,17 @@ pub(crate) async fn collect_input(
         .retrieve_graph_by_handle(&request.graph_handle)
         .await?;
 
+    let object_id = if request.deterministic_object_id {
+        let graph_version = server_impl
+            .storage
+            .graph_version(&graph_key)
+            .await
+            .to_status_result()?;
+        deterministic_object_id(&request, &graph_key, &graph_version)
+    } else {
+        uuid::Uuid::new_v4().to_string()
+    };
+
     let downsampled_graph = if request.downsampled_prerouting {
         // attempt to find a suitable graph at a lower resolution
 
@@ -105,22 +151,42 @@ pub(crate) async fn collect_input(
     let (disturbance, within_buffer) = {
         let disturbance_wkb_geometry = std::mem::take(&mut request.disturbance_wkb_geometry);
         let radius_meters = request.radius_meters;
+        let disturbance_containment = request.disturbance_containment().into();
         tokio::task::block_in_place(|| {
             disturbance_and_buffered_cells(
                 graph.h3_resolution(),
                 &disturbance_wkb_geometry,
+                disturbance_containment,
                 radius_meters,
+                server_impl.config.max_polyfill_cells,
             )
         })?
     };
 
+    let ref_dataset_join = match request.ref_dataset_join() {
+        super::api::generated::RefDatasetJoin::Inner => RefDatasetJoin::Inner,
+        super::api::generated::RefDatasetJoin::Left => RefDatasetJoin::Left,
+        super::api::generated::RefDatasetJoin::LeftFillZero => RefDatasetJoin::LeftFillZero,
+    };
+
+    let destinations = destination_cells(request.destinations, graph.h3_resolution())?;
+    let ref_dataset = server_impl.dataset_by_name(&request.ref_dataset_name)?;
+
+    // the values of the reached destinations are aggregated, so these need
+    // to be fetched as well
+    let ref_cells = if ref_dataset.value_column_name.is_some() {
+        let mut ref_cells = within_buffer.clone();
+        ref_cells.extend_from_slice(&destinations);
+        ref_cells.sort_unstable();
+        ref_cells.dedup();
+        ref_cells
+    } else {
+        within_buffer.clone()
+    };
+
     let ref_dataframe = server_impl
         .storage
-        .retrieve_dataframe(
-            server_impl.dataset_by_name(&request.ref_dataset_name)?,
-            &within_buffer,
-            graph.h3_resolution(),
-        )
+        .retrieve_dataframe(ref_dataset, &ref_cells, graph.h3_resolution())
         .await
         .to_status_result()?
         .ok_or_else(|| logged_status!("ref_dataset was empty", Code::NotFound, Level::WARN))?;
@@ -133,19 +199,66 @@ pub(crate) async fn collect_input(
         .flatten()
         .collect();
 
+    let mut options = request.options.unwrap_or_default();
+    options.clamp_num_destinations_to_reach(server_impl.config.max_destinations_to_reach);
+
     Ok(DspInput {
+        object_id,
+        deterministic_object_id: request.deterministic_object_id,
         disturbance,
         within_buffer,
-        destinations: destination_cells(request.destinations, graph.h3_resolution())?,
+        destinations,
+        value_column_name: ref_dataset.value_column_name.clone(),
         store_output: request.store_output,
-        options: request.options.unwrap_or_default(),
+        only_changed: request.only_changed,
+        ref_dataset_join,
+        options,
         graph,
+        graph_key,
         downsampled_graph,
         ref_dataframe,
         ref_dataframe_cells,
     })
 }
 
+/// derive a stable object id from the content of the `request`, the key of the graph
+/// it resolved to and the version of that graph as returned by [`Storage::graph_version`].
+///
+/// Requests only share an id - and with it a stored output - as long as they resolve to the
+/// same, unchanged graph. Changes of the ref dataset are not detected.
+///
+/// The flags only controlling the handling of the output do not contribute to the id.
+///
+/// [`Storage::graph_version`]: crate::io::storage::Storage::graph_version
+pub(crate) fn deterministic_object_id(
+    request: &DifferentialShortestPathRequest,
+    graph_key: &GraphKey,
+    graph_version: &str,
+) -> String {
+    let mut request = request.clone();
+    request.store_output = false;
+    request.deterministic_object_id = false;
+    let mut name = request.encode_to_vec();
+    name.extend_from_slice(graph_key.to_string().as_bytes());
+    name.push(0);
+    name.extend_from_slice(graph_version.as_bytes());
+    uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, &name).to_string()
+}
+
+/// resolve the graph of the `request` and derive its [`deterministic_object_id`].
+pub(crate) async fn resolve_deterministic_object_id(
+    request: &DifferentialShortestPathRequest,
+    server_impl: &ServerImpl,
+) -> Result<String, Status> {
+    let graph_key = server_impl.resolve_graph_key(&request.graph_handle).await?;
+    let graph_version = server_impl
+        .storage
+        .graph_version(&graph_key)
+        .await
+        .to_status_result()?;
+    Ok(deterministic_object_id(request, &graph_key, &graph_version))
+}
+
 /// cells to route to
 fn destination_cells(
     destinations: Vec<super::api::generated::Point>,
@@ -166,16 +279,25 @@ fn destination_cells(
 fn disturbance_and_buffered_cells(
     h3_resolution: Resolution,
     disturbance_wkb_geometry: &[u8],
+    disturbance_containment: Containment,
     radius_meters: f64,
+    max_polyfill_cells: Option<usize>,
 ) -> Result<(H3Treemap<CellIndex>, Vec<CellIndex>), Status> {
     let disturbance_geom = from_wkb(disturbance_wkb_geometry)?;
-    let disturbed_cells: H3Treemap<CellIndex> =
-        H3Treemap::from_iter(geom_to_h3(disturbance_geom.clone(), h3_resolution, true)?);
+    let disturbed_cells: H3Treemap<CellIndex> = H3Treemap::from_iter(geom_to_h3(
+        disturbance_geom.clone(),
+        h3_resolution,
+        disturbance_containment,
+        true,
+        max_polyfill_cells,
+    )?);
 
     let buffered_cells = geom_to_h3(
         buffer_meters(&disturbance_geom, radius_meters)?,
         h3_resolution,
+        Containment::Centroid,
         true,
+        max_polyfill_cells,
     )?;
     Ok((disturbed_cells, buffered_cells))
 }
@@ -186,6 +308,14 @@ pub struct DspOutput {
     pub ref_dataframe: CellDataFrame,
     pub ref_dataframe_cells: CellSet,
 
+    /// column of the `ref_dataframe` to aggregate over the reached destinations
+    pub value_column_name: Option<String>,
+
+    pub ref_dataset_join: RefDatasetJoin,
+
+    /// simplification to apply to the geometries of the routes
+    pub route_simplification: RouteSimplification,
+
     /// tuple: (origin h3 cell, diff)
     pub differential_shortest_paths: Vec<(CellIndex, ExclusionDiff<Path<StandardWeight>>)>,
 }
@@ -199,13 +329,20 @@ impl StrId for DspOutput {
 ///
 ///
 pub fn calculate(input: DspInput) -> Result<DspOutput, Status> {
+    let started = Instant::now();
+    let route_simplification = input.options.route_simplification()?;
     let origin_cells: Vec<CellIndex> = {
         let origin_cells: Vec<CellIndex> = {
+            // the left joins keep the origins missing in the `ref_dataframe`, so these
+            // need to be routed as well
+            let require_ref_cell = input.ref_dataset_join == RefDatasetJoin::Inner;
             let mut origin_cells = Vec::with_capacity(input.within_buffer.len());
             for cell in &input.within_buffer {
-                // exclude the cells of the disturbance itself as well as all origin cells without
-                // any population from routing
-                if input.ref_dataframe_cells.contains(cell) && !input.disturbance.contains(cell) {
+                // exclude the cells of the disturbance itself as well as - for inner joins - all
+                // origin cells without any population from routing
+                if (!require_ref_cell || input.ref_dataframe_cells.contains(cell))
+                    && !input.disturbance.contains(cell)
+                {
                     origin_cells.push(*cell);
                 }
             }
@@ -280,6 +417,15 @@ pub fn calculate(input: DspInput) -> Result<DspOutput, Status> {
         }
     };
 
+    let _entered = routing_span(
+        "differential_shortest_path",
+        &input.graph_key,
+        origin_cells.len(),
+        Some(input.destinations.len()),
+        Some(input.options.max_distance_to_graph()),
+    )
+    .entered();
+
     let diff: Vec<_> = input
         .graph
         .differential_shortest_path(
@@ -292,12 +438,17 @@ pub fn calculate(input: DspInput) -> Result<DspOutput, Status> {
             "calculating differential_shortest_path failed".to_string()
         })?
         .into_iter()
+        .filter(|(_, diff)| !input.only_changed || diff.is_changed())
         .collect();
+    routing_finished(started.elapsed(), diff.len());
 
     Ok(DspOutput {
-        object_id: uuid::Uuid::new_v4().to_string(),
+        object_id: input.object_id,
         ref_dataframe: input.ref_dataframe,
         ref_dataframe_cells: input.ref_dataframe_cells,
+        value_column_name: input.value_column_name,
+        ref_dataset_join: input.ref_dataset_join,
+        route_simplification,
         differential_shortest_paths: diff,
     })
 }
@@ -336,6 +487,21 @@ fn disturbance_statistics_internal(output: &DspOutput) -> Result<DataFrame, Stat
         paths.first().map(|p| u64::from(p.destination_cell))
     };
 
+    let cell_values = output
+        .value_column_name
+        .as_ref()
+        .map(|value_column_name| output.ref_dataframe.cell_values(value_column_name))
+        .transpose()
+        .to_status_result()?;
+    let value_reached = |paths: &[Path<StandardWeight>]| -> Option<f64> {
+        cell_values.as_ref().map(|cell_values| {
+            paths
+                .iter()
+                .filter_map(|p| cell_values.get(&p.destination_cell))
+                .sum::<f64>()
+        })
+    };
+
     let mut cell_h3indexes = Vec::with_capacity(output.differential_shortest_paths.len());
     let mut num_reached_without_disturbance =
         Vec::with_capacity(output.differential_shortest_paths.len());
@@ -353,6 +519,10 @@ fn disturbance_statistics_internal(output: &DspOutput) -> Result<DataFrame, Stat
         Vec::with_capacity(output.differential_shortest_paths.len());
     let mut preferred_destination_with_disturbance =
         Vec::with_capacity(output.differential_shortest_paths.len());
+    let mut value_reached_without_disturbance =
+        Vec::with_capacity(output.differential_shortest_paths.len());
+    let mut value_reached_with_disturbance =
+        Vec::with_capacity(output.differential_shortest_paths.len());
     for (origin_cell, diff) in &output.differential_shortest_paths {
         cell_h3indexes.push(u64::from(*origin_cell));
         //population_at_origin.push(output.population_at_origins.get(origin_cell).cloned());
@@ -371,9 +541,12 @@ fn disturbance_statistics_internal(output: &DspOutput) -> Result<DataFrame, Stat
             .push(preferred_destination(&diff.before_cell_exclusion));
         preferred_destination_with_disturbance
             .push(preferred_destination(&diff.after_cell_exclusion));
+
+        value_reached_without_disturbance.push(value_reached(&diff.before_cell_exclusion));
+        value_reached_with_disturbance.push(value_reached(&diff.after_cell_exclusion));
     }
 
-    let df = DataFrame::new(vec![
+    let mut columns = vec![
         Series::new("h3index_origin", &cell_h3indexes),
         Series::new(
             "preferred_dest_h3index_without_disturbance",
@@ -407,16 +580,45 @@ fn disturbance_statistics_internal(output: &DspOutput) -> Result<DataFrame, Stat
             "avg_edge_preference_with_disturbance",
             &avg_edge_preference_with_disturbance,
         ),
-    ])
-    .to_status_result()?;
-    let df = df
+    ];
+    if cell_values.is_some() {
+        columns.push(Series::new(
+            "value_reached_without_disturbance",
+            &value_reached_without_disturbance,
+        ));
+        columns.push(Series::new(
+            "value_reached_with_disturbance",
+            &value_reached_with_disturbance,
+        ));
+    }
+    let df = DataFrame::new(columns).to_status_result()?;
+    let join_type = match output.ref_dataset_join {
+        RefDatasetJoin::Inner => JoinType::Inner,
+        RefDatasetJoin::Left | RefDatasetJoin::LeftFillZero => JoinType::Left,
+    };
+    let mut df = df
         .join(
             &output.ref_dataframe.dataframe,
             ["h3index_origin"],
             [output.ref_dataframe.cell_column_name.as_str()],
-            JoinArgs::new(JoinType::Inner),
+            JoinArgs::new(join_type),
         )
         .to_status_result()?;
+
+    if output.ref_dataset_join == RefDatasetJoin::LeftFillZero {
+        for column_name in output.ref_dataframe.dataframe.get_column_names() {
+            if column_name == output.ref_dataframe.cell_column_name {
+                continue;
+            }
+            let column = df.column(column_name).to_status_result()?;
+            if column.dtype().is_numeric() {
+                let filled = column
+                    .fill_null(FillNullStrategy::Zero)
+                    .to_status_result()?;
+                df.with_column(filled).to_status_result()?;
+            }
+        }
+    }
     Ok(df)
 }
 
@@ -427,18 +629,305 @@ pub fn disturbance_statistics(output: &DspOutput) -> Result<DataFrame, Status> {
 pub fn build_routes_response(
     diff: &ExclusionDiff<Path<StandardWeight>>,
     smoothen_geometries: bool,
+    simplification: RouteSimplification,
 ) -> Result<DifferentialShortestPathRoutes, Status> {
     let response = DifferentialShortestPathRoutes {
         routes_without_disturbance: diff
             .before_cell_exclusion
             .iter()
-            .map(|path| RouteWkb::from_path(path, smoothen_geometries))
+            .map(|path| RouteWkb::from_path(path, smoothen_geometries, simplification))
             .collect::<Result<_, _>>()?,
         routes_with_disturbance: diff
             .after_cell_exclusion
             .iter()
-            .map(|path| RouteWkb::from_path(path, smoothen_geometries))
+            .map(|path| RouteWkb::from_path(path, smoothen_geometries, simplification))
             .collect::<Result<_, _>>()?,
     };
     Ok(response)
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use h3o::geom::ToGeo;
+    use h3o::{CellIndex, LatLng, Resolution};
+    use hexigraph::container::treemap::H3Treemap;
+    use hexigraph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use polars::prelude::{DataFrame, IpcWriter, NamedFrom, SerWriter, Series, TakeRandom};
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::grpc::api::generated::{
+        DifferentialShortestPathRequest, GraphHandle, Point, RefDatasetJoin as RefDatasetJoinProto,
+    };
+    use crate::grpc::geometry::to_wkb;
+    use crate::grpc::tests::{test_server_config, write_grid_graph};
+    use crate::grpc::ServerImpl;
+    use crate::io::dataframe::{CellDataFrame, DataframeDataset};
+    use crate::io::tests::TempDir;
+    use crate::io::GraphKey;
+    use crate::weight::StandardWeight;
+
+    use super::{
+        calculate, collect_input, deterministic_object_id, disturbance_statistics, DspInput,
+    };
+
+    /// a grid graph around `center` with `center` as the only destination. All neighbors
+    /// of `center` except one are disturbed.
+    pub(crate) fn grid_input(center: CellIndex, only_changed: bool) -> DspInput {
+        let mut graph = H3EdgeGraph::new(center.resolution());
+        for cell in center.grid_disk::<Vec<_>>(4) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+            }
+        }
+        let graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let mut neighbors: Vec<_> = center
+            .grid_disk::<Vec<_>>(1)
+            .into_iter()
+            .filter(|cell| *cell != center)
+            .collect();
+        neighbors.sort_unstable();
+        let disturbance = H3Treemap::from_iter(neighbors.into_iter().skip(1));
+
+        let within_buffer: Vec<_> = center.grid_disk(3);
+        let ref_dataframe = CellDataFrame {
+            dataframe: DataFrame::new(vec![
+                Series::new(
+                    "h3index",
+                    within_buffer
+                        .iter()
+                        .map(|cell| u64::from(*cell))
+                        .collect::<Vec<_>>(),
+                ),
+                Series::new("population", vec![3u32; within_buffer.len()]),
+            ])
+            .unwrap(),
+            cell_column_name: "h3index".to_string(),
+        };
+
+        DspInput {
+            object_id: uuid::Uuid::new_v4().to_string(),
+            deterministic_object_id: false,
+            disturbance,
+            ref_dataframe_cells: within_buffer.iter().copied().collect(),
+            within_buffer,
+            destinations: vec![center],
+            store_output: false,
+            only_changed,
+            options: Default::default(),
+            graph: Arc::new(graph),
+            graph_key: GraphKey {
+                name: "test".to_string(),
+                h3_resolution: center.resolution(),
+            },
+            downsampled_graph: None,
+            ref_dataframe,
+            value_column_name: None,
+            ref_dataset_join: Default::default(),
+        }
+    }
+
+    #[test]
+    fn only_changed() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+
+        let all = calculate(grid_input(center, false)).unwrap();
+        // all cells of the buffer except the five disturbed ones
+        assert_eq!(all.differential_shortest_paths.len(), 37 - 5);
+        let num_changed = all
+            .differential_shortest_paths
+            .iter()
+            .filter(|(_, diff)| diff.is_changed())
+            .count();
+        assert!(num_changed > 0);
+        assert!(num_changed < all.differential_shortest_paths.len());
+
+        let changed = calculate(grid_input(center, true)).unwrap();
+        assert_eq!(changed.differential_shortest_paths.len(), num_changed);
+        assert!(changed
+            .differential_shortest_paths
+            .iter()
+            .all(|(_, diff)| diff.is_changed()));
+    }
+
+    #[test]
+    fn value_reached() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let mut input = grid_input(center, false);
+        input.value_column_name = Some("population".to_string());
+        let output = calculate(input).unwrap();
+
+        let df = disturbance_statistics(&output).unwrap();
+        assert_eq!(df.height(), output.differential_shortest_paths.len());
+        let origins = df.column("h3index_origin").unwrap().u64().unwrap();
+        let without = df
+            .column("value_reached_without_disturbance")
+            .unwrap()
+            .f64()
+            .unwrap();
+        let with = df
+            .column("value_reached_with_disturbance")
+            .unwrap()
+            .f64()
+            .unwrap();
+
+        for (origin, diff) in output.differential_shortest_paths.iter() {
+            let idx = origins
+                .into_iter()
+                .position(|h3index| h3index == Some(u64::from(*origin)))
+                .unwrap();
+            // the only destination has a value of 3
+            assert_eq!(
+                without.get(idx),
+                Some(3.0 * diff.before_cell_exclusion.len() as f64)
+            );
+            assert_eq!(
+                with.get(idx),
+                Some(3.0 * diff.after_cell_exclusion.len() as f64)
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ref_dataset_join() {
+        let root = TempDir::new();
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        write_grid_graph(&root, &graph_key, center);
+
+        // the reference dataset only covers the inner part of the buffer, which spans
+        // the cells up to two rings around the disturbance
+        let ref_cells: Vec<_> = center.grid_disk(1);
+        let mut files: HashMap<CellIndex, Vec<u64>> = HashMap::new();
+        for cell in ref_cells.iter() {
+            files
+                .entry(cell.parent(Resolution::Five).unwrap())
+                .or_default()
+                .push(u64::from(*cell));
+        }
+        std::fs::create_dir_all(root.join("population/5")).unwrap();
+        for (file_cell, h3indexes) in files {
+            let mut df = DataFrame::new(vec![
+                Series::new("population", vec![3u32; h3indexes.len()]),
+                Series::new("h3index", h3indexes),
+            ])
+            .unwrap();
+            IpcWriter::new(
+                File::create(root.join(format!("population/5/{file_cell}.arrow"))).unwrap(),
+            )
+            .finish(&mut df)
+            .unwrap();
+        }
+
+        let mut server_config = test_server_config(&root);
+        server_config.datasets.insert(
+            "population".to_string(),
+            DataframeDataset {
+                key_pattern: "population/{ file_h3_resolution }/{ h3cell }.arrow".to_string(),
+                resolutions: HashMap::from([(Resolution::Nine, Resolution::Five)]),
+                h3index_column_name: Some("h3index".to_string()),
+                cell_column_candidates: vec![],
+                value_column_name: None,
+                overlap_strategy: Default::default(),
+            },
+        );
+        let server_impl = ServerImpl::create(server_config).await.unwrap();
+
+        let disturbance = center.to_geom(true).unwrap();
+        let destination = center.grid_ring_fast(3).flatten().next().unwrap();
+        let request = |ref_dataset_join: RefDatasetJoinProto| DifferentialShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: graph_key.name.clone(),
+                h3_resolution: u8::from(graph_key.h3_resolution) as u32,
+            }),
+            disturbance_wkb_geometry: to_wkb(&disturbance.clone().into()).unwrap(),
+            radius_meters: 800.0,
+            ref_dataset_name: "population".to_string(),
+            destinations: vec![Point {
+                x: LatLng::from(destination).lng(),
+                y: LatLng::from(destination).lat(),
+            }],
+            ref_dataset_join: ref_dataset_join as i32,
+            ..Default::default()
+        };
+        let statistics = |ref_dataset_join| {
+            let request = request(ref_dataset_join);
+            let server_impl = &server_impl;
+            async move {
+                let input = collect_input(request, server_impl).await.unwrap();
+                let output = calculate(input).unwrap();
+                disturbance_statistics(&output).unwrap()
+            }
+        };
+
+        let inner = statistics(RefDatasetJoinProto::Inner).await;
+        assert!(inner.height() > 0);
+        assert_eq!(inner.column("population").unwrap().null_count(), 0);
+
+        // the origins outside of the reference dataset are kept by the left joins
+        let left = statistics(RefDatasetJoinProto::Left).await;
+        assert!(left.height() > inner.height());
+        assert_eq!(
+            left.column("population").unwrap().null_count(),
+            left.height() - inner.height()
+        );
+
+        let left_filled = statistics(RefDatasetJoinProto::LeftFillZero).await;
+        assert_eq!(left_filled.height(), left.height());
+        assert_eq!(left_filled.column("population").unwrap().null_count(), 0);
+    }
+
+    #[test]
+    fn deterministic_object_ids() {
+        let request = |radius_meters| DifferentialShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: "test".to_string(),
+                h3_resolution: 9,
+            }),
+            disturbance_wkb_geometry: vec![1, 2, 3],
+            radius_meters,
+            ref_dataset_name: "population".to_string(),
+            deterministic_object_id: true,
+            ..Default::default()
+        };
+
+        let graph_key = GraphKey {
+            name: "test".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let object_id = |request: &DifferentialShortestPathRequest| {
+            deterministic_object_id(request, &graph_key, "v1")
+        };
+
+        let id = object_id(&request(1000.0));
+        assert_eq!(id, object_id(&request(1000.0)));
+        assert_ne!(id, object_id(&request(2000.0)));
+
+        // storing the output does not change the content of the output
+        let mut stored = request(1000.0);
+        stored.store_output = true;
+        assert_eq!(id, object_id(&stored));
+
+        // the same request routed on another or a changed graph
+        let other_graph_key = GraphKey {
+            h3_resolution: Resolution::Ten,
+            ..graph_key.clone()
+        };
+        assert_ne!(
+            id,
+            deterministic_object_id(&request(1000.0), &other_graph_key, "v1")
+        );
+        assert_ne!(
+            id,
+            deterministic_object_id(&request(1000.0), &graph_key, "v2")
+        );
+    }
+}
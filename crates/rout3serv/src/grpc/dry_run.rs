@@ -0,0 +1,116 @@
+//! Validation of a server configuration against the objectstore without serving requests.
+use std::fmt;
+
+use crate::config::ServerConfig;
+use crate::io::{GraphKey, Storage};
+
+/// Result of a successful [`dry_run`].
+pub struct DryRunReport {
+    pub graphs: Vec<GraphKey>,
+
+    /// the configured datasets with the number of columns of their schema. `None`
+    /// when no files of the dataset exist.
+    pub datasets: Vec<(String, Option<usize>)>,
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "graphs:")?;
+        for graph_key in self.graphs.iter() {
+            writeln!(f, "  {}", graph_key.to_string())?;
+        }
+        writeln!(f, "datasets:")?;
+        for (name, num_columns) in self.datasets.iter() {
+            match num_columns {
+                Some(num_columns) => writeln!(f, "  {name}: {num_columns} columns")?,
+                None => writeln!(f, "  {name}: no files found")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// check that the objectstore of the `config` is reachable, contains graphs including the
+/// ones to preload, and that the schemas of the present datasets can be read.
+///
+/// Datasets without any files are only reported, as these may be populated later on.
+pub async fn dry_run(config: &ServerConfig) -> anyhow::Result<DryRunReport> {
+    config.validate()?;
+    let storage = Storage::from_config(config)?;
+
+    let mut graphs = storage.list_graphs().await?;
+    if graphs.is_empty() {
+        return Err(anyhow::Error::msg("no graphs found"));
+    }
+    graphs.sort_unstable_by_key(|graph_key| graph_key.to_string());
+    for graph_key in config.graphs.preload_graph_keys()? {
+        if !graphs.contains(&graph_key) {
+            return Err(anyhow::Error::msg(format!(
+                "graph {} to preload not found",
+                graph_key.to_string()
+            )));
+        }
+    }
+
+    let mut datasets = Vec::with_capacity(config.datasets.len());
+    for (name, is_present) in storage.list_datasets(&config.datasets).await {
+        // an unknown presence is resolved by reading the schema, so failures get reported
+        let num_columns = if is_present != Some(false) {
+            let schema = storage
+                .dataset_schema(&name, &config.datasets[&name])
+                .await
+                .map_err(|e| {
+                    anyhow::Error::msg(format!("reading the schema of dataset {name} failed: {e}"))
+                })?;
+            schema.map(|schema| schema.len())
+        } else {
+            None
+        };
+        datasets.push((name, num_columns));
+    }
+    Ok(DryRunReport { graphs, datasets })
+}
+
+#[cfg(test)]
+mod tests {
+    use h3o::{LatLng, Resolution};
+
+    use crate::config::ServerConfig;
+    use crate::grpc::tests::{test_server_config, write_grid_graph};
+    use crate::io::tests::TempDir;
+    use crate::io::GraphKey;
+
+    use super::dry_run;
+
+    fn config(root: &std::path::Path) -> ServerConfig {
+        let mut config = test_server_config(root);
+        config.graphs.preload = vec!["testgraph_9.ipc".to_string()];
+        config
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dry_run_missing_objectstore_root() {
+        let root = TempDir::new();
+        assert!(dry_run(&config(&root)).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dry_run_valid_config() {
+        let root = TempDir::new();
+
+        // the directory exists, but the graph to preload is missing
+        std::fs::create_dir_all(&root).unwrap();
+        assert!(dry_run(&config(&root)).await.is_err());
+
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let center = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        write_grid_graph(&root, &graph_key, center);
+
+        let report = dry_run(&config(&root)).await.unwrap();
+        assert_eq!(report.graphs, vec![graph_key]);
+        assert!(report.datasets.is_empty());
+    }
+}
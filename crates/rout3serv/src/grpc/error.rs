@@ -17,6 +17,12 @@ impl StatusCodeAndMessage for crate::io::Error {
     fn status_code_and_message(&self) -> (Code, String) {
         if self.is_not_found() {
             (Code::NotFound, "not found".to_string())
+        } else if let Self::AlreadyExists(_) = self {
+            (Code::AlreadyExists, self.to_string())
+        } else if let Self::FetchTimeout { .. } = self {
+            (Code::DeadlineExceeded, self.to_string())
+        } else if let Self::GraphLimitExceeded { .. } = self {
+            (Code::ResourceExhausted, self.to_string())
         } else {
             (Code::Internal, format!("IO error: {self:?}"))
         }
@@ -34,7 +40,13 @@ where
 
 impl StatusCodeAndMessage for hexigraph::error::Error {
     fn status_code_and_message(&self) -> (Code, String) {
-        (Code::Internal, format!("{self:?}"))
+        match self {
+            Self::EmptyOriginSet | Self::EmptyDestinationSet | Self::DestinationsNotInGraph => {
+                (Code::InvalidArgument, self.to_string())
+            }
+            Self::EdgelessGraph => (Code::FailedPrecondition, self.to_string()),
+            _ => (Code::Internal, format!("{self:?}")),
+        }
     }
 }
 
@@ -70,6 +82,8 @@ macro_rules! error_status_code_impl {
 error_status_code_impl!(tokio::task::JoinError);
 //error_status_code_impl!(anyhow::Error);
 error_status_code_impl!(polars_core::error::PolarsError);
+#[cfg(feature = "arrow-flight")]
+error_status_code_impl!(polars::export::arrow::error::Error);
 
 macro_rules! logged_status {
     ($msg:expr, $code: expr, $lvl:expr, $caused_by:expr) => {{
@@ -0,0 +1,406 @@
+//! Minimal [Arrow Flight](https://arrow.apache.org/docs/format/Flight.html) service
+//! for analytics clients.
+//!
+//! Serves the statistics of stored differential shortest path outputs. The object id
+//! is used as the ticket and as the single path element of a `FlightDescriptor`.
+//!
+//! Requires the `arrow-flight` feature. The protocol is vendored in `proto/Flight.proto`
+//! and the data gets encoded using the arrow2 implementation polars is built upon.
+use std::pin::Pin;
+use std::sync::Arc;
+
+use arrow_format::ipc::planus::ReadAsRoot;
+use arrow_format::ipc::MessageRef;
+use futures::{stream, Stream, StreamExt};
+use object_store::path::Path;
+use polars::export::arrow::io::ipc::write::{StreamWriter, WriteOptions};
+use polars::prelude::DataFrame;
+use tonic::{Code, Request, Response, Status, Streaming};
+use tracing::Level;
+
+use crate::grpc::differential_shortest_path::{disturbance_statistics, DspOutput};
+use crate::grpc::error::{logged_status, ToStatusResult};
+use crate::grpc::util::spawn_blocking_status;
+use crate::io::Storage;
+
+use self::protocol::flight_service_server::FlightService;
+use self::protocol::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PutResult, SchemaResult, Ticket,
+};
+
+pub mod protocol {
+    #![allow(clippy::derive_partial_eq_without_eq)] // for the generated code. https://github.com/tokio-rs/prost/issues/661
+    #![allow(dead_code)] // not all messages of the protocol are used by this service
+
+    tonic::include_proto!("arrow.flight.protocol"); // autogenerated by tonic build (see build.rs)
+}
+
+type FlightStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + 'static>>;
+
+/// marks the start of every message of an Arrow IPC stream
+const CONTINUATION_MARKER: [u8; 4] = [0xff; 4];
+
+pub(crate) struct FlightServiceImpl {
+    storage: Arc<Storage>,
+    outputs_prefix: String,
+}
+
+impl FlightServiceImpl {
+    pub fn new(storage: Arc<Storage>, outputs_prefix: String) -> Self {
+        Self {
+            storage,
+            outputs_prefix,
+        }
+    }
+
+    /// load the statistics of a stored output as arrow IPC messages
+    async fn load_ipc_messages(&self, object_id: &str) -> Result<IpcMessages, Status> {
+        let path: Path = format!("{}{}", self.outputs_prefix, object_id).into();
+        let output: DspOutput = self.storage.retrieve(&path).await.to_status_result()?;
+
+        spawn_blocking_status(move || {
+            let mut dataframe = disturbance_statistics(&output)?;
+            IpcMessages::from_dataframe(&mut dataframe)
+        })
+        .await?
+    }
+}
+
+/// a dataframe encoded as the messages of an Arrow IPC stream
+struct IpcMessages {
+    /// the schema message in its IPC form, including the continuation marker
+    /// and the length prefix.
+    schema: Vec<u8>,
+
+    /// the schema message followed by one message per record batch
+    flight_data: Vec<FlightData>,
+
+    num_rows: usize,
+}
+
+impl IpcMessages {
+    fn from_dataframe(dataframe: &mut DataFrame) -> Result<Self, Status> {
+        dataframe.align_chunks();
+        let mut writer = StreamWriter::new(Vec::new(), WriteOptions { compression: None });
+        writer
+            .start(&dataframe.schema().to_arrow(), None)
+            .to_status_result()?;
+        for chunk in dataframe.iter_chunks() {
+            writer.write(&chunk, None).to_status_result()?;
+        }
+        writer.finish().to_status_result()?;
+        let ipc_stream = writer.into_inner();
+
+        let messages = split_ipc_stream(&ipc_stream)?;
+        let schema_len = messages.first().map_or(0, |(header, _)| header.len() + 8);
+        Ok(Self {
+            schema: ipc_stream[..schema_len].to_vec(),
+            flight_data: messages
+                .into_iter()
+                .map(|(header, body)| FlightData {
+                    data_header: header.to_vec(),
+                    data_body: body.to_vec(),
+                    ..Default::default()
+                })
+                .collect(),
+            num_rows: dataframe.height(),
+        })
+    }
+}
+
+/// the flatbuffer header and the body of an Arrow IPC message
+type IpcMessage<'a> = (&'a [u8], &'a [u8]);
+
+/// split an Arrow IPC stream into its messages.
+fn split_ipc_stream(mut ipc_stream: &[u8]) -> Result<Vec<IpcMessage<'_>>, Status> {
+    let malformed = || {
+        logged_status!(
+            "encoding the arrow IPC stream failed",
+            Code::Internal,
+            Level::ERROR
+        )
+    };
+
+    let mut messages = vec![];
+    loop {
+        let (prefix, remaining) = split_at_checked(ipc_stream, 8).ok_or_else(malformed)?;
+        if prefix[..4] != CONTINUATION_MARKER {
+            return Err(malformed());
+        }
+        let header_len = u32::from_le_bytes([prefix[4], prefix[5], prefix[6], prefix[7]]) as usize;
+        if header_len == 0 {
+            // end of stream
+            break;
+        }
+        let (header, remaining) = split_at_checked(remaining, header_len).ok_or_else(malformed)?;
+        let body_len = MessageRef::read_as_root(header)
+            .and_then(|message| message.body_length())
+            .map_err(|_| malformed())?;
+        let (body, remaining) =
+            split_at_checked(remaining, body_len as usize).ok_or_else(malformed)?;
+        messages.push((header, body));
+        ipc_stream = remaining;
+    }
+    Ok(messages)
+}
+
+fn split_at_checked(bytes: &[u8], mid: usize) -> Option<(&[u8], &[u8])> {
+    (mid <= bytes.len()).then(|| bytes.split_at(mid))
+}
+
+fn object_id_from_descriptor(descriptor: &FlightDescriptor) -> Result<&str, Status> {
+    match descriptor.path.as_slice() {
+        [object_id] => Ok(object_id.as_str()),
+        _ => Err(logged_status!(
+            "expected a descriptor with the object id as single path element",
+            Code::InvalidArgument,
+            Level::DEBUG
+        )),
+    }
+}
+
+fn object_id_from_ticket(ticket: &Ticket) -> Result<&str, Status> {
+    std::str::from_utf8(&ticket.ticket).map_err(|_| {
+        logged_status!(
+            "ticket is not a valid object id",
+            Code::InvalidArgument,
+            Level::DEBUG
+        )
+    })
+}
+
+fn unimplemented<T>(method: &str) -> Result<T, Status> {
+    Err(Status::unimplemented(format!("{method} is not supported")))
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightServiceImpl {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        unimplemented("handshake")
+    }
+
+    type ListFlightsStream = FlightStream<FlightInfo>;
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        unimplemented("list_flights")
+    }
+
+    async fn get_flight_info(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        crate::metrics::rpc_request("flight_get_flight_info");
+        let descriptor = request.into_inner();
+        let object_id = object_id_from_descriptor(&descriptor)?.to_string();
+        let ipc_messages = self.load_ipc_messages(&object_id).await?;
+
+        let flight_info = FlightInfo {
+            schema: ipc_messages.schema,
+            flight_descriptor: Some(descriptor),
+            endpoint: vec![FlightEndpoint {
+                ticket: Some(Ticket {
+                    ticket: object_id.into_bytes(),
+                }),
+                location: vec![],
+            }],
+            total_records: ipc_messages.num_rows as i64,
+            total_bytes: -1,
+        };
+        Ok(Response::new(flight_info))
+    }
+
+    async fn get_schema(
+        &self,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        crate::metrics::rpc_request("flight_get_schema");
+        let object_id = object_id_from_descriptor(request.get_ref())?;
+        let ipc_messages = self.load_ipc_messages(object_id).await?;
+        Ok(Response::new(SchemaResult {
+            schema: ipc_messages.schema,
+        }))
+    }
+
+    type DoGetStream = FlightStream<FlightData>;
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        crate::metrics::rpc_request("flight_do_get");
+        let object_id = object_id_from_ticket(request.get_ref())?;
+        let ipc_messages = self.load_ipc_messages(object_id).await?;
+        Ok(Response::new(
+            stream::iter(ipc_messages.flight_data.into_iter().map(Ok)).boxed(),
+        ))
+    }
+
+    type DoPutStream = FlightStream<PutResult>;
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        unimplemented("do_put")
+    }
+
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        unimplemented("do_exchange")
+    }
+
+    type DoActionStream = FlightStream<protocol::Result>;
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        unimplemented("do_action")
+    }
+
+    type ListActionsStream = FlightStream<ActionType>;
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use std::io::Cursor;
+
+    use crate::config::GraphsConfig;
+    use crate::grpc::differential_shortest_path::tests::grid_input;
+    use crate::grpc::differential_shortest_path::{calculate, disturbance_statistics};
+    use crate::io::objectstore::{ObjectStore, ObjectStoreConfig};
+    use crate::io::storage::OverwritePolicy;
+    use crate::io::tests::TempDir;
+    use crate::io::Storage;
+    use futures::TryStreamExt;
+    use h3o::{LatLng, Resolution};
+    use object_store::path::Path;
+    use polars::export::arrow::io::ipc::read::{read_stream_metadata, StreamReader, StreamState};
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::Server;
+
+    use super::protocol::flight_service_client::FlightServiceClient;
+    use super::protocol::flight_service_server::FlightServiceServer;
+    use super::protocol::{FlightData, FlightDescriptor, Ticket};
+    use super::{FlightServiceImpl, CONTINUATION_MARKER};
+
+    /// reassemble the arrow IPC stream from the messages received via `do_get`
+    fn ipc_stream(flight_data: &[FlightData]) -> Vec<u8> {
+        let mut ipc_stream = vec![];
+        for message in flight_data {
+            ipc_stream.extend_from_slice(&CONTINUATION_MARKER);
+            ipc_stream.extend_from_slice(&(message.data_header.len() as u32).to_le_bytes());
+            ipc_stream.extend_from_slice(&message.data_header);
+            ipc_stream.extend_from_slice(&message.data_body);
+        }
+        ipc_stream.extend_from_slice(&CONTINUATION_MARKER);
+        ipc_stream.extend_from_slice(&0u32.to_le_bytes());
+        ipc_stream
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retrieve_stored_output() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+        let storage = Storage::new(
+            ObjectStore::try_from(ObjectStoreConfig::Filesystem {
+                root: root.to_string_lossy().to_string(),
+            })
+            .unwrap(),
+            &GraphsConfig {
+                prefix: "graphs".to_string(),
+                cache_size: None,
+                preload: vec![],
+                max_graph_bytes: None,
+                max_graph_nodes: None,
+            },
+        );
+
+        let center = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let output = calculate(grid_input(center, false)).unwrap();
+        let expected_num_rows = disturbance_statistics(&output).unwrap().height();
+        storage
+            .store(
+                &Path::from(format!("outputs/{}", output.object_id)),
+                &output,
+                OverwritePolicy::Overwrite,
+            )
+            .await
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(FlightServiceServer::new(FlightServiceImpl::new(
+                    Arc::new(storage),
+                    "outputs/".to_string(),
+                )))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = FlightServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+
+        let schema_result = client
+            .get_schema(FlightDescriptor {
+                path: vec![output.object_id.clone()],
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let metadata = read_stream_metadata(&mut Cursor::new(schema_result.schema)).unwrap();
+        assert!(metadata
+            .schema
+            .fields
+            .iter()
+            .any(|field| field.name == "population"));
+
+        let flight_data: Vec<_> = client
+            .do_get(Ticket {
+                ticket: output.object_id.clone().into_bytes(),
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .try_collect()
+            .await
+            .unwrap();
+        let mut reader = Cursor::new(ipc_stream(&flight_data));
+        let metadata = read_stream_metadata(&mut reader).unwrap();
+        let mut num_rows = 0;
+        for state in StreamReader::new(reader, metadata, None) {
+            match state.unwrap() {
+                StreamState::Some(chunk) => num_rows += chunk.len(),
+                StreamState::Waiting => unreachable!(),
+            }
+        }
+        assert!(num_rows > 0);
+        assert_eq!(num_rows, expected_num_rows);
+    }
+}
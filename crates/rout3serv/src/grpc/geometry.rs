@@ -1,14 +1,16 @@
 //! vector geometry handling
 //!
 use geo::algorithm::centroid::Centroid;
-use geo_types::Geometry;
-use h3o::geom::{PolyfillConfig, ToCells};
+use geo::GeodesicArea;
+use geo_types::{Geometry, MultiPolygon, Polygon};
+use h3o::geom::{ContainmentMode, PolyfillConfig, ToCells};
 use h3o::{CellIndex, LatLng, Resolution};
 use tonic::{Code, Status};
 use tracing::Level;
 use uom::si::f64::Length;
 use uom::si::length::meter;
 
+use crate::grpc::api::generated;
 use crate::grpc::error::{logged_status, ToStatusResult};
 
 /// read binary WKB into a gdal `Geometry`
@@ -17,23 +19,113 @@ pub fn from_wkb(wkb_bytes: &[u8]) -> Result<Geometry, Status> {
         .map_err(|e| logged_status!("Can not parse WKB", Code::InvalidArgument, Level::WARN, &e))
 }
 
-/// convert a [`Geometry`] to a vec of [`CellIndex`].
+/// the polygons of a polygonal `geom`.
+///
+/// Geometry collections are flattened. All non-polygonal geometry types are rejected.
+fn polygons(geom: Geometry) -> Result<Vec<Polygon>, Status> {
+    match geom {
+        Geometry::Polygon(polygon) => Ok(vec![polygon]),
+        Geometry::MultiPolygon(multi_polygon) => Ok(multi_polygon.0),
+        Geometry::Rect(rect) => Ok(vec![rect.to_polygon()]),
+        Geometry::Triangle(triangle) => Ok(vec![triangle.to_polygon()]),
+        Geometry::GeometryCollection(collection) => {
+            let mut polygons_out = vec![];
+            for geom in collection {
+                polygons_out.extend(polygons(geom)?);
+            }
+            Ok(polygons_out)
+        }
+        _ => Err(logged_status!(
+            "unsupported geometry type, only polygonal geometries are supported",
+            Code::InvalidArgument,
+            Level::DEBUG
+        )),
+    }
+}
+
+/// which cells are selected when converting a geometry to cells
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Containment {
+    /// cells with their centroid within the geometry
+    #[default]
+    Centroid,
+
+    /// all cells overlapping the geometry, including the ones only touching its boundary
+    Covers,
+
+    /// only cells completely within the geometry
+    Full,
+}
+
+impl From<Containment> for ContainmentMode {
+    fn from(containment: Containment) -> Self {
+        match containment {
+            Containment::Centroid => Self::ContainsCentroid,
+            Containment::Covers => Self::IntersectsBoundary,
+            Containment::Full => Self::ContainsBoundary,
+        }
+    }
+}
+
+impl From<generated::GeometryContainment> for Containment {
+    fn from(containment: generated::GeometryContainment) -> Self {
+        match containment {
+            generated::GeometryContainment::Centroid => Self::Centroid,
+            generated::GeometryContainment::Covers => Self::Covers,
+            generated::GeometryContainment::Full => Self::Full,
+        }
+    }
+}
+
+/// convert a polygonal [`Geometry`] to a vec of [`CellIndex`].
+///
+/// Each polygon of a `MultiPolygon` is polyfilled on its own, so the result is the
+/// union of the cells of all polygons. The cells included at the boundary of the
+/// geometry are selected according to `containment`.
+///
+/// When `max_cells` is set, geometries which would result in more than `max_cells`
+/// cells are rejected before polyfilling. The number of cells is estimated using the
+/// area of the geometry.
 pub fn geom_to_h3(
     geom: Geometry,
     h3_resolution: Resolution,
+    containment: Containment,
     include_centroid: bool,
+    max_cells: Option<usize>,
 ) -> Result<Vec<CellIndex>, Status> {
-    let mut cells = h3o::geom::Geometry::from_degrees(geom.clone())
-        .to_status_result()?
-        .to_cells(PolyfillConfig::new(h3_resolution))
-        .collect::<Vec<_>>();
-
-    if include_centroid {
-        // add centroid in case of small geometries
-        if let Some(ll) = geom.centroid().and_then(|pt| LatLng::try_from(pt.0).ok()) {
-            cells.push(ll.to_cell(h3_resolution));
-            // TODO: port intersecting cells
+    if let Some(max_cells) = max_cells {
+        let estimated_num_cells = geom.geodesic_area_unsigned() / h3_resolution.area_m2();
+        if estimated_num_cells > max_cells as f64 {
+            return Err(logged_status!(
+                format!(
+                    "geometry too large: would result in ~{} cells at resolution {}, the limit is {}",
+                    estimated_num_cells.round(),
+                    h3_resolution,
+                    max_cells
+                ),
+                Code::InvalidArgument,
+                Level::DEBUG
+            ));
+        }
+    }
+
+    let mut cells = vec![];
+    for polygon in polygons(geom)? {
+        if include_centroid {
+            // add centroid in case of small geometries
+            if let Some(ll) = polygon
+                .centroid()
+                .and_then(|pt| LatLng::try_from(pt.0).ok())
+            {
+                cells.push(ll.to_cell(h3_resolution));
+                // TODO: port intersecting cells
+            }
         }
+        cells.extend(
+            h3o::geom::Polygon::from_degrees(polygon)
+                .to_status_result()?
+                .to_cells(PolyfillConfig::new(h3_resolution).containment_mode(containment.into())),
+        );
     }
 
     // remove duplicates in case of multi* geometries
@@ -42,19 +134,31 @@ pub fn geom_to_h3(
     Ok(cells)
 }
 
-/// buffer a geometry in meters
+/// buffer a polygonal geometry in meters
+///
+/// Each polygon is buffered on its own, the result is a `MultiPolygon` of all
+/// buffered polygons. These may overlap.
 ///
 /// This function creates some distortion as the geometry is transformed
 /// between WGS84 and Spherical Mercator
 pub fn buffer_meters(geom: &Geometry, meters: f64) -> Result<Geometry, Status> {
-    crate::geo::buffer(geom, Length::new::<meter>(meters)).map_err(|e| {
-        logged_status!(
-            "geometry buffering failed",
-            Code::Internal,
-            Level::ERROR,
-            &e
-        )
-    })
+    let mut buffered_polygons = vec![];
+    for polygon in polygons(geom.clone())? {
+        let buffered =
+            crate::geo::buffer(&Geometry::Polygon(polygon), Length::new::<meter>(meters)).map_err(
+                |e| {
+                    logged_status!(
+                        "geometry buffering failed",
+                        Code::Internal,
+                        Level::ERROR,
+                        &e
+                    )
+                },
+            )?;
+        // other geometry types are only returned for empty results
+        buffered_polygons.extend(polygons(buffered).unwrap_or_default());
+    }
+    Ok(MultiPolygon::new(buffered_polygons).into())
 }
 
 /// convert a geotypes `Geometry` to WKB using GDAL
@@ -68,3 +172,140 @@ pub fn to_wkb(geom: &Geometry) -> Result<Vec<u8>, Status> {
         )
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use geo_types::{Geometry, MultiPolygon, Point, Rect};
+    use h3o::Resolution;
+    use tonic::Code;
+
+    use super::{buffer_meters, geom_to_h3, Containment};
+
+    #[test]
+    fn geom_to_h3_max_cells() {
+        let small: Geometry = Rect::new((9.2, 48.8), (9.21, 48.81)).into();
+        let cells = geom_to_h3(
+            small,
+            Resolution::Twelve,
+            Containment::Centroid,
+            false,
+            Some(1_000_000),
+        )
+        .unwrap();
+        assert!(!cells.is_empty());
+
+        let huge: Geometry = Rect::new((-10.0, 35.0), (30.0, 60.0)).into();
+        let status = geom_to_h3(
+            huge,
+            Resolution::Twelve,
+            Containment::Centroid,
+            false,
+            Some(1_000_000),
+        )
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn geom_to_h3_multipolygon() {
+        let square1 = Rect::new((9.2, 48.8), (9.21, 48.81));
+        let square2 = Rect::new((9.3, 48.8), (9.31, 48.81));
+        let multi_polygon: Geometry =
+            MultiPolygon::new(vec![square1.to_polygon(), square2.to_polygon()]).into();
+
+        let cells1 = geom_to_h3(
+            square1.into(),
+            Resolution::Ten,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        let cells2 = geom_to_h3(
+            square2.into(),
+            Resolution::Ten,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        assert!(!cells1.is_empty());
+        assert!(!cells2.is_empty());
+        let mut expected = [cells1.clone(), cells2.clone()].concat();
+        expected.sort_unstable();
+        expected.dedup();
+
+        let cells = geom_to_h3(
+            multi_polygon.clone(),
+            Resolution::Ten,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        assert_eq!(cells, expected);
+
+        // the buffer covers both squares
+        let buffered = buffer_meters(&multi_polygon, 100.0).unwrap();
+        assert!(matches!(&buffered, Geometry::MultiPolygon(mp) if mp.0.len() == 2));
+        let buffered_cells = geom_to_h3(
+            buffered,
+            Resolution::Ten,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        assert!(cells1
+            .iter()
+            .chain(cells2.iter())
+            .all(|cell| buffered_cells.binary_search(cell).is_ok()));
+    }
+
+    #[test]
+    fn geom_to_h3_unsupported_geometry() {
+        let point: Geometry = Point::new(9.2, 48.8).into();
+        let status = geom_to_h3(
+            point.clone(),
+            Resolution::Ten,
+            Containment::Centroid,
+            true,
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let status = buffer_meters(&point, 100.0).unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn geom_to_h3_containment() {
+        // a thin strip, narrower than the cells
+        let strip: Geometry = Rect::new((9.2, 48.8), (9.25, 48.8002)).into();
+
+        let centroid = geom_to_h3(
+            strip.clone(),
+            Resolution::Nine,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        let covers = geom_to_h3(
+            strip.clone(),
+            Resolution::Nine,
+            Containment::Covers,
+            false,
+            None,
+        )
+        .unwrap();
+        let full = geom_to_h3(strip, Resolution::Nine, Containment::Full, false, None).unwrap();
+
+        assert!(covers.len() > centroid.len());
+        assert!(centroid
+            .iter()
+            .all(|cell| covers.binary_search(cell).is_ok()));
+        assert!(full.is_empty());
+    }
+}
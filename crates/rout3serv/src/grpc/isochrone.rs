@@ -0,0 +1,208 @@
+use std::time::Instant;
+
+use geo_types::{Geometry, MultiPolygon};
+use hexigraph::algorithm::graph::covered_area::cells_covered_area;
+use hexigraph::algorithm::graph::WithinWeightThresholdMany;
+use hexigraph::HasH3Resolution;
+use tonic::{Code, Response, Status};
+use tracing::Level;
+use uom::si::f32::Time;
+use uom::si::time::second;
+
+use crate::customization::{CustomizedGraph, CustomizedWeight};
+use crate::grpc::api::generated::{H3IsochroneRequest, H3IsochroneResponse, IsochroneBand};
+use crate::grpc::error::{logged_status, ToStatusResult};
+use crate::grpc::geometry::to_wkb;
+use crate::grpc::util::{routing_finished, routing_span, spawn_blocking_status};
+use crate::grpc::within_threshold::travel_duration_bands;
+use crate::grpc::{LoadedCellSelection, ServerImpl};
+use crate::io::GraphKey;
+use crate::weight::Weight;
+
+pub struct H3IsochroneParameters {
+    pub graph: CustomizedGraph,
+    pub graph_key: GraphKey,
+    pub origins: LoadedCellSelection,
+
+    /// ascending travel durations, one isochrone is built per band
+    pub bands: Vec<Time>,
+
+    /// reduce the resolution of the reached cells by this before building the polygons
+    pub reduce_resolution_by: u8,
+}
+
+pub(crate) async fn create_parameters(
+    request: H3IsochroneRequest,
+    server_impl: &ServerImpl,
+) -> Result<H3IsochroneParameters, Status> {
+    let bands = travel_duration_bands(&request.travel_duration_secs_bands)?;
+    let routing_mode = server_impl.config.get_routing_mode(&request.routing_mode)?;
+    let (graph, graph_key) = server_impl
+        .retrieve_graph_by_handle(&request.graph_handle)
+        .await
+        .map(|(graph, graph_key)| {
+            let mut cg = CustomizedGraph::from(graph);
+            cg.set_routing_mode(routing_mode);
+            (cg, graph_key)
+        })?;
+
+    let origins = server_impl
+        .load_cell_selection(&request.origins, graph.h3_resolution(), "origins")
+        .await?;
+
+    Ok(H3IsochroneParameters {
+        graph,
+        graph_key,
+        origins,
+        bands,
+        reduce_resolution_by: u8::try_from(request.reduce_resolution_by).unwrap_or(u8::MAX),
+    })
+}
+
+pub async fn isochrone(
+    parameters: H3IsochroneParameters,
+) -> Result<Response<H3IsochroneResponse>, Status> {
+    let span = routing_span(
+        "isochrone",
+        &parameters.graph_key,
+        parameters.origins.cells.len(),
+        None,
+        None,
+    );
+    let response =
+        spawn_blocking_status(move || span.in_scope(|| isochrone_internal(parameters))).await??;
+    Ok(Response::new(response))
+}
+
+fn isochrone_internal(parameters: H3IsochroneParameters) -> Result<H3IsochroneResponse, Status> {
+    let started = Instant::now();
+    let bands: Vec<_> = parameters
+        .bands
+        .into_iter()
+        .map(CustomizedWeight::from_travel_duration)
+        .collect();
+
+    // a single traversal up to the largest threshold covers all bands
+    let threshold_weight = *bands
+        .last()
+        .ok_or_else(|| logged_status!("no threshold given", Code::InvalidArgument, Level::DEBUG))?;
+
+    // use the minimum weight encountered
+    let agg_fn = |existing: &mut CustomizedWeight, new: CustomizedWeight| {
+        if new < *existing {
+            *existing = new;
+        }
+    };
+
+    let cellmap = parameters
+        .graph
+        .cells_within_weight_threshold_many(&parameters.origins.cells, threshold_weight, agg_fn)
+        .to_status_result_with_message(Code::Internal, || {
+            "isolating cells within threshold failed".to_string()
+        })?;
+    routing_finished(started.elapsed(), cellmap.len());
+
+    let h3_resolution = parameters.graph.h3_resolution();
+    let mut isochrone_bands = Vec::with_capacity(bands.len());
+    for band in bands {
+        // the bands are cumulative, each one contains all cells of the smaller ones.
+        let cells: Vec<_> = cellmap
+            .iter()
+            .filter(|(_, weight)| **weight <= band)
+            .map(|(cell, _)| *cell)
+            .collect();
+        let area = if cells.is_empty() {
+            MultiPolygon::new(vec![])
+        } else {
+            cells_covered_area(cells.iter(), h3_resolution, parameters.reduce_resolution_by)
+                .to_status_result_with_message(Code::Internal, || {
+                    "building the isochrone polygons failed".to_string()
+                })?
+        };
+        isochrone_bands.push(IsochroneBand {
+            travel_duration_secs: band.travel_duration().get::<second>(),
+            wkb: to_wkb(&Geometry::MultiPolygon(area))?,
+            num_cells: cells.len() as u64,
+        });
+    }
+    Ok(H3IsochroneResponse {
+        bands: isochrone_bands,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use geo::{Area, BooleanOps};
+    use geo_types::Geometry;
+    use h3o::{LatLng, Resolution};
+    use hexigraph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::customization::CustomizedGraph;
+    use crate::geo::wkb::from_wkb;
+    use crate::grpc::LoadedCellSelection;
+    use crate::io::GraphKey;
+    use crate::weight::StandardWeight;
+
+    use super::{isochrone_internal, H3IsochroneParameters};
+
+    #[test]
+    fn nested_isochrones() {
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for cell in origin.grid_disk::<Vec<_>>(6) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let response = isochrone_internal(H3IsochroneParameters {
+            graph: CustomizedGraph::from(Arc::new(prepared_graph)),
+            graph_key: GraphKey {
+                name: "test".to_string(),
+                h3_resolution: Resolution::Nine,
+            },
+            origins: LoadedCellSelection {
+                cells: vec![origin],
+                dataframe: None,
+            },
+            bands: [15.0, 35.0, 55.0]
+                .into_iter()
+                .map(Time::new::<second>)
+                .collect(),
+            reduce_resolution_by: 0,
+        })
+        .unwrap();
+
+        assert_eq!(response.bands.len(), 3);
+        let areas: Vec<_> = response
+            .bands
+            .iter()
+            .map(|band| match from_wkb(&band.wkb).unwrap() {
+                Geometry::MultiPolygon(mp) => mp,
+                _ => panic!("expected a multipolygon"),
+            })
+            .collect();
+
+        // rings of 1, 3 and 5 cells around the origin
+        assert_eq!(
+            response
+                .bands
+                .iter()
+                .map(|band| band.num_cells)
+                .collect::<Vec<_>>(),
+            vec![7, 37, 91]
+        );
+        for pair in areas.windows(2) {
+            let (smaller, larger) = (&pair[0], &pair[1]);
+            assert!(smaller.unsigned_area() > 0.0);
+            assert!(larger.unsigned_area() > smaller.unsigned_area());
+            // the smaller isochrone is contained in the larger one
+            assert!(smaller.difference(larger).unsigned_area() < smaller.unsigned_area() * 1e-6);
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
+use std::net::SocketAddr;
 use std::ops::Add;
 use std::sync::Arc;
 
@@ -7,12 +9,16 @@ use num_traits::Zero;
 use object_store::path::Path;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 use tokio::task::block_in_place;
-use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
 use tonic::codec::CompressionEncoding;
 use tonic::transport::Server;
 use tonic::{Code, Request, Response, Status};
+use tonic_web::GrpcWebLayer;
+use tower::ServiceBuilder;
+use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing::{info, warn, Level};
 
@@ -23,23 +29,33 @@ use hexigraph::graph::PreparedH3EdgeGraph;
 use crate::config::ServerConfig;
 use crate::grpc::api::generated::rout3_serv_server::{Rout3Serv, Rout3ServServer};
 use crate::grpc::api::generated::{
-    CellSelection, DifferentialShortestPathRequest, DifferentialShortestPathRoutes,
-    DifferentialShortestPathRoutesRequest, Empty, GraphHandle, H3ShortestPathRequest,
-    H3WithinThresholdRequest, IdRef, ListDatasetsResponse, ListGraphsResponse, RouteH3Indexes,
-    RouteWkb, VersionResponse,
+    CellSelection, ClassifyCellsRequest, ClassifyCellsResponse, DatasetColumn, DatasetInfo,
+    DescribeDatasetRequest, DescribeDatasetResponse, DifferentialShortestPathRequest,
+    DifferentialShortestPathRoutes, DifferentialShortestPathRoutesRequest, Empty, GraphHandle,
+    H3IsochroneRequest, H3IsochroneResponse, H3ShortestPathRequest, H3WithinThresholdRequest,
+    HealthCheckResponse, IdRef, ListDatasetsResponse, ListGraphsResponse, RouteH3EdgeWeights,
+    RouteH3Indexes, RouteWkb, VersionResponse,
 };
 use crate::grpc::api::RouteH3IndexesKind;
 use crate::grpc::error::ToStatusResult;
 use crate::grpc::error::{logged_status, StatusCodeAndMessage};
-use crate::grpc::util::{spawn_blocking_status, stream_dataframe, ArrowIpcChunkStream};
+use crate::grpc::geometry::{from_wkb, geom_to_h3};
+use crate::grpc::util::{
+    spawn_blocking_status, spawn_send_all, stream_dataframe, ArrowIpcChunkStream,
+};
 use crate::io::dataframe::{CellDataFrame, DataframeDataset};
+use crate::io::storage::{OverwritePolicy, Readiness};
 use crate::io::{GraphKey, Storage};
 use crate::weight::{StandardWeight, Weight};
 
 mod api;
 mod differential_shortest_path;
+mod dry_run;
 mod error;
+#[cfg(feature = "arrow-flight")]
+mod flight;
 mod geometry;
+mod isochrone;
 mod names;
 mod shortest_path;
 mod util;
@@ -74,9 +90,24 @@ impl ServerImpl {
     pub async fn create(config: ServerConfig) -> anyhow::Result<Self> {
         let config = Arc::new(config);
         let storage = Arc::new(Storage::from_config(&config)?);
+
+        let preload_graph_keys = config.graphs.preload_graph_keys()?;
+        if !preload_graph_keys.is_empty() {
+            info!("preloading {} graphs", preload_graph_keys.len());
+            storage
+                .preload_graphs(&preload_graph_keys)
+                .await
+                .map_err(|e| anyhow::Error::msg(format!("preloading graphs failed: {e:?}")))?;
+        }
         Ok(Self { storage, config })
     }
 
+    /// Arrow Flight service sharing the storage of this server
+    #[cfg(feature = "arrow-flight")]
+    fn flight_service(&self) -> flight::FlightServiceImpl {
+        flight::FlightServiceImpl::new(self.storage.clone(), self.config.outputs.prefix.clone())
+    }
+
     fn build_output_key(&self, output_id: &str) -> Path {
         format!("{}{}", self.config.outputs.prefix, output_id).into()
     }
@@ -85,7 +116,7 @@ impl ServerImpl {
         &self,
         graph_handle: &Option<GraphHandle>,
     ) -> Result<(Arc<PreparedH3EdgeGraph<StandardWeight>>, GraphKey), Status> {
-        let gk: GraphKey = graph_handle.try_into()?;
+        let gk = self.resolve_graph_key(graph_handle).await?;
         self.storage
             .retrieve_graph(gk.clone())
             .await
@@ -93,6 +124,30 @@ impl ServerImpl {
             .map(|g| (g, gk))
     }
 
+    /// the key of the graph the `graph_handle` refers to
+    async fn resolve_graph_key(
+        &self,
+        graph_handle: &Option<GraphHandle>,
+    ) -> Result<GraphKey, Status> {
+        let gk: GraphKey = match graph_handle {
+            // an unset resolution selects the graph with the highest available resolution
+            Some(gh) if gh.h3_resolution == 0 && !gh.name.is_empty() => self
+                .storage
+                .resolve_highest_resolution(&gh.name)
+                .await
+                .to_status_result()?
+                .ok_or_else(|| {
+                    logged_status!(
+                        format!("no graph named {} found", gh.name),
+                        Code::NotFound,
+                        Level::DEBUG
+                    )
+                })?,
+            _ => graph_handle.try_into()?,
+        };
+        Ok(gk)
+    }
+
     fn dataset_by_name(&self, dataset_name: &str) -> Result<&DataframeDataset, Status> {
         self.config.datasets.get(dataset_name).ok_or_else(|| {
             logged_status!(
@@ -120,25 +175,13 @@ impl ServerImpl {
     ) -> Result<LoadedCellSelection, Status> {
         let Some(cell_selection) = cell_selection else { return Err(logged_status!(format!("empty cell selection '{selection_name}' given"), Code::InvalidArgument, Level::INFO)) };
 
-        // build a complete list of the requested h3cells transformed to the
-        // correct resolution
         let mut cells = block_in_place(|| {
-            let mut cells: Vec<_> = transform_resolution(
-                cell_selection.cells.iter().filter_map(|v| {
-                    if let Ok(cell) = CellIndex::try_from(*v) {
-                        Some(cell)
-                    } else {
-                        warn!("invalid h3 index {} ignored", v);
-                        None
-                    }
-                }),
+            selected_cells(
+                cell_selection,
                 h3_resolution,
+                self.config.max_polyfill_cells,
             )
-            .collect();
-            cells.sort_unstable();
-            cells.dedup();
-            cells
-        });
+        })?;
 
         if cells.is_empty() || cell_selection.dataset_name.is_empty() {
             Ok(LoadedCellSelection {
@@ -175,16 +218,38 @@ impl ServerImpl {
 #[tonic::async_trait]
 impl Rout3Serv for ServerImpl {
     async fn version(&self, _request: Request<Empty>) -> Result<Response<VersionResponse>, Status> {
+        crate::metrics::rpc_request("version");
         Ok(Response::new(VersionResponse {
             version: crate::build_info::version().to_string(),
             git_commit_sha: crate::build_info::git_comit_sha().to_string(),
             build_timestamp: crate::build_info::build_timestamp().to_string(),
+            graph_format_version: crate::build_info::graph_format_version(),
         }))
     }
+
+    async fn health_check(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        crate::metrics::rpc_request("health_check");
+        let response = match self.storage.readiness().await {
+            Readiness::Ready(graph_key) => HealthCheckResponse {
+                ready: true,
+                message: format!("graph {} is loadable", graph_key.to_string()),
+            },
+            Readiness::NotReady(reason) => HealthCheckResponse {
+                ready: false,
+                message: reason,
+            },
+        };
+        Ok(Response::new(response))
+    }
+
     async fn list_graphs(
         &self,
         _request: Request<Empty>,
     ) -> Result<Response<ListGraphsResponse>, Status> {
+        crate::metrics::rpc_request("list_graphs");
         let resp = ListGraphsResponse {
             graphs: self
                 .storage
@@ -202,18 +267,75 @@ impl Rout3Serv for ServerImpl {
         &self,
         _request: Request<Empty>,
     ) -> Result<Response<ListDatasetsResponse>, Status> {
+        crate::metrics::rpc_request("list_datasets");
+        let datasets = self.storage.list_datasets(&self.config.datasets).await;
         let response = ListDatasetsResponse {
             dataset_name: self.config.datasets.keys().cloned().collect(),
+            datasets: datasets
+                .into_iter()
+                .map(|(name, is_present)| DatasetInfo { name, is_present })
+                .collect(),
+        };
+        Ok(Response::new(response))
+    }
+
+    async fn describe_dataset(
+        &self,
+        request: Request<DescribeDatasetRequest>,
+    ) -> Result<Response<DescribeDatasetResponse>, Status> {
+        crate::metrics::rpc_request("describe_dataset");
+        let req = request.into_inner();
+        let dataset = self.dataset_by_name(&req.dataset_name)?;
+        let schema = self
+            .storage
+            .dataset_schema(&req.dataset_name, dataset)
+            .await
+            .to_status_result()?
+            .ok_or_else(|| {
+                logged_status!(
+                    format!("dataset {} contains no files", req.dataset_name),
+                    Code::NotFound,
+                    Level::DEBUG
+                )
+            })?;
+        let cell_column_name = dataset
+            .cell_column_names()
+            .into_iter()
+            .find(|candidate| schema.iter().any(|(name, _)| name == candidate))
+            .unwrap_or_default();
+        let response = DescribeDatasetResponse {
+            value_columns: schema
+                .into_iter()
+                .filter(|(name, _)| name != &cell_column_name)
+                .map(|(name, dtype)| DatasetColumn {
+                    name,
+                    dtype: dtype.to_string(),
+                })
+                .collect(),
+            cell_column_name,
+            dataset_name: req.dataset_name,
         };
         Ok(Response::new(response))
     }
 
+    async fn classify_cells(
+        &self,
+        request: Request<ClassifyCellsRequest>,
+    ) -> Result<Response<ClassifyCellsResponse>, Status> {
+        crate::metrics::rpc_request("classify_cells");
+        let req = request.into_inner();
+        let (graph, _) = self.retrieve_graph_by_handle(&req.graph_handle).await?;
+        let response = ClassifyCellsResponse::classify(graph.as_ref(), &req.cells)?;
+        Ok(Response::new(response))
+    }
+
     type H3ShortestPathStream = ArrowIpcChunkStream;
 
     async fn h3_shortest_path(
         &self,
         request: Request<H3ShortestPathRequest>,
     ) -> Result<Response<Self::H3ShortestPathStream>, Status> {
+        crate::metrics::rpc_request("h3_shortest_path");
         shortest_path::h3_shortest_path(
             shortest_path::create_parameters(request.into_inner(), self).await?,
         )
@@ -226,11 +348,17 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<H3ShortestPathRequest>,
     ) -> Result<Response<Self::H3ShortestPathRoutesStream>, Status> {
+        crate::metrics::rpc_request("h3_shortest_path_routes");
         let req = request.into_inner();
         let smoothen_geometries = req.smoothen_geometries;
+        let simplification = req
+            .options
+            .clone()
+            .unwrap_or_default()
+            .route_simplification()?;
         shortest_path::h3_shortest_path_routes(
             shortest_path::create_parameters(req, self).await?,
-            move |p| RouteWkb::from_path(&p, smoothen_geometries),
+            move |p| RouteWkb::from_path(&p, smoothen_geometries, simplification),
         )
         .await
     }
@@ -241,9 +369,26 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<H3ShortestPathRequest>,
     ) -> Result<Response<Self::H3ShortestPathCellsStream>, Status> {
+        crate::metrics::rpc_request("h3_shortest_path_cells");
+        let req = request.into_inner();
+        let target_resolution = req
+            .target_resolution
+            .map(|r| {
+                u8::try_from(r)
+                    .ok()
+                    .and_then(|r| Resolution::try_from(r).ok())
+                    .ok_or_else(|| {
+                        logged_status!(
+                            "invalid target_resolution",
+                            Code::InvalidArgument,
+                            Level::INFO
+                        )
+                    })
+            })
+            .transpose()?;
         shortest_path::h3_shortest_path_routes(
-            shortest_path::create_parameters(request.into_inner(), self).await?,
-            move |p| RouteH3Indexes::from_path(&p, RouteH3IndexesKind::Cells),
+            shortest_path::create_parameters(req, self).await?,
+            move |p| RouteH3Indexes::from_path(&p, RouteH3IndexesKind::Cells, target_resolution),
         )
         .await
     }
@@ -254,22 +399,63 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<H3ShortestPathRequest>,
     ) -> Result<Response<Self::H3ShortestPathEdgesStream>, Status> {
+        crate::metrics::rpc_request("h3_shortest_path_edges");
         shortest_path::h3_shortest_path_routes(
             shortest_path::create_parameters(request.into_inner(), self).await?,
-            move |p| RouteH3Indexes::from_path(&p, RouteH3IndexesKind::Edges),
+            move |p| RouteH3Indexes::from_path(&p, RouteH3IndexesKind::Edges, None),
         )
         .await
     }
 
+    type H3ShortestPathEdgeWeightsStream = ReceiverStream<Result<RouteH3EdgeWeights, Status>>;
+
+    async fn h3_shortest_path_edge_weights(
+        &self,
+        request: Request<H3ShortestPathRequest>,
+    ) -> Result<Response<Self::H3ShortestPathEdgeWeightsStream>, Status> {
+        crate::metrics::rpc_request("h3_shortest_path_edge_weights");
+        let parameters = shortest_path::create_parameters(request.into_inner(), self).await?;
+        let graph = parameters.graph().clone();
+        shortest_path::h3_shortest_path_routes(parameters, move |p| {
+            RouteH3EdgeWeights::from_path(&p, &graph)
+        })
+        .await
+    }
+
     type DifferentialShortestPathStream = ArrowIpcChunkStream;
 
     async fn differential_shortest_path(
         &self,
         request: Request<DifferentialShortestPathRequest>,
     ) -> Result<Response<ArrowIpcChunkStream>, Status> {
-        let input = differential_shortest_path::collect_input(request.into_inner(), self).await?;
+        crate::metrics::rpc_request("differential_shortest_path");
+        let request = request.into_inner();
+        if request.deterministic_object_id {
+            // reuse the output of an identical earlier request without routing again
+            let object_id =
+                differential_shortest_path::resolve_deterministic_object_id(&request, self).await?;
+            let path = self.build_output_key(&object_id);
+            if self.storage.exists(&path).await.to_status_result()? {
+                let output: differential_shortest_path::DspOutput =
+                    self.storage.retrieve(&path).await.to_status_result()?;
+                return stream_dataframe(
+                    output.object_id.clone(),
+                    differential_shortest_path::disturbance_statistics(&output)?,
+                    self.config.stream_channel_capacity,
+                )
+                .await;
+            }
+        }
+        let input = differential_shortest_path::collect_input(request, self).await?;
 
         let do_store_output = input.store_output;
+        let overwrite_policy = if input.deterministic_object_id {
+            // an output of an identical earlier request can be reused
+            OverwritePolicy::SkipIfExists
+        } else {
+            // random ids never refer to the output of another request
+            OverwritePolicy::Error
+        };
         let output = spawn_blocking_status(move || differential_shortest_path::calculate(input))
             .await?
             .to_status_result()?;
@@ -277,6 +463,7 @@ impl Rout3Serv for ServerImpl {
         let response_fut = stream_dataframe(
             output.object_id.clone(),
             differential_shortest_path::disturbance_statistics(&output)?,
+            self.config.stream_channel_capacity,
         );
 
         let response = if do_store_output {
@@ -284,7 +471,7 @@ impl Rout3Serv for ServerImpl {
             let (_, response) = tokio::try_join!(
                 async {
                     self.storage
-                        .store(&path, &output)
+                        .store(&path, &output, overwrite_policy)
                         .await
                         .map_err(|e| e.status())
                 }, // save the output for later
@@ -303,6 +490,7 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<IdRef>,
     ) -> Result<Response<ArrowIpcChunkStream>, Status> {
+        crate::metrics::rpc_request("get_differential_shortest_path");
         let inner = request.into_inner();
         let output: differential_shortest_path::DspOutput = self
             .storage
@@ -313,6 +501,7 @@ impl Rout3Serv for ServerImpl {
         stream_dataframe(
             output.object_id.clone(),
             differential_shortest_path::disturbance_statistics(&output)?,
+            self.config.stream_channel_capacity,
         )
         .await
     }
@@ -324,7 +513,7 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<DifferentialShortestPathRoutesRequest>,
     ) -> Result<Response<Self::GetDifferentialShortestPathRoutesStream>, Status> {
-        let (tx, rx) = mpsc::channel(20);
+        crate::metrics::rpc_request("get_differential_shortest_path_routes");
         let inner = request.into_inner();
         let output: differential_shortest_path::DspOutput = self
             .storage
@@ -332,35 +521,33 @@ impl Rout3Serv for ServerImpl {
             .await
             .to_status_result()?;
 
-        tokio::spawn(async move {
-            let cell_lookup: CellSet = inner
-                .cells
-                .iter()
-                .filter_map(|h3index| {
-                    if let Ok(cell) = CellIndex::try_from(*h3index) {
-                        Some(cell)
-                    } else {
-                        warn!("received invalid h3index: {}", h3index);
-                        None
-                    }
-                })
-                .collect();
-
-            for (origin_cell, diff) in output.differential_shortest_paths.iter() {
-                if cell_lookup.contains(origin_cell) {
-                    if let Err(e) = tx
-                        .send(differential_shortest_path::build_routes_response(
-                            diff,
-                            inner.smoothen_geometries,
-                        ))
-                        .await
-                    {
-                        warn!("streaming of routes aborted. reason: {}", e);
-                        break;
-                    }
+        let cell_lookup: CellSet = inner
+            .cells
+            .iter()
+            .filter_map(|h3index| {
+                if let Ok(cell) = CellIndex::try_from(*h3index) {
+                    Some(cell)
+                } else {
+                    warn!("received invalid h3index: {}", h3index);
+                    None
                 }
-            }
-        });
+            })
+            .collect();
+
+        let route_simplification = output.route_simplification;
+        let routes = output
+            .differential_shortest_paths
+            .into_iter()
+            .filter(move |(origin_cell, _)| cell_lookup.contains(origin_cell))
+            .map(move |(_, diff)| {
+                differential_shortest_path::build_routes_response(
+                    &diff,
+                    inner.smoothen_geometries,
+                    route_simplification,
+                )
+            });
+        let (tx, rx) = mpsc::channel(self.config.stream_channel_capacity);
+        spawn_send_all(tx, routes, "routes");
         Ok(Response::new(ReceiverStream::new(rx)))
     }
 
@@ -370,11 +557,20 @@ impl Rout3Serv for ServerImpl {
         &self,
         request: Request<H3WithinThresholdRequest>,
     ) -> Result<Response<Self::H3CellsWithinThresholdStream>, Status> {
+        crate::metrics::rpc_request("h3_cells_within_threshold");
         within_threshold::within_threshold(
             within_threshold::create_parameters(request.into_inner(), self).await?,
         )
         .await
     }
+
+    async fn h3_isochrone(
+        &self,
+        request: Request<H3IsochroneRequest>,
+    ) -> Result<Response<H3IsochroneResponse>, Status> {
+        crate::metrics::rpc_request("h3_isochrone");
+        isochrone::isochrone(isochrone::create_parameters(request.into_inner(), self).await?).await
+    }
 }
 
 pub fn launch_server(server_config: ServerConfig) -> anyhow::Result<()> {
@@ -385,25 +581,133 @@ pub fn launch_server(server_config: ServerConfig) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// validate the `server_config` against the objectstore without binding the port.
+pub fn launch_dry_run(server_config: ServerConfig) -> anyhow::Result<dry_run::DryRunReport> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(dry_run::dry_run(&server_config))
+}
+
 async fn run_server(server_config: ServerConfig) -> anyhow::Result<()> {
-    let addr = server_config.bind_to.parse()?;
+    let addr: SocketAddr = server_config.bind_to.parse()?;
+    if let Some(metrics_config) = server_config.metrics.as_ref() {
+        crate::metrics::install_exporter(metrics_config)?;
+    }
+    let grpc_web = server_config.grpc_web;
+    let cors_layer = server_config
+        .cors
+        .as_ref()
+        .map(|cors_config| cors_config.cors_layer())
+        .transpose()?;
     info!("creating grpc server");
     let server_impl: ServerImpl = ServerImpl::create(server_config).await?;
 
+    let listener = TcpListener::bind(addr).await?;
     info!("{} is listening on {}", env!("CARGO_PKG_NAME"), addr);
+    serve(server_impl, grpc_web, cors_layer, listener).await
+}
+
+/// serve the services of `server_impl` on the connections accepted by `listener`.
+///
+/// With `grpc_web` enabled HTTP/1.1 gets accepted as well and gRPC-web requests get
+/// translated to native gRPC before reaching the services.
+async fn serve(
+    server_impl: ServerImpl,
+    grpc_web: bool,
+    cors_layer: Option<CorsLayer>,
+    listener: TcpListener,
+) -> anyhow::Result<()> {
+    if grpc_web {
+        info!("accepting gRPC-web requests");
+    }
 
-    Server::builder()
+    #[cfg(feature = "arrow-flight")]
+    let flight_service = flight::protocol::flight_service_server::FlightServiceServer::new(
+        server_impl.flight_service(),
+    );
+
+    let rout3serv_service = Rout3ServServer::new(server_impl)
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+
+    let layer = ServiceBuilder::new()
         .layer(TraceLayer::new_for_grpc())
-        .add_service(
-            Rout3ServServer::new(server_impl)
-                .send_compressed(CompressionEncoding::Gzip)
-                .accept_compressed(CompressionEncoding::Gzip),
-        )
-        .serve(addr)
+        .option_layer(cors_layer)
+        .option_layer(grpc_web.then(GrpcWebLayer::new))
+        .into_inner();
+
+    let router = Server::builder()
+        .accept_http1(grpc_web)
+        .layer(layer)
+        .add_service(rout3serv_service);
+
+    #[cfg(feature = "arrow-flight")]
+    let router = router.add_service(flight_service);
+
+    router
+        .serve_with_incoming(TcpListenerStream::new(listener))
         .await?;
     Ok(())
 }
 
+/// build a complete, deduplicated list of the cells of the `cell_selection` transformed
+/// to `h3_resolution`. Invalid cells get ignored.
+///
+/// With `strict_resolution` set in the selection, inputs mixing multiple resolutions
+/// are rejected instead of being transformed. The cells of the geometry of the selection
+/// are polyfilled at `h3_resolution`, limited to `max_polyfill_cells`.
+fn selected_cells(
+    cell_selection: &CellSelection,
+    h3_resolution: Resolution,
+    max_polyfill_cells: Option<usize>,
+) -> Result<Vec<CellIndex>, Status> {
+    let input_cells: Vec<_> = cell_selection
+        .cells
+        .iter()
+        .filter_map(|v| {
+            if let Ok(cell) = CellIndex::try_from(*v) {
+                Some(cell)
+            } else {
+                warn!("invalid h3 index {} ignored", v);
+                None
+            }
+        })
+        .collect();
+
+    if cell_selection.strict_resolution {
+        let resolutions: BTreeSet<_> = input_cells.iter().map(|cell| cell.resolution()).collect();
+        if resolutions.len() > 1 {
+            return Err(logged_status!(
+                format!(
+                    "cell selection mixes multiple h3 resolutions: {}",
+                    resolutions
+                        .iter()
+                        .map(|r| u8::from(*r).to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                Code::InvalidArgument,
+                Level::INFO
+            ));
+        }
+    }
+
+    let mut cells: Vec<_> = transform_resolution(input_cells.into_iter(), h3_resolution).collect();
+    if !cell_selection.geometry_wkb.is_empty() {
+        cells.extend(geom_to_h3(
+            from_wkb(&cell_selection.geometry_wkb)?,
+            h3_resolution,
+            cell_selection.geometry_containment().into(),
+            false,
+            max_polyfill_cells,
+        )?);
+    }
+    cells.sort_unstable();
+    cells.dedup();
+    Ok(cells)
+}
+
 fn filter_cells_by_dataframe_contents(
     df: &CellDataFrame,
     cells: &mut Vec<CellIndex>,
@@ -421,3 +725,464 @@ fn filter_cells_by_dataframe_contents(
     }
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::fs::File;
+    use std::io::{BufWriter, Read, Write};
+
+    use flate2::read::GzDecoder;
+    use h3o::{CellIndex, LatLng, Resolution};
+    use hexigraph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::MetricKind;
+    use prost::Message;
+    use tokio::net::TcpListener;
+    use tokio_stream::StreamExt;
+    use tonic::Request;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::config::ServerConfig;
+    use crate::grpc::api::generated::rout3_serv_server::Rout3Serv;
+    use crate::grpc::api::generated::{
+        CellSelection, DifferentialShortestPathRequest, GraphHandle, H3ShortestPathRequest,
+        RouteH3Indexes,
+    };
+    use crate::grpc::differential_shortest_path::tests::grid_input;
+    use crate::grpc::differential_shortest_path::{calculate, resolve_deterministic_object_id};
+    use crate::grpc::geometry::{geom_to_h3, to_wkb, Containment};
+    use crate::grpc::{selected_cells, serve, ServerImpl};
+    use crate::io::ipc::WriteIPC;
+    use crate::io::storage::OverwritePolicy;
+    use crate::io::tests::TempDir;
+    use crate::io::GraphKey;
+    use crate::metrics::{METRIC_COMPUTE_DURATION, METRIC_RPC_REQUESTS};
+    use crate::weight::StandardWeight;
+
+    /// configuration of a server keeping all of its data in the filesystem below `root`
+    pub(crate) fn test_server_config(root: &std::path::Path) -> ServerConfig {
+        serde_yaml::from_str(&format!(
+            r#"
+bind_to: "127.0.0.1:0"
+objectstore:
+  type: filesystem
+  root: "{}"
+graphs:
+  prefix: "graphs/"
+outputs: {{}}
+datasets: {{}}
+"#,
+            root.display()
+        ))
+        .unwrap()
+    }
+
+    /// a server with the [`test_server_config`]
+    pub(crate) async fn test_server_impl(root: &std::path::Path) -> ServerImpl {
+        // the root of the filesystem objectstore must exist
+        std::fs::create_dir_all(root).unwrap();
+        ServerImpl::create(test_server_config(root)).await.unwrap()
+    }
+
+    /// write a graph of all cells within 3 cells of `center` to the `graphs/` directory in `root`
+    pub(crate) fn write_grid_graph(
+        root: &std::path::Path,
+        graph_key: &GraphKey,
+        center: CellIndex,
+    ) {
+        let mut graph = H3EdgeGraph::new(graph_key.h3_resolution);
+        for cell in center.grid_disk::<Vec<_>>(3) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let graph_dir = root.join("graphs");
+        std::fs::create_dir_all(&graph_dir).unwrap();
+        let writer = BufWriter::new(File::create(graph_dir.join(graph_key.to_string())).unwrap());
+        prepared_graph.write_ipc(writer).unwrap();
+    }
+
+    /// split a gRPC-web response body into its frames as (flags, payload)
+    fn grpc_web_frames(mut body: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = vec![];
+        while !body.is_empty() {
+            let flags = body[0];
+            let len = u32::from_be_bytes(body[1..5].try_into().unwrap()) as usize;
+            frames.push((flags, body[5..5 + len].to_vec()));
+            body = &body[5 + len..];
+        }
+        frames
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn grpc_web_shortest_path_cells() {
+        let root = TempDir::new();
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let destination = origin
+            .grid_ring_fast(2)
+            .flatten()
+            .next()
+            .expect("destination cell");
+        write_grid_graph(&root, &graph_key, origin);
+
+        let mut server_config = test_server_config(&root);
+        server_config.grpc_web = true;
+        server_config.validate().unwrap();
+
+        let server_impl = ServerImpl::create(server_config).await.unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(server_impl, true, None, listener));
+
+        let message = H3ShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: graph_key.name.clone(),
+                h3_resolution: u8::from(graph_key.h3_resolution) as u32,
+            }),
+            origins: Some(CellSelection {
+                cells: vec![u64::from(origin)],
+                dataset_name: "".to_string(),
+                strict_resolution: false,
+                ..Default::default()
+            }),
+            destinations: Some(CellSelection {
+                cells: vec![u64::from(destination)],
+                dataset_name: "".to_string(),
+                strict_resolution: false,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+        .encode_to_vec();
+        let mut request_body = vec![0u8];
+        request_body.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        request_body.extend_from_slice(&message);
+
+        let request = hyper::Request::post(format!(
+            "http://{addr}/rout3serv.Rout3Serv/H3ShortestPathCells"
+        ))
+        .header("content-type", "application/grpc-web+proto")
+        .header("x-grpc-web", "1")
+        .header("grpc-accept-encoding", "gzip")
+        .body(hyper::Body::from(request_body))
+        .unwrap();
+        let response = hyper::Client::new().request(request).await.unwrap();
+        assert_eq!(response.status(), hyper::StatusCode::OK);
+        assert_eq!(
+            response.headers()["content-type"],
+            "application/grpc-web+proto"
+        );
+        assert!(response
+            .headers()
+            .get("grpc-status")
+            .map_or(true, |status| status == "0"));
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let frames = grpc_web_frames(&body);
+        assert_eq!(frames.len(), 2);
+
+        // data frame, gzip compressed as the server compresses responses
+        let (flags, payload) = &frames[0];
+        assert_eq!(*flags, 0x01);
+        let mut decompressed = vec![];
+        GzDecoder::new(payload.as_slice())
+            .read_to_end(&mut decompressed)
+            .unwrap();
+        let route = RouteH3Indexes::decode(decompressed.as_slice()).unwrap();
+        assert_eq!(route.origin_cell, u64::from(origin));
+        assert_eq!(route.destination_cell, u64::from(destination));
+        assert_eq!(route.h3indexes.first(), Some(&u64::from(origin)));
+        assert_eq!(route.h3indexes.last(), Some(&u64::from(destination)));
+        assert!(route.travel_duration_secs > 0.0);
+
+        // trailer frame
+        let (flags, payload) = &frames[1];
+        assert_eq!(*flags, 0x80);
+        let trailers = String::from_utf8_lossy(payload).to_lowercase();
+        assert!(trailers.contains("grpc-status:0"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn deterministic_object_id_reuses_stored_output() {
+        let root = TempDir::new();
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        write_grid_graph(&root, &graph_key, center);
+        let server_impl = test_server_impl(&root).await;
+
+        // the disturbance of the request can not be parsed, so routing would fail
+        let request = DifferentialShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: graph_key.name.clone(),
+                h3_resolution: u8::from(graph_key.h3_resolution) as u32,
+            }),
+            disturbance_wkb_geometry: vec![1, 2, 3],
+            radius_meters: 1000.0,
+            deterministic_object_id: true,
+            ..Default::default()
+        };
+        assert!(server_impl
+            .differential_shortest_path(Request::new(request.clone()))
+            .await
+            .is_err());
+
+        let mut output = calculate(grid_input(center, false)).unwrap();
+        output.object_id = resolve_deterministic_object_id(&request, &server_impl)
+            .await
+            .unwrap();
+        server_impl
+            .storage
+            .store(
+                &server_impl.build_output_key(&output.object_id),
+                &output,
+                OverwritePolicy::Overwrite,
+            )
+            .await
+            .unwrap();
+
+        let mut chunks = server_impl
+            .differential_shortest_path(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        let chunk = chunks.next().await.unwrap().unwrap();
+        assert_eq!(chunk.object_id, output.object_id);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn deterministic_object_id_changes_with_the_graph() {
+        let root = TempDir::new();
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        write_grid_graph(&root, &graph_key, center);
+        let server_impl = test_server_impl(&root).await;
+
+        // without a resolution the graph with the highest resolution is selected
+        let request = |h3_resolution| DifferentialShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: graph_key.name.clone(),
+                h3_resolution,
+            }),
+            disturbance_wkb_geometry: vec![1, 2, 3],
+            radius_meters: 1000.0,
+            deterministic_object_id: true,
+            ..Default::default()
+        };
+        let object_id = resolve_deterministic_object_id(&request(0), &server_impl)
+            .await
+            .unwrap();
+        assert_eq!(
+            object_id,
+            resolve_deterministic_object_id(&request(0), &server_impl)
+                .await
+                .unwrap()
+        );
+
+        // a graph with a higher resolution gets added
+        let finer_graph_key = GraphKey {
+            h3_resolution: Resolution::Ten,
+            ..graph_key.clone()
+        };
+        write_grid_graph(
+            &root,
+            &finer_graph_key,
+            center.center_child(Resolution::Ten).unwrap(),
+        );
+        let finer_object_id = resolve_deterministic_object_id(&request(0), &server_impl)
+            .await
+            .unwrap();
+        assert_ne!(object_id, finer_object_id);
+
+        // the graph of the request gets replaced by a changed one
+        let explicit_object_id = resolve_deterministic_object_id(&request(9), &server_impl)
+            .await
+            .unwrap();
+        let mut graph_file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(root.join("graphs").join(graph_key.to_string()))
+            .unwrap();
+        graph_file.write_all(b"changed").unwrap();
+        drop(graph_file);
+        assert_ne!(
+            explicit_object_id,
+            resolve_deterministic_object_id(&request(9), &server_impl)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn shortest_path_request_is_recorded() {
+        // the routing runs on other threads than the test, so the recorder must be global
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        recorder.install().unwrap();
+
+        let root = TempDir::new();
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Nine,
+        };
+        let origin = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        write_grid_graph(&root, &graph_key, origin);
+        let server_impl = test_server_impl(&root).await;
+
+        let request = H3ShortestPathRequest {
+            graph_handle: Some(GraphHandle {
+                name: graph_key.name.clone(),
+                h3_resolution: u8::from(graph_key.h3_resolution) as u32,
+            }),
+            origins: Some(CellSelection {
+                cells: vec![u64::from(origin)],
+                ..Default::default()
+            }),
+            destinations: Some(CellSelection {
+                cells: origin.grid_ring_fast(2).flatten().map(u64::from).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut chunks = server_impl
+            .h3_shortest_path(Request::new(request))
+            .await
+            .unwrap()
+            .into_inner();
+        while let Some(chunk) = chunks.next().await {
+            chunk.unwrap();
+        }
+
+        let snapshot = snapshotter.snapshot().into_vec();
+        let requests_counted: Vec<_> = snapshot
+            .iter()
+            .filter_map(|(ck, _, _, value)| {
+                let key = ck.key();
+                (ck.kind() == MetricKind::Counter
+                    && key.name() == METRIC_RPC_REQUESTS
+                    && key
+                        .labels()
+                        .any(|l| l.key() == "rpc" && l.value() == "h3_shortest_path"))
+                .then_some(value)
+            })
+            .collect();
+        assert_eq!(requests_counted, vec![&DebugValue::Counter(1)]);
+
+        let compute_durations_recorded = snapshot.iter().any(|(ck, _, _, value)| {
+            ck.kind() == MetricKind::Histogram
+                && ck.key().name() == METRIC_COMPUTE_DURATION
+                && matches!(value, DebugValue::Histogram(durations) if !durations.is_empty())
+        });
+        assert!(compute_durations_recorded);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn out_of_range_target_resolution_is_rejected() {
+        let root = TempDir::new();
+        let server_impl = test_server_impl(&root).await;
+
+        // 265 would become the valid resolution 9 when truncated to u8
+        for target_resolution in [16, 265] {
+            let status = server_impl
+                .h3_shortest_path_cells(Request::new(H3ShortestPathRequest {
+                    target_resolution: Some(target_resolution),
+                    ..Default::default()
+                }))
+                .await
+                .unwrap_err();
+            assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn selected_cells_mixed_resolutions() {
+        let cell = LatLng::new(12.3, 23.3).unwrap().to_cell(Resolution::Nine);
+        let parent = cell.parent(Resolution::Eight).unwrap();
+        let mut cell_selection = CellSelection {
+            cells: vec![u64::from(cell), u64::from(parent)],
+            dataset_name: "".to_string(),
+            strict_resolution: false,
+            ..Default::default()
+        };
+
+        // lenient: the cells get transformed to the requested resolution
+        assert_eq!(
+            selected_cells(&cell_selection, Resolution::Eight, None).unwrap(),
+            vec![parent]
+        );
+
+        cell_selection.strict_resolution = true;
+        let status = selected_cells(&cell_selection, Resolution::Eight, None).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert!(status.message().contains("8, 9"));
+
+        // a single resolution is accepted in strict mode as well
+        cell_selection.cells = vec![u64::from(cell)];
+        assert_eq!(
+            selected_cells(&cell_selection, Resolution::Eight, None).unwrap(),
+            vec![parent]
+        );
+    }
+
+    #[test]
+    fn selected_cells_by_geometry() {
+        let polygon = geo_types::Polygon::new(
+            geo_types::LineString::from(vec![
+                (23.3, 12.3),
+                (23.32, 12.3),
+                (23.32, 12.32),
+                (23.3, 12.32),
+                (23.3, 12.3),
+            ]),
+            vec![],
+        );
+        let explicit_cells = geom_to_h3(
+            polygon.clone().into(),
+            Resolution::Nine,
+            Containment::Centroid,
+            false,
+            None,
+        )
+        .unwrap();
+        assert!(explicit_cells.len() > 10);
+
+        let by_cells = CellSelection {
+            cells: explicit_cells.iter().map(|cell| u64::from(*cell)).collect(),
+            ..Default::default()
+        };
+        let by_geometry = CellSelection {
+            geometry_wkb: to_wkb(&polygon.into()).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            selected_cells(&by_geometry, Resolution::Nine, None).unwrap(),
+            selected_cells(&by_cells, Resolution::Nine, None).unwrap()
+        );
+
+        // the polyfill limit applies to selections by geometry
+        let status = selected_cells(&by_geometry, Resolution::Nine, Some(5)).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let invalid = CellSelection {
+            geometry_wkb: vec![1, 2, 3],
+            ..Default::default()
+        };
+        assert_eq!(
+            selected_cells(&invalid, Resolution::Nine, None)
+                .unwrap_err()
+                .code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+}
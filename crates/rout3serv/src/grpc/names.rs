@@ -2,4 +2,6 @@ pub static COL_H3INDEX_DESTINATION: &str = "h3index_cell_destination";
 pub static COL_H3INDEX_ORIGIN: &str = "h3index_cell_origin";
 pub static COL_PATH_LENGTH_METERS: &str = "path_length_meters";
 pub static COL_TRAVEL_DURATION_SECS: &str = "travel_duration_secs";
+pub static COL_TRAVEL_DURATION_SECS_BAND: &str = "travel_duration_secs_band";
 pub static COL_EDGE_PREFERENCE: &str = "edge_preference";
+pub static COL_GEOMETRY: &str = "geometry";
@@ -1,31 +1,69 @@
 use h3o::CellIndex;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
+use std::time::Instant;
 
 use hexigraph::algorithm::graph::path::Path;
-use hexigraph::algorithm::graph::shortest_path::ShortestPathOptions;
+use hexigraph::algorithm::graph::shortest_path::{PreparedDestinations, ShortestPathOptions};
 use hexigraph::algorithm::graph::ShortestPathManyToMany;
+use hexigraph::algorithm::resolution::transform_resolution;
+use hexigraph::container::treemap::H3Treemap;
+use hexigraph::container::CellMap;
+use hexigraph::graph::modifiers::ExcludeCells;
 use hexigraph::HasH3Resolution;
 use ordered_float::OrderedFloat;
 use polars::prelude::{DataFrame, NamedFrom, Series};
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
+use tonic::{Code, Response, Status};
+use tracing::{Level, Span};
 use uom::si::time::second;
 
 use crate::customization::{CustomizedGraph, CustomizedWeight};
 use crate::grpc::api::Route;
-use crate::grpc::error::{StatusCodeAndMessage, ToStatusResult};
+use crate::grpc::error::{logged_status, StatusCodeAndMessage, ToStatusResult};
 use crate::grpc::util::{
-    inner_join_h3dataframe, spawn_blocking_status, stream_dataframe, stream_routes,
-    ArrowIpcChunkStream,
+    inner_join_h3dataframe, routing_finished, routing_span, spawn_blocking_status,
+    stream_dataframe_batches, stream_routes, ArrowIpcChunkStream, STREAM_MAX_ROWS,
 };
 use crate::grpc::{names, LoadedCellSelection, ServerImpl};
+use crate::io::dataframe::CellDataFrame;
+use crate::io::GraphKey;
 use crate::weight::Weight;
 
 pub struct H3ShortestPathParameters {
     graph: CustomizedGraph,
+    graph_key: GraphKey,
     options: super::api::generated::ShortestPathOptions,
     origins: LoadedCellSelection,
     destinations: LoadedCellSelection,
+
+    /// cells routes must not pass through
+    avoid_cells: Option<H3Treemap<CellIndex>>,
+
+    page: Page,
+
+    /// buffer size of the response stream
+    stream_channel_capacity: usize,
+}
+
+/// a window of the found routes to respond with
+#[derive(Default, Clone, Copy)]
+struct Page {
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Page {
+    /// order the paths by origin, then destination cell and return the
+    /// paths within the window of this page.
+    fn select<W>(&self, mut paths: Vec<Path<W>>) -> Vec<Path<W>> {
+        paths.sort_unstable_by_key(|path| (path.origin_cell, path.destination_cell));
+        paths
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
 }
 
 pub(crate) async fn create_parameters(
@@ -33,13 +71,18 @@ pub(crate) async fn create_parameters(
     server_impl: &ServerImpl,
 ) -> Result<H3ShortestPathParameters, Status> {
     let routing_mode = server_impl.config.get_routing_mode(&request.routing_mode)?;
-    let graph = server_impl
+    let mut options = request.options.unwrap_or_default();
+    options.clamp_num_destinations_to_reach(server_impl.config.max_destinations_to_reach);
+    let weight_scale = options.weight_scaling()?;
+
+    let (graph, graph_key) = server_impl
         .retrieve_graph_by_handle(&request.graph_handle)
         .await
-        .map(|(graph, _)| {
+        .map(|(graph, graph_key)| {
             let mut cg = CustomizedGraph::from(graph);
             cg.set_routing_mode(routing_mode);
-            cg
+            cg.set_weight_scale(weight_scale);
+            (cg, graph_key)
         })
         .to_status_result()?;
 
@@ -51,29 +94,163 @@ pub(crate) async fn create_parameters(
         .load_cell_selection(&request.destinations, graph.h3_resolution(), "destinations")
         .await?;
 
+    let avoid_cells = if request.avoid_cells.is_empty() {
+        None
+    } else {
+        let cells = request
+            .avoid_cells
+            .iter()
+            .map(|h3index| CellIndex::try_from(*h3index))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| {
+                logged_status!(
+                    "invalid h3 index in avoid_cells",
+                    Code::InvalidArgument,
+                    Level::DEBUG,
+                    e
+                )
+            })?;
+        Some(H3Treemap::from_iter(transform_resolution(
+            cells,
+            graph.h3_resolution(),
+        )))
+    };
+
     Ok(H3ShortestPathParameters {
         graph,
-        options: request.options.unwrap_or_default(),
+        graph_key,
+        options,
         origins,
         destinations,
+        avoid_cells,
+        page: Page {
+            offset: request.offset as usize,
+            limit: (request.limit > 0).then_some(request.limit as usize),
+        },
+        stream_channel_capacity: server_impl.config.stream_channel_capacity,
     })
 }
 
-async fn spawn_h3_shortest_path<F, R, E>(func: F) -> Result<R, Status>
+impl H3ShortestPathParameters {
+    pub fn graph(&self) -> &CustomizedGraph {
+        &self.graph
+    }
+
+    /// route from all origins to the destinations while respecting the `avoid_cells`.
+    fn shortest_path_many_to_many_map<PM, O>(
+        &self,
+        path_transform_fn: PM,
+    ) -> Result<CellMap<Vec<O>>, hexigraph::error::Error>
+    where
+        PM: Fn(Path<CustomizedWeight>) -> Result<O, hexigraph::error::Error> + Send + Sync,
+        O: Send + Ord + Clone,
+    {
+        match self.avoid_cells.as_ref() {
+            Some(avoid_cells) => ExcludeCells::new(&self.graph, avoid_cells)
+                .shortest_path_many_to_many_map(
+                    &self.origins.cells,
+                    &self.destinations.cells,
+                    &self.options,
+                    path_transform_fn,
+                ),
+            None => self.graph.shortest_path_many_to_many_map(
+                &self.origins.cells,
+                &self.destinations.cells,
+                &self.options,
+                path_transform_fn,
+            ),
+        }
+    }
+
+    /// locate the destinations for routing from all origins while respecting the `avoid_cells`.
+    fn prepared_destinations(&self) -> Result<PreparedDestinations, hexigraph::error::Error> {
+        match self.avoid_cells.as_ref() {
+            Some(avoid_cells) => PreparedDestinations::for_origins(
+                &ExcludeCells::new(&self.graph, avoid_cells),
+                &self.origins.cells,
+                &self.destinations.cells,
+                &self.options,
+            ),
+            None => PreparedDestinations::for_origins(
+                &self.graph,
+                &self.origins.cells,
+                &self.destinations.cells,
+                &self.options,
+            ),
+        }
+    }
+
+    /// route from the `origin_cells` to the prepared `destinations` while respecting the `avoid_cells`.
+    fn shortest_path_many_to_many_prepared_map<PM, O>(
+        &self,
+        origin_cells: &[CellIndex],
+        destinations: &PreparedDestinations,
+        path_transform_fn: PM,
+    ) -> Result<CellMap<Vec<O>>, hexigraph::error::Error>
+    where
+        PM: Fn(Path<CustomizedWeight>) -> Result<O, hexigraph::error::Error> + Send + Sync,
+        O: Send + Ord + Clone,
+    {
+        match self.avoid_cells.as_ref() {
+            Some(avoid_cells) => ExcludeCells::new(&self.graph, avoid_cells)
+                .shortest_path_many_to_many_prepared_map(
+                    origin_cells,
+                    destinations,
+                    &self.options,
+                    path_transform_fn,
+                ),
+            None => self.graph.shortest_path_many_to_many_prepared_map(
+                origin_cells,
+                destinations,
+                &self.options,
+                path_transform_fn,
+            ),
+        }
+    }
+
+    /// the maximum number of paths found for a single origin
+    fn paths_per_origin(&self) -> usize {
+        self.options
+            .num_destinations_to_reach()
+            .unwrap_or(self.destinations.cells.len())
+    }
+
+    fn span(&self, operation: &'static str) -> Span {
+        routing_span(
+            operation,
+            &self.graph_key,
+            self.origins.cells.len(),
+            Some(self.destinations.cells.len()),
+            Some(self.options.max_distance_to_graph()),
+        )
+    }
+}
+
+async fn spawn_h3_shortest_path<F, R, E>(span: Span, func: F) -> Result<R, Status>
 where
     F: FnOnce() -> Result<R, E> + Send + 'static,
     E: Debug + Send + 'static + StatusCodeAndMessage,
     R: Send + 'static,
 {
-    spawn_blocking_status(func).await?.to_status_result()
+    spawn_blocking_status(move || span.in_scope(func))
+        .await?
+        .to_status_result()
 }
 
 pub async fn h3_shortest_path(
     parameters: H3ShortestPathParameters,
 ) -> Result<Response<ArrowIpcChunkStream>, Status> {
-    stream_dataframe(
+    let span = parameters.span("h3_shortest_path");
+    let stream_channel_capacity = parameters.stream_channel_capacity;
+    let batches = spawn_blocking_status(move || {
+        let batch_span = span.clone();
+        span.in_scope(|| h3_shortest_path_batches(parameters, STREAM_MAX_ROWS, batch_span))
+    })
+    .await??;
+    stream_dataframe_batches(
         uuid::Uuid::new_v4().to_string(),
-        spawn_h3_shortest_path(move || h3_shortest_path_internal(parameters)).await?,
+        batches,
+        stream_channel_capacity,
     )
     .await
 }
@@ -92,37 +269,139 @@ where
     fn from(path: Path<W>) -> Self {
         Self {
             cost: path.cost,
-            path_length_m: path.directed_edge_path.length_m().into(),
+            path_length_m: path.length_m().into(),
             destination_cell: path.destination_cell,
         }
     }
 }
 
-fn h3_shortest_path_internal(parameters: H3ShortestPathParameters) -> Result<DataFrame, Status> {
-    let pathmap = parameters
-        .graph
-        .shortest_path_many_to_many_map(
-            &parameters.origins.cells,
-            &parameters.destinations.cells,
-            &parameters.options,
-            |path| Ok(PathSummary::from(path)),
-        )
-        .to_status_result()?;
+/// columns for the [`Weight::report_fields`] of the rows of a dataframe.
+///
+/// Fields missing in some of the rows are null there.
+#[derive(Default)]
+struct ReportFieldColumns {
+    num_rows: usize,
+    columns: BTreeMap<String, Vec<Option<f64>>>,
+}
+
+impl ReportFieldColumns {
+    /// add a row with the given `report_fields`
+    fn push(&mut self, report_fields: Vec<(String, f64)>) {
+        for (name, value) in report_fields {
+            self.columns
+                .entry(name)
+                .or_insert_with(|| vec![None; self.num_rows])
+                .push(Some(value));
+        }
+        self.num_rows += 1;
+        for column in self.columns.values_mut() {
+            column.resize(self.num_rows, None);
+        }
+    }
+
+    fn into_series(self) -> impl Iterator<Item = Series> {
+        self.columns
+            .into_iter()
+            .map(|(name, values)| Series::new(&name, values))
+    }
+}
 
+/// route from the origins in batches of at most `max_rows` rows.
+///
+/// Concatenated, the batches form the same table as routing from all origins in a single run.
+/// The rows are ordered by the origin cell in the [`names::COL_H3INDEX_ORIGIN`] column, so the
+/// results of an origin are adjacent. The routing for a batch only happens once it is taken from
+/// the returned iterator, so only the paths of a single batch of origins are held in memory.
+///
+/// The end of the routing is recorded once all batches have been taken.
+fn h3_shortest_path_batches(
+    parameters: H3ShortestPathParameters,
+    max_rows: usize,
+    span: Span,
+) -> Result<impl Iterator<Item = Result<DataFrame, Status>> + Send + 'static, Status> {
+    let started = Instant::now();
+    let destinations = parameters.prepared_destinations().to_status_result()?;
+
+    let mut origin_cells = parameters.origins.cells.clone();
+    origin_cells.sort_unstable();
+    origin_cells.dedup();
+    let origins_per_batch = (max_rows / parameters.paths_per_origin().max(1)).max(1);
+    let mut origin_batches = origin_cells
+        .chunks(origins_per_batch)
+        .map(<[CellIndex]>::to_vec)
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    let mut routing_elapsed = started.elapsed();
+    let mut num_paths = 0;
+    let mut finished = false;
+    Ok(std::iter::from_fn(move || {
+        span.in_scope(|| loop {
+            if finished {
+                return None;
+            }
+            let Some(origin_cells) = origin_batches.next() else {
+                finished = true;
+                routing_finished(routing_elapsed, num_paths);
+                return None;
+            };
+
+            let started = Instant::now();
+            let pathmap = match parameters.shortest_path_many_to_many_prepared_map(
+                &origin_cells,
+                &destinations,
+                |path| Ok(PathSummary::from(path)),
+            ) {
+                Ok(pathmap) => pathmap,
+                // none of the origins of this batch is connected to the graph
+                Err(hexigraph::error::Error::EmptyOriginSet) => continue,
+                Err(e) => {
+                    finished = true;
+                    return Some(Err(e).to_status_result());
+                }
+            };
+            routing_elapsed += started.elapsed();
+            num_paths += pathmap.values().map(|paths| paths.len()).sum::<usize>();
+
+            match paths_dataframe(
+                &pathmap,
+                parameters.paths_per_origin(),
+                parameters.origins.dataframe.clone(),
+                parameters.destinations.dataframe.clone(),
+            ) {
+                Ok(df) if df.height() == 0 => continue,
+                result => return Some(result),
+            }
+        })
+    }))
+}
+
+/// build the dataframe of the paths of the `pathmap` and join the dataframes of the
+/// origins and destinations to it.
+///
+/// The rows are ordered by the origin cell in the [`names::COL_H3INDEX_ORIGIN`] column.
+fn paths_dataframe(
+    pathmap: &CellMap<Vec<PathSummary<CustomizedWeight>>>,
+    paths_per_origin: usize,
+    origins_dataframe: Option<CellDataFrame>,
+    destinations_dataframe: Option<CellDataFrame>,
+) -> Result<DataFrame, Status> {
     let mut shortest_path_df = {
-        let capacity = pathmap.len()
-            * parameters
-                .options
-                .num_destinations_to_reach()
-                .unwrap_or(parameters.destinations.cells.len());
+        let capacity = pathmap.len() * paths_per_origin;
 
         let mut origin_cell_vec = Vec::with_capacity(capacity);
         let mut destination_cell_vec = Vec::with_capacity(capacity);
         let mut path_cell_length_m_vec = Vec::with_capacity(capacity);
         let mut travel_duration_secs_vec = Vec::with_capacity(capacity);
         let mut edge_preferences_vec = Vec::with_capacity(capacity);
+        let mut report_field_columns = ReportFieldColumns::default();
+
+        // group the rows by origin
+        let mut origin_cells: Vec<_> = pathmap.keys().copied().collect();
+        origin_cells.sort_unstable();
 
-        for (origin_cell, paths) in pathmap.iter() {
+        for origin_cell in origin_cells.iter() {
+            let paths = &pathmap[origin_cell];
             if paths.is_empty() {
                 // keep one entry for the origin regardless if a route to a
                 // destination was found.
@@ -132,6 +411,7 @@ fn h3_shortest_path_internal(parameters: H3ShortestPathParameters) -> Result<Dat
                 path_cell_length_m_vec.push(None);
                 travel_duration_secs_vec.push(None);
                 edge_preferences_vec.push(None);
+                report_field_columns.push(Vec::new());
             } else {
                 for path_summary in paths.iter() {
                     origin_cell_vec.push(u64::from(*origin_cell));
@@ -140,37 +420,48 @@ fn h3_shortest_path_internal(parameters: H3ShortestPathParameters) -> Result<Dat
                     travel_duration_secs_vec
                         .push(Some(path_summary.cost.travel_duration().get::<second>()));
                     edge_preferences_vec.push(Some(path_summary.cost.edge_preference()));
+                    report_field_columns.push(path_summary.cost.report_fields());
                 }
             }
         }
-        DataFrame::new(vec![
+        let mut columns = vec![
             Series::new(names::COL_H3INDEX_ORIGIN, origin_cell_vec),
             Series::new(names::COL_H3INDEX_DESTINATION, destination_cell_vec),
             Series::new(names::COL_PATH_LENGTH_METERS, path_cell_length_m_vec),
             Series::new(names::COL_TRAVEL_DURATION_SECS, travel_duration_secs_vec),
             Series::new(names::COL_EDGE_PREFERENCE, edge_preferences_vec),
-        ])
-        .to_status_result()?
+        ];
+        columns.extend(report_field_columns.into_series());
+        DataFrame::new(columns).to_status_result()?
     };
 
-    if let Some(origin_h3df) = parameters.origins.dataframe {
+    let mut joined = false;
+    if let Some(origin_h3df) = origins_dataframe {
         inner_join_h3dataframe(
             &mut shortest_path_df,
             names::COL_H3INDEX_ORIGIN,
             origin_h3df,
             "origin_",
         )?;
+        joined = true;
     }
 
-    if let Some(destination_h3df) = parameters.destinations.dataframe {
+    if let Some(destination_h3df) = destinations_dataframe {
         inner_join_h3dataframe(
             &mut shortest_path_df,
             names::COL_H3INDEX_DESTINATION,
             destination_h3df,
             "dest_",
         )?;
+        joined = true;
     }
 
+    if joined {
+        // joins do not guarantee to keep the order of the rows
+        shortest_path_df = shortest_path_df
+            .sort([names::COL_H3INDEX_ORIGIN], false, true)
+            .to_status_result()?;
+    }
     Ok(shortest_path_df)
 }
 
@@ -183,23 +474,319 @@ where
     E: Debug + Send + 'static + StatusCodeAndMessage,
     F: FnMut(Path<CustomizedWeight>) -> Result<R, E> + Send + 'static,
 {
-    let routes = spawn_h3_shortest_path(move || {
+    let span = parameters.span("h3_shortest_path_routes");
+    let stream_channel_capacity = parameters.stream_channel_capacity;
+    let routes = spawn_h3_shortest_path(span, move || {
+        let started = Instant::now();
         parameters
-            .graph
-            .shortest_path_many_to_many(
-                &parameters.origins.cells,
-                &parameters.destinations.cells,
-                &parameters.options,
-            )
+            .shortest_path_many_to_many_map(Ok)
             .map(|pathmap| {
-                pathmap
+                routing_finished(
+                    started.elapsed(),
+                    pathmap.values().map(|paths| paths.len()).sum(),
+                );
+                parameters
+                    .page
+                    .select(pathmap.into_iter().flat_map(|(_k, v)| v).collect())
                     .into_iter()
-                    .flat_map(|(_k, v)| v)
                     .map(transformer)
                     .collect::<Result<Vec<_>, _>>()
                     .to_status_result()
             })
     })
     .await??;
-    stream_routes(routes).await
+    stream_routes(routes, stream_channel_capacity).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use float_cmp::approx_eq;
+    use h3o::{CellIndex, LatLng, Resolution};
+    use hexigraph::algorithm::graph::path::{DirectedEdgePath, Path};
+    use hexigraph::container::treemap::H3Treemap;
+    use hexigraph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use polars::prelude::DataFrame;
+    use tracing::Span;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::customization::{CustomizedGraph, CustomizedWeight};
+    use crate::grpc::api::generated::ShortestPathOptions;
+    use crate::grpc::names;
+    use crate::grpc::LoadedCellSelection;
+    use crate::io::GraphKey;
+    use crate::weight::{StandardWeight, Weight};
+
+    use super::{
+        h3_shortest_path_batches, H3ShortestPathParameters, Page, PathSummary, ReportFieldColumns,
+    };
+
+    /// parameters for routing within a graph of all cells within 4 cells of `center`
+    fn grid_parameters(
+        center: CellIndex,
+        origins: Vec<CellIndex>,
+        destinations: Vec<CellIndex>,
+    ) -> H3ShortestPathParameters {
+        let mut graph = H3EdgeGraph::new(center.resolution());
+        for cell in center.grid_disk::<Vec<_>>(4) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        H3ShortestPathParameters {
+            graph: CustomizedGraph::from(Arc::new(prepared_graph)),
+            graph_key: GraphKey {
+                name: "test".to_string(),
+                h3_resolution: center.resolution(),
+            },
+            options: Default::default(),
+            origins: LoadedCellSelection {
+                cells: origins,
+                dataframe: None,
+            },
+            destinations: LoadedCellSelection {
+                cells: destinations,
+                dataframe: None,
+            },
+            avoid_cells: None,
+            page: Default::default(),
+            stream_channel_capacity: 5,
+        }
+    }
+
+    /// route from all origins in a single batch
+    fn single_batch(parameters: H3ShortestPathParameters) -> DataFrame {
+        let mut batches = h3_shortest_path_batches(parameters, usize::MAX, Span::none())
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        batches.remove(0)
+    }
+
+    fn cost(parameters: &H3ShortestPathParameters, origin: CellIndex) -> CustomizedWeight {
+        let pathmap = parameters
+            .shortest_path_many_to_many_map(|path| Ok(PathSummary::from(path)))
+            .unwrap();
+        assert_eq!(pathmap.len(), 1);
+        pathmap.get(&origin).unwrap()[0].cost
+    }
+
+    #[test]
+    fn avoid_cells_forces_detour() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+
+        // origin and destination on opposite sides of the center
+        let ring: Vec<_> = center
+            .grid_disk::<Vec<_>>(2)
+            .into_iter()
+            .filter(|cell| cell.grid_distance(center) == Ok(2))
+            .collect();
+        let origin = ring[0];
+        let destination = *ring
+            .iter()
+            .max_by_key(|cell| cell.grid_distance(origin).unwrap())
+            .unwrap();
+
+        let mut parameters = grid_parameters(center, vec![origin], vec![destination]);
+        let direct_cost = cost(&parameters, origin);
+
+        parameters.avoid_cells = Some(H3Treemap::from_iter(center.grid_disk::<Vec<_>>(1)));
+        let detour_cost = cost(&parameters, origin);
+
+        assert!(detour_cost > direct_cost);
+    }
+
+    #[test]
+    fn weight_scale_reflected_in_costs() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let destination = center
+            .grid_disk::<Vec<_>>(3)
+            .into_iter()
+            .find(|cell| cell.grid_distance(center) == Ok(3))
+            .unwrap();
+
+        // the three edges of the path take 10 seconds each
+        let mut parameters = grid_parameters(center, vec![center], vec![destination]);
+        let unscaled = cost(&parameters, center).travel_duration().value;
+
+        parameters.graph.set_weight_scale(1.2);
+        let scaled = cost(&parameters, center).travel_duration().value;
+        assert!(approx_eq!(f32, scaled - unscaled, 6.0, epsilon = 0.001));
+
+        parameters.options.weight_scale = Some(0.0);
+        assert!(parameters.options.weight_scaling().is_err());
+    }
+
+    #[test]
+    fn num_destinations_to_reach_clamped() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let mut parameters = grid_parameters(center, vec![center], center.grid_disk(2));
+        parameters.options = ShortestPathOptions {
+            num_destinations_to_reach: 100,
+            ..Default::default()
+        };
+        parameters.options.clamp_num_destinations_to_reach(Some(3));
+        assert_eq!(parameters.options.num_destinations_to_reach, 3);
+
+        let pathmap = parameters.shortest_path_many_to_many_map(Ok).unwrap();
+        assert_eq!(pathmap.get(&center).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn multiple_origins_grouped_by_origin() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let ring: Vec<_> = center
+            .grid_disk::<Vec<_>>(2)
+            .into_iter()
+            .filter(|cell| cell.grid_distance(center) == Ok(2))
+            .collect();
+        let origins = vec![ring[0], ring[6]];
+        let destinations = vec![center, ring[3]];
+
+        let df = single_batch(grid_parameters(center, origins.clone(), destinations));
+        assert_eq!(df.height(), 4);
+
+        let origin_column: Vec<_> = df
+            .column(names::COL_H3INDEX_ORIGIN)
+            .unwrap()
+            .u64()
+            .unwrap()
+            .into_no_null_iter()
+            .collect();
+        let mut expected_origins: Vec<_> = origins.iter().map(|cell| u64::from(*cell)).collect();
+        expected_origins.sort_unstable();
+        assert_eq!(origin_column[..2], [expected_origins[0]; 2]);
+        assert_eq!(origin_column[2..], [expected_origins[1]; 2]);
+
+        let destination_column = df
+            .column(names::COL_H3INDEX_DESTINATION)
+            .unwrap()
+            .u64()
+            .unwrap();
+        let duration_column = df
+            .column(names::COL_TRAVEL_DURATION_SECS)
+            .unwrap()
+            .f32()
+            .unwrap();
+        // every edge of the grid takes 10 seconds, the travel durations only differ by that
+        let offsets: Vec<_> = origin_column
+            .iter()
+            .zip(destination_column.into_no_null_iter())
+            .zip(duration_column.into_no_null_iter())
+            .map(|((origin, destination), duration)| {
+                let origin = CellIndex::try_from(*origin).unwrap();
+                let destination = CellIndex::try_from(destination).unwrap();
+                duration - origin.grid_distance(destination).unwrap() as f32 * 10.0
+            })
+            .collect();
+        for offset in offsets.iter() {
+            assert!(approx_eq!(f32, *offset, offsets[0], epsilon = 0.001));
+        }
+    }
+
+    #[test]
+    fn batches_reassemble_to_single_run() {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let ring: Vec<_> = center
+            .grid_disk::<Vec<_>>(2)
+            .into_iter()
+            .filter(|cell| cell.grid_distance(center) == Ok(2))
+            .collect();
+        let mut origins = ring.clone();
+        // not connected to the graph
+        origins.push(
+            center
+                .grid_disk::<Vec<_>>(7)
+                .into_iter()
+                .find(|cell| cell.grid_distance(center) == Ok(7))
+                .unwrap(),
+        );
+        let destinations = vec![center, ring[3], ring[9]];
+
+        let expected = single_batch(grid_parameters(
+            center,
+            origins.clone(),
+            destinations.clone(),
+        ));
+        assert_eq!(expected.height(), ring.len() * destinations.len());
+
+        // two origins per batch
+        let batches = h3_shortest_path_batches(
+            grid_parameters(center, origins, destinations),
+            7,
+            Span::none(),
+        )
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+        assert!(batches.len() >= ring.len() / 2);
+        assert!(batches.iter().all(|batch| batch.height() <= 7));
+
+        let mut reassembled = batches[0].clone();
+        for batch in batches.iter().skip(1) {
+            reassembled.vstack_mut(batch).unwrap();
+        }
+        assert!(reassembled.frame_equal_missing(&expected));
+    }
+    #[test]
+    fn report_field_columns_padded_with_nulls() {
+        let mut columns = ReportFieldColumns::default();
+        columns.push(Vec::new());
+        columns.push(vec![("toll".to_string(), 2.5)]);
+        columns.push(Vec::new());
+
+        let series: Vec<_> = columns.into_series().collect();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].name(), "toll");
+        assert_eq!(
+            series[0].f64().unwrap().into_iter().collect::<Vec<_>>(),
+            vec![None, Some(2.5), None]
+        );
+    }
+
+    #[test]
+    fn pages_cover_all_paths_without_overlap() {
+        let origin = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let paths: Vec<_> = origin
+            .grid_disk::<Vec<_>>(2)
+            .into_iter()
+            .map(|destination| {
+                Path::new(
+                    origin,
+                    destination,
+                    1,
+                    DirectedEdgePath::OriginIsDestination(origin),
+                )
+            })
+            .collect();
+        assert_eq!(paths.len(), 19);
+
+        let page_size = 10;
+        let first_page = Page {
+            offset: 0,
+            limit: Some(page_size),
+        }
+        .select(paths.clone());
+        let second_page = Page {
+            offset: page_size,
+            limit: Some(page_size),
+        }
+        .select(paths.clone());
+        assert_eq!(first_page.len(), 10);
+        assert_eq!(second_page.len(), 9);
+
+        let mut paged: Vec<_> = first_page
+            .iter()
+            .chain(second_page.iter())
+            .map(|path| path.destination_cell)
+            .collect();
+        assert!(paged.windows(2).all(|w| w[0] < w[1]));
+        paged.dedup();
+        assert_eq!(paged.len(), paths.len());
+    }
 }
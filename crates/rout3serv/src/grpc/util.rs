@@ -1,21 +1,24 @@
 //! utility functions to use within the grpc context, most of them
 //! return a `tonic::Status` on error and a somewhat useful error message + logging.
 
+use std::time::{Duration, Instant};
+
 use h3o::{CellIndex, Resolution};
 use hexigraph::algorithm::resolution::transform_resolution;
 use itertools::Itertools;
 use polars::prelude::{DataFrame, DataFrameJoinOps, IpcWriter, JoinType, SerWriter};
 use polars_core::prelude::JoinArgs;
 use tokio::sync::mpsc;
-use tokio::task::block_in_place;
+use tokio::task::{block_in_place, JoinHandle};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Code, Response, Status};
-use tracing::{debug, warn};
+use tracing::{debug, info, info_span, warn, Span};
 
 use crate::grpc::api::generated::ArrowIpcChunk;
 use crate::grpc::api::Route;
 use crate::grpc::error::ToStatusResult;
 use crate::io::dataframe::CellDataFrame;
+use crate::io::GraphKey;
 
 /// wrapper around tokios `spawn_blocking` to directly
 /// return the `JoinHandle` as a tonic `Status`.
@@ -24,7 +27,42 @@ where
     F: FnOnce() -> R + Send + 'static,
     R: Send + 'static,
 {
-    tokio::task::spawn_blocking(f).await.to_status_result()
+    let started = Instant::now();
+    let result = tokio::task::spawn_blocking(f).await.to_status_result();
+    crate::metrics::compute_duration(started.elapsed());
+    result
+}
+
+/// span to correlate all events emitted during a routing computation
+/// with the request it belongs to.
+///
+/// `num_destinations` and `max_distance_to_graph` are left empty for operations
+/// without destination cells or without snapping the cells to the graph.
+pub fn routing_span(
+    operation: &'static str,
+    graph_key: &GraphKey,
+    num_origins: usize,
+    num_destinations: Option<usize>,
+    max_distance_to_graph: Option<u32>,
+) -> Span {
+    info_span!(
+        "routing",
+        operation,
+        graph_name = %graph_key.name,
+        h3_resolution = u8::from(graph_key.h3_resolution),
+        num_origins,
+        num_destinations,
+        max_distance_to_graph,
+    )
+}
+
+/// emit an event with the `elapsed` compute time. To be called from within
+/// a `routing_span` once the computation is done.
+pub fn routing_finished(elapsed: Duration, cells_reached: usize) {
+    info!(
+        elapsed_ms = elapsed.as_millis() as u64,
+        cells_reached, "routing finished"
+    );
 }
 
 pub trait StrId {
@@ -37,20 +75,46 @@ pub type ArrowIpcChunkStream = ReceiverStream<Result<ArrowIpcChunk, Status>>;
 /// stream `RouteWKB` instances
 pub async fn stream_routes<R>(
     routewkbs: Vec<R>,
+    channel_capacity: usize,
 ) -> Result<Response<ReceiverStream<Result<R, Status>>>, Status>
 where
     R: Route + Send + 'static,
 {
-    let (tx, rx) = mpsc::channel(5);
+    let (tx, rx) = mpsc::channel(channel_capacity);
+    spawn_send_all(tx, routewkbs.into_iter().map(Ok), "routes");
+    Ok(Response::new(ReceiverStream::new(rx)))
+}
+
+/// send all `items` to `tx` from a separate task.
+///
+/// An item is only taken from the iterator after capacity in the channel has been
+/// reserved, so lazily produced items do not get computed ahead of a slow client. Stops
+/// early when the receiver was dropped. The task returns the number of items sent.
+pub fn spawn_send_all<T, I>(tx: mpsc::Sender<T>, items: I, what: &'static str) -> JoinHandle<usize>
+where
+    T: Send + 'static,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: Send + 'static,
+{
+    let mut items = items.into_iter();
     tokio::spawn(async move {
-        for routewkb in routewkbs {
-            if let Err(e) = tx.send(Ok(routewkb)).await {
-                warn!("Streaming routes aborted. reason: {}", e);
-                break;
+        let mut num_sent = 0;
+        loop {
+            let permit = match tx.reserve().await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    warn!("Streaming {} aborted. reason: {}", what, e);
+                    break;
+                }
+            };
+            match items.next() {
+                Some(item) => permit.send(item),
+                None => break,
             }
+            num_sent += 1;
         }
-    });
-    Ok(Response::new(ReceiverStream::new(rx)))
+        num_sent
+    })
 }
 
 pub fn change_cell_resolution_dedup(
@@ -63,12 +127,16 @@ pub fn change_cell_resolution_dedup(
     out_cells
 }
 
+/// max. number of rows of a dataframe to send within a single chunk.
+pub const STREAM_MAX_ROWS: usize = 3000;
+
 #[inline]
 pub async fn stream_dataframe(
     id: String,
     dataframe: DataFrame,
+    channel_capacity: usize,
 ) -> Result<Response<ArrowIpcChunkStream>, Status> {
-    stream_dataframe_with_max_rows(id, dataframe, 3000).await
+    stream_dataframe_with_max_rows(id, dataframe, STREAM_MAX_ROWS, channel_capacity).await
 }
 
 /// respond with a dataframe as a stream of size limited Arrow IPC chunks.
@@ -79,6 +147,7 @@ pub async fn stream_dataframe_with_max_rows(
     id: String,
     dataframe: DataFrame,
     max_rows: usize,
+    channel_capacity: usize,
 ) -> Result<Response<ArrowIpcChunkStream>, Status> {
     let df_shape = dataframe.shape();
     debug!(
@@ -98,28 +167,60 @@ pub async fn stream_dataframe_with_max_rows(
         i += 1;
     }
 
-    let (tx, rx) = mpsc::channel(5);
-    tokio::spawn(async move {
-        for mut df_part in dataframe_parts {
-            let serialization_result = block_in_place(|| dataframe_to_bytes(&mut df_part))
-                .to_status_result_with_message(Code::Internal, || {
-                    "serializing dataframe failed".to_string()
-                })
-                .map(|ipc_bytes| ArrowIpcChunk {
-                    object_id: id.clone(),
-                    data: ipc_bytes,
-                });
-            if let Err(e) = tx.send(serialization_result).await {
-                warn!("Streaming dataframe parts aborted. reason: {}", e);
-                break;
-            }
+    let (tx, rx) = mpsc::channel(channel_capacity);
+    let chunks = dataframe_parts.into_iter().map(move |mut df_part| {
+        block_in_place(|| dataframe_to_bytes(&mut df_part))
+            .to_status_result_with_message(Code::Internal, || {
+                "serializing dataframe failed".to_string()
+            })
+            .map(|ipc_bytes| ArrowIpcChunk {
+                object_id: id.clone(),
+                data: ipc_bytes,
+            })
+    });
+    spawn_send_all(tx, chunks, "dataframe parts");
+    Ok(Response::new(ReceiverStream::new(rx)))
+}
+
+/// respond with a stream of Arrow IPC chunks, one for each dataframe produced by `batches`.
+///
+/// In contrast to [`stream_dataframe`] the batches are only computed once there is capacity
+/// in the response stream, so the complete result never needs to be held in memory at once.
+/// The stream ends after the first failed batch.
+pub async fn stream_dataframe_batches<I>(
+    id: String,
+    batches: I,
+    channel_capacity: usize,
+) -> Result<Response<ArrowIpcChunkStream>, Status>
+where
+    I: IntoIterator<Item = Result<DataFrame, Status>>,
+    I::IntoIter: Send + 'static,
+{
+    let mut batches = batches.into_iter();
+    let mut failed = false;
+    let chunks = std::iter::from_fn(move || {
+        if failed {
+            return None;
         }
+        let ipc_bytes = block_in_place(|| {
+            batches
+                .next()
+                .map(|batch| batch.and_then(|mut df| dataframe_to_bytes(&mut df)))
+        })?;
+        failed = ipc_bytes.is_err();
+        Some(ipc_bytes.map(|data| ArrowIpcChunk {
+            object_id: id.clone(),
+            data,
+        }))
     });
+
+    let (tx, rx) = mpsc::channel(channel_capacity);
+    spawn_send_all(tx, chunks, "dataframe batches");
     Ok(Response::new(ReceiverStream::new(rx)))
 }
 
 /// serialize a [`DataFrame`] into arrow IPC format
-fn dataframe_to_bytes(dataframe: &mut DataFrame) -> Result<Vec<u8>, Status> {
+pub fn dataframe_to_bytes(dataframe: &mut DataFrame) -> Result<Vec<u8>, Status> {
     let mut buf: Vec<u8> = Vec::with_capacity(30_000);
     IpcWriter::new(&mut buf)
         .finish(dataframe)
@@ -171,3 +272,72 @@ pub fn inner_join_h3dataframe(
         })?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use h3o::Resolution;
+    use tokio::sync::mpsc;
+    use tracing_test::traced_test;
+
+    use crate::io::GraphKey;
+
+    use super::{routing_finished, routing_span, spawn_send_all};
+
+    #[traced_test]
+    #[test]
+    fn routing_span_records_fields() {
+        let graph_key = GraphKey {
+            name: "test-graph".to_string(),
+            h3_resolution: Resolution::Seven,
+        };
+        routing_span("h3_shortest_path", &graph_key, 3, Some(2), Some(1))
+            .in_scope(|| routing_finished(Duration::from_millis(3), 5));
+
+        assert!(logs_contain("graph_name=test-graph"));
+        assert!(logs_contain("h3_resolution=7"));
+        assert!(logs_contain("num_origins=3"));
+        assert!(logs_contain("num_destinations=2"));
+        assert!(logs_contain("max_distance_to_graph=1"));
+        assert!(logs_contain("cells_reached=5"));
+        assert!(logs_contain("elapsed_ms="));
+    }
+
+    #[traced_test]
+    #[test]
+    fn routing_span_without_destinations() {
+        let graph_key = GraphKey {
+            name: "test-graph".to_string(),
+            h3_resolution: Resolution::Seven,
+        };
+        routing_span("within_threshold", &graph_key, 3, None, None)
+            .in_scope(|| routing_finished(Duration::from_millis(3), 5));
+
+        assert!(logs_contain("num_origins=3"));
+        assert!(!logs_contain("num_destinations="));
+        assert!(!logs_contain("max_distance_to_graph="));
+        assert!(logs_contain("cells_reached=5"));
+    }
+
+    #[tokio::test]
+    async fn send_all_stops_on_dropped_receiver() {
+        let (tx, mut rx) = mpsc::channel(2);
+        let producer = spawn_send_all(tx, 0..1_000_000, "numbers");
+        assert_eq!(rx.recv().await, Some(0));
+        drop(rx);
+
+        let num_sent = producer.await.unwrap();
+        assert!(num_sent < 10);
+    }
+
+    #[tokio::test]
+    async fn send_all_sends_everything() {
+        let (tx, rx) = mpsc::channel(2);
+        let producer = spawn_send_all(tx, 0..100, "numbers");
+        let received: Vec<_> =
+            tokio_stream::StreamExt::collect(tokio_stream::wrappers::ReceiverStream::new(rx)).await;
+        assert_eq!(received, (0..100).collect::<Vec<_>>());
+        assert_eq!(producer.await.unwrap(), 100);
+    }
+}
@@ -1,4 +1,8 @@
-use hexigraph::algorithm::graph::WithinWeightThresholdMany;
+use std::time::Instant;
+
+use h3o::CellIndex;
+use hexigraph::algorithm::graph::within_weight_threshold::threshold_band;
+use hexigraph::algorithm::graph::{WithinWeightThresholdMany, WithinWeightThresholdOptions};
 use hexigraph::HasH3Resolution;
 use polars::prelude::{DataFrame, NamedFrom, Series};
 use tonic::{Code, Response, Status};
@@ -7,30 +11,74 @@ use uom::si::f32::Time;
 use uom::si::time::second;
 
 use crate::customization::{CustomizedGraph, CustomizedWeight};
+use crate::grpc::api::generated;
 use crate::grpc::error::{logged_status, ToStatusResult};
 use crate::grpc::util::{
-    inner_join_h3dataframe, spawn_blocking_status, stream_dataframe, ArrowIpcChunkStream,
+    inner_join_h3dataframe, routing_finished, routing_span, spawn_blocking_status,
+    stream_dataframe_batches, ArrowIpcChunkStream, STREAM_MAX_ROWS,
 };
 use crate::grpc::{LoadedCellSelection, ServerImpl};
+use crate::io::dataframe::{CellDataFrame, CellGeometry, CellGeometryCrs};
+use crate::io::GraphKey;
 use crate::weight::Weight;
 
 use super::names;
 
 pub enum Threshold {
     TravelDuration(Time),
+
+    /// ascending travel durations. Each cell gets assigned the smallest one it is reachable within.
+    TravelDurationBands(Vec<Time>),
 }
 
 pub struct H3WithinThresholdParameters {
     pub graph: CustomizedGraph,
+    pub graph_key: GraphKey,
     pub origins: LoadedCellSelection,
     pub threshold: Threshold,
+
+    /// add a geometry column for the cells to the output
+    pub cell_geometry: Option<CellGeometry>,
+
+    /// coordinate reference system of the `cell_geometry`
+    pub cell_geometry_crs: CellGeometryCrs,
+
+    /// how to include the origins in the output
+    pub include_origin: Option<bool>,
+
+    /// buffer size of the response stream
+    pub stream_channel_capacity: usize,
+}
+
+/// validate and convert the `travel_duration_secs_bands` of a request.
+pub(crate) fn travel_duration_bands(
+    travel_duration_secs_bands: &[f32],
+) -> Result<Vec<Time>, Status> {
+    let valid = !travel_duration_secs_bands.is_empty()
+        && travel_duration_secs_bands
+            .iter()
+            .all(|secs| secs.is_normal() && *secs > 0.0)
+        && travel_duration_secs_bands.windows(2).all(|w| w[0] < w[1]);
+    if !valid {
+        return Err(logged_status!(
+            "travel_duration_secs_bands must be positive and strictly ascending",
+            Code::InvalidArgument,
+            Level::DEBUG
+        ));
+    }
+    Ok(travel_duration_secs_bands
+        .iter()
+        .map(|secs| Time::new::<second>(*secs))
+        .collect())
 }
 
 pub(crate) async fn create_parameters(
     request: super::api::generated::H3WithinThresholdRequest,
     server_impl: &ServerImpl,
 ) -> Result<H3WithinThresholdParameters, Status> {
-    let threshold = if request.travel_duration_secs_threshold.is_normal()
+    let threshold = if !request.travel_duration_secs_bands.is_empty() {
+        Threshold::TravelDurationBands(travel_duration_bands(&request.travel_duration_secs_bands)?)
+    } else if request.travel_duration_secs_threshold.is_normal()
         && request.travel_duration_secs_threshold > 0.0
     {
         Threshold::TravelDuration(Time::new::<second>(request.travel_duration_secs_threshold))
@@ -41,14 +89,19 @@ pub(crate) async fn create_parameters(
             Level::DEBUG
         ));
     };
+    let cell_geometry = match request.cell_geometry() {
+        generated::CellGeometry::None => None,
+        generated::CellGeometry::Centroid => Some(CellGeometry::Centroid),
+        generated::CellGeometry::Boundary => Some(CellGeometry::Boundary),
+    };
     let routing_mode = server_impl.config.get_routing_mode(&request.routing_mode)?;
-    let graph = server_impl
+    let (graph, graph_key) = server_impl
         .retrieve_graph_by_handle(&request.graph_handle)
         .await
-        .map(|(graph, _)| {
+        .map(|(graph, graph_key)| {
             let mut cg = CustomizedGraph::from(graph);
             cg.set_routing_mode(routing_mode);
-            cg
+            (cg, graph_key)
         })?;
 
     let origins = server_impl
@@ -57,31 +110,68 @@ pub(crate) async fn create_parameters(
 
     Ok(H3WithinThresholdParameters {
         graph,
+        graph_key,
         origins,
         threshold,
+        cell_geometry,
+        cell_geometry_crs: if request.cell_geometry_webmercator {
+            CellGeometryCrs::WebMercator
+        } else {
+            CellGeometryCrs::Wgs84
+        },
+        include_origin: request.include_origin,
+        stream_channel_capacity: server_impl.config.stream_channel_capacity,
     })
 }
 
 pub async fn within_threshold(
     parameters: H3WithinThresholdParameters,
 ) -> Result<Response<ArrowIpcChunkStream>, Status> {
-    stream_dataframe(
+    let span = routing_span(
+        "within_threshold",
+        &parameters.graph_key,
+        parameters.origins.cells.len(),
+        None,
+        None,
+    );
+    let stream_channel_capacity = parameters.stream_channel_capacity;
+    let batches = spawn_blocking_status(move || {
+        span.in_scope(|| within_threshold_batches(parameters, STREAM_MAX_ROWS))
+    })
+    .await??;
+    stream_dataframe_batches(
         uuid::Uuid::new_v4().to_string(),
-        spawn_blocking_status(move || within_threshold_internal(parameters))
-            .await?
-            .to_status_result_with_message(Code::Internal, || {
-                "calculating within threshold failed".to_string()
-            })?,
+        batches,
+        stream_channel_capacity,
     )
     .await
 }
 
-fn within_threshold_internal(parameters: H3WithinThresholdParameters) -> Result<DataFrame, Status> {
-    let threshold_weight = match parameters.threshold {
+/// find the cells within the threshold and build the dataframes in batches of at most
+/// `max_rows` cells.
+///
+/// The traversal of the graph happens upfront, but the dataframe for a batch - including the
+/// cell geometries and the joined origin columns - is only built once it is taken from the
+/// returned iterator. At least one batch is returned, so the schema reaches the client even
+/// when no cells have been found.
+fn within_threshold_batches(
+    parameters: H3WithinThresholdParameters,
+    max_rows: usize,
+) -> Result<impl Iterator<Item = Result<DataFrame, Status>> + Send + 'static, Status> {
+    let started = Instant::now();
+    let bands: Vec<_> = match parameters.threshold {
         Threshold::TravelDuration(travel_duration) => {
-            CustomizedWeight::from_travel_duration(travel_duration)
+            vec![CustomizedWeight::from_travel_duration(travel_duration)]
         }
+        Threshold::TravelDurationBands(travel_durations) => travel_durations
+            .into_iter()
+            .map(CustomizedWeight::from_travel_duration)
+            .collect(),
     };
+    // a single traversal up to the largest threshold covers all bands
+    let threshold_weight = *bands
+        .last()
+        .ok_or_else(|| logged_status!("no threshold given", Code::InvalidArgument, Level::DEBUG))?;
 
     // use the minimum weight encountered
     let agg_fn = |existing: &mut CustomizedWeight, new: CustomizedWeight| {
@@ -92,20 +182,55 @@ fn within_threshold_internal(parameters: H3WithinThresholdParameters) -> Result<
 
     let cellmap = parameters
         .graph
-        .cells_within_weight_threshold_many(&parameters.origins.cells, threshold_weight, agg_fn)
+        .cells_within_weight_threshold_many_with_options(
+            &parameters.origins.cells,
+            threshold_weight,
+            &WithinWeightThresholdOptions {
+                include_origin: parameters.include_origin,
+            },
+            agg_fn,
+        )
         .to_status_result_with_message(Code::Internal, || {
             "isolating cells within threshold failed".to_string()
         })?;
+    routing_finished(started.elapsed(), cellmap.len());
+
+    let cells: Vec<_> = cellmap.into_iter().collect();
+    let max_rows = max_rows.max(1);
+    let num_batches = cells.len().div_ceil(max_rows).max(1);
+    let cell_geometry = parameters.cell_geometry;
+    let cell_geometry_crs = parameters.cell_geometry_crs;
+    let origin_h3df = parameters.origins.dataframe;
+    Ok((0..num_batches).map(move |batch_i| {
+        let batch_cells = &cells
+            [(batch_i * max_rows).min(cells.len())..((batch_i + 1) * max_rows).min(cells.len())];
+        within_threshold_dataframe(
+            batch_cells,
+            &bands,
+            cell_geometry,
+            cell_geometry_crs,
+            origin_h3df.clone(),
+        )
+    }))
+}
 
-    let capacity = cellmap.len();
-    let (cell_h3indexes, travel_duration_secs, edge_preferences) = cellmap.iter().fold(
+/// build the dataframe for the `cells` found within the largest of the `bands`
+fn within_threshold_dataframe(
+    cells: &[(CellIndex, CustomizedWeight)],
+    bands: &[CustomizedWeight],
+    cell_geometry: Option<CellGeometry>,
+    cell_geometry_crs: CellGeometryCrs,
+    origin_h3df: Option<CellDataFrame>,
+) -> Result<DataFrame, Status> {
+    let capacity = cells.len();
+    let (cell_h3indexes, travel_duration_secs, edge_preferences) = cells.iter().fold(
         (
             Vec::with_capacity(capacity),
             Vec::with_capacity(capacity),
             Vec::with_capacity(capacity),
         ),
         |mut acc, item| {
-            acc.0.push(u64::from(*item.0));
+            acc.0.push(u64::from(item.0));
             acc.1.push(item.1.travel_duration().get::<second>());
             acc.2.push(item.1.edge_preference());
             acc
@@ -119,9 +244,106 @@ fn within_threshold_internal(parameters: H3WithinThresholdParameters) -> Result<
     ])
     .to_status_result()?;
 
+    // emitted for a single threshold as well, so clients get the same schema for both
+    let band_secs: Vec<_> = cells
+        .iter()
+        .map(|(_, weight)| {
+            threshold_band(bands, weight).map(|band| bands[band].travel_duration().get::<second>())
+        })
+        .collect();
+    df.with_column(Series::new(names::COL_TRAVEL_DURATION_SECS_BAND, band_secs))
+        .to_status_result()?;
+
+    if let Some(cell_geometry) = cell_geometry {
+        df = CellDataFrame {
+            dataframe: df,
+            cell_column_name: names::COL_H3INDEX_ORIGIN.to_string(),
+        }
+        .to_geodataframe(cell_geometry, cell_geometry_crs, names::COL_GEOMETRY)
+        .to_status_result()?;
+    }
+
     // join origin dataframe if there is any
-    if let Some(origin_h3df) = parameters.origins.dataframe {
+    if let Some(origin_h3df) = origin_h3df {
         inner_join_h3dataframe(&mut df, names::COL_H3INDEX_ORIGIN, origin_h3df, "origin_")?;
     }
     Ok(df)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use h3o::{LatLng, Resolution};
+    use hexigraph::graph::{H3EdgeGraph, PreparedH3EdgeGraph};
+    use polars::prelude::DataFrame;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::customization::CustomizedGraph;
+    use crate::grpc::names;
+    use crate::grpc::LoadedCellSelection;
+    use crate::io::dataframe::{CellGeometry, CellGeometryCrs};
+    use crate::io::GraphKey;
+    use crate::weight::StandardWeight;
+
+    use super::{within_threshold_batches, H3WithinThresholdParameters, Threshold};
+
+    fn batches(max_rows: usize) -> Vec<DataFrame> {
+        let center = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Nine);
+        let mut graph = H3EdgeGraph::new(center.resolution());
+        for cell in center.grid_disk::<Vec<_>>(4) {
+            for edge in cell.edges() {
+                graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(10.0)));
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let parameters = H3WithinThresholdParameters {
+            graph: CustomizedGraph::from(Arc::new(prepared_graph)),
+            graph_key: GraphKey {
+                name: "test".to_string(),
+                h3_resolution: center.resolution(),
+            },
+            origins: LoadedCellSelection {
+                cells: vec![center],
+                dataframe: None,
+            },
+            threshold: Threshold::TravelDurationBands(vec![
+                Time::new::<second>(15.0),
+                Time::new::<second>(35.0),
+            ]),
+            cell_geometry: Some(CellGeometry::Centroid),
+            cell_geometry_crs: CellGeometryCrs::Wgs84,
+            include_origin: None,
+            stream_channel_capacity: 5,
+        };
+        within_threshold_batches(parameters, max_rows)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn batches_reassemble_to_single_batch() {
+        let single = batches(usize::MAX);
+        assert_eq!(single.len(), 1);
+        let expected = single[0]
+            .sort([names::COL_H3INDEX_ORIGIN], false, false)
+            .unwrap();
+        assert!(expected.height() > 5);
+
+        let batched = batches(5);
+        assert!(batched.len() > 1);
+        assert!(batched.iter().all(|df| df.height() <= 5));
+
+        let mut reassembled = batched[0].clone();
+        for df in batched.iter().skip(1) {
+            reassembled.vstack_mut(df).unwrap();
+        }
+        let reassembled = reassembled
+            .sort([names::COL_H3INDEX_ORIGIN], false, false)
+            .unwrap();
+        assert!(reassembled.frame_equal_missing(&expected));
+    }
+}
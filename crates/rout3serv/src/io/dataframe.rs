@@ -1,10 +1,20 @@
-use h3o::Resolution;
-use polars_core::prelude::{DataFrame, UInt64Chunked};
-use std::collections::HashMap;
+use geo::MapCoords;
+use geo_types::{Coord, Geometry, Point};
+use h3o::geom::ToGeo;
+use h3o::{CellIndex, LatLng, Resolution};
+use hexigraph::container::CellMap;
+use polars_core::prelude::{
+    BinaryChunked, BooleanChunked, DataFrame, DataType, IntoSeries, NamedFrom, Series,
+    UInt64Chunked,
+};
+use polars_core::utils::concat_df;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use serde::Deserialize;
-use tracing::error;
+use tracing::debug;
 
+use crate::geo::webmercator::wgs84_to_webmercator;
+use crate::geo::wkb::to_wkb;
 use crate::io::format::FileFormat;
 use crate::io::Error;
 use crate::io::Error::MissingCellColumn;
@@ -12,10 +22,64 @@ use crate::io::Error::MissingCellColumn;
 #[derive(Deserialize)]
 pub struct DataframeDataset {
     pub key_pattern: String,
-    /// maps data resolutions to the file h3 resolutions
+    /// maps data resolutions to the file h3 resolutions.
+    ///
+    /// Resolutions which are not configured are detected from the keys of the files
+    /// of the dataset.
+    #[serde(default)]
     pub resolutions: HashMap<Resolution, Resolution>,
 
-    pub h3index_column_name: String,
+    /// name of the column containing the cells. Takes priority over the
+    /// `cell_column_candidates`.
+    #[serde(default)]
+    pub h3index_column_name: Option<String>,
+
+    /// names of columns tried in order when the `h3index_column_name` is not set or
+    /// not present in the files of the dataset.
+    #[serde(default = "default_cell_column_candidates")]
+    pub cell_column_candidates: Vec<String>,
+
+    /// numeric column to aggregate over the reached destinations in the
+    /// differential shortest path statistics. For example the population.
+    #[serde(default)]
+    pub value_column_name: Option<String>,
+
+    /// how to merge the rows of cells contained in multiple files of the dataset
+    #[serde(default)]
+    pub overlap_strategy: OverlapStrategy,
+}
+
+fn default_cell_column_candidates() -> Vec<String> {
+    ["h3index", "h3", "cell", "h3_cell"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+/// How to merge multiple rows of the same cell.
+///
+/// `Sum`, `Max` and `Mean` are applied to all numeric columns, all other
+/// columns keep the value of the first row. With `Mean` the numeric columns are
+/// always of type `Float64`, the other strategies keep the type of the column.
+#[derive(Deserialize, Clone, Copy, Default, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapStrategy {
+    /// keep the first row
+    #[default]
+    First,
+    Sum,
+    Max,
+    Mean,
+}
+
+impl OverlapStrategy {
+    /// the type of a numeric column of type `dtype` after merging its rows
+    fn aggregated_dtype(self, dtype: &DataType) -> DataType {
+        match self {
+            Self::Mean => DataType::Float64,
+            _ => dtype.clone(),
+        }
+    }
 }
 
 impl DataframeDataset {
@@ -29,37 +93,164 @@ impl DataframeDataset {
         Ok(())
     }
 
-    pub fn file_h3_resolution(&self, data_h3_resolution: Resolution) -> Result<Resolution, Error> {
-        self.resolutions
-            .get(&data_h3_resolution)
-            .copied()
-            .ok_or_else(|| {
-                error!(
-                    "unsupported h3 resolution for building a key: {}",
-                    data_h3_resolution
-                );
-                Error::UnsupportedH3Resolution(data_h3_resolution)
-            })
+    /// names of the columns to try as the cell column, in the order of their priority.
+    pub fn cell_column_names(&self) -> Vec<String> {
+        self.h3index_column_name
+            .iter()
+            .chain(self.cell_column_candidates.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// the constant part of the `key_pattern` up to the last `/` before the first placeholder.
+    ///
+    /// All files of the dataset are stored below this prefix.
+    pub fn key_prefix(&self) -> &str {
+        let constant_part = self
+            .key_pattern
+            .split_once('{')
+            .map(|(constant_part, _)| constant_part)
+            .unwrap_or(&self.key_pattern);
+        constant_part
+            .rfind('/')
+            .map(|pos| &constant_part[..pos])
+            .unwrap_or("")
     }
 }
 
 pub trait ToDataFrame {
     fn to_dataframe(&self) -> Result<DataFrame, Error>;
+
+    /// custom metadata to store in the schema of serialized dataframes
+    fn schema_metadata(&self) -> SchemaMetadata {
+        Default::default()
+    }
 }
 
 pub trait FromDataFrame {
     fn from_dataframe(df: DataFrame) -> Result<Self, Error>
     where
         Self: Sized;
+
+    /// validate the custom metadata of the schema of a serialized dataframe. This
+    /// happens before any columns are decoded.
+    fn check_schema_metadata(_metadata: &SchemaMetadata) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+/// custom key-value metadata of the schema of serialized dataframes
+pub type SchemaMetadata = BTreeMap<String, String>;
+
+/// the geometry used to represent a cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellGeometry {
+    Centroid,
+    Boundary,
+}
+
+/// the coordinate reference system of the geometries of cells
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CellGeometryCrs {
+    /// WGS84, EPSG:4326
+    #[default]
+    Wgs84,
+
+    /// spherical mercator, EPSG:3857. Allows map clients to use the geometries for tiling
+    /// without transforming them first.
+    WebMercator,
+}
+
+impl CellGeometry {
+    /// the geometry of `cell` in the `crs` as WKB. `None` in case no geometry could be built.
+    pub fn to_wkb(self, cell: CellIndex, crs: CellGeometryCrs) -> Result<Option<Vec<u8>>, Error> {
+        let geom = match self {
+            Self::Centroid => {
+                let centroid = Point::from(Coord::from(LatLng::from(cell)));
+                Some(Geometry::Point(centroid))
+            }
+            Self::Boundary => cell.to_geom(true).ok().map(Geometry::Polygon),
+        };
+        let geom = match crs {
+            CellGeometryCrs::Wgs84 => geom,
+            CellGeometryCrs::WebMercator => geom.map(|geom| geom.map_coords(wgs84_to_webmercator)),
+        };
+        Ok(geom.map(|geom| to_wkb(&geom)).transpose()?)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 pub struct CellDataFrame {
     pub dataframe: DataFrame,
     pub cell_column_name: String,
 }
 
 impl CellDataFrame {
+    /// concatenate `frames` and merge the rows of cells which are contained in more than one
+    /// of the frames using the `overlap_strategy`. Duplicates within a single frame are kept.
+    ///
+    /// Datasets split into files may contain the same cell in multiple files. Without
+    /// deduplication these would be counted multiple times in aggregates.
+    pub fn concat(
+        cell_column_name: String,
+        frames: Vec<DataFrame>,
+        overlap_strategy: OverlapStrategy,
+    ) -> Result<Self, Error> {
+        Self::concat_with_cell_column_candidates(&[cell_column_name], frames, overlap_strategy)
+    }
+
+    /// Variant of [`CellDataFrame::concat`] using the first of the `cell_column_candidates`
+    /// which is present and castable to `UInt64` as the cell column.
+    pub fn concat_with_cell_column_candidates(
+        cell_column_candidates: &[String],
+        mut frames: Vec<DataFrame>,
+        overlap_strategy: OverlapStrategy,
+    ) -> Result<Self, Error> {
+        let frame_heights: Vec<_> = frames.iter().map(DataFrame::height).collect();
+        let mut dataframe = match frames.len() {
+            0 => DataFrame::default(),
+            1 => frames.pop().unwrap(),
+            _ => concat_df(frames.iter())?,
+        };
+        let (dataframe, cell_column_name) = if dataframe.is_empty() {
+            (
+                dataframe,
+                cell_column_candidates.first().cloned().unwrap_or_default(),
+            )
+        } else {
+            let cell_column_name = select_cell_column(&mut dataframe, cell_column_candidates)?;
+            let cells = dataframe.column(&cell_column_name)?.u64()?;
+            let overlapping_cells = cells_in_multiple_frames(cells, &frame_heights);
+
+            // keep the first row of each overlapping cell
+            let mut seen = HashSet::new();
+            let keep: BooleanChunked = cells
+                .into_iter()
+                .map(|cell| match cell {
+                    Some(cell) if overlapping_cells.contains(&cell) => seen.insert(cell),
+                    _ => true,
+                })
+                .collect();
+            let mut deduplicated = dataframe.filter(&keep)?;
+
+            // also without overlaps, so the column types do not depend on the data
+            if overlap_strategy != OverlapStrategy::First {
+                aggregate_numeric_columns(
+                    &dataframe,
+                    &mut deduplicated,
+                    &cell_column_name,
+                    &overlapping_cells,
+                    overlap_strategy,
+                )?;
+            }
+            (deduplicated, cell_column_name)
+        };
+        Ok(Self {
+            dataframe,
+            cell_column_name,
+        })
+    }
+
     pub fn cell_u64s(&self) -> Result<&UInt64Chunked, Error> {
         self.dataframe
             .column(&self.cell_column_name)
@@ -67,4 +258,416 @@ impl CellDataFrame {
             .u64()
             .map_err(Error::from)
     }
+
+    /// the values of the `value_column_name` column keyed by the cell of the row.
+    ///
+    /// Rows with an invalid cell or a null value are skipped.
+    pub fn cell_values(&self, value_column_name: &str) -> Result<CellMap<f64>, Error> {
+        let values = self
+            .dataframe
+            .column(value_column_name)?
+            .cast(&DataType::Float64)?;
+        let mut cell_values = CellMap::default();
+        for (h3index, value) in self.cell_u64s()?.into_iter().zip(values.f64()?) {
+            if let (Some(cell), Some(value)) = (
+                h3index.and_then(|h3index| CellIndex::try_from(h3index).ok()),
+                value,
+            ) {
+                cell_values.insert(cell, value);
+            }
+        }
+        Ok(cell_values)
+    }
+
+    /// return a copy of the dataframe with an additional column `geometry_column_name`
+    /// containing the `cell_geometry` of each cell in the `crs` as WKB.
+    ///
+    /// Rows with a missing or invalid cell index get a null geometry.
+    pub fn to_geodataframe(
+        &self,
+        cell_geometry: CellGeometry,
+        crs: CellGeometryCrs,
+        geometry_column_name: &str,
+    ) -> Result<DataFrame, Error> {
+        let mut geometries: BinaryChunked = self
+            .cell_u64s()?
+            .into_iter()
+            .map(
+                |h3index| match h3index.and_then(|h3index| CellIndex::try_from(h3index).ok()) {
+                    Some(cell) => cell_geometry.to_wkb(cell, crs),
+                    None => Ok(None),
+                },
+            )
+            .collect::<Result<_, _>>()?;
+        geometries.rename(geometry_column_name);
+
+        let mut dataframe = self.dataframe.clone();
+        dataframe.with_column(geometries.into_series())?;
+        Ok(dataframe)
+    }
+}
+
+/// select the first of the `candidates` present in the `dataframe` which can be cast to `UInt64`.
+///
+/// The column gets cast in case it is of another type.
+fn select_cell_column(dataframe: &mut DataFrame, candidates: &[String]) -> Result<String, Error> {
+    for candidate in candidates {
+        let cast_column = match dataframe.column(candidate) {
+            Ok(column) if column.dtype() == &DataType::UInt64 => None,
+            Ok(column) => match column.strict_cast(&DataType::UInt64) {
+                Ok(cast_column) => Some(cast_column),
+                Err(_) => {
+                    debug!("column {} can not be used as cell column", candidate);
+                    continue;
+                }
+            },
+            Err(_) => continue,
+        };
+        if let Some(cast_column) = cast_column {
+            dataframe.with_column(cast_column)?;
+        }
+        debug!("using column {} as cell column", candidate);
+        return Ok(candidate.clone());
+    }
+    Err(MissingCellColumn(candidates.join(", ")))
+}
+
+/// the cells contained in more than one of the frames the `cells` have been concatenated
+/// from. `frame_heights` are the numbers of rows of these frames.
+fn cells_in_multiple_frames(cells: &UInt64Chunked, frame_heights: &[usize]) -> HashSet<u64> {
+    let mut first_frame_index = HashMap::new();
+    let mut overlapping_cells = HashSet::new();
+    let mut offset = 0;
+    for (frame_index, height) in frame_heights.iter().enumerate() {
+        for cell in cells.slice(offset as i64, *height).into_iter().flatten() {
+            if *first_frame_index.entry(cell).or_insert(frame_index) != frame_index {
+                overlapping_cells.insert(cell);
+            }
+        }
+        offset += height;
+    }
+    overlapping_cells
+}
+
+/// replace the values of the `overlapping_cells` in the numeric columns of `deduplicated`
+/// with the values of all rows of the same cell in `dataframe` aggregated using the
+/// `overlap_strategy`.
+fn aggregate_numeric_columns(
+    dataframe: &DataFrame,
+    deduplicated: &mut DataFrame,
+    cell_column_name: &str,
+    overlapping_cells: &HashSet<u64>,
+    overlap_strategy: OverlapStrategy,
+) -> Result<(), Error> {
+    let cells = dataframe.column(cell_column_name)?.u64()?;
+    let deduplicated_cells: Vec<_> = deduplicated
+        .column(cell_column_name)?
+        .u64()?
+        .into_iter()
+        .collect();
+
+    for column in dataframe.get_columns() {
+        if column.name() == cell_column_name || !column.dtype().is_numeric() {
+            continue;
+        }
+
+        // cell -> (aggregated value, number of values)
+        let mut aggregated: HashMap<u64, (f64, usize)> = HashMap::new();
+        let values = column.cast(&DataType::Float64)?;
+        for (cell, value) in cells.into_iter().zip(values.f64()?) {
+            let (Some(cell), Some(value)) = (cell, value) else {
+                continue;
+            };
+            if !overlapping_cells.contains(&cell) {
+                continue;
+            }
+            aggregated
+                .entry(cell)
+                .and_modify(|(agg, count)| {
+                    *agg = match overlap_strategy {
+                        OverlapStrategy::First => *agg,
+                        OverlapStrategy::Sum | OverlapStrategy::Mean => *agg + value,
+                        OverlapStrategy::Max => agg.max(value),
+                    };
+                    *count += 1;
+                })
+                .or_insert((value, 1));
+        }
+
+        let deduplicated_values = deduplicated
+            .column(column.name())?
+            .cast(&DataType::Float64)?;
+        let merged: Vec<Option<f64>> = deduplicated_cells
+            .iter()
+            .zip(deduplicated_values.f64()?)
+            .map(
+                |(cell, value)| match cell.and_then(|cell| aggregated.get(&cell)) {
+                    Some((agg, count)) if overlap_strategy == OverlapStrategy::Mean => {
+                        Some(agg / *count as f64)
+                    }
+                    Some((agg, _)) => Some(*agg),
+                    None => value,
+                },
+            )
+            .collect();
+        let merged = Series::new(column.name(), merged)
+            .cast(&overlap_strategy.aggregated_dtype(column.dtype()))?;
+        deduplicated.with_column(merged)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+    use geo::MapCoords;
+    use geo_types::Geometry;
+    use h3o::{LatLng, Resolution};
+    use polars_core::prelude::{DataFrame, DataType, NamedFrom, Series, TakeRandom};
+
+    use crate::geo::webmercator::webmercator_to_wgs84;
+    use crate::geo::wkb::from_wkb;
+    use crate::io::Error::MissingCellColumn;
+
+    use super::{CellDataFrame, CellGeometry, CellGeometryCrs, DataframeDataset, OverlapStrategy};
+
+    #[test]
+    fn to_geodataframe() {
+        let cell = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let cdf = CellDataFrame {
+            dataframe: DataFrame::new(vec![
+                Series::new("h3index", &[Some(u64::from(cell)), Some(0), None]),
+                Series::new("value", &[1u32, 2, 3]),
+            ])
+            .unwrap(),
+            cell_column_name: "h3index".to_string(),
+        };
+
+        let gdf = cdf
+            .to_geodataframe(CellGeometry::Boundary, CellGeometryCrs::Wgs84, "geometry")
+            .unwrap();
+        assert_eq!(gdf.shape(), (3, 3));
+
+        let geometries = gdf.column("geometry").unwrap().binary().unwrap();
+        assert_eq!(geometries.null_count(), 2);
+
+        let wkb = geometries.get(0).unwrap();
+        assert!(matches!(from_wkb(wkb).unwrap(), Geometry::Polygon(_)));
+    }
+
+    #[test]
+    fn cell_boundary_in_webmercator() {
+        let cell = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let boundary =
+            |crs| match from_wkb(&CellGeometry::Boundary.to_wkb(cell, crs).unwrap().unwrap())
+                .unwrap()
+            {
+                Geometry::Polygon(polygon) => polygon,
+                _ => panic!("expected a polygon"),
+            };
+        let native = boundary(CellGeometryCrs::Wgs84);
+        let webmercator = boundary(CellGeometryCrs::WebMercator);
+        assert!(webmercator.exterior().0[0].x.abs() > 180.0);
+
+        let transformed_back = webmercator.map_coords(webmercator_to_wgs84);
+        assert_eq!(
+            native.exterior().0.len(),
+            transformed_back.exterior().0.len()
+        );
+        for (a, b) in native
+            .exterior()
+            .0
+            .iter()
+            .zip(transformed_back.exterior().0.iter())
+        {
+            assert!(approx_eq!(f64, a.x, b.x, epsilon = 1e-9));
+            assert!(approx_eq!(f64, a.y, b.y, epsilon = 1e-9));
+        }
+    }
+
+    /// concatenate two frames overlapping in a single cell. Returns the value
+    /// of the overlapping cell.
+    fn concat_overlapping(overlap_strategy: OverlapStrategy) -> f64 {
+        let cell1 = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let cell2 = LatLng::new(48.9, 9.2).unwrap().to_cell(Resolution::Eight);
+        let cell3 = LatLng::new(49.0, 9.2).unwrap().to_cell(Resolution::Eight);
+
+        let frame1 = DataFrame::new(vec![
+            Series::new("h3index", &[u64::from(cell1), u64::from(cell2)]),
+            Series::new("value", &[1u32, 2]),
+            Series::new("name", &["a", "b"]),
+        ])
+        .unwrap();
+        let frame2 = DataFrame::new(vec![
+            Series::new("h3index", &[u64::from(cell2), u64::from(cell3)]),
+            Series::new("value", &[20u32, 3]),
+            Series::new("name", &["c", "d"]),
+        ])
+        .unwrap();
+
+        let cdf = CellDataFrame::concat(
+            "h3index".to_string(),
+            vec![frame1, frame2],
+            overlap_strategy,
+        )
+        .unwrap();
+        assert_eq!(cdf.dataframe.shape(), (3, 3));
+
+        // non-numeric columns keep the value of the first row
+        let names: Vec<_> = cdf
+            .dataframe
+            .column("name")
+            .unwrap()
+            .utf8()
+            .unwrap()
+            .into_iter()
+            .collect();
+        assert_eq!(names, vec![Some("a"), Some("b"), Some("d")]);
+
+        let values = cdf.cell_values("value").unwrap();
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[&cell1], 1.0);
+        assert_eq!(values[&cell3], 3.0);
+        values[&cell2]
+    }
+
+    #[test]
+    fn concat_overlap_strategies() {
+        assert_eq!(concat_overlapping(OverlapStrategy::First), 2.0);
+        assert_eq!(concat_overlapping(OverlapStrategy::Sum), 22.0);
+        assert_eq!(concat_overlapping(OverlapStrategy::Max), 20.0);
+        assert_eq!(concat_overlapping(OverlapStrategy::Mean), 11.0);
+    }
+
+    #[test]
+    fn concat_keeps_duplicates_within_a_frame() {
+        let cell1 = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let cell2 = LatLng::new(48.9, 9.2).unwrap().to_cell(Resolution::Eight);
+        let frame1 = DataFrame::new(vec![
+            Series::new("h3index", &[u64::from(cell1), u64::from(cell1)]),
+            Series::new("value", &[1u32, 2]),
+        ])
+        .unwrap();
+        let frame2 = DataFrame::new(vec![
+            Series::new("h3index", &[u64::from(cell2), u64::from(cell2)]),
+            Series::new("value", &[3u32, 4]),
+        ])
+        .unwrap();
+
+        for overlap_strategy in [OverlapStrategy::First, OverlapStrategy::Sum] {
+            let cdf = CellDataFrame::concat(
+                "h3index".to_string(),
+                vec![frame1.clone(), frame2.clone()],
+                overlap_strategy,
+            )
+            .unwrap();
+            let values: Vec<_> = cdf
+                .dataframe
+                .column("value")
+                .unwrap()
+                .u32()
+                .unwrap()
+                .into_iter()
+                .collect();
+            assert_eq!(values, vec![Some(1), Some(2), Some(3), Some(4)]);
+        }
+    }
+
+    #[test]
+    fn concat_overlap_strategy_column_types() {
+        let cell1 = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let cell2 = LatLng::new(48.9, 9.2).unwrap().to_cell(Resolution::Eight);
+        let value_dtype = |overlap_strategy, cell| {
+            let frames = vec![
+                DataFrame::new(vec![
+                    Series::new("h3index", &[u64::from(cell1)]),
+                    Series::new("value", &[1u32]),
+                ])
+                .unwrap(),
+                DataFrame::new(vec![
+                    Series::new("h3index", &[u64::from(cell)]),
+                    Series::new("value", &[2u32]),
+                ])
+                .unwrap(),
+            ];
+            CellDataFrame::concat("h3index".to_string(), frames, overlap_strategy)
+                .unwrap()
+                .dataframe
+                .column("value")
+                .unwrap()
+                .dtype()
+                .clone()
+        };
+
+        // the type must not depend on whether the frames overlap
+        for cell in [cell1, cell2] {
+            assert_eq!(value_dtype(OverlapStrategy::Mean, cell), DataType::Float64);
+            assert_eq!(value_dtype(OverlapStrategy::Sum, cell), DataType::UInt32);
+            assert_eq!(value_dtype(OverlapStrategy::Max, cell), DataType::UInt32);
+        }
+    }
+
+    #[test]
+    fn concat_with_cell_column_candidates() {
+        let cell = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+        let dataset: DataframeDataset = serde_yaml::from_str(
+            r#"
+key_pattern: "population/{ h3cell }.arrow"
+"#,
+        )
+        .unwrap();
+        assert!(dataset.h3index_column_name.is_none());
+
+        // stored as signed integers
+        let frame = DataFrame::new(vec![
+            Series::new("value", &[1u32]),
+            Series::new("h3", &[u64::from(cell) as i64]),
+        ])
+        .unwrap();
+        let cdf = CellDataFrame::concat_with_cell_column_candidates(
+            &dataset.cell_column_names(),
+            vec![frame],
+            OverlapStrategy::First,
+        )
+        .unwrap();
+        assert_eq!(cdf.cell_column_name, "h3");
+        assert_eq!(cdf.cell_values("value").unwrap()[&cell], 1.0);
+
+        let frame = DataFrame::new(vec![Series::new("value", &[1u32])]).unwrap();
+        assert!(matches!(
+            CellDataFrame::concat_with_cell_column_candidates(
+                &dataset.cell_column_names(),
+                vec![frame],
+                OverlapStrategy::First,
+            ),
+            Err(MissingCellColumn(_))
+        ));
+    }
+
+    #[test]
+    fn concat_empty() {
+        assert!(
+            CellDataFrame::concat("h3index".to_string(), vec![], OverlapStrategy::First)
+                .unwrap()
+                .dataframe
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn cell_geometry_to_wkb() {
+        let cell = LatLng::new(48.8, 9.2).unwrap().to_cell(Resolution::Eight);
+
+        let centroid = CellGeometry::Centroid
+            .to_wkb(cell, CellGeometryCrs::Wgs84)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(from_wkb(&centroid).unwrap(), Geometry::Point(_)));
+
+        let boundary = CellGeometry::Boundary
+            .to_wkb(cell, CellGeometryCrs::Wgs84)
+            .unwrap()
+            .unwrap();
+        assert!(matches!(from_wkb(&boundary).unwrap(), Geometry::Polygon(_)));
+    }
 }
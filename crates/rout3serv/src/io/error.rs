@@ -41,6 +41,41 @@ pub enum Error {
 
     #[error(transparent)]
     Hexigraph(#[from] hexigraph::error::Error),
+
+    #[error(transparent)]
+    Geo(#[from] crate::geo::Error),
+
+    #[error("unsupported url: {0}")]
+    UnsupportedUrl(String),
+
+    #[error("object already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("fetching {path} timed out after {timeout:?}")]
+    FetchTimeout {
+        path: String,
+        timeout: std::time::Duration,
+    },
+
+    #[error("invalid graph format version: {0}")]
+    InvalidGraphFormatVersion(String),
+
+    #[error("unsupported graph format version {found}, supported are versions up to {supported}")]
+    UnsupportedGraphFormatVersion { found: u32, supported: u32 },
+
+    #[error("graph {path} has {found} {what}, exceeding the configured limit of {limit}")]
+    GraphLimitExceeded {
+        path: String,
+        what: &'static str,
+        found: u64,
+        limit: u64,
+    },
+
+    #[error("invalid weight of edge {edge}: {reason}")]
+    InvalidEdgeWeight {
+        edge: h3o::DirectedEdgeIndex,
+        reason: &'static str,
+    },
 }
 
 impl From<tokio::task::JoinError> for Error {
@@ -1,26 +1,101 @@
-use crate::io::dataframe::{FromDataFrame, ToDataFrame};
+use crate::io::dataframe::{FromDataFrame, SchemaMetadata, ToDataFrame};
 use crate::io::Error;
+use polars::export::arrow::io::ipc::read::{read_file_metadata, read_stream_metadata};
+use polars::export::arrow::io::ipc::write::{Compression, FileWriter, WriteOptions};
 use polars::io::mmap::MmapBytesReader;
-use polars::prelude::{IpcCompression, IpcReader, IpcWriter, SerReader, SerWriter};
-use std::io::Write;
+use polars::prelude::{DataFrame, IpcReader, PolarsError, SerReader};
+use std::io::{Cursor, ErrorKind, SeekFrom, Write};
+use std::ops::Range;
+
+/// write `df` in the arrow IPC format, storing `metadata` in the schema.
+///
+/// polars does not support writing schema metadata itself.
+pub(crate) fn write_dataframe_ipc<Writer>(
+    writer: Writer,
+    mut df: DataFrame,
+    metadata: SchemaMetadata,
+) -> Result<(), Error>
+where
+    Writer: Write,
+{
+    let schema = df.schema().to_arrow().with_metadata(metadata);
+    let mut ipc_writer = FileWriter::try_new(
+        writer,
+        schema,
+        None,
+        WriteOptions {
+            compression: Some(Compression::ZSTD),
+        },
+    )
+    .map_err(PolarsError::from)?;
+    df.align_chunks();
+    for batch in df.iter_chunks() {
+        ipc_writer.write(&batch, None).map_err(PolarsError::from)?;
+    }
+    ipc_writer.finish().map_err(PolarsError::from)?;
+    Ok(())
+}
 
 fn write_ipc<Writer, T>(writer: Writer, value: &T) -> Result<(), Error>
 where
     Writer: Write,
     T: ToDataFrame,
 {
-    let mut df = value.to_dataframe()?;
-    IpcWriter::new(writer)
-        .with_compression(Some(IpcCompression::ZSTD))
-        .finish(&mut df)?;
-    Ok(())
+    write_dataframe_ipc(writer, value.to_dataframe()?, value.schema_metadata())
+}
+
+/// read the dataframe `T` is serialized in after checking the metadata of its schema
+pub(crate) fn read_dataframe_ipc<Reader: MmapBytesReader, T>(
+    mut reader: Reader,
+) -> Result<DataFrame, Error>
+where
+    T: FromDataFrame,
+{
+    let metadata = read_file_metadata(&mut reader).map_err(PolarsError::from)?;
+    T::check_schema_metadata(&metadata.schema.metadata)?;
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(IpcReader::new(reader).finish()?)
+}
+
+/// length of the prefix of IPC files needed to locate the schema, see [`ipc_schema_range`].
+pub(crate) const IPC_SCHEMA_PREFIX_LEN: usize = 16;
+
+/// magic bytes and padding preceding the schema message in IPC files
+const IPC_FILE_HEADER_LEN: usize = 8;
+
+const IPC_CONTINUATION_MARKER: [u8; 4] = [0xff; 4];
+
+/// the range of the start of an IPC file up to the end of its schema message, determined
+/// from the first [`IPC_SCHEMA_PREFIX_LEN`] bytes of the file.
+///
+/// This allows reading the schema without fetching the record batches.
+pub(crate) fn ipc_schema_range(prefix: &[u8]) -> Result<Range<usize>, Error> {
+    let invalid = || std::io::Error::new(ErrorKind::InvalidData, "not an arrow IPC file");
+    if prefix.len() < IPC_SCHEMA_PREFIX_LEN || !prefix.starts_with(b"ARROW1") {
+        return Err(invalid().into());
+    }
+    let mut offset = IPC_FILE_HEADER_LEN;
+    if prefix[offset..offset + 4] == IPC_CONTINUATION_MARKER {
+        offset += 4;
+    }
+    let message_len = i32::from_le_bytes(prefix[offset..offset + 4].try_into().unwrap());
+    let message_len = usize::try_from(message_len).map_err(|_| invalid())?;
+    Ok(0..(offset + 4 + message_len))
+}
+
+/// read the custom metadata of the schema from the start of an IPC file, spanning at
+/// least the [`ipc_schema_range`].
+pub(crate) fn read_schema_metadata_ipc(file_start: &[u8]) -> Result<SchemaMetadata, Error> {
+    let mut reader = Cursor::new(file_start.get(IPC_FILE_HEADER_LEN..).unwrap_or_default());
+    let metadata = read_stream_metadata(&mut reader).map_err(PolarsError::from)?;
+    Ok(metadata.schema.metadata)
 }
 
 fn read_ipc<Reader: MmapBytesReader, T>(reader: Reader) -> Result<T, Error>
 where
     T: FromDataFrame,
 {
-    T::from_dataframe(IpcReader::new(reader).finish()?)
+    T::from_dataframe(read_dataframe_ipc::<_, T>(reader)?)
 }
 
 pub trait WriteIPC {
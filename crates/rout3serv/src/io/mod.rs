@@ -11,3 +11,42 @@ pub mod memory_cache;
 pub mod objectstore;
 pub mod serde_util;
 pub mod storage;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::ops::Deref;
+    use std::path::{Path, PathBuf};
+
+    /// a uniquely named directory below the temp directory of the system.
+    ///
+    /// The directory is not created, but it is removed together with its contents when
+    /// the guard gets dropped - also when a test fails.
+    pub(crate) struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub(crate) fn new() -> Self {
+            Self(std::env::temp_dir().join(format!("rout3serv-test-{}", uuid::Uuid::new_v4())))
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Self::Target {
+            self.0.as_path()
+        }
+    }
+
+    impl AsRef<Path> for TempDir {
+        fn as_ref(&self) -> &Path {
+            self
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            // the directory may never have been created
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+}
@@ -1,19 +1,154 @@
-use std::ops::Deref;
+use std::future::Future;
+use std::io::{BufWriter, Write};
+use std::ops::{Deref, Range};
+use std::time::Duration;
 
+use bytes::Bytes;
 use object_store::aws::AmazonS3Builder;
 use object_store::local::LocalFileSystem;
-use object_store::RetryConfig;
+use object_store::path::Path;
+use object_store::ObjectMeta;
 use serde::Deserialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Handle;
+use tokio::task::block_in_place;
+use tracing::warn;
+use url::Url;
 
+use crate::config::RetryConfig;
 use crate::io::Error;
 
-pub struct ObjectStore(pub Box<dyn object_store::ObjectStore>);
+pub struct ObjectStore {
+    inner: Box<dyn object_store::ObjectStore>,
+
+    /// upper limit for the duration of fetching a single object
+    fetch_timeout: Option<Duration>,
+}
 
 impl Deref for ObjectStore {
     type Target = dyn object_store::ObjectStore;
 
     fn deref(&self) -> &Self::Target {
-        self.0.deref()
+        self.inner.deref()
+    }
+}
+
+/// size of the buffer to collect small writes before passing them to the multipart upload
+const MULTIPART_BUFFER_SIZE: usize = 1024 * 1024;
+
+impl ObjectStore {
+    pub fn new(inner: Box<dyn object_store::ObjectStore>) -> Self {
+        Self {
+            inner,
+            fetch_timeout: None,
+        }
+    }
+
+    /// fail fetches taking longer than `fetch_timeout` with [`Error::FetchTimeout`].
+    pub fn with_fetch_timeout(mut self, fetch_timeout: Option<Duration>) -> Self {
+        self.fetch_timeout = fetch_timeout;
+        self
+    }
+
+    /// fetch the complete contents of the object at `location`.
+    ///
+    /// Stalled connections are aborted once the fetch timeout is exceeded.
+    pub async fn get_bytes(&self, location: &Path) -> Result<Bytes, Error> {
+        self.with_fetch_timeout_applied(location, async {
+            self.inner.get(location).await?.bytes().await
+        })
+        .await
+    }
+
+    /// fetch the bytes within `range` of the object at `location`.
+    ///
+    /// Stalled connections are aborted once the fetch timeout is exceeded.
+    pub async fn get_range_bytes(
+        &self,
+        location: &Path,
+        range: Range<usize>,
+    ) -> Result<Bytes, Error> {
+        self.with_fetch_timeout_applied(location, self.inner.get_range(location, range))
+            .await
+    }
+
+    /// fetch the metadata of the object at `location`.
+    ///
+    /// Stalled connections are aborted once the fetch timeout is exceeded.
+    pub async fn head_object(&self, location: &Path) -> Result<ObjectMeta, Error> {
+        self.with_fetch_timeout_applied(location, self.inner.head(location))
+            .await
+    }
+
+    async fn with_fetch_timeout_applied<T, F>(&self, location: &Path, fetch: F) -> Result<T, Error>
+    where
+        F: Future<Output = object_store::Result<T>>,
+    {
+        let result = match self.fetch_timeout {
+            Some(fetch_timeout) => {
+                tokio::time::timeout(fetch_timeout, fetch)
+                    .await
+                    .map_err(|_| Error::FetchTimeout {
+                        path: location.to_string(),
+                        timeout: fetch_timeout,
+                    })?
+            }
+            None => fetch.await,
+        };
+        Ok(result?)
+    }
+
+    /// store the data written by `write_fn` at `location` using a multipart upload.
+    ///
+    /// The data is streamed to the objectstore while it is written instead of being buffered in
+    /// memory completely. This avoids the size limit of single requests some providers have.
+    /// `write_fn` gets called in a blocking context. When it fails, the upload is aborted.
+    ///
+    /// Must be called from within a multi-threaded tokio runtime.
+    pub async fn put_multipart_with<F>(&self, location: &Path, write_fn: F) -> Result<(), Error>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<(), Error>,
+    {
+        let (multipart_id, writer) = self.inner.put_multipart(location).await?;
+        let handle = Handle::current();
+
+        let result = block_in_place(|| {
+            let mut buffered = BufWriter::with_capacity(
+                MULTIPART_BUFFER_SIZE,
+                BlockingWriter {
+                    writer,
+                    handle: handle.clone(),
+                },
+            );
+            write_fn(&mut buffered)?;
+            let mut blocking_writer = buffered.into_inner().map_err(|e| e.into_error())?;
+            // completes the upload
+            handle.block_on(blocking_writer.writer.shutdown())?;
+            Ok(())
+        });
+
+        if result.is_err() {
+            if let Err(e) = self.inner.abort_multipart(location, &multipart_id).await {
+                warn!("aborting multipart upload to {} failed: {:?}", location, e);
+            }
+        }
+        result
+    }
+}
+
+/// adapter to use an [`AsyncWrite`] from a blocking context
+struct BlockingWriter {
+    writer: Box<dyn AsyncWrite + Unpin + Send>,
+    handle: Handle,
+}
+
+impl Write for BlockingWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.handle.block_on(self.writer.write(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.handle.block_on(self.writer.flush())
     }
 }
 
@@ -42,13 +177,46 @@ impl TryFrom<ObjectStoreConfig> for ObjectStore {
     type Error = Error;
 
     fn try_from(sc: ObjectStoreConfig) -> Result<Self, Self::Error> {
+        Self::try_from_config(sc, &RetryConfig::default())
+    }
+}
+
+impl ObjectStore {
+    /// the objectstore and the path of the object referenced by `url`.
+    ///
+    /// Supported are `s3://bucket/key` - with S3 being configured by the environment
+    /// variables like [`ObjectStoreConfig::S3ByEnvironment`] - and `file:///path` URLs.
+    pub fn from_url(url: &Url) -> Result<(Self, Path), Error> {
+        match url.scheme() {
+            "s3" | "s3a" => {
+                let store = AmazonS3Builder::from_env().with_url(url.as_str()).build()?;
+                let path = Path::from_url_path(url.path()).map_err(object_store::Error::from)?;
+                Ok((Self::new(Box::new(store)), path))
+            }
+            "file" => {
+                let filename = url
+                    .to_file_path()
+                    .map_err(|_| Error::UnsupportedUrl(url.to_string()))?;
+                let path =
+                    Path::from_filesystem_path(filename).map_err(object_store::Error::from)?;
+                Ok((Self::new(Box::new(LocalFileSystem::new())), path))
+            }
+            _ => Err(Error::UnsupportedUrl(url.to_string())),
+        }
+    }
+
+    /// create the objectstore retrying failed requests as configured by `retry_config`
+    pub fn try_from_config(
+        sc: ObjectStoreConfig,
+        retry_config: &RetryConfig,
+    ) -> Result<Self, Error> {
         let store = match sc {
             ObjectStoreConfig::Filesystem { root } => {
-                Self(Box::new(LocalFileSystem::new_with_prefix(root)?))
+                Self::new(Box::new(LocalFileSystem::new_with_prefix(root)?))
             }
             ObjectStoreConfig::S3ByEnvironment {} => {
-                let builder = AmazonS3Builder::from_env().with_retry(RetryConfig::default());
-                Self(Box::new(builder.build()?))
+                let builder = AmazonS3Builder::from_env().with_retry(retry_config.into());
+                Self::new(Box::new(builder.build()?))
             }
             ObjectStoreConfig::S3 {
                 endpoint,
@@ -65,11 +233,189 @@ impl TryFrom<ObjectStoreConfig> for ObjectStore {
                     .with_secret_access_key(secret_access_key)
                     .with_allow_http(allow_http.unwrap_or(false))
                     .with_bucket_name(bucket_name)
-                    .with_retry(RetryConfig::default());
+                    .with_retry(retry_config.into());
 
-                Self(Box::new(builder.build()?))
+                Self::new(Box::new(builder.build()?))
             }
         };
         Ok(store)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use object_store::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    use crate::config::RetryConfig;
+    use crate::io::Error;
+
+    use super::{ObjectStore, ObjectStoreConfig};
+
+    const BODY: &[u8] = b"some content";
+
+    /// answer the first `num_failures` requests with a `503 Service Unavailable`, all
+    /// following requests with the contents of an object.
+    async fn flaky_s3(num_failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let num_requests = Arc::new(AtomicUsize::new(0));
+
+        let num_requests_server = num_requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve_connection(
+                    stream,
+                    num_requests_server.clone(),
+                    num_failures,
+                ));
+            }
+        });
+        (endpoint, num_requests)
+    }
+
+    async fn serve_connection(
+        mut stream: TcpStream,
+        num_requests: Arc<AtomicUsize>,
+        num_failures: usize,
+    ) {
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            match stream.read(&mut buf).await {
+                Ok(0) | Err(_) => return,
+                Ok(n) => request.extend_from_slice(&buf[..n]),
+            }
+            // requests without a body are complete after the headers
+            while let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                request.drain(..pos + 4);
+
+                let response = if num_requests.fetch_add(1, Ordering::SeqCst) < num_failures {
+                    b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\n\r\n".to_vec()
+                } else {
+                    let mut response = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\
+                        last-modified: Tue, 15 Nov 1994 12:45:26 GMT\r\n\
+                        etag: \"abc\"\r\n\r\n",
+                        BODY.len()
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(BODY);
+                    response
+                };
+                if stream.write_all(&response).await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// accept connections but never answer any request.
+    async fn stalled_s3() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut connections = vec![];
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                connections.push(stream);
+            }
+        });
+        endpoint
+    }
+
+    fn s3_objectstore(endpoint: String, retry_config: &RetryConfig) -> ObjectStore {
+        ObjectStore::try_from_config(
+            ObjectStoreConfig::S3 {
+                endpoint,
+                access_key: "access_key".to_string(),
+                secret_access_key: "secret".to_string(),
+                region: "us-east-1".to_string(),
+                bucket_name: "bucket".to_string(),
+                allow_http: Some(true),
+            },
+            retry_config,
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let (endpoint, num_requests) = flaky_s3(2).await;
+        let objectstore = s3_objectstore(
+            endpoint,
+            &RetryConfig {
+                max_retries: 3,
+                initial_backoff_ms: 10,
+                max_elapsed_ms: 10_000,
+            },
+        );
+
+        let bytes = objectstore
+            .get(&Path::from("some/object"))
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(bytes.as_ref(), BODY);
+        assert_eq!(num_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_budget_exhausted() {
+        let (endpoint, num_requests) = flaky_s3(2).await;
+        let objectstore = s3_objectstore(
+            endpoint,
+            &RetryConfig {
+                max_retries: 1,
+                initial_backoff_ms: 10,
+                max_elapsed_ms: 10_000,
+            },
+        );
+
+        assert!(objectstore.get(&Path::from("some/object")).await.is_err());
+        assert_eq!(num_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn fetch_timeout_exceeded() {
+        let objectstore = s3_objectstore(stalled_s3().await, &RetryConfig::default())
+            .with_fetch_timeout(Some(Duration::from_millis(200)));
+
+        let started = Instant::now();
+        let err = objectstore
+            .get_bytes(&Path::from("some/object"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::FetchTimeout { .. }));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let started = Instant::now();
+        let err = objectstore
+            .head_object(&Path::from("some/object"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::FetchTimeout { .. }));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn fetch_within_timeout() {
+        let (endpoint, _) = flaky_s3(0).await;
+        let objectstore = s3_objectstore(endpoint, &RetryConfig::default())
+            .with_fetch_timeout(Some(Duration::from_secs(10)));
+
+        let bytes = objectstore
+            .get_bytes(&Path::from("some/object"))
+            .await
+            .unwrap();
+        assert_eq!(bytes.as_ref(), BODY);
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::io::Cursor;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 use bytesize::ByteSize;
@@ -12,59 +14,133 @@ use hexigraph::container::CellSet;
 use hexigraph::graph::PreparedH3EdgeGraph;
 use object_store::path::Path;
 use once_cell::sync::Lazy;
-use polars::prelude::DataFrame;
-use polars_core::utils::concat_df;
+use polars_core::prelude::DataType;
 use regex::Regex;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use tokio::sync::Mutex;
 use tokio::task;
 use tokio::task::block_in_place;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::config::ServerConfig;
-use crate::io::dataframe::{CellDataFrame, DataframeDataset};
-use crate::io::ipc::ReadIPC;
+use crate::config::{GraphsConfig, ServerConfig};
+use crate::io::dataframe::{CellDataFrame, DataframeDataset, FromDataFrame};
+use crate::io::format::FileFormat;
+use crate::io::ipc::{
+    ipc_schema_range, read_dataframe_ipc, read_schema_metadata_ipc, IPC_SCHEMA_PREFIX_LEN,
+};
 use crate::io::memory_cache::{CacheFetcher, FetchError, MemoryCache};
 use crate::io::objectstore::ObjectStore;
 use crate::io::serde_util::{deserialize_from_byte_slice, serialize_into};
 use crate::io::{Error, GraphKey};
-use crate::weight::StandardWeight;
+use crate::weight::{count_graph_nodes, graph_num_nodes_from_metadata, StandardWeight};
+
+/// duration for which the probed presence of a dataset is cached
+const DATASET_PRESENCE_TTL: Duration = Duration::from_secs(60);
+
+/// maximum number of keys listed when detecting the file h3 resolution of a dataset
+const FILE_H3_RESOLUTION_DETECTION_MAX_KEYS: usize = 100;
 
 pub struct Storage {
     objectstore: Arc<ObjectStore>,
     graphs: MemoryCache<GraphFetcher>,
+
+    /// dataset name -> (time of the probe, dataset is present)
+    dataset_presence: Mutex<HashMap<String, (Instant, bool)>>,
+
+    /// dataset name -> names and datatypes of the columns of the dataset
+    dataset_schemas: Mutex<HashMap<String, Vec<(String, DataType)>>>,
+
+    /// (key pattern, data h3 resolution) -> detected file h3 resolution
+    detected_file_h3_resolutions: Mutex<HashMap<(String, Resolution), Resolution>>,
+}
+
+/// How [`Storage::store`] handles an already existing object at the path to store to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    /// replace the existing object
+    #[default]
+    Overwrite,
+
+    /// keep the existing object and do not store the data
+    SkipIfExists,
+
+    /// fail with [`Error::AlreadyExists`]
+    Error,
+}
+
+/// Readiness of the [`Storage`] to serve requests.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Readiness {
+    /// the objectstore is reachable and at least the contained graph could be loaded.
+    Ready(GraphKey),
+    NotReady(String),
 }
 
 impl Storage {
     pub fn from_config(config: &ServerConfig) -> Result<Self, Error> {
-        let objectstore = Arc::new(ObjectStore::try_from(config.objectstore.clone())?);
+        Ok(Self::new(
+            ObjectStore::try_from_config(config.objectstore.clone(), &config.objectstore_retry)?
+                .with_fetch_timeout(config.fetch_timeout()),
+            &config.graphs,
+        ))
+    }
+
+    pub fn new(objectstore: ObjectStore, graphs_config: &GraphsConfig) -> Self {
         let graphs = MemoryCache::new(
-            config.graphs.cache_size.unwrap_or(10),
+            graphs_config.cache_size.unwrap_or(10),
             GraphFetcher {
-                prefix: config.graphs.prefix.clone(),
+                prefix: graphs_config.prefix.clone(),
+                limits: GraphLimits {
+                    max_bytes: graphs_config.max_graph_bytes,
+                    max_nodes: graphs_config.max_graph_nodes,
+                },
             },
         );
-
-        Ok(Self {
-            objectstore,
+        Self {
+            objectstore: Arc::new(objectstore),
             graphs,
-        })
+            dataset_presence: Default::default(),
+            dataset_schemas: Default::default(),
+            detected_file_h3_resolutions: Default::default(),
+        }
     }
 
-    pub async fn store<T>(&self, path: &Path, data: &T) -> Result<(), Error>
+    /// serialize and store `data` at `path`.
+    ///
+    /// The serialized data is streamed to the objectstore, so large objects do not need to
+    /// be buffered in memory. Objects already existing at `path` are handled according to
+    /// the `overwrite_policy`. Checking for existing objects is not atomic, so concurrent
+    /// stores to the same path may still replace each other.
+    pub async fn store<T>(
+        &self,
+        path: &Path,
+        data: &T,
+        overwrite_policy: OverwritePolicy,
+    ) -> Result<(), Error>
     where
         T: Serialize,
     {
-        let serialized: Bytes = block_in_place(move || {
-            let mut serialized: Vec<u8> = Vec::with_capacity(50_000);
-            serialize_into(&mut serialized, data, true).map(|_| serialized.into())
-        })?;
-
+        if overwrite_policy != OverwritePolicy::Overwrite && self.exists(path).await? {
+            return if overwrite_policy == OverwritePolicy::SkipIfExists {
+                debug!("not storing {path}: object already exists");
+                Ok(())
+            } else {
+                Err(Error::AlreadyExists(path.to_string()))
+            };
+        }
         self.objectstore
-            .0
-            .put(path, serialized)
+            .put_multipart_with(path, |writer| serialize_into(writer, data, true))
             .await
-            .map_err(Error::from)
+    }
+
+    /// check if an object exists at `path`
+    pub async fn exists(&self, path: &Path) -> Result<bool, Error> {
+        match self.objectstore.head_object(path).await {
+            Ok(_) => Ok(true),
+            Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => Ok(false),
+            Err(e) => Err(e),
+        }
     }
 
     pub async fn retrieve<T>(&self, path: &Path) -> Result<T, Error>
@@ -81,15 +157,253 @@ impl Storage {
         &self,
         graph_key: GraphKey,
     ) -> Result<Arc<PreparedH3EdgeGraph<StandardWeight>>, FetchError<Error>> {
-        self.graphs
+        let graph = self
+            .graphs
             .get_from(self.objectstore.clone(), graph_key)
-            .await
+            .await;
+        crate::metrics::graph_cache_size(self.graphs.cache_len().await);
+        graph
+    }
+
+    /// a string identifying the version of the stored graph.
+    ///
+    /// This is the ETag of the object, or its modification time and size when the
+    /// objectstore provides no ETag.
+    pub async fn graph_version(&self, graph_key: &GraphKey) -> Result<String, Error> {
+        let object_meta = self
+            .objectstore
+            .head_object(&self.graphs.inner().path(graph_key))
+            .await?;
+        Ok(object_meta.e_tag.unwrap_or_else(|| {
+            format!(
+                "{}-{}",
+                object_meta.last_modified.to_rfc3339(),
+                object_meta.size
+            )
+        }))
     }
 
     pub async fn list_graphs(&self) -> Result<Vec<GraphKey>, Error> {
         self.graphs.inner().list(self.objectstore.clone()).await
     }
 
+    /// the key of the graph named `name` with the highest available h3 resolution.
+    pub async fn resolve_highest_resolution(&self, name: &str) -> Result<Option<GraphKey>, Error> {
+        Ok(self
+            .list_graphs()
+            .await?
+            .into_iter()
+            .filter(|graph_key| graph_key.name == name)
+            .max_by_key(|graph_key| graph_key.h3_resolution))
+    }
+
+    /// load the given graphs into the memory cache
+    pub async fn preload_graphs(&self, graph_keys: &[GraphKey]) -> Result<(), FetchError<Error>> {
+        for graph_key in graph_keys {
+            info!("preloading graph {}", graph_key.to_string());
+            self.retrieve_graph(graph_key.clone()).await?;
+        }
+        Ok(())
+    }
+
+    /// check if the objectstore is reachable and at least one of the graphs can be loaded.
+    ///
+    /// Graphs found in the memory cache will be served from there, so after preloading
+    /// graphs this is cheap to call.
+    pub async fn readiness(&self) -> Readiness {
+        let graph_keys = match self.list_graphs().await {
+            Ok(graph_keys) => graph_keys,
+            Err(e) => return Readiness::NotReady(format!("listing graphs failed: {e}")),
+        };
+
+        for graph_key in graph_keys {
+            match self.retrieve_graph(graph_key.clone()).await {
+                Ok(_) => return Readiness::Ready(graph_key),
+                Err(e) => {
+                    warn!(
+                        "readiness: graph {} could not be loaded: {:?}",
+                        graph_key.to_string(),
+                        e
+                    );
+                }
+            }
+        }
+        Readiness::NotReady("no loadable graph found".to_string())
+    }
+
+    /// the names of the `datasets` together with the information if any files of
+    /// the dataset are present in the objectstore.
+    ///
+    /// The presence is `None` when it is unknown: when probing the objectstore failed, or
+    /// when the `key_pattern` of the dataset has no constant prefix to probe below.
+    /// Probing such a dataset would list the whole objectstore.
+    ///
+    /// Known presences are cached for a short time to avoid listing the objectstore
+    /// on every call. Sorted by the name of the dataset.
+    pub async fn list_datasets(
+        &self,
+        datasets: &HashMap<String, DataframeDataset>,
+    ) -> Vec<(String, Option<bool>)> {
+        let cached: HashMap<String, bool> = {
+            let dataset_presence = self.dataset_presence.lock().await;
+            dataset_presence
+                .iter()
+                .filter(|(_, (probed_at, _))| probed_at.elapsed() < DATASET_PRESENCE_TTL)
+                .map(|(name, (_, is_present))| (name.clone(), *is_present))
+                .collect()
+        };
+
+        let mut listed = Vec::with_capacity(datasets.len());
+        let mut probed = Vec::new();
+        for (name, dataset) in datasets.iter() {
+            let is_present = match cached.get(name) {
+                Some(is_present) => Some(*is_present),
+                None => {
+                    let is_present = self.probe_dataset(name, dataset).await;
+                    if let Some(is_present) = is_present {
+                        probed.push((name.clone(), is_present));
+                    }
+                    is_present
+                }
+            };
+            listed.push((name.clone(), is_present));
+        }
+
+        if !probed.is_empty() {
+            let mut dataset_presence = self.dataset_presence.lock().await;
+            for (name, is_present) in probed {
+                dataset_presence.insert(name, (Instant::now(), is_present));
+            }
+        }
+        listed.sort_unstable();
+        listed
+    }
+
+    /// check if at least one object exists below the key prefix of the dataset.
+    ///
+    /// `None` when the presence could not be determined.
+    async fn probe_dataset(&self, name: &str, dataset: &DataframeDataset) -> Option<bool> {
+        let key_prefix = dataset.key_prefix();
+        if key_prefix.is_empty() {
+            debug!("dataset {name} has no key prefix, not probing its presence");
+            return None;
+        }
+        let prefix: Path = key_prefix.into();
+        let first_object = async {
+            self.objectstore
+                .list(Some(&prefix))
+                .await?
+                .try_next()
+                .await
+                .map_err(Error::from)
+        };
+        match first_object.await {
+            Ok(first_object) => Some(first_object.is_some()),
+            Err(e) => {
+                warn!("probing the presence of dataset {name} failed: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// the names and datatypes of the columns of the dataset `name`.
+    ///
+    /// All files of a dataset are expected to share the same schema, so it is read from
+    /// the first file found below the key prefix of the dataset and cached afterwards.
+    /// Returns `None` when no files of the dataset exist.
+    pub async fn dataset_schema(
+        &self,
+        name: &str,
+        dataset: &DataframeDataset,
+    ) -> Result<Option<Vec<(String, DataType)>>, Error> {
+        if let Some(schema) = self.dataset_schemas.lock().await.get(name) {
+            return Ok(Some(schema.clone()));
+        }
+
+        let fileformat = dataset.fileformat()?;
+        let prefix: Path = dataset.key_prefix().into();
+        let mut objects = self.objectstore.list(Some(&prefix)).await?;
+        while let Some(object_meta) = objects.try_next().await? {
+            if FileFormat::from_filename(object_meta.location.as_ref())
+                .ok()
+                .as_ref()
+                != Some(&fileformat)
+            {
+                continue;
+            }
+            debug!(
+                "Reading schema of dataset {} from {}",
+                name, object_meta.location
+            );
+            let bytes = self.objectstore.get_bytes(&object_meta.location).await?;
+            let schema: Vec<_> = block_in_place(|| fileformat.dataframe_from_slice(&bytes))?
+                .get_columns()
+                .iter()
+                .map(|column| (column.name().to_string(), column.dtype().clone()))
+                .collect();
+            self.dataset_schemas
+                .lock()
+                .await
+                .insert(name.to_string(), schema.clone());
+            return Ok(Some(schema));
+        }
+        Ok(None)
+    }
+
+    /// the h3 resolution of the files of the `dataset` containing data in `data_h3_resolution`.
+    ///
+    /// When the resolution is not configured for the dataset, it is detected from the keys
+    /// of the existing files by matching these against the key pattern. The detected
+    /// resolution is cached.
+    pub async fn dataset_file_h3_resolution(
+        &self,
+        dataset: &DataframeDataset,
+        data_h3_resolution: Resolution,
+    ) -> Result<Resolution, Error> {
+        if let Some(file_h3_resolution) = dataset.resolutions.get(&data_h3_resolution) {
+            return Ok(*file_h3_resolution);
+        }
+        let cache_key = (dataset.key_pattern.clone(), data_h3_resolution);
+        if let Some(file_h3_resolution) = self
+            .detected_file_h3_resolutions
+            .lock()
+            .await
+            .get(&cache_key)
+        {
+            return Ok(*file_h3_resolution);
+        }
+
+        let key_regex = dataset_key_regex(dataset, data_h3_resolution);
+        let prefix: Path = dataset.key_prefix().into();
+        let mut objects = self.objectstore.list(Some(&prefix)).await?;
+        let mut num_keys = 0;
+        while let Some(object_meta) = objects.try_next().await? {
+            if let Some(file_h3_resolution) = key_regex
+                .captures(object_meta.location.as_ref())
+                .and_then(|captures| file_h3_resolution_from_captures(&captures))
+            {
+                info!(
+                    "detected file h3 resolution {} for data h3 resolution {} of dataset {} using {}",
+                    file_h3_resolution, data_h3_resolution, dataset.key_pattern, object_meta.location
+                );
+                self.detected_file_h3_resolutions
+                    .lock()
+                    .await
+                    .insert(cache_key, file_h3_resolution);
+                return Ok(file_h3_resolution);
+            }
+            num_keys += 1;
+            if num_keys >= FILE_H3_RESOLUTION_DETECTION_MAX_KEYS {
+                break;
+            }
+        }
+        error!(
+            "unsupported h3 resolution for building a key: {}",
+            data_h3_resolution
+        );
+        Err(Error::UnsupportedH3Resolution(data_h3_resolution))
+    }
+
     pub async fn retrieve_dataframe(
         &self,
         dataset: &DataframeDataset,
@@ -100,16 +414,15 @@ impl Storage {
             return Ok(Default::default());
         }
         let fileformat = dataset.fileformat()?;
-        let file_cells: CellSet = transform_resolution(
-            cells.iter(),
-            dataset.file_h3_resolution(data_h3_resolution)?,
-        )
-        .collect();
+        let file_h3_resolution = self
+            .dataset_file_h3_resolution(dataset, data_h3_resolution)
+            .await?;
+        let file_cells: CellSet = transform_resolution(cells.iter(), file_h3_resolution).collect();
 
-        let mut paths = file_cells
+        let mut paths: Vec<_> = file_cells
             .iter()
-            .map(|cell| build_dataset_path(dataset, *cell, data_h3_resolution))
-            .collect::<Result<Vec<_>, _>>()?;
+            .map(|cell| build_dataset_path(dataset, *cell, data_h3_resolution, file_h3_resolution))
+            .collect();
         paths.sort_unstable(); // remove duplicates when the keys are not grouped using a file resolution
         paths.dedup();
 
@@ -117,57 +430,67 @@ impl Storage {
             let objectstore = self.objectstore.clone();
             task::spawn(async move {
                 debug!("Loading dataset file {}", path);
-                objectstore
-                    .get(&path)
-                    .await
-                    .map_err(|e| (e, path.clone()))
-                    .map(|gr| (gr, path))
+                let result = objectstore.get_bytes(&path).await;
+                (result, path)
             })
         }))
         .await?;
 
         let mut dataframes = Vec::with_capacity(file_cells.len());
-        for task_result in task_results.into_iter() {
-            match task_result {
-                Ok((getresult, path)) => {
-                    match getresult.bytes().await {
-                        Ok(bytes) => {
-                            dataframes
-                                .push(block_in_place(|| fileformat.dataframe_from_slice(&bytes))?);
-                        }
-                        Err(object_store::Error::NotFound { .. }) => {
-                            // missing files are to be expected with sparse datasets
-                            debug!("Dataset does not contain file {}", path);
-                        }
-                        Err(e) => {
-                            error!("Dataset file {} could not be loaded: {:?}", path, e);
-                            return Err(e.into());
-                        }
-                    }
+        for (result, path) in task_results.into_iter() {
+            match result {
+                Ok(bytes) => {
+                    dataframes.push(block_in_place(|| fileformat.dataframe_from_slice(&bytes))?);
                 }
-                Err((e, path)) => {
-                    error!("Dataset file {} could not be requested: {:?}", path, e);
-                    return Err(e.into());
+                Err(Error::ObjectStore(object_store::Error::NotFound { .. })) => {
+                    // missing files are to be expected with sparse datasets
+                    debug!("Dataset does not contain file {}", path);
+                }
+                Err(e) => {
+                    error!("Dataset file {} could not be loaded: {:?}", path, e);
+                    return Err(e);
                 }
             }
         }
-        let dataframe = match dataframes.len() {
-            0 => DataFrame::default(),
-            1 => dataframes.pop().unwrap(),
-            _ => {
-                debug!("concatenating dataframe from {} parts", dataframes.len());
-                block_in_place(|| concat_df(dataframes.iter()))?
-            }
-        };
-        Ok(Some(CellDataFrame {
-            dataframe,
-            cell_column_name: dataset.h3index_column_name.clone(),
-        }))
+        debug!("concatenating dataframe from {} parts", dataframes.len());
+        let celldataframe = block_in_place(|| {
+            CellDataFrame::concat_with_cell_column_candidates(
+                &dataset.cell_column_names(),
+                dataframes,
+                dataset.overlap_strategy,
+            )
+        })?;
+        Ok(Some(celldataframe))
     }
 }
 
 pub struct GraphFetcher {
     prefix: String,
+    limits: GraphLimits,
+}
+
+/// Limits for the graphs to load, to refuse graphs which would exhaust the memory.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GraphLimits {
+    /// max. size of the serialized graph
+    pub max_bytes: Option<u64>,
+
+    /// max. number of nodes of the graph
+    pub max_nodes: Option<usize>,
+}
+
+impl GraphLimits {
+    fn check(path: &Path, what: &'static str, found: u64, limit: Option<u64>) -> Result<(), Error> {
+        match limit {
+            Some(limit) if found > limit => Err(Error::GraphLimitExceeded {
+                path: path.to_string(),
+                what,
+                found,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
 }
 
 impl GraphFetcher {
@@ -179,6 +502,11 @@ impl GraphFetcher {
         }
     }
 
+    /// path of the graph with the `key` in the objectstore
+    pub fn path(&self, key: &GraphKey) -> Path {
+        format!("{}{}", self.prefix(), key.to_string()).into()
+    }
+
     pub async fn list(&self, objectstore: Arc<ObjectStore>) -> Result<Vec<GraphKey>, Error> {
         let p = self.prefix();
         let prefix_len = p.len();
@@ -209,22 +537,87 @@ impl CacheFetcher for GraphFetcher {
         objectstore: Arc<ObjectStore>,
         key: Self::Key,
     ) -> Result<Self::Value, Self::Error> {
-        let path: Path = format!("{}{}", self.prefix(), key.to_string()).into();
-        fetch(objectstore.as_ref(), &path, |bytes| {
-            let cur = Cursor::new(bytes.as_ref());
-            PreparedH3EdgeGraph::read_ipc(cur)
-        })
-        .await
+        read_graph_with_limits(objectstore.as_ref(), &self.path(&key), &self.limits).await
     }
 }
 
+/// read the graph stored at `path` in the `objectstore`.
+///
+/// Must be called from within a multi-threaded tokio runtime.
+pub async fn read_graph(
+    objectstore: &ObjectStore,
+    path: &Path,
+) -> Result<PreparedH3EdgeGraph<StandardWeight>, Error> {
+    read_graph_with_limits(objectstore, path, &GraphLimits::default()).await
+}
+
+/// read the graph stored at `path` in the `objectstore` and refuse it when it exceeds the `limits`.
+///
+/// Both limits are checked before fetching the graph: the size using the metadata of the
+/// object, the number of nodes using the schema metadata of the serialized graph. Graphs
+/// written without the number of nodes in their metadata get their nodes counted
+/// after fetching them, but still before building the graph.
+///
+/// Must be called from within a multi-threaded tokio runtime.
+pub async fn read_graph_with_limits(
+    objectstore: &ObjectStore,
+    path: &Path,
+    limits: &GraphLimits,
+) -> Result<PreparedH3EdgeGraph<StandardWeight>, Error> {
+    if limits.max_bytes.is_some() {
+        let size = objectstore.head_object(path).await?.size as u64;
+        GraphLimits::check(path, "bytes", size, limits.max_bytes)?;
+    }
+    let mut count_nodes = false;
+    if let Some(max_nodes) = limits.max_nodes {
+        match read_graph_num_nodes(objectstore, path).await? {
+            Some(num_nodes) => {
+                GraphLimits::check(path, "nodes", num_nodes as u64, Some(max_nodes as u64))?
+            }
+            None => count_nodes = true,
+        }
+    }
+    fetch(objectstore, path, |bytes| {
+        let df = read_dataframe_ipc::<_, PreparedH3EdgeGraph<StandardWeight>>(Cursor::new(
+            bytes.as_ref(),
+        ))?;
+        if count_nodes {
+            GraphLimits::check(
+                path,
+                "nodes",
+                count_graph_nodes(&df)? as u64,
+                limits.max_nodes.map(|max_nodes| max_nodes as u64),
+            )?;
+        }
+        PreparedH3EdgeGraph::from_dataframe(df)
+    })
+    .await
+}
+
+/// number of nodes of the graph stored at `path` as found in its schema metadata.
+///
+/// Only the start of the object up to the end of the schema gets fetched.
+async fn read_graph_num_nodes(
+    objectstore: &ObjectStore,
+    path: &Path,
+) -> Result<Option<usize>, Error> {
+    let prefix = objectstore
+        .get_range_bytes(path, 0..IPC_SCHEMA_PREFIX_LEN)
+        .await?;
+    let file_start = objectstore
+        .get_range_bytes(path, ipc_schema_range(&prefix)?)
+        .await?;
+    Ok(graph_num_nodes_from_metadata(&read_schema_metadata_ipc(
+        &file_start,
+    )?))
+}
+
 async fn fetch<T, F>(objectstore: &ObjectStore, path: &Path, f: F) -> Result<T, Error>
 where
     F: FnOnce(Bytes) -> Result<T, Error>,
 {
-    match objectstore.get(path).await {
-        Ok(get_result) => {
-            let bytes = get_result.bytes().await?;
+    match objectstore.get_bytes(path).await {
+        Ok(bytes) => {
             info!(
                 "fetch: {} -> received {} bytes ({})",
                 path,
@@ -235,16 +628,14 @@ where
             //let data: T = block_in_place(move || deserialize_from_byte_slice(&bytes))?;
             //Ok(data)
         }
-        Err(err) => match err {
-            object_store::Error::NotFound { .. } => {
+        Err(err) => {
+            if err.is_not_found() {
                 info!("fetch: {} -> not found", path);
-                Err(Error::from(err))
-            }
-            _ => {
+            } else {
                 error!("fetch: {} -> {}", path, err.to_string());
-                Err(Error::from(err))
             }
-        },
+            Err(err)
+        }
     }
 }
 
@@ -253,23 +644,546 @@ static RE_S3KEY_DATA_H3_RESOLUTION: Lazy<Regex> =
 static RE_S3KEY_FILE_H3_RESOLUTION: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"\{\s*file_h3_resolution\s*\}").unwrap());
 static RE_S3KEY_H3_CELL: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\s*h3cell\s*\}").unwrap());
+static RE_S3KEY_PLACEHOLDER: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\{\s*(data_h3_resolution|file_h3_resolution|h3cell)\s*\}").unwrap());
 
 fn build_dataset_path(
     dataset: &DataframeDataset,
     cell: CellIndex,
     data_h3_resolution: Resolution,
-) -> Result<Path, Error> {
-    Ok(RE_S3KEY_H3_CELL
+    file_h3_resolution: Resolution,
+) -> Path {
+    RE_S3KEY_H3_CELL
         .replace_all(
             &RE_S3KEY_FILE_H3_RESOLUTION.replace_all(
                 &RE_S3KEY_DATA_H3_RESOLUTION.replace_all(
                     dataset.key_pattern.as_ref(),
                     u8::from(data_h3_resolution).to_string(),
                 ),
-                u8::from(dataset.file_h3_resolution(data_h3_resolution)?).to_string(),
+                u8::from(file_h3_resolution).to_string(),
             ),
             cell.to_string(),
         )
         .to_string()
-        .into())
+        .into()
+}
+
+/// regex matching the keys of the files of the `dataset` containing data in `data_h3_resolution`.
+///
+/// The first occurrences of the file h3 resolution and the cell are captured
+/// as `file_h3_resolution` and `h3cell`.
+fn dataset_key_regex(dataset: &DataframeDataset, data_h3_resolution: Resolution) -> Regex {
+    let mut pattern = "^".to_string();
+    let mut captured = vec![];
+    let mut last_end = 0;
+    for captures in RE_S3KEY_PLACEHOLDER.captures_iter(&dataset.key_pattern) {
+        let placeholder = captures.get(0).unwrap();
+        pattern.push_str(&regex::escape(
+            &dataset.key_pattern[last_end..placeholder.start()],
+        ));
+        let name = captures.get(1).unwrap().as_str();
+        let value_pattern = match name {
+            "data_h3_resolution" => u8::from(data_h3_resolution).to_string(),
+            "file_h3_resolution" => r"\d{1,2}".to_string(),
+            _ => "[0-9a-fA-F]{15,16}".to_string(),
+        };
+        if name == "data_h3_resolution" || captured.contains(&name) {
+            pattern.push_str(&value_pattern);
+        } else {
+            pattern.push_str(&format!("(?P<{name}>{value_pattern})"));
+            captured.push(name);
+        }
+        last_end = placeholder.end();
+    }
+    pattern.push_str(&regex::escape(&dataset.key_pattern[last_end..]));
+    pattern.push('$');
+
+    // all parts taken from the key pattern are escaped, so this is always a valid regex
+    Regex::new(&pattern).unwrap()
+}
+
+/// the file h3 resolution from the captures of the [`dataset_key_regex`]. The cell of the
+/// file is preferred over the resolution number, as it is always in the file h3 resolution.
+fn file_h3_resolution_from_captures(captures: &regex::Captures) -> Option<Resolution> {
+    if let Some(cell) = captures
+        .name("h3cell")
+        .and_then(|h3cell| CellIndex::from_str(h3cell.as_str()).ok())
+    {
+        return Some(cell.resolution());
+    }
+    captures
+        .name("file_h3_resolution")
+        .and_then(|file_h3_resolution| file_h3_resolution.as_str().parse::<u8>().ok())
+        .and_then(|file_h3_resolution| Resolution::try_from(file_h3_resolution).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    use h3o::{LatLng, Resolution};
+    use hexigraph::graph::{GetStats, H3EdgeGraph, PreparedH3EdgeGraph};
+    use object_store::path::Path;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+
+    use crate::config::GraphsConfig;
+    use polars::prelude::{DataFrame, IpcWriter, NamedFrom, SerWriter, Series};
+    use polars_core::prelude::DataType;
+
+    use crate::io::dataframe::{DataframeDataset, ToDataFrame};
+    use crate::io::ipc::{ipc_schema_range, WriteIPC};
+    use crate::io::objectstore::{ObjectStore, ObjectStoreConfig};
+    use crate::io::tests::TempDir;
+    use crate::io::{Error, GraphKey};
+    use crate::weight::StandardWeight;
+
+    use super::{read_graph_with_limits, GraphLimits, OverwritePolicy, Readiness, Storage};
+
+    fn make_storage(root: &std::path::Path) -> Storage {
+        Storage::new(
+            ObjectStore::try_from(ObjectStoreConfig::Filesystem {
+                root: root.to_string_lossy().to_string(),
+            })
+            .unwrap(),
+            &GraphsConfig {
+                prefix: "graphs".to_string(),
+                cache_size: None,
+                preload: vec![],
+                max_graph_bytes: None,
+                max_graph_nodes: None,
+            },
+        )
+    }
+
+    fn make_graph(graph_key: &GraphKey) -> PreparedH3EdgeGraph<StandardWeight> {
+        let cell = LatLng::new(12.3, 23.3)
+            .unwrap()
+            .to_cell(graph_key.h3_resolution);
+        let mut graph = H3EdgeGraph::new(graph_key.h3_resolution);
+        for edge in cell.edges() {
+            graph.add_edge(edge, StandardWeight::new(1.0, Time::new::<second>(3.0)));
+        }
+        graph.try_into().unwrap()
+    }
+
+    fn write_graph(root: &std::path::Path, graph_key: &GraphKey) {
+        let prepared_graph = make_graph(graph_key);
+
+        let graph_dir = root.join("graphs");
+        std::fs::create_dir_all(&graph_dir).unwrap();
+        let writer = BufWriter::new(File::create(graph_dir.join(graph_key.to_string())).unwrap());
+        prepared_graph.write_ipc(writer).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn readiness() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+
+        let storage = make_storage(&root);
+        assert!(matches!(storage.readiness().await, Readiness::NotReady(_)));
+
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Eight,
+        };
+        write_graph(&root, &graph_key);
+
+        storage.preload_graphs(&[graph_key.clone()]).await.unwrap();
+        assert_eq!(storage.graphs.cache_len().await, 1);
+        assert_eq!(storage.readiness().await, Readiness::Ready(graph_key));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn resolve_highest_resolution() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+        let storage = make_storage(&root);
+
+        for (name, h3_resolution) in [
+            ("testgraph", Resolution::Eight),
+            ("testgraph", Resolution::Ten),
+            ("othergraph", Resolution::Eleven),
+        ] {
+            write_graph(
+                &root,
+                &GraphKey {
+                    name: name.to_string(),
+                    h3_resolution,
+                },
+            );
+        }
+
+        assert_eq!(
+            storage
+                .resolve_highest_resolution("testgraph")
+                .await
+                .unwrap(),
+            Some(GraphKey {
+                name: "testgraph".to_string(),
+                h3_resolution: Resolution::Ten,
+            })
+        );
+        assert_eq!(
+            storage.resolve_highest_resolution("missing").await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn list_datasets_presence() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(root.join("present/5")).unwrap();
+        std::fs::write(root.join("present/5/85283473fffffff.arrow"), b"data").unwrap();
+        let storage = make_storage(&root);
+
+        let dataset = |key_pattern: &str| DataframeDataset {
+            key_pattern: key_pattern.to_string(),
+            resolutions: HashMap::from([(Resolution::Ten, Resolution::Five)]),
+            h3index_column_name: Some("h3index".to_string()),
+            cell_column_candidates: vec![],
+            value_column_name: None,
+            overlap_strategy: Default::default(),
+        };
+        let datasets = HashMap::from([
+            (
+                "present".to_string(),
+                dataset("present/{ file_h3_resolution }/{ h3cell }.arrow"),
+            ),
+            (
+                "missing".to_string(),
+                dataset("missing/{ file_h3_resolution }/{ h3cell }.arrow"),
+            ),
+        ]);
+
+        let expected = vec![
+            ("missing".to_string(), Some(false)),
+            ("present".to_string(), Some(true)),
+        ];
+        assert_eq!(storage.list_datasets(&datasets).await, expected);
+
+        // the probe is cached, so files added later are not noticed immediately
+        std::fs::create_dir_all(root.join("missing/5")).unwrap();
+        std::fs::write(root.join("missing/5/85283473fffffff.arrow"), b"data").unwrap();
+        assert_eq!(storage.list_datasets(&datasets).await, expected);
+
+        // without a constant key prefix the presence is not probed
+        let datasets = HashMap::from([(
+            "unprefixed".to_string(),
+            dataset("{ file_h3_resolution }/{ h3cell }.arrow"),
+        )]);
+        assert_eq!(
+            storage.list_datasets(&datasets).await,
+            vec![("unprefixed".to_string(), None)]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dataset_schema_of_fixture() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(root.join("population/5")).unwrap();
+        std::fs::write(root.join("population/README.txt"), b"not a dataset file").unwrap();
+        let mut df = DataFrame::new(vec![
+            Series::new("h3index", &[0x8a2a1072b59ffff_u64]),
+            Series::new("population", &[12.5_f64]),
+            Series::new("district", &["center"]),
+        ])
+        .unwrap();
+        IpcWriter::new(File::create(root.join("population/5/85283473fffffff.arrow")).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        let storage = make_storage(&root);
+
+        let dataset = |key_pattern: &str| DataframeDataset {
+            key_pattern: key_pattern.to_string(),
+            resolutions: HashMap::from([(Resolution::Ten, Resolution::Five)]),
+            h3index_column_name: Some("h3index".to_string()),
+            cell_column_candidates: vec![],
+            value_column_name: Some("population".to_string()),
+            overlap_strategy: Default::default(),
+        };
+
+        let expected = vec![
+            ("h3index".to_string(), DataType::UInt64),
+            ("population".to_string(), DataType::Float64),
+            ("district".to_string(), DataType::Utf8),
+        ];
+        let population = dataset("population/{ file_h3_resolution }/{ h3cell }.arrow");
+        assert_eq!(
+            storage
+                .dataset_schema("population", &population)
+                .await
+                .unwrap(),
+            Some(expected.clone())
+        );
+
+        // the schema is cached
+        std::fs::remove_dir_all(root.join("population/5")).unwrap();
+        assert_eq!(
+            storage
+                .dataset_schema("population", &population)
+                .await
+                .unwrap(),
+            Some(expected)
+        );
+
+        let missing = dataset("missing/{ file_h3_resolution }/{ h3cell }.arrow");
+        assert_eq!(
+            storage.dataset_schema("missing", &missing).await.unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn detect_dataset_file_h3_resolution() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(root.join("population/10")).unwrap();
+        std::fs::write(root.join("population/README.txt"), b"not a dataset file").unwrap();
+        std::fs::write(root.join("population/10/85283473fffffff.arrow"), b"data").unwrap();
+        let storage = make_storage(&root);
+
+        let dataset = |key_pattern: &str| DataframeDataset {
+            key_pattern: key_pattern.to_string(),
+            resolutions: Default::default(),
+            h3index_column_name: Some("h3index".to_string()),
+            cell_column_candidates: vec![],
+            value_column_name: None,
+            overlap_strategy: Default::default(),
+        };
+
+        // from the cell of the key
+        let population = dataset("population/{ data_h3_resolution }/{ h3cell }.arrow");
+        assert_eq!(
+            storage
+                .dataset_file_h3_resolution(&population, Resolution::Ten)
+                .await
+                .unwrap(),
+            Resolution::Five
+        );
+        assert!(storage
+            .dataset_file_h3_resolution(&population, Resolution::Nine)
+            .await
+            .is_err());
+
+        // the detected resolution is cached
+        std::fs::remove_dir_all(root.join("population/10")).unwrap();
+        assert_eq!(
+            storage
+                .dataset_file_h3_resolution(&population, Resolution::Ten)
+                .await
+                .unwrap(),
+            Resolution::Five
+        );
+
+        // from the resolution in the key
+        std::fs::create_dir_all(root.join("other/6")).unwrap();
+        std::fs::write(root.join("other/6/10.arrow"), b"data").unwrap();
+        let other = dataset("other/{ file_h3_resolution }/{ data_h3_resolution }.arrow");
+        assert_eq!(
+            storage
+                .dataset_file_h3_resolution(&other, Resolution::Ten)
+                .await
+                .unwrap(),
+            Resolution::Six
+        );
+
+        // configured resolutions take precedence
+        let mut configured = dataset("population/{ data_h3_resolution }/{ h3cell }.arrow");
+        configured.resolutions = HashMap::from([(Resolution::Ten, Resolution::Four)]);
+        assert_eq!(
+            storage
+                .dataset_file_h3_resolution(&configured, Resolution::Ten)
+                .await
+                .unwrap(),
+            Resolution::Four
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn graph_limits() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+        let storage = make_storage(&root);
+
+        // a cell and its six neighbors
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Eight,
+        };
+        write_graph(&root, &graph_key);
+        let path = Path::from(format!("graphs/{}", graph_key.to_string()));
+
+        let read = |limits: GraphLimits| {
+            let objectstore = storage.objectstore.clone();
+            let path = path.clone();
+            async move { read_graph_with_limits(objectstore.as_ref(), &path, &limits).await }
+        };
+
+        let graph = read(GraphLimits {
+            max_bytes: None,
+            max_nodes: Some(7),
+        })
+        .await
+        .unwrap();
+        assert_eq!(graph.get_stats().unwrap().num_nodes, 7);
+
+        assert!(matches!(
+            read(GraphLimits {
+                max_bytes: None,
+                max_nodes: Some(6),
+            })
+            .await,
+            Err(Error::GraphLimitExceeded {
+                what: "nodes",
+                found: 7,
+                limit: 6,
+                ..
+            })
+        ));
+        assert!(matches!(
+            read(GraphLimits {
+                max_bytes: Some(10),
+                max_nodes: None,
+            })
+            .await,
+            Err(Error::GraphLimitExceeded { what: "bytes", .. })
+        ));
+
+        // the number of nodes is taken from the schema metadata, so graphs exceeding the
+        // limit are refused without fetching their record batches
+        let graph_file = root.join("graphs").join(graph_key.to_string());
+        let graph_bytes = std::fs::read(&graph_file).unwrap();
+        let schema_end = ipc_schema_range(&graph_bytes).unwrap().end;
+        std::fs::write(&graph_file, &graph_bytes[..schema_end]).unwrap();
+        assert!(matches!(
+            read(GraphLimits {
+                max_bytes: None,
+                max_nodes: Some(6),
+            })
+            .await,
+            Err(Error::GraphLimitExceeded {
+                what: "nodes",
+                found: 7,
+                limit: 6,
+                ..
+            })
+        ));
+
+        // graphs written without the number of nodes in their metadata get their nodes counted
+        let mut df = make_graph(&graph_key).to_dataframe().unwrap();
+        IpcWriter::new(File::create(&graph_file).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        assert!(matches!(
+            read(GraphLimits {
+                max_bytes: None,
+                max_nodes: Some(6),
+            })
+            .await,
+            Err(Error::GraphLimitExceeded {
+                what: "nodes",
+                found: 7,
+                limit: 6,
+                ..
+            })
+        ));
+        let graph = read(GraphLimits {
+            max_bytes: None,
+            max_nodes: Some(7),
+        })
+        .await
+        .unwrap();
+        assert_eq!(graph.get_stats().unwrap().num_nodes, 7);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn multipart_upload_roundtrip() {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+        let storage = make_storage(&root);
+
+        // graph written using the streaming upload
+        let graph_key = GraphKey {
+            name: "testgraph".to_string(),
+            h3_resolution: Resolution::Eight,
+        };
+        let prepared_graph = make_graph(&graph_key);
+        storage
+            .objectstore
+            .put_multipart_with(
+                &Path::from(format!("graphs/{}", graph_key.to_string())),
+                |writer| prepared_graph.write_ipc(writer),
+            )
+            .await
+            .unwrap();
+        let retrieved_graph = storage.retrieve_graph(graph_key).await.unwrap();
+        let (retrieved_stats, expected_stats) = (
+            retrieved_graph.get_stats().unwrap(),
+            prepared_graph.get_stats().unwrap(),
+        );
+        assert_eq!(retrieved_stats.num_nodes, expected_stats.num_nodes);
+        assert_eq!(retrieved_stats.num_edges, expected_stats.num_edges);
+
+        // a payload spanning multiple parts. The values are pseudo-random to
+        // prevent the compression from shrinking it too much.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let payload: Vec<u64> = (0..3_000_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect();
+        let path = Path::from("outputs/large");
+        storage
+            .store(&path, &payload, OverwritePolicy::Overwrite)
+            .await
+            .unwrap();
+        let retrieved_payload: Vec<u64> = storage.retrieve(&path).await.unwrap();
+        assert_eq!(retrieved_payload, payload);
+    }
+
+    /// store `data` with the `overwrite_policy` to a path where `vec![1, 2, 3]` has
+    /// been stored before and return the result together with the stored value afterwards.
+    async fn store_over_existing(
+        overwrite_policy: OverwritePolicy,
+    ) -> (Result<(), crate::io::Error>, Vec<u32>) {
+        let root = TempDir::new();
+        std::fs::create_dir_all(&root).unwrap();
+        let storage = make_storage(&root);
+
+        let path = Path::from("outputs/existing");
+        storage
+            .store(&path, &vec![1u32, 2, 3], OverwritePolicy::Error)
+            .await
+            .unwrap();
+        let result = storage.store(&path, &vec![4u32, 5], overwrite_policy).await;
+        let stored: Vec<u32> = storage.retrieve(&path).await.unwrap();
+        (result, stored)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_overwrite_policy_overwrite() {
+        let (result, stored) = store_over_existing(OverwritePolicy::Overwrite).await;
+        assert!(result.is_ok());
+        assert_eq!(stored, vec![4, 5]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_overwrite_policy_skip_if_exists() {
+        let (result, stored) = store_over_existing(OverwritePolicy::SkipIfExists).await;
+        assert!(result.is_ok());
+        assert_eq!(stored, vec![1, 2, 3]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn store_overwrite_policy_error() {
+        let (result, stored) = store_over_existing(OverwritePolicy::Error).await;
+        assert!(matches!(result, Err(crate::io::Error::AlreadyExists(_))));
+        assert_eq!(stored, vec![1, 2, 3]);
+    }
 }
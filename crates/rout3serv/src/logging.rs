@@ -0,0 +1,136 @@
+//! Initialization of the logging.
+//!
+//! The format of the log output is selected using the [`LOG_FORMAT_ENV`] environment variable,
+//! the level using `RUST_LOG`.
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// environment variable selecting the [`LogFormat`]
+pub const LOG_FORMAT_ENV: &str = "ROUT3SERV_LOG_FORMAT";
+
+/// level used when `RUST_LOG` is not set
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// format of the log output
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// human-readable text
+    #[default]
+    Text,
+
+    /// one JSON object per line including the spans of the event, for the
+    /// ingestion by log aggregation systems.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(anyhow!("unsupported log format: {s}")),
+        }
+    }
+}
+
+/// initialize the logging in the format selected by the [`LOG_FORMAT_ENV`] environment variable.
+pub fn init() -> Result<()> {
+    let log_format = match std::env::var(LOG_FORMAT_ENV) {
+        Ok(value) => value.parse()?,
+        Err(_) => LogFormat::default(),
+    };
+    match log_format {
+        LogFormat::Text => env_logger::init_from_env(
+            env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, DEFAULT_LOG_LEVEL),
+        ),
+        LogFormat::Json => {
+            let env_filter = EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_LEVEL));
+
+            // also captures the records of the `log` crate
+            json_subscriber(std::io::stdout, env_filter).try_init()?
+        }
+    }
+    Ok(())
+}
+
+/// a subscriber writing JSON log lines to `make_writer`
+fn json_subscriber<W>(make_writer: W, env_filter: EnvFilter) -> impl Subscriber + Send + Sync
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_env_filter(env_filter)
+        .with_writer(make_writer)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{debug, info, info_span};
+    use tracing_subscriber::EnvFilter;
+
+    use super::{json_subscriber, LogFormat};
+
+    #[derive(Clone, Default)]
+    struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn parse_log_format() {
+        assert_eq!("".parse::<LogFormat>().unwrap(), LogFormat::Text);
+        assert_eq!("text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+        assert_eq!("JSON".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn json_log_lines() {
+        let output = CapturedOutput::default();
+        let make_writer = {
+            let output = output.clone();
+            move || output.clone()
+        };
+        let subscriber = json_subscriber(make_writer, EnvFilter::new("info"));
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = info_span!("routing", graph = "testgraph").entered();
+            info!(num_origins = 3, "routing started");
+            debug!("filtered out");
+        });
+
+        let captured = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = captured.lines().collect();
+        assert_eq!(lines.len(), 1);
+
+        let line: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert!(line["timestamp"].is_string());
+        assert_eq!(line["fields"]["message"], "routing started");
+        assert_eq!(line["fields"]["num_origins"], 3);
+        assert_eq!(line["span"]["name"], "routing");
+        assert_eq!(line["span"]["graph"], "testgraph");
+        assert_eq!(line["spans"][0]["name"], "routing");
+    }
+}
@@ -1,39 +1,50 @@
 use std::convert::TryFrom;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, Write};
-use std::path::Path;
 
 use anyhow::Result;
-use clap::{Arg, ArgMatches, Command};
-use flatgeobuf::{ColumnType, FgbCrs, FgbWriter, FgbWriterOptions, GeometryType};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use geo_types::{Geometry, LineString};
-use geozero::{ColumnValue, PropertyProcessor};
 use h3o::geom::ToGeo;
-use h3o::Resolution;
+use h3o::{CellIndex, Resolution};
 use hexigraph::algorithm::edge::cell_centroid_distance_avg_m_at_resolution;
-use hexigraph::algorithm::graph::CoveredArea;
-use hexigraph::graph::{GetStats, H3EdgeGraphBuilder, PreparedH3EdgeGraph};
-use hexigraph::io::osm::OsmPbfH3EdgeGraphBuilder;
+use hexigraph::algorithm::graph::shortest_path::DefaultShortestPathOptions;
+use hexigraph::algorithm::graph::{CoveredArea, ShortestPath};
+use hexigraph::graph::diff::diff_graphs;
+use hexigraph::graph::h3edge::downsample_graph;
+use hexigraph::graph::validate::{validate_graph, GraphValidation};
+use hexigraph::graph::{FastForwardOptions, GetStats, H3EdgeGraph, PreparedH3EdgeGraph};
+use hexigraph::io::osm::build_h3edge_graph_from_pbf;
+use hexigraph::HasH3Resolution;
 use mimalloc::MiMalloc;
+use serde::Serialize;
 use tracing::info;
 use uom::si::f32::Length;
 use uom::si::length::meter;
 use uom::si::time::second;
+use url::Url;
 
 use crate::config::ServerConfig;
+use crate::geo::ToGeoJsonFeature;
 use crate::io::ipc::{ReadIPC, WriteIPC};
+use crate::io::objectstore::ObjectStore;
+use crate::io::serde_util::{deserialize_from_byte_slice, serialize_into};
+use crate::io::storage::read_graph;
 use crate::osm::car::CarAnalyzer;
-use crate::weight::{StandardWeight, Weight};
+use crate::weight::{validate_edge_weights, StandardWeight, Weight};
 
 #[global_allocator]
 static GLOBAL: MiMalloc = MiMalloc;
 
 mod build_info;
 mod config;
+mod cors;
 mod customization;
 mod geo;
 mod grpc;
 mod io;
+mod logging;
+mod metrics;
 mod osm;
 mod weight;
 
@@ -41,13 +52,18 @@ const SC_GRPC_SERVER: &str = "grpc";
 const SC_GRAPH: &str = "graph";
 const SC_GRAPH_STATS: &str = "stats";
 const SC_GRAPH_COVERED_AREA: &str = "covered-area";
+const SC_GRAPH_SHORTEST_PATH: &str = "shortest-path";
 const SC_GRAPH_TO_FGB: &str = "to-fgb";
 const SC_GRAPH_FROM_OSM_PBF: &str = "from-osm-pbf";
+const SC_GRAPH_DIFF: &str = "diff";
+const SC_GRAPH_VALIDATE: &str = "validate";
+const SC_GRAPH_PREPARE: &str = "prepare";
+const SC_GRAPH_DOWNSAMPLE: &str = "downsample";
+
+const GRAPH_ARG_HELP: &str = "graph file or objectstore URL like s3://bucket/key";
 
 fn main() -> Result<()> {
-    env_logger::init_from_env(
-        env_logger::Env::default().filter_or(env_logger::DEFAULT_FILTER_ENV, "info"),
-    );
+    logging::init()?;
     let app = Command::new(env!("CARGO_PKG_NAME"))
         .version(build_info::version())
         .long_version(build_info::long_version())
@@ -58,28 +74,71 @@ fn main() -> Result<()> {
                 .subcommand(
                     Command::new(SC_GRAPH_STATS)
                         .about("Load a graph and print some basic stats")
-                        .arg(Arg::new("GRAPH").help("graph").required(true)),
+                        .arg(Arg::new("GRAPH").help(GRAPH_ARG_HELP).required(true)),
                 )
                 .subcommand(
                     Command::new(SC_GRAPH_COVERED_AREA)
                         .about("Extract the area covered by the graph as geojson")
-                        .arg(Arg::new("GRAPH").help("graph").required(true))
+                        .arg(
+                            Arg::new("max_vertices")
+                                .long("max-vertices")
+                                .help("maximum number of vertices of the output geometry")
+                                .num_args(1),
+                        )
+                        .arg(Arg::new("GRAPH").help(GRAPH_ARG_HELP).required(true))
                         .arg(
                             Arg::new("OUT-GEOJSON")
                                 .help("output file to write the geojson geometry to")
                                 .required(true),
                         ),
                 )
+                .subcommand(
+                    Command::new(SC_GRAPH_SHORTEST_PATH)
+                        .about("Route between two cells and write the path as geojson")
+                        .arg(Arg::new("GRAPH").help(GRAPH_ARG_HELP).required(true))
+                        .arg(
+                            Arg::new("ORIGIN")
+                                .help("origin cell as hex string")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("DESTINATION")
+                                .help("destination cell as hex string")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("OUT-GEOJSON")
+                                .help("output file to write the geojson feature collection to")
+                                .required(true),
+                        ),
+                )
                 .subcommand(
                     Command::new(SC_GRAPH_TO_FGB)
                         .about("Export the input graph to a flatgeobuf dataset")
-                        .arg(Arg::new("GRAPH").help("graph").required(true))
+                        .arg(Arg::new("GRAPH").help(GRAPH_ARG_HELP).required(true))
                         .arg(
                             Arg::new("OUTPUT")
                                 .help("output file to write the vector data to")
                                 .required(true),
                         ),
                 )
+                .subcommand(
+                    Command::new(SC_GRAPH_DIFF)
+                        .about("Compare the edges of two graphs")
+                        .arg(
+                            Arg::new("changed_fgb")
+                                .long("changed-fgb")
+                                .help("export the changed edges to this flatgeobuf file")
+                                .num_args(1),
+                        )
+                        .arg(Arg::new("OLD-GRAPH").help("old graph").required(true))
+                        .arg(Arg::new("NEW-GRAPH").help("new graph").required(true)),
+                )
+                .subcommand(
+                    Command::new(SC_GRAPH_VALIDATE)
+                        .about("Check the graph for inconsistencies and report its connectivity")
+                        .arg(Arg::new("GRAPH").help(GRAPH_ARG_HELP).required(true)),
+                )
                 .subcommand(
                     Command::new(SC_GRAPH_FROM_OSM_PBF)
                         .about("Build a routing graph from an OSM PBF file")
@@ -89,6 +148,13 @@ fn main() -> Result<()> {
                                 .num_args(1)
                                 .default_value("10"),
                         )
+                        .args(fastforward_args())
+                        .arg(
+                            Arg::new("no_prepare")
+                                .long("no-prepare")
+                                .help("write the graph unprepared, for the prepare subcommand")
+                                .action(ArgAction::SetTrue),
+                        )
                         .arg(
                             Arg::new("OUTPUT-GRAPH")
                                 .help("output file to write the graph to")
@@ -100,11 +166,57 @@ fn main() -> Result<()> {
                                 .required(true)
                                 .num_args(1..),
                         ),
+                )
+                .subcommand(
+                    Command::new(SC_GRAPH_PREPARE)
+                        .about("Prepare a graph written by from-osm-pbf using --no-prepare")
+                        .args(fastforward_args())
+                        .arg(
+                            Arg::new("INPUT-GRAPH")
+                                .help("unprepared input graph")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("OUTPUT-GRAPH")
+                                .help("output file to write the graph to")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new(SC_GRAPH_DOWNSAMPLE)
+                        .about("Change a prepared graph to a lower h3 resolution")
+                        .long_about(
+                            "Change a prepared graph to a lower h3 resolution.\n\n\
+                            Multiple edges get condensed into one, which may change the \
+                            topology of the graph, so routing results can differ in parts \
+                            from the ones of the full-resolution graph.",
+                        )
+                        .arg(
+                            Arg::new("target_resolution")
+                                .long("target-resolution")
+                                .short('r')
+                                .help("h3 resolution of the downsampled graph")
+                                .num_args(1)
+                                .required(true),
+                        )
+                        .args(fastforward_args())
+                        .arg(Arg::new("INPUT-GRAPH").help(GRAPH_ARG_HELP).required(true))
+                        .arg(
+                            Arg::new("OUTPUT-GRAPH")
+                                .help("output file to write the graph to")
+                                .required(true),
+                        ),
                 ),
         )
         .subcommand(
             Command::new(SC_GRPC_SERVER)
                 .about("Start the GRPC server")
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .help("validate the configuration against the objectstore and exit without starting the server")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("CONFIG-FILE")
                         .help("server configuration file")
@@ -115,7 +227,43 @@ fn main() -> Result<()> {
     dispatch_command(app.get_matches())
 }
 
+fn fastforward_args() -> [Arg; 2] {
+    [
+        Arg::new("fastforward_min")
+            .long("fastforward-min")
+            .help("minimum number of edges a fastforward must consist of")
+            .num_args(1)
+            .default_value("5"),
+        Arg::new("fastforward_max")
+            .long("fastforward-max")
+            .help("maximum number of edges of a fastforward (default: unlimited)")
+            .num_args(1),
+    ]
+}
+
+fn fastforward_options(sc_matches: &ArgMatches) -> Result<FastForwardOptions> {
+    Ok(FastForwardOptions {
+        min_length: sc_matches
+            .get_one::<String>("fastforward_min")
+            .unwrap()
+            .parse()?,
+        max_length: sc_matches
+            .get_one::<String>("fastforward_max")
+            .map(|v| v.parse())
+            .transpose()?,
+    })
+}
+
+/// read a graph from a local file or - when `filename` is an URL like `s3://bucket/key` -
+/// from the objectstore.
 fn read_graph_from_filename(filename: &str) -> Result<PreparedH3EdgeGraph<StandardWeight>> {
+    if filename.contains("://") {
+        let (objectstore, path) = ObjectStore::from_url(&Url::parse(filename)?)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()?;
+        return Ok(runtime.block_on(read_graph(&objectstore, &path))?);
+    }
     let f = File::open(filename)?;
     Ok(PreparedH3EdgeGraph::read_ipc(BufReader::new(f))?)
 }
@@ -130,7 +278,22 @@ fn dispatch_command(matches: ArgMatches) -> Result<()> {
             }
             Some((SC_GRAPH_TO_FGB, sc_matches)) => subcommand_graph_to_fgb(sc_matches)?,
             Some((SC_GRAPH_COVERED_AREA, sc_matches)) => subcommand_graph_covered_area(sc_matches)?,
+            Some((SC_GRAPH_SHORTEST_PATH, sc_matches)) => {
+                subcommand_graph_shortest_path(sc_matches)?
+            }
             Some((SC_GRAPH_FROM_OSM_PBF, sc_matches)) => subcommand_from_osm_pbf(sc_matches)?,
+            Some((SC_GRAPH_PREPARE, sc_matches)) => prepare_graph_file(
+                sc_matches.get_one::<String>("INPUT-GRAPH").unwrap(),
+                sc_matches.get_one::<String>("OUTPUT-GRAPH").unwrap(),
+                &fastforward_options(sc_matches)?,
+            )?,
+            Some((SC_GRAPH_DOWNSAMPLE, sc_matches)) => subcommand_graph_downsample(sc_matches)?,
+            Some((SC_GRAPH_DIFF, sc_matches)) => subcommand_graph_diff(sc_matches)?,
+            Some((SC_GRAPH_VALIDATE, sc_matches)) => {
+                let graph_filename: &String = sc_matches.get_one("GRAPH").unwrap();
+                let validation = validate_graph_file(graph_filename)?;
+                println!("{}", serde_yaml::to_string(&validation)?);
+            }
             _ => {
                 println!("unknown subcommand");
             }
@@ -144,107 +307,105 @@ fn dispatch_command(matches: ArgMatches) -> Result<()> {
 }
 
 fn subcommand_graph_to_fgb(sc_matches: &ArgMatches) -> Result<()> {
+    let _ = sc_matches;
+    todo!()
+}
+
+fn subcommand_graph_covered_area(sc_matches: &ArgMatches) -> Result<()> {
     let graph_filename: &String = sc_matches.get_one("GRAPH").unwrap();
-    let graph = read_graph_from_filename(graph_filename)?;
+    let prepared_graph = read_graph_from_filename(graph_filename)?;
+
     let mut writer = BufWriter::new(File::create(
-        sc_matches.get_one::<String>("OUTPUT").unwrap(),
+        sc_matches.get_one::<String>("OUT-GEOJSON").unwrap(),
     )?);
+    let multi_poly = match sc_matches.get_one::<String>("max_vertices") {
+        Some(max_vertices) => prepared_graph.covered_area_auto(max_vertices.parse()?)?,
+        None => prepared_graph.covered_area(2)?,
+    };
+    let gj_geom = geojson::Geometry::try_from(&multi_poly)?;
+    writer.write_all(gj_geom.to_string().as_ref())?;
 
-    let mut fgb = FgbWriter::create_with_options(
-        "edges",
-        GeometryType::LineString,
-        FgbWriterOptions {
-            description: Some("graph edges"),
-            crs: FgbCrs {
-                code: 4326,
-                ..Default::default()
-            },
-            ..Default::default()
-        },
-    )?;
-
-    fgb.add_column("travel_duration_secs", ColumnType::Float, |_fbb, col| {
-        col.nullable = false;
-    });
-    fgb.add_column("edge_preference", ColumnType::Float, |_fbb, col| {
-        col.nullable = false;
-    });
-    fgb.add_column("is_long_edge", ColumnType::Bool, |_fbb, col| {
-        col.nullable = false;
-    });
-    fgb.add_column("num_edges", ColumnType::UInt, |_fbb, col| {
-        col.nullable = false;
-    });
-
-    for (edge, edgeweight) in graph.iter_edges() {
-        let line = edge.to_geom(true).unwrap();
-        fgb.add_feature_geom(Geometry::LineString(LineString::from(line)), |feat| {
-            feat.property(
-                0,
-                "travel_duration_secs",
-                &ColumnValue::Float(edgeweight.weight.travel_duration().get::<second>()),
-            )
-            .unwrap();
-            feat.property(
-                1,
-                "edge_preference",
-                &ColumnValue::Float(edgeweight.weight.edge_preference()),
-            )
-            .unwrap();
-            feat.property(2, "is_long_edge", &ColumnValue::Bool(false))
-                .unwrap();
-            feat.property(3, "num_edges", &ColumnValue::UInt(1))
-                .unwrap();
-        })?;
-
-        if let Some((fastforward, fastforward_weight)) = edgeweight.fastforward {
-            fgb.add_feature_geom(Geometry::LineString(fastforward.to_linestring()?), |feat| {
-                feat.property(
-                    0,
-                    "travel_duration_secs",
-                    &ColumnValue::Float(fastforward_weight.travel_duration().get::<second>()),
-                )
-                .unwrap();
-                feat.property(
-                    1,
-                    "edge_preference",
-                    &ColumnValue::Float(fastforward_weight.edge_preference()),
-                )
-                .unwrap();
-                feat.property(2, "is_long_edge", &ColumnValue::Bool(true))
-                    .unwrap();
-                feat.property(
-                    3,
-                    "num_edges",
-                    &ColumnValue::UInt(fastforward.h3edges_len() as u32),
-                )
-                .unwrap();
-            })?;
-        }
-    }
-    fgb.write(&mut writer)?;
+    writer.flush()?;
     Ok(())
 }
 
-fn subcommand_graph_covered_area(sc_matches: &ArgMatches) -> Result<()> {
+fn subcommand_graph_shortest_path(sc_matches: &ArgMatches) -> Result<()> {
     let graph_filename: &String = sc_matches.get_one("GRAPH").unwrap();
     let prepared_graph = read_graph_from_filename(graph_filename)?;
+    let origin: CellIndex = sc_matches.get_one::<String>("ORIGIN").unwrap().parse()?;
+    let destination: CellIndex = sc_matches
+        .get_one::<String>("DESTINATION")
+        .unwrap()
+        .parse()?;
+
+    let features = prepared_graph
+        .shortest_path(origin, [destination], &DefaultShortestPathOptions::new())?
+        .iter()
+        .map(|path| path.to_geojson_feature())
+        .collect::<Result<Vec<_>, _>>()?;
+    if features.is_empty() {
+        info!("no path found from {} to {}", origin, destination);
+    }
 
     let mut writer = BufWriter::new(File::create(
         sc_matches.get_one::<String>("OUT-GEOJSON").unwrap(),
     )?);
-    let multi_poly = prepared_graph.covered_area(2)?;
-    let gj_geom = geojson::Geometry::try_from(&multi_poly)?;
-    writer.write_all(gj_geom.to_string().as_ref())?;
+    let feature_collection: geojson::FeatureCollection = features.into_iter().collect();
+    writer.write_all(feature_collection.to_string().as_ref())?;
 
     writer.flush()?;
     Ok(())
 }
 
+/// validate the graph stored in `graph_filename`.
+///
+/// Fails when the graph violates any invariants. Invalid edge weights are already
+/// rejected when loading the graph.
+fn validate_graph_file(graph_filename: &str) -> Result<GraphValidation> {
+    let graph = read_graph_from_filename(graph_filename)?;
+    let validation = validate_graph(&graph);
+    if validation.has_violations() {
+        return Err(anyhow::Error::msg(format!(
+            "graph {graph_filename} is invalid:\n{}",
+            serde_yaml::to_string(&validation)?
+        )));
+    }
+    Ok(validation)
+}
+
+#[derive(Serialize)]
+struct GraphDiffSummary {
+    added_edges: usize,
+    removed_edges: usize,
+    changed_edges: usize,
+}
+
+fn subcommand_graph_diff(sc_matches: &ArgMatches) -> Result<()> {
+    let old_graph = read_graph_from_filename(sc_matches.get_one::<String>("OLD-GRAPH").unwrap())?;
+    let new_graph = read_graph_from_filename(sc_matches.get_one::<String>("NEW-GRAPH").unwrap())?;
+    let diff = diff_graphs(&old_graph, &new_graph)?;
+
+    println!(
+        "{}",
+        serde_yaml::to_string(&GraphDiffSummary {
+            added_edges: diff.added_edges.len(),
+            removed_edges: diff.removed_edges.len(),
+            changed_edges: diff.changed_edges.len(),
+        })?
+    );
+
+    Ok(())
+}
+
 fn subcommand_grpc_server(sc_matches: &ArgMatches) -> Result<()> {
     let config_contents =
         std::fs::read_to_string(sc_matches.get_one::<String>("CONFIG-FILE").unwrap())?;
     let config: ServerConfig = serde_yaml::from_str(&config_contents)?;
+    if sc_matches.get_flag("dry_run") {
+        let report = grpc::launch_dry_run(config)?;
+        print!("{report}");
+        return Ok(());
+    }
     config.validate()?;
     grpc::launch_server(config)?;
     Ok(())
@@ -257,6 +418,7 @@ fn subcommand_from_osm_pbf(sc_matches: &ArgMatches) -> Result<()> {
         .parse()?;
     let h3_resolution: Resolution = h3_resolution.try_into()?;
     let graph_output: &String = sc_matches.get_one("OUTPUT-GRAPH").unwrap();
+    let fastforward_options = fastforward_options(sc_matches)?;
 
     let edge_length =
         Length::new::<meter>(cell_centroid_distance_avg_m_at_resolution(h3_resolution) as f32);
@@ -264,21 +426,307 @@ fn subcommand_from_osm_pbf(sc_matches: &ArgMatches) -> Result<()> {
         "Building graph using resolution {} with edge length ~= {:?}",
         h3_resolution, edge_length
     );
-    let mut builder = OsmPbfH3EdgeGraphBuilder::new(h3_resolution, CarAnalyzer {});
-    for pbf_input in sc_matches.get_many::<String>("OSM-PBF").unwrap() {
-        builder.read_pbf(Path::new(&pbf_input))?;
+    let graph = build_h3edge_graph_from_pbf(
+        sc_matches.get_many::<String>("OSM-PBF").unwrap(),
+        h3_resolution,
+        CarAnalyzer {},
+    )?;
+
+    if sc_matches.get_flag("no_prepare") {
+        info!("Created unprepared graph ({} edges)", graph.num_edges());
+        write_unprepared_graph(&graph, graph_output)?;
+    } else {
+        write_prepared_graph(graph, graph_output, &fastforward_options)?;
     }
-    let graph = builder.build_graph()?;
+    Ok(())
+}
 
-    info!("Preparing graph");
-    let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 5)?;
+/// write the graph in the serialization used for unprepared graphs. See [`prepare_graph_file`].
+fn write_unprepared_graph(graph: &H3EdgeGraph<StandardWeight>, filename: &str) -> Result<()> {
+    let writer = BufWriter::new(File::create(filename)?);
+    serialize_into(writer, graph, true)?;
+    Ok(())
+}
+
+fn write_prepared_graph(
+    graph: H3EdgeGraph<StandardWeight>,
+    filename: &str,
+    fastforward_options: &FastForwardOptions,
+) -> Result<()> {
+    validate_edge_weights(&graph)?;
+    let prepared_graph =
+        PreparedH3EdgeGraph::from_h3edge_graph_with_options(graph, fastforward_options)?;
 
     let stats = prepared_graph.get_stats()?;
     info!(
         "Created graph ({} nodes, {} edges)",
         stats.num_nodes, stats.num_edges
     );
-    let writer = BufWriter::new(File::create(graph_output)?);
+    let writer = BufWriter::new(File::create(filename)?);
     prepared_graph.write_ipc(writer)?;
     Ok(())
 }
+
+/// prepare the unprepared graph written by `from-osm-pbf --no-prepare`.
+///
+/// This allows trying different fastforward options without parsing the OSM data again.
+fn prepare_graph_file(
+    input_filename: &str,
+    output_filename: &str,
+    fastforward_options: &FastForwardOptions,
+) -> Result<()> {
+    let graph: H3EdgeGraph<StandardWeight> =
+        deserialize_from_byte_slice(&std::fs::read(input_filename)?)?;
+    write_prepared_graph(graph, output_filename, fastforward_options)
+}
+
+fn subcommand_graph_downsample(sc_matches: &ArgMatches) -> Result<()> {
+    let target_resolution: u8 = sc_matches
+        .get_one::<String>("target_resolution")
+        .unwrap()
+        .parse()?;
+    downsample_graph_file(
+        sc_matches.get_one::<String>("INPUT-GRAPH").unwrap(),
+        sc_matches.get_one::<String>("OUTPUT-GRAPH").unwrap(),
+        target_resolution.try_into()?,
+        &fastforward_options(sc_matches)?,
+    )
+}
+
+/// change the resolution of a prepared graph to the lower `target_resolution`.
+///
+/// See [`downsample_travel_durations`]. As with the downsampling of the differential
+/// shortest path, this has the potential to change the topology of the graph.
+fn downsample_graph_file(
+    input_filename: &str,
+    output_filename: &str,
+    target_resolution: Resolution,
+    fastforward_options: &FastForwardOptions,
+) -> Result<()> {
+    let graph: H3EdgeGraph<StandardWeight> = read_graph_from_filename(input_filename)?.into();
+    let downsampled_graph = downsample_travel_durations(&graph, target_resolution)?;
+    write_prepared_graph(downsampled_graph, output_filename, fastforward_options)
+}
+
+/// The endpoints of all edges are mapped to their parent cells. Of all the edges
+/// condensed into a single edge, the one with the lowest travel duration is kept.
+///
+/// That edge only spans the distance between two cells of the resolution of `graph`, so
+/// its travel duration gets scaled up to the distance between two cells of the
+/// `target_resolution`.
+fn downsample_travel_durations(
+    graph: &H3EdgeGraph<StandardWeight>,
+    target_resolution: Resolution,
+) -> Result<H3EdgeGraph<StandardWeight>> {
+    let downsampled_graph = downsample_graph(graph, target_resolution, std::cmp::min)?;
+    let factor = (cell_centroid_distance_avg_m_at_resolution(target_resolution)
+        / cell_centroid_distance_avg_m_at_resolution(graph.h3_resolution()))
+        as f32;
+
+    let mut scaled_graph = H3EdgeGraph::new(target_resolution);
+    for (edge, weight) in downsampled_graph.iter_edges() {
+        scaled_graph.add_edge(edge, weight.scale_travel_duration(factor));
+    }
+    Ok(scaled_graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::io::BufWriter;
+
+    use h3o::{LatLng, Resolution};
+    use hexigraph::algorithm::edge::continuous_cells_to_edges;
+    use hexigraph::algorithm::graph::shortest_path::DefaultShortestPathOptions;
+    use hexigraph::algorithm::graph::ShortestPath;
+    use hexigraph::graph::diff::diff_graphs;
+    use hexigraph::graph::{FastForwardOptions, GetStats, H3EdgeGraph, PreparedH3EdgeGraph};
+    use hexigraph::HasH3Resolution;
+    use uom::si::f32::Time;
+    use uom::si::time::second;
+    use url::Url;
+
+    use crate::io::ipc::WriteIPC;
+    use crate::weight::{StandardWeight, Weight};
+
+    use super::{
+        downsample_graph_file, downsample_travel_durations, prepare_graph_file,
+        read_graph_from_filename, validate_graph_file, write_unprepared_graph,
+    };
+
+    fn make_graph(corrupt: bool) -> H3EdgeGraph<StandardWeight> {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let destination = LatLng::new(10.0, 20.05).unwrap().to_cell(Resolution::Nine);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let weight = StandardWeight::new(1.0, Time::new::<second>(3.0));
+
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for edge in continuous_cells_to_edges(cells) {
+            graph.add_edge(edge, weight);
+        }
+        if corrupt {
+            // an edge of a different resolution
+            let cell = LatLng::new(11.0, 20.0).unwrap().to_cell(Resolution::Ten);
+            graph.add_edge(cell.edges().next().unwrap(), weight);
+        }
+        graph
+    }
+
+    fn temp_path(suffix: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("rout3serv-test-{}{suffix}", uuid::Uuid::new_v4()))
+    }
+
+    fn write_graph(corrupt: bool) -> std::path::PathBuf {
+        let prepared_graph =
+            PreparedH3EdgeGraph::from_h3edge_graph(make_graph(corrupt), 3).unwrap();
+
+        let path = temp_path(".ipc");
+        prepared_graph
+            .write_ipc(BufWriter::new(File::create(&path).unwrap()))
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn read_graph_from_url() {
+        let path = write_graph(false);
+        let url = Url::from_file_path(&path).unwrap();
+        assert_eq!(url.scheme(), "file");
+
+        let from_url = read_graph_from_filename(url.as_str()).unwrap();
+        let from_file = read_graph_from_filename(path.to_str().unwrap()).unwrap();
+        assert_eq!(
+            from_url.get_stats().unwrap().num_edges,
+            from_file.get_stats().unwrap().num_edges
+        );
+
+        assert!(read_graph_from_filename("ftp://example.com/graph_9.ipc").is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn validate_graph() {
+        let path = write_graph(false);
+        let validation = validate_graph_file(path.to_str().unwrap()).unwrap();
+        assert_eq!(validation.num_connected_components, 1);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// mixed resolutions are already rejected when reading the graph, the command
+    /// must fail nonetheless.
+    #[test]
+    fn validate_corrupted_graph_fails() {
+        let path = write_graph(true);
+        assert!(validate_graph_file(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn prepare_unprepared_graph() {
+        let graph = make_graph(false);
+        let fastforward_options = FastForwardOptions {
+            min_length: 3,
+            max_length: None,
+        };
+
+        let unprepared_path = temp_path(".bincode");
+        let prepared_path = temp_path(".ipc");
+        write_unprepared_graph(&graph, unprepared_path.to_str().unwrap()).unwrap();
+        prepare_graph_file(
+            unprepared_path.to_str().unwrap(),
+            prepared_path.to_str().unwrap(),
+            &fastforward_options,
+        )
+        .unwrap();
+        let prepared_graph = read_graph_from_filename(prepared_path.to_str().unwrap()).unwrap();
+
+        let expected =
+            PreparedH3EdgeGraph::from_h3edge_graph_with_options(graph, &fastforward_options)
+                .unwrap();
+        assert_eq!(prepared_graph.count_edges(), expected.count_edges());
+        assert!(expected.count_edges().1 > 0);
+        let diff = diff_graphs(&expected, &prepared_graph).unwrap();
+        assert!(diff.added_edges.is_empty());
+        assert!(diff.removed_edges.is_empty());
+        assert!(diff.changed_edges.is_empty());
+
+        std::fs::remove_file(&unprepared_path).unwrap();
+        std::fs::remove_file(&prepared_path).unwrap();
+    }
+
+    #[test]
+    fn prepare_rejects_invalid_weights() {
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for (edge, weight) in make_graph(false).iter_edges() {
+            graph.add_edge(edge, weight.scale_travel_duration(-1.0));
+        }
+
+        let unprepared_path = temp_path(".bincode");
+        let prepared_path = temp_path(".ipc");
+        write_unprepared_graph(&graph, unprepared_path.to_str().unwrap()).unwrap();
+        assert!(prepare_graph_file(
+            unprepared_path.to_str().unwrap(),
+            prepared_path.to_str().unwrap(),
+            &FastForwardOptions::default(),
+        )
+        .is_err());
+        assert!(!prepared_path.exists());
+
+        std::fs::remove_file(&unprepared_path).unwrap();
+    }
+
+    #[test]
+    fn downsample_line_graph() {
+        let input_path = write_graph(false);
+        let output_path = temp_path(".ipc");
+        downsample_graph_file(
+            input_path.to_str().unwrap(),
+            output_path.to_str().unwrap(),
+            Resolution::Seven,
+            &FastForwardOptions::default(),
+        )
+        .unwrap();
+
+        let downsampled = read_graph_from_filename(output_path.to_str().unwrap()).unwrap();
+        assert_eq!(downsampled.h3_resolution(), Resolution::Seven);
+        assert!(downsampled.count_edges().0 > 0);
+        let validation = validate_graph_file(output_path.to_str().unwrap()).unwrap();
+        assert_eq!(validation.num_connected_components, 1);
+
+        std::fs::remove_file(&input_path).unwrap();
+        std::fs::remove_file(&output_path).unwrap();
+    }
+
+    #[test]
+    fn downsample_keeps_travel_durations() {
+        let graph = make_graph(false);
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let destination = LatLng::new(10.0, 20.05).unwrap().to_cell(Resolution::Nine);
+
+        let travel_duration_secs = |graph: H3EdgeGraph<StandardWeight>| {
+            let h3_resolution = graph.h3_resolution();
+            let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+            let path = prepared_graph
+                .shortest_path(
+                    origin.parent(h3_resolution).unwrap(),
+                    [destination.parent(h3_resolution).unwrap()],
+                    &DefaultShortestPathOptions::default(),
+                )
+                .unwrap()
+                .remove(0);
+            path.cost.travel_duration().get::<second>()
+        };
+
+        let downsampled = downsample_travel_durations(&graph, Resolution::Seven).unwrap();
+        assert_eq!(downsampled.h3_resolution(), Resolution::Seven);
+
+        let before = travel_duration_secs(graph);
+        let after = travel_duration_secs(downsampled);
+        assert!(after > before * 0.5, "{after} > {before} * 0.5");
+        assert!(after < before * 2.0, "{after} < {before} * 2.0");
+    }
+}
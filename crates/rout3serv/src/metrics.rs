@@ -0,0 +1,57 @@
+//! Prometheus metrics
+//!
+//! Metrics are always recorded using the `metrics` facade. Exporting them to
+//! prometheus requires the `metrics` feature.
+use std::time::Duration;
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone)]
+pub struct MetricsConfig {
+    /// address to serve the prometheus `/metrics` endpoint on
+    pub bind_to: String,
+}
+
+pub const METRIC_RPC_REQUESTS: &str = "rout3serv_rpc_requests_total";
+pub const METRIC_COMPUTE_DURATION: &str = "rout3serv_compute_duration_seconds";
+pub const METRIC_GRAPH_CACHE_SIZE: &str = "rout3serv_graph_cache_size";
+
+/// launch the HTTP listener for the prometheus exporter.
+///
+/// Must be called from within a tokio runtime.
+#[cfg(feature = "metrics")]
+pub fn install_exporter(config: &MetricsConfig) -> anyhow::Result<()> {
+    let addr: std::net::SocketAddr = config.bind_to.parse()?;
+    metrics_exporter_prometheus::PrometheusBuilder::new()
+        .with_http_listener(addr)
+        .install()?;
+    tracing::info!("serving prometheus metrics on {}", addr);
+    Ok(())
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn install_exporter(config: &MetricsConfig) -> anyhow::Result<()> {
+    tracing::warn!(
+        "metrics are configured to be served on {}, but the server was built without the metrics feature",
+        config.bind_to
+    );
+    Ok(())
+}
+
+/// count a request to the RPC `rpc_name`
+#[inline]
+pub fn rpc_request(rpc_name: &'static str) {
+    ::metrics::increment_counter!(METRIC_RPC_REQUESTS, "rpc" => rpc_name);
+}
+
+/// record the duration of a blocking computation
+#[inline]
+pub fn compute_duration(duration: Duration) {
+    ::metrics::histogram!(METRIC_COMPUTE_DURATION, duration.as_secs_f64());
+}
+
+/// record the number of entries in the graph cache
+#[inline]
+pub fn graph_cache_size(size: usize) {
+    ::metrics::gauge!(METRIC_GRAPH_CACHE_SIZE, size as f64);
+}
@@ -1,16 +1,19 @@
 use h3o::DirectedEdgeIndex;
 use hexigraph::algorithm::edge::cell_centroid_distance_m;
+use hexigraph::graph::EdgeAttributes;
 use hexigraph::io::osm::osmpbfreader::Tags;
-use hexigraph::io::osm::{EdgeProperties, WayAnalyzer};
+use hexigraph::io::osm::{tag_attributes, EdgeProperties, WayAnalyzer};
 use uom::si::f32::{Length, Velocity};
 use uom::si::length::meter;
 use uom::si::velocity::kilometer_per_hour;
 
-use crate::osm::tags::maxspeed::{infer_maxspeed, MaxSpeed};
+use crate::osm::tags::conditional::{format_maxspeed_conditional, infer_maxspeed_conditional};
+use crate::osm::tags::maxspeed::{infer_maxspeed_directional, MaxSpeed};
 use crate::weight::StandardWeight;
 
 pub struct CarWayProperties {
-    max_speed: Velocity,
+    max_speed_forward: Velocity,
+    max_speed_backward: Velocity,
     edge_preference: f32,
     is_bidirectional: bool,
 }
@@ -49,14 +52,20 @@ impl WayAnalyzer<StandardWeight> for CarAnalyzer {
                 .map(|v| v.to_lowercase() != "yes")
                 .unwrap_or(true);
 
-            let max_speed = match infer_maxspeed(tags, &highway_class) {
-                MaxSpeed::Limited(v) => v,
-                MaxSpeed::Unlimited => Velocity::new::<kilometer_per_hour>(130.0),
-                MaxSpeed::Unknown => Velocity::new::<kilometer_per_hour>(40.0),
-            } * estimated_speed_reduction_percent;
+            let (max_speed_forward, max_speed_backward) =
+                infer_maxspeed_directional(tags, &highway_class);
+            let to_velocity = |max_speed| {
+                let velocity = match max_speed {
+                    MaxSpeed::Limited(v) => v,
+                    MaxSpeed::Unlimited => Velocity::new::<kilometer_per_hour>(130.0),
+                    MaxSpeed::Unknown => Velocity::new::<kilometer_per_hour>(40.0),
+                };
+                velocity * estimated_speed_reduction_percent
+            };
 
             Ok(Some(CarWayProperties {
-                max_speed,
+                max_speed_forward: to_velocity(max_speed_forward),
+                max_speed_backward: to_velocity(max_speed_backward),
                 edge_preference: category_weight,
                 is_bidirectional,
             }))
@@ -70,25 +79,53 @@ impl WayAnalyzer<StandardWeight> for CarAnalyzer {
         edge: DirectedEdgeIndex,
         way_properties: &Self::WayProperties,
     ) -> Result<EdgeProperties<StandardWeight>, hexigraph::error::Error> {
+        let length = Length::new::<meter>(cell_centroid_distance_m(edge) as f32);
         let weight = StandardWeight::new(
             way_properties.edge_preference,
-            Length::new::<meter>(cell_centroid_distance_m(edge) as f32) / way_properties.max_speed,
+            length / way_properties.max_speed_forward,
         );
+        let reverse_weight = way_properties.is_bidirectional.then(|| {
+            StandardWeight::new(
+                way_properties.edge_preference,
+                length / way_properties.max_speed_backward,
+            )
+        });
         Ok(EdgeProperties {
-            is_bidirectional: way_properties.is_bidirectional,
             weight,
+            reverse_weight,
         })
     }
+
+    /// the highway class is kept to break down the routes by the classes of their edges. Time-of-day
+    /// speed restrictions are kept as well, as they are not yet reflected in the weights.
+    fn way_edge_attributes(&self, tags: &Tags) -> Option<EdgeAttributes> {
+        let mut attributes = tag_attributes(tags, &["highway"]);
+        let maxspeed_conditional = infer_maxspeed_conditional(tags);
+        if !maxspeed_conditional.is_empty() {
+            attributes.get_or_insert_with(Default::default).insert(
+                "maxspeed:conditional",
+                format_maxspeed_conditional(&maxspeed_conditional),
+            );
+        }
+        attributes
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use float_cmp::approx_eq;
-    use h3o::Resolution;
+    use h3o::{LatLng, Resolution};
+    use hexigraph::io::osm::osmpbfreader::Tags;
+    use hexigraph::io::osm::WayAnalyzer;
     use uom::si::f32::{Length, Velocity};
     use uom::si::length::meter;
+    use uom::si::time::second;
     use uom::si::velocity::kilometer_per_hour;
 
+    use crate::weight::Weight;
+
+    use super::CarAnalyzer;
+
     #[test]
     fn test_calc() {
         let speed = Velocity::new::<kilometer_per_hour>(30.0);
@@ -98,4 +135,52 @@ mod tests {
         assert!(approx_eq!(f32, travel_time.value, 446.94388f32));
         dbg!(travel_time);
     }
+
+    #[test]
+    fn test_directional_maxspeed() {
+        let mut tags = Tags::new();
+        tags.insert("highway".into(), "secondary".into());
+        tags.insert("maxspeed:forward".into(), "50".into());
+        tags.insert("maxspeed:backward".into(), "100".into());
+
+        let analyzer = CarAnalyzer {};
+        let way_properties = analyzer.analyze_way_tags(&tags).unwrap().unwrap();
+        let edge = LatLng::new(10.0, 20.0)
+            .unwrap()
+            .to_cell(Resolution::Ten)
+            .edges()
+            .next()
+            .unwrap();
+        let edge_properties = analyzer.way_edge_properties(edge, &way_properties).unwrap();
+
+        let forward_secs = edge_properties.weight.travel_duration().get::<second>();
+        let backward_secs = edge_properties
+            .reverse_weight
+            .unwrap()
+            .travel_duration()
+            .get::<second>();
+        assert!(approx_eq!(
+            f32,
+            forward_secs,
+            backward_secs * 2.0,
+            epsilon = 0.001
+        ));
+    }
+
+    #[test]
+    fn test_maxspeed_conditional_attribute() {
+        let mut tags = Tags::new();
+        tags.insert("highway".into(), "residential".into());
+        tags.insert(
+            "maxspeed:conditional".into(),
+            "30 @ (07:00-09:00); 20 @ wet".into(),
+        );
+
+        let attributes = CarAnalyzer {}.way_edge_attributes(&tags).unwrap();
+        assert_eq!(attributes.get("highway"), Some("residential"));
+        assert_eq!(
+            attributes.get("maxspeed:conditional"),
+            Some("30 @ (07:00-09:00)")
+        );
+    }
 }
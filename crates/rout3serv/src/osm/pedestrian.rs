@@ -13,6 +13,7 @@ use hexigraph::io::osm::{EdgeProperties, WayAnalyzer};
 use uom::si::f32::Length;
 use uom::si::length::meter;
 
+use crate::osm::tags::comfort::comfort_factor;
 use crate::osm::tags::sidewalk::infer_sidewalk;
 use crate::osm::WALKING_SPEED;
 use crate::StandardWeight;
@@ -61,8 +62,9 @@ impl WayAnalyzer<StandardWeight> for FootwayAnalyzer {
             };
         }
 
+        // prefer lit and smooth ways
         Ok(edge_preference.map(|rcw| FootwayProperties {
-            edge_preference: rcw,
+            edge_preference: rcw * comfort_factor(tags),
         }))
     }
 
@@ -76,8 +78,48 @@ impl WayAnalyzer<StandardWeight> for FootwayAnalyzer {
             Length::new::<meter>(cell_centroid_distance_m(edge) as f32) / *WALKING_SPEED,
         );
         Ok(EdgeProperties {
-            is_bidirectional: true,
             weight,
+            reverse_weight: Some(weight),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use hexigraph::io::osm::osmpbfreader::Tags;
+    use hexigraph::io::osm::WayAnalyzer;
+
+    use super::FootwayAnalyzer;
+
+    fn edge_preference(kvs: &[(&str, &str)]) -> f32 {
+        let mut tags = Tags::new();
+        for (key, value) in kvs {
+            tags.insert((*key).into(), (*value).into());
+        }
+        FootwayAnalyzer {}
+            .analyze_way_tags(&tags)
+            .unwrap()
+            .unwrap()
+            .edge_preference
+    }
+
+    #[test]
+    fn test_prefer_lit_and_smooth_ways() {
+        let lit_smooth = edge_preference(&[
+            ("highway", "footway"),
+            ("lit", "yes"),
+            ("surface", "asphalt"),
+        ]);
+        let unlit_cobbled = edge_preference(&[
+            ("highway", "footway"),
+            ("lit", "no"),
+            ("surface", "cobblestone"),
+        ]);
+        let unknown = edge_preference(&[("highway", "footway")]);
+
+        assert!(lit_smooth < unlit_cobbled);
+        assert_eq!(lit_smooth, unknown);
+        assert!(edge_preference(&[("highway", "footway"), ("lit", "no")]) > lit_smooth);
+        assert!(edge_preference(&[("highway", "footway"), ("surface", "sett")]) > lit_smooth);
+    }
+}
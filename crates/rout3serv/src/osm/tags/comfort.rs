@@ -0,0 +1,35 @@
+//! Comfort of ways for pedestrians and cyclists based on lighting and surface.
+//!
+//! Reference at <https://wiki.openstreetmap.org/wiki/Key:lit> and
+//! <https://wiki.openstreetmap.org/wiki/Key:surface>
+
+use hexigraph::io::osm::osmpbfreader::Tags;
+
+use crate::osm::tags::str_to_bool;
+
+/// factor to multiply the `edge_preference` of a way with.
+///
+/// `1.0` for lit and smooth ways and when nothing is known about the way. Higher values
+/// for unlit or rough ways.
+pub fn comfort_factor(tags: &Tags) -> f32 {
+    let lit_factor = match tags
+        .get("lit")
+        .and_then(|value| str_to_bool(value.to_lowercase().trim()))
+    {
+        Some(false) => 1.2,
+        Some(true) | None => 1.0,
+    };
+
+    let surface_factor = tags
+        .get("surface")
+        .map(|value| match value.to_lowercase().trim() {
+            "paved" | "asphalt" | "concrete" | "concrete:plates" | "paving_stones" => 1.0,
+            "compacted" | "fine_gravel" | "wood" | "metal" => 1.1,
+            "sett" | "cobblestone" | "unhewn_cobblestone" | "gravel" | "pebblestone"
+            | "unpaved" | "ground" | "dirt" | "grass" | "mud" | "sand" => 1.3,
+            _ => 1.0,
+        })
+        .unwrap_or(1.0);
+
+    lit_factor * surface_factor
+}
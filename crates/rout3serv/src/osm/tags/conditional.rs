@@ -0,0 +1,172 @@
+//! Parsing of time-of-day conditional restrictions like `maxspeed:conditional`.
+//!
+//! Reference at <https://wiki.openstreetmap.org/wiki/Conditional_restrictions>
+//!
+//! The graph has no time-dependent weights yet, so the parsed restrictions do not change the
+//! weights of the edges. They are kept as edge attributes to be included in the routing results.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hexigraph::io::osm::osmpbfreader::Tags;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use uom::si::f32::Velocity;
+use uom::si::velocity::kilometer_per_hour;
+
+use crate::osm::tags::maxspeed::MaxSpeed;
+
+/// A range of the time of day, given in minutes since midnight.
+///
+/// Ranges where `end` is before `start` wrap around midnight.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl TimeRange {
+    pub fn new(start: (u16, u16), end: (u16, u16)) -> Self {
+        Self {
+            start: start.0 * 60 + start.1,
+            end: end.0 * 60 + end.1,
+        }
+    }
+}
+
+impl fmt::Display for TimeRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+static RE_TIME_RANGE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<sh>[0-9]{1,2}):(?P<sm>[0-9]{2})\s*-\s*(?P<eh>[0-9]{1,2}):(?P<em>[0-9]{2})$")
+        .unwrap()
+});
+
+impl FromStr for TimeRange {
+    type Err = ();
+
+    /// parse a `hh:mm-hh:mm` time range
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cap = RE_TIME_RANGE.captures(s.trim()).ok_or(())?;
+        let num = |name| cap.name(name).unwrap().as_str().parse::<u16>().unwrap();
+        let (sh, sm, eh, em) = (num("sh"), num("sm"), num("eh"), num("em"));
+        // 24:00 is a valid end of a range
+        if sh > 24 || eh > 24 || sm > 59 || em > 59 {
+            return Err(());
+        }
+        Ok(Self::new((sh, sm), (eh, em)))
+    }
+}
+
+/// parse the contents of a `maxspeed:conditional` tag.
+///
+/// Entries are separated by `;` and have the form `value @ (hh:mm-hh:mm[, hh:mm-hh:mm...])`. One
+/// item is returned for each time range. Entries with other conditions - like weekdays or
+/// weather - are skipped.
+pub fn parse_maxspeed_conditional(s: &str) -> Vec<(Velocity, TimeRange)> {
+    let mut restrictions = vec![];
+    for entry in s.split(';') {
+        let Some((value, condition)) = entry.split_once('@') else {
+            continue;
+        };
+        let Some(velocity) = MaxSpeed::from_str(value.trim()).unwrap().velocity() else {
+            continue;
+        };
+        let condition = condition.trim();
+        let condition = condition
+            .strip_prefix('(')
+            .and_then(|c| c.strip_suffix(')'))
+            .unwrap_or(condition);
+
+        let time_ranges: Result<Vec<_>, _> =
+            condition.split(',').map(TimeRange::from_str).collect();
+        if let Ok(time_ranges) = time_ranges {
+            restrictions.extend(time_ranges.into_iter().map(|tr| (velocity, tr)));
+        }
+    }
+    restrictions
+}
+
+pub fn infer_maxspeed_conditional(tags: &Tags) -> Vec<(Velocity, TimeRange)> {
+    tags.get("maxspeed:conditional")
+        .map(|value| parse_maxspeed_conditional(value.as_str()))
+        .unwrap_or_default()
+}
+
+/// format restrictions in the `maxspeed:conditional` syntax, with the speeds given in km/h.
+pub fn format_maxspeed_conditional(restrictions: &[(Velocity, TimeRange)]) -> String {
+    restrictions
+        .iter()
+        .map(|(velocity, time_range)| {
+            format!(
+                "{:.0} @ ({})",
+                velocity.get::<kilometer_per_hour>(),
+                time_range
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use uom::si::f32::Velocity;
+    use uom::si::velocity::kilometer_per_hour;
+
+    use super::{format_maxspeed_conditional, parse_maxspeed_conditional, TimeRange};
+
+    fn kmh(value: f32) -> Velocity {
+        Velocity::new::<kilometer_per_hour>(value)
+    }
+
+    #[test]
+    fn test_parse_maxspeed_conditional() {
+        assert_eq!(
+            parse_maxspeed_conditional("30 @ (07:00-09:00)"),
+            vec![(kmh(30.0), TimeRange::new((7, 0), (9, 0)))]
+        );
+        assert_eq!(
+            parse_maxspeed_conditional("100 @ (06:00-22:00); 80 @ (22:00-06:00)"),
+            vec![
+                (kmh(100.0), TimeRange::new((6, 0), (22, 0))),
+                (kmh(80.0), TimeRange::new((22, 0), (6, 0)))
+            ]
+        );
+        assert_eq!(
+            parse_maxspeed_conditional("30 @ (07:00-09:00,16:00-18:00)"),
+            vec![
+                (kmh(30.0), TimeRange::new((7, 0), (9, 0))),
+                (kmh(30.0), TimeRange::new((16, 0), (18, 0)))
+            ]
+        );
+        // weekday and weather conditions are not supported
+        assert!(parse_maxspeed_conditional("30 @ (Mo-Fr 07:00-17:00)").is_empty());
+        assert_eq!(
+            parse_maxspeed_conditional("80 @ wet; 50 @ (19:00-06:00)"),
+            vec![(kmh(50.0), TimeRange::new((19, 0), (6, 0)))]
+        );
+    }
+
+    #[test]
+    fn test_format_maxspeed_conditional() {
+        let s = "100 @ (06:00-22:00); 80 @ (22:00-06:00)";
+        assert_eq!(
+            format_maxspeed_conditional(&parse_maxspeed_conditional(s)),
+            s
+        );
+        assert_eq!(
+            format_maxspeed_conditional(&parse_maxspeed_conditional("30 @ (7:00-9:30)")),
+            "30 @ (07:00-09:30)"
+        );
+    }
+}
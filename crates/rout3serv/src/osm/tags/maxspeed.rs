@@ -85,6 +85,24 @@ pub fn infer_maxspeed(tags: &Tags, highway_class: &str) -> MaxSpeed {
         })
 }
 
+/// infer the maxspeeds in (forward, backward) direction of the way.
+///
+/// Direction-specific limits from `maxspeed:forward` and `maxspeed:backward` take precedence
+/// over the symmetric limit found by [`infer_maxspeed`].
+pub fn infer_maxspeed_directional(tags: &Tags, highway_class: &str) -> (MaxSpeed, MaxSpeed) {
+    let maxspeed = infer_maxspeed(tags, highway_class);
+    let directional = |key: &str| {
+        tags.get(key)
+            .map(|value| MaxSpeed::from_str(value.as_str()).unwrap())
+            .unwrap_or_default()
+            .known_or_else(|| maxspeed)
+    };
+    (
+        directional("maxspeed:forward"),
+        directional("maxspeed:backward"),
+    )
+}
+
 static RE_MAXSPEED: Lazy<Regex> = Lazy::new(|| {
     Regex::new(r"^([A-Za-z\-]+:(zone:?)?)?(?P<value>[1-9][0-9]*)(\s*(?P<units>[a-zA-Z/]+))?")
         .unwrap()
@@ -187,7 +205,9 @@ mod tests {
     use uom::si::f32::Velocity;
     use uom::si::velocity::{kilometer_per_hour, knot};
 
-    use crate::osm::tags::maxspeed::MaxSpeed;
+    use hexigraph::io::osm::osmpbfreader::Tags;
+
+    use crate::osm::tags::maxspeed::{infer_maxspeed_directional, MaxSpeed};
 
     #[test]
     fn test_parse_maxspeed() {
@@ -241,4 +261,51 @@ mod tests {
             MaxSpeed::new_limited_kmh(5.0)
         );
     }
+
+    fn tags(kvs: &[(&str, &str)]) -> Tags {
+        let mut tags = Tags::new();
+        for (key, value) in kvs {
+            tags.insert((*key).into(), (*value).into());
+        }
+        tags
+    }
+
+    #[test]
+    fn test_infer_maxspeed_directional() {
+        assert_eq!(
+            infer_maxspeed_directional(
+                &tags(&[
+                    ("maxspeed", "70"),
+                    ("maxspeed:forward", "50"),
+                    ("maxspeed:backward", "80")
+                ]),
+                "secondary"
+            ),
+            (
+                MaxSpeed::new_limited_kmh(50.0),
+                MaxSpeed::new_limited_kmh(80.0)
+            )
+        );
+
+        // fall back to the symmetric limit for the direction without a specific limit
+        assert_eq!(
+            infer_maxspeed_directional(
+                &tags(&[("maxspeed", "70"), ("maxspeed:backward", "30")]),
+                "secondary"
+            ),
+            (
+                MaxSpeed::new_limited_kmh(70.0),
+                MaxSpeed::new_limited_kmh(30.0)
+            )
+        );
+
+        // ... and to the implicit limit of the highway class
+        assert_eq!(
+            infer_maxspeed_directional(&tags(&[("maxspeed:forward", "60")]), "residential"),
+            (
+                MaxSpeed::new_limited_kmh(60.0),
+                MaxSpeed::new_limited_kmh(30.0)
+            )
+        );
+    }
 }
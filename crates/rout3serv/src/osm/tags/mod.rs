@@ -1,3 +1,5 @@
+pub mod comfort;
+pub mod conditional;
 pub mod maxspeed;
 pub mod sidewalk;
 
@@ -1,10 +1,15 @@
-use h3o::DirectedEdgeIndex;
+use h3o::{CellIndex, DirectedEdgeIndex};
 use std::cmp::Ordering;
 use std::ops::Add;
 
+use hexigraph::algorithm::graph::BucketKey;
 use hexigraph::container::block::Decompressor;
+use hexigraph::container::treemap::H3Treemap;
+use hexigraph::container::DirectedEdgeMap;
 use hexigraph::graph::prepared::FromIterItem;
-use hexigraph::graph::PreparedH3EdgeGraph;
+use hexigraph::graph::{
+    EdgeAttributes, GetEdgeAttributes, GetStats, H3EdgeGraph, PreparedH3EdgeGraph, TravelDuration,
+};
 use itertools::izip;
 use num_traits::Zero;
 use polars_core::frame::DataFrame;
@@ -15,7 +20,7 @@ use uom::si::f32::Time;
 use uom::si::time::second;
 
 use crate::grpc::ServerWeight;
-use crate::io::dataframe::{FromDataFrame, ToDataFrame};
+use crate::io::dataframe::{FromDataFrame, SchemaMetadata, ToDataFrame};
 use crate::io::Error;
 
 pub trait Weight {
@@ -27,6 +32,12 @@ pub trait Weight {
         0.0
     }
 
+    /// additional fields of the weight in their native units, reported next to the
+    /// travel duration and the edge preference. Empty by default.
+    fn report_fields(&self) -> Vec<(String, f64)> {
+        Vec::new()
+    }
+
     fn from_travel_duration(travel_duration: Time) -> Self;
 }
 
@@ -50,6 +61,32 @@ impl StandardWeight {
             travel_duration,
         }
     }
+
+    /// the weight with the travel duration multiplied by `factor`
+    pub fn scale_travel_duration(mut self, factor: f32) -> Self {
+        self.travel_duration *= factor;
+        self
+    }
+}
+
+impl StandardWeight {
+    /// check the weight can be used for routing.
+    ///
+    /// Negative or non-finite values break the assumptions of the shortest path algorithms.
+    fn validate(&self, edge: DirectedEdgeIndex) -> Result<(), Error> {
+        let reason = if !self.travel_duration.value.is_finite() {
+            "travel duration is not finite"
+        } else if self.travel_duration.value < 0.0 {
+            "travel duration is negative"
+        } else if !self.edge_preference.is_finite() {
+            "edge preference is not finite"
+        } else if self.edge_preference < 0.0 {
+            "edge preference is negative"
+        } else {
+            return Ok(());
+        };
+        Err(Error::InvalidEdgeWeight { edge, reason })
+    }
 }
 
 impl Weight for StandardWeight {
@@ -71,6 +108,12 @@ impl Weight for StandardWeight {
 
 impl ServerWeight for StandardWeight {}
 
+impl TravelDuration for StandardWeight {
+    fn travel_duration_secs(&self) -> f64 {
+        self.travel_duration.get::<second>() as f64
+    }
+}
+
 impl Add for StandardWeight {
     type Output = Self;
 
@@ -132,12 +175,34 @@ impl PartialOrd for StandardWeight {
     }
 }
 
+impl BucketKey for StandardWeight {
+    /// whole seconds of the travel duration
+    fn bucket_key(&self) -> u64 {
+        self.travel_duration.get::<second>() as u64
+    }
+}
+
 const COL_EDGE: &str = "edge";
 const COL_EDGE_PREFERENCE: &str = "edge_preference";
 const COL_EDGE_TRAVEL_DURATION: &str = "edge_travel_duration";
 const COL_LONG_EDGE: &str = "long_edge";
 const COL_LONG_EDGE_PREFERENCE: &str = "long_edge_preference";
 const COL_LONG_EDGE_TRAVEL_DURATION: &str = "long_edge_travel_duration";
+const COL_EDGE_ATTRIBUTE_KEYS: &str = "edge_attribute_keys";
+const COL_EDGE_ATTRIBUTE_VALUES: &str = "edge_attribute_values";
+
+/// key of the format version in the schema metadata of serialized graphs
+const METADATA_FORMAT_VERSION: &str = "rout3serv.graph_format_version";
+
+/// key of the number of nodes in the schema metadata of serialized graphs
+const METADATA_NUM_NODES: &str = "rout3serv.graph_num_nodes";
+
+/// version of the serialization format of graphs.
+///
+/// Stored in the schema metadata of serialized graphs. Graphs without this
+/// metadata predate the versioning and are of version 1. Graphs of higher versions
+/// than this one are refused when reading.
+pub const GRAPH_FORMAT_VERSION: u32 = 1;
 
 impl ToDataFrame for PreparedH3EdgeGraph<StandardWeight> {
     fn to_dataframe(&self) -> Result<DataFrame, Error> {
@@ -147,6 +212,8 @@ impl ToDataFrame for PreparedH3EdgeGraph<StandardWeight> {
         let mut le_directed_edges = Vec::with_capacity(directed_edges.capacity());
         let mut le_edge_preferences = Vec::with_capacity(directed_edges.capacity());
         let mut le_travel_durations = Vec::with_capacity(directed_edges.capacity());
+        let mut attribute_keys = Vec::with_capacity(directed_edges.capacity());
+        let mut attribute_values = Vec::with_capacity(directed_edges.capacity());
 
         let mut decompressor = Decompressor::new();
         for (edge, edgeweight) in self.iter_edges() {
@@ -154,6 +221,15 @@ impl ToDataFrame for PreparedH3EdgeGraph<StandardWeight> {
             edge_preferences.push(edgeweight.weight.edge_preference);
             travel_durations.push(edgeweight.weight.travel_duration.get::<second>());
 
+            if let Some(attributes) = self.get_edge_attributes(edge) {
+                let (keys, values): (Vec<_>, Vec<_>) = attributes.iter().unzip();
+                attribute_keys.push(Some(Series::new("", keys)));
+                attribute_values.push(Some(Series::new("", values)));
+            } else {
+                attribute_keys.push(None);
+                attribute_values.push(None);
+            }
+
             if let Some((fastforward, fastforward_weight)) = edgeweight.fastforward {
                 let ff_edges: Vec<_> = decompressor
                     .decompress_block(&fastforward.edge_path)?
@@ -177,8 +253,21 @@ impl ToDataFrame for PreparedH3EdgeGraph<StandardWeight> {
             Series::new(COL_LONG_EDGE, le_directed_edges),
             Series::new(COL_LONG_EDGE_PREFERENCE, le_edge_preferences),
             Series::new(COL_LONG_EDGE_TRAVEL_DURATION, le_travel_durations),
+            Series::new(COL_EDGE_ATTRIBUTE_KEYS, attribute_keys),
+            Series::new(COL_EDGE_ATTRIBUTE_VALUES, attribute_values),
         ])?)
     }
+
+    fn schema_metadata(&self) -> SchemaMetadata {
+        let mut metadata = SchemaMetadata::from([(
+            METADATA_FORMAT_VERSION.to_string(),
+            GRAPH_FORMAT_VERSION.to_string(),
+        )]);
+        if let Ok(stats) = self.get_stats() {
+            metadata.insert(METADATA_NUM_NODES.to_string(), stats.num_nodes.to_string());
+        }
+        metadata
+    }
 }
 
 impl FromDataFrame for PreparedH3EdgeGraph<StandardWeight> {
@@ -186,12 +275,96 @@ impl FromDataFrame for PreparedH3EdgeGraph<StandardWeight> {
     where
         Self: Sized,
     {
-        Ok(PreparedH3EdgeGraph::try_from_iter(
-            collect_edges(df)?.into_iter(),
-        )?)
+        let edge_attributes = collect_edge_attributes(&df)?;
+        let mut graph = PreparedH3EdgeGraph::try_from_iter(collect_edges(df)?.into_iter())?;
+        graph.set_edge_attributes(edge_attributes);
+        Ok(graph)
+    }
+
+    /// refuse graphs written in a format version newer than [`GRAPH_FORMAT_VERSION`].
+    fn check_schema_metadata(metadata: &SchemaMetadata) -> Result<(), Error> {
+        let found = match metadata.get(METADATA_FORMAT_VERSION) {
+            Some(version) => version
+                .parse()
+                .map_err(|_| Error::InvalidGraphFormatVersion(version.clone()))?,
+            None => 1,
+        };
+        if found > GRAPH_FORMAT_VERSION {
+            return Err(Error::UnsupportedGraphFormatVersion {
+                found,
+                supported: GRAPH_FORMAT_VERSION,
+            });
+        }
+        Ok(())
     }
 }
 
+/// number of nodes of the graph serialized in `df`.
+///
+/// Only looks at the edges, so this is a lot cheaper than building the graph.
+pub fn count_graph_nodes(df: &DataFrame) -> Result<usize, Error> {
+    let mut cells = H3Treemap::<CellIndex>::default();
+    for de in df.column(COL_EDGE)?.u64()?.into_iter().flatten() {
+        let edge = DirectedEdgeIndex::try_from(de)?;
+        cells.insert(edge.origin());
+        cells.insert(edge.destination());
+    }
+    Ok(cells.len())
+}
+
+/// check all edge weights of `graph` can be used for routing.
+///
+/// Graphs read from IPC files get their weights validated while reading, this covers
+/// graphs from other sources before these get prepared.
+pub fn validate_edge_weights(graph: &H3EdgeGraph<StandardWeight>) -> Result<(), Error> {
+    graph
+        .iter_edges()
+        .try_for_each(|(edge, weight)| weight.validate(edge))
+}
+
+/// number of nodes of a serialized graph as stored in the schema `metadata`.
+///
+/// This allows checking the size of a graph before fetching it. Graphs written before
+/// the number of nodes was stored have no such metadata, for these the nodes need to be
+/// counted using [`count_graph_nodes`].
+pub fn graph_num_nodes_from_metadata(metadata: &SchemaMetadata) -> Option<usize> {
+    metadata
+        .get(METADATA_NUM_NODES)
+        .and_then(|num_nodes| num_nodes.parse().ok())
+}
+
+/// the attributes of the edges serialized in `df`.
+///
+/// Graphs written before the attributes were stored have no attribute columns.
+fn collect_edge_attributes(
+    df: &DataFrame,
+) -> Result<Option<DirectedEdgeMap<EdgeAttributes>>, Error> {
+    let column_names = df.get_column_names();
+    if !(column_names.contains(&COL_EDGE_ATTRIBUTE_KEYS)
+        && column_names.contains(&COL_EDGE_ATTRIBUTE_VALUES))
+    {
+        return Ok(None);
+    }
+
+    let mut edge_attributes = DirectedEdgeMap::default();
+    for (de, keys, values) in izip!(
+        df.column(COL_EDGE)?.u64()?.into_iter(),
+        df.column(COL_EDGE_ATTRIBUTE_KEYS)?.list()?,
+        df.column(COL_EDGE_ATTRIBUTE_VALUES)?.list()?,
+    ) {
+        if let (Some(de), Some(keys), Some(values)) = (de, keys, values) {
+            let attributes: EdgeAttributes = keys
+                .utf8()?
+                .into_iter()
+                .zip(values.utf8()?.into_iter())
+                .filter_map(|(key, value)| key.zip(value))
+                .collect();
+            edge_attributes.insert(DirectedEdgeIndex::try_from(de)?, attributes);
+        }
+    }
+    Ok((!edge_attributes.is_empty()).then_some(edge_attributes))
+}
+
 fn collect_edges(df: DataFrame) -> Result<Vec<FromIterItem<StandardWeight>>, Error> {
     let directed_edges = df.column(COL_EDGE)?.u64()?;
     let edge_preferences = df.column(COL_EDGE_PREFERENCE)?.f32()?;
@@ -212,6 +385,7 @@ fn collect_edges(df: DataFrame) -> Result<Vec<FromIterItem<StandardWeight>>, Err
         if let (Some(de), Some(de_pref), Some(de_td)) = (de, de_pref, de_td) {
             let edge = DirectedEdgeIndex::try_from(de)?;
             let edge_weight = StandardWeight::new(de_pref, Time::new::<second>(de_td));
+            edge_weight.validate(edge)?;
 
             let fastforward =
                 if let (Some(ff_edges), Some(ff_pref), Some(ff_td)) = (ff_edges, ff_pref, ff_td) {
@@ -226,6 +400,7 @@ fn collect_edges(df: DataFrame) -> Result<Vec<FromIterItem<StandardWeight>>, Err
                             .collect::<Result<Vec<_>, _>>()?;
 
                         let le_weight = StandardWeight::new(ff_pref, Time::new::<second>(ff_td));
+                        le_weight.validate(edge)?;
                         Some((le_edges, le_weight))
                     }
                 } else {
@@ -240,10 +415,25 @@ fn collect_edges(df: DataFrame) -> Result<Vec<FromIterItem<StandardWeight>>, Err
 
 #[cfg(test)]
 mod tests {
+    use std::io::Cursor;
+
+    use h3o::{LatLng, Resolution};
+    use hexigraph::algorithm::edge::continuous_cells_to_edges;
+    use hexigraph::algorithm::graph::shortest_path::DefaultShortestPathOptions;
+    use hexigraph::algorithm::graph::ShortestPath;
+    use hexigraph::graph::{
+        EdgeAttributes, GetEdgeAttributes, H3EdgeGraph, PreparedH3EdgeGraph, TravelDuration,
+    };
+    use polars::prelude::{IpcWriter, SerWriter};
     use uom::si::f32::Time;
     use uom::si::time::second;
 
-    use crate::weight::StandardWeight;
+    use crate::io::dataframe::ToDataFrame;
+    use crate::io::ipc::{write_dataframe_ipc, ReadIPC, WriteIPC};
+    use crate::io::Error;
+    use crate::weight::{
+        validate_edge_weights, StandardWeight, GRAPH_FORMAT_VERSION, METADATA_FORMAT_VERSION,
+    };
 
     macro_rules! secs {
         ($s:expr) => {
@@ -263,4 +453,165 @@ mod tests {
         let rw2 = StandardWeight::new(6.0, secs!(15));
         assert_eq!(rw1 + rw2, StandardWeight::new(5.2, secs!(25)));
     }
+
+    fn ipc_roundtrip(
+        invalid_weight: StandardWeight,
+    ) -> Result<PreparedH3EdgeGraph<StandardWeight>, Error> {
+        let cell = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for (i, edge) in cell.edges().enumerate() {
+            if i == 0 {
+                graph.add_edge(edge, invalid_weight);
+            } else {
+                graph.add_edge(edge, StandardWeight::new(1.0, secs!(10)));
+            }
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let mut buf = vec![];
+        prepared_graph.write_ipc(&mut buf).unwrap();
+        PreparedH3EdgeGraph::read_ipc(Cursor::new(buf))
+    }
+
+    #[test]
+    fn read_rejects_invalid_weights() {
+        assert!(ipc_roundtrip(StandardWeight::new(1.0, secs!(10))).is_ok());
+
+        assert!(matches!(
+            ipc_roundtrip(StandardWeight::new(1.0, secs!(f32::NAN))),
+            Err(Error::InvalidEdgeWeight { .. })
+        ));
+        assert!(matches!(
+            ipc_roundtrip(StandardWeight::new(1.0, secs!(-5))),
+            Err(Error::InvalidEdgeWeight { .. })
+        ));
+        assert!(matches!(
+            ipc_roundtrip(StandardWeight::new(f32::INFINITY, secs!(10))),
+            Err(Error::InvalidEdgeWeight { .. })
+        ));
+    }
+
+    #[test]
+    fn validate_edge_weights_of_unprepared_graphs() {
+        let graph = |weight| {
+            let cell = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+            let mut graph = H3EdgeGraph::new(Resolution::Nine);
+            for edge in cell.edges() {
+                graph.add_edge(edge, weight);
+            }
+            graph
+        };
+
+        assert!(validate_edge_weights(&graph(StandardWeight::new(1.0, secs!(10)))).is_ok());
+        assert!(matches!(
+            validate_edge_weights(&graph(StandardWeight::new(1.0, secs!(-5)))),
+            Err(Error::InvalidEdgeWeight { .. })
+        ));
+        assert!(matches!(
+            validate_edge_weights(&graph(StandardWeight::new(f32::NAN, secs!(10)))),
+            Err(Error::InvalidEdgeWeight { .. })
+        ));
+    }
+
+    #[test]
+    fn edge_attributes_roundtrip() {
+        let cell = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        let mut edges = cell.edges();
+        let attributed_edge = edges.next().unwrap();
+        graph.add_edge(attributed_edge, StandardWeight::new(1.0, secs!(10)));
+        graph.set_edge_attributes(
+            attributed_edge,
+            EdgeAttributes::from_iter([("highway", "residential"), ("name", "Main Street")]),
+        );
+        for edge in edges {
+            graph.add_edge(edge, StandardWeight::new(1.0, secs!(10)));
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let mut buf = vec![];
+        prepared_graph.write_ipc(&mut buf).unwrap();
+        let read_graph = PreparedH3EdgeGraph::<StandardWeight>::read_ipc(Cursor::new(buf)).unwrap();
+
+        for edge in cell.edges() {
+            assert_eq!(
+                read_graph.get_edge_attributes(edge),
+                prepared_graph.get_edge_attributes(edge)
+            );
+        }
+        let attributes = read_graph.get_edge_attributes(attributed_edge).unwrap();
+        assert_eq!(attributes.get("highway"), Some("residential"));
+        assert_eq!(attributes.len(), 2);
+    }
+
+    #[test]
+    fn max_edge_speed_is_admissible() {
+        let origin = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let destination = LatLng::new(10.0, 20.02).unwrap().to_cell(Resolution::Nine);
+        let cells: Vec<_> = origin
+            .grid_path_cells(destination)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        let mut fastest_speed = 0.0f64;
+        for (i, edge) in continuous_cells_to_edges(cells.clone()).enumerate() {
+            let weight = StandardWeight::new(1.0, secs!(10 + (i % 3) * 5));
+            let (cell_from, cell_to) = edge.cells();
+            let distance_m = LatLng::from(cell_from).distance_m(LatLng::from(cell_to));
+            fastest_speed = fastest_speed.max(distance_m / weight.travel_duration_secs());
+            graph.add_edge(edge, weight);
+        }
+        let prepared_graph = PreparedH3EdgeGraph::from_h3edge_graph(graph, 3).unwrap();
+
+        assert!((prepared_graph.max_edge_speed_m_per_s() - fastest_speed).abs() < 1e-9);
+
+        let paths = prepared_graph
+            .shortest_path(origin, cells.iter(), &DefaultShortestPathOptions::new())
+            .unwrap();
+        assert_eq!(paths.len(), cells.len());
+        for path in paths {
+            let estimate =
+                prepared_graph.min_travel_duration_secs(path.origin_cell, path.destination_cell);
+            assert!(estimate <= path.cost.travel_duration_secs());
+            if path.origin_cell != path.destination_cell {
+                assert!(estimate > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn read_rejects_future_format_version() {
+        let cell = LatLng::new(10.0, 20.0).unwrap().to_cell(Resolution::Nine);
+        let mut graph = H3EdgeGraph::new(Resolution::Nine);
+        for edge in cell.edges() {
+            graph.add_edge(edge, StandardWeight::new(1.0, secs!(10)));
+        }
+        let prepared_graph: PreparedH3EdgeGraph<_> = graph.try_into().unwrap();
+
+        let mut metadata = prepared_graph.schema_metadata();
+        assert_eq!(
+            metadata.get(METADATA_FORMAT_VERSION),
+            Some(&GRAPH_FORMAT_VERSION.to_string())
+        );
+        metadata.insert(
+            METADATA_FORMAT_VERSION.to_string(),
+            (GRAPH_FORMAT_VERSION + 1).to_string(),
+        );
+
+        let mut buf = vec![];
+        write_dataframe_ipc(&mut buf, prepared_graph.to_dataframe().unwrap(), metadata).unwrap();
+        assert!(matches!(
+            PreparedH3EdgeGraph::<StandardWeight>::read_ipc(Cursor::new(buf)),
+            Err(Error::UnsupportedGraphFormatVersion { found, .. }) if found == GRAPH_FORMAT_VERSION + 1
+        ));
+
+        // graphs written before the versioning are still readable
+        let mut buf = vec![];
+        IpcWriter::new(&mut buf)
+            .finish(&mut prepared_graph.to_dataframe().unwrap())
+            .unwrap();
+        assert!(PreparedH3EdgeGraph::<StandardWeight>::read_ipc(Cursor::new(buf)).is_ok());
+    }
 }